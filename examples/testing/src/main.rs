@@ -5,11 +5,17 @@ use std::{
 
 use liten::{net::TcpListener, task};
 use tracing::Level;
+use tracing_subscriber::fmt::format::FmtSpan;
 
 #[liten::main]
 async fn main() -> Result<(), Box<dyn Error>> {
-  tracing_subscriber::fmt().with_max_level(Level::TRACE).finish();
-  tracing::subscriber::set_global_default(tracing_subscriber::fmt().finish())?;
+  // Every task gets a span, closing it prints how long the task was polled for.
+  tracing::subscriber::set_global_default(
+    tracing_subscriber::fmt()
+      .with_max_level(Level::TRACE)
+      .with_span_events(FmtSpan::CLOSE)
+      .finish(),
+  )?;
 
   let tcp = TcpListener::bind("localhost:9001")?;
 
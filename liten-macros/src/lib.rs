@@ -1,12 +1,21 @@
+mod select;
+
 use proc_macro::TokenStream;
 use quote::ToTokens;
 use syn::parenthesized;
 use syn::parse::Parse;
 use syn::parse_macro_input;
 
+use syn::punctuated::Punctuated;
 use syn::Block;
+use syn::Expr;
+use syn::ExprLit;
 use syn::FnArg;
 use syn::Ident;
+use syn::ItemFn;
+use syn::Lit;
+use syn::LitInt;
+use syn::MetaNameValue;
 use syn::ReturnType;
 use syn::Token;
 
@@ -18,20 +27,43 @@ pub fn main(_: TokenStream, function: TokenStream) -> TokenStream {
   MainFn(testing).into_token_stream().into()
 }
 
+/// Runs an async test on a fresh runtime.
+///
+/// Other attributes like `#[should_panic]` or `#[ignore]` are kept, and the test can return a
+/// `Result` like any other test. Options:
+///
+/// - `worker_threads = N`: how many workers run spawned tasks.
+/// - `flavor = "multi_thread" | "current_thread"`: `current_thread` runs spawned tasks on a single
+///   worker thread, next to the test's own thread.
+/// - `start_paused = true`: pauses time before the test starts, see `liten::time::pause`.
 #[proc_macro_attribute]
-pub fn test(_: TokenStream, function: TokenStream) -> TokenStream {
-  let func = function.clone();
-  let testing = parse_macro_input!(func as CallerFn);
+pub fn test(options: TokenStream, function: TokenStream) -> TokenStream {
+  let function = parse_macro_input!(function as ItemFn);
+  let options = parse_macro_input!(options as TestOptions);
 
-  TestFn(testing).into_token_stream().into()
+  TestFn { function, options, krate: quote::quote!(liten) }
+    .into_token_stream()
+    .into()
 }
 
 #[proc_macro_attribute]
-pub fn internal_test(_: TokenStream, function: TokenStream) -> TokenStream {
-  let func = function.clone();
-  let testing = parse_macro_input!(func as CallerFn);
+pub fn internal_test(
+  options: TokenStream,
+  function: TokenStream,
+) -> TokenStream {
+  let function = parse_macro_input!(function as ItemFn);
+  let options = parse_macro_input!(options as TestOptions);
+
+  TestFn { function, options, krate: quote::quote!(crate) }
+    .into_token_stream()
+    .into()
+}
 
-  InternalTestFn(testing).into_token_stream().into()
+// Implements `liten::select!`, which is the public entry point.
+#[doc(hidden)]
+#[proc_macro]
+pub fn select(input: TokenStream) -> TokenStream {
+  parse_macro_input!(input as select::Select).into_token_stream().into()
 }
 
 struct CallerFn {
@@ -43,8 +75,71 @@ struct CallerFn {
 
 struct MainFn(CallerFn);
 
-struct TestFn(CallerFn);
-struct InternalTestFn(CallerFn);
+struct TestFn {
+  function: ItemFn,
+  options: TestOptions,
+  // Path to the liten crate, `crate` inside liten itself.
+  krate: proc_macro2::TokenStream,
+}
+
+#[derive(Default)]
+struct TestOptions {
+  worker_threads: Option<LitInt>,
+  start_paused: bool,
+}
+
+impl Parse for TestOptions {
+  fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+    let mut options = TestOptions::default();
+    let mut current_thread = None;
+
+    let pairs =
+      Punctuated::<MetaNameValue, Token![,]>::parse_terminated(input)?;
+    for pair in pairs {
+      let Some(name) = pair.path.get_ident() else {
+        return Err(syn::Error::new_spanned(&pair.path, "unknown option"));
+      };
+      let Expr::Lit(ExprLit { lit, .. }) = &pair.value else {
+        return Err(syn::Error::new_spanned(&pair.value, "expected a literal"));
+      };
+
+      match (name.to_string().as_str(), lit) {
+        ("worker_threads", Lit::Int(threads)) => {
+          options.worker_threads = Some(threads.clone())
+        }
+        ("flavor", Lit::Str(flavor)) => match flavor.value().as_str() {
+          "multi_thread" => current_thread = Some(false),
+          "current_thread" => current_thread = Some(true),
+          _ => {
+            return Err(syn::Error::new_spanned(
+              flavor,
+              "expected \"multi_thread\" or \"current_thread\"",
+            ))
+          }
+        },
+        ("start_paused", Lit::Bool(paused)) => {
+          options.start_paused = paused.value
+        }
+        ("worker_threads" | "flavor" | "start_paused", _) => {
+          return Err(syn::Error::new_spanned(lit, "unexpected value"))
+        }
+        _ => return Err(syn::Error::new_spanned(name, "unknown option")),
+      }
+    }
+
+    if current_thread == Some(true) {
+      if let Some(threads) = &options.worker_threads {
+        return Err(syn::Error::new_spanned(
+          threads,
+          "worker_threads can't be set with the current_thread flavor",
+        ));
+      }
+      options.worker_threads = Some(LitInt::new("1", input.span()));
+    }
+
+    Ok(options)
+  }
+}
 
 impl Parse for CallerFn {
   fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
@@ -89,32 +184,37 @@ impl ToTokens for MainFn {
 
 impl ToTokens for TestFn {
   fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
-    let CallerFn { return_type, block, ident, args } = &self.0;
-
-    let tokens_to_extend = quote::quote! {
-        #[test]
-        fn #ident(#(#args),*) #return_type {
-            liten::runtime::Runtime::new()
-                .block_on(async #block)
-        }
-    };
-
-    tokens.extend(tokens_to_extend);
-  }
-}
+    let TestFn { function, options, krate } = self;
+    let ItemFn { attrs, vis, sig, block } = function;
+    let ident = &sig.ident;
+    let output = &sig.output;
+
+    if sig.asyncness.is_none() {
+      let error =
+        syn::Error::new_spanned(sig.fn_token, "the test has to be async");
+      tokens.extend(error.to_compile_error());
+      return;
+    }
 
-impl ToTokens for InternalTestFn {
-  fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
-    let CallerFn { return_type, block, ident, args } = &self.0;
+    let worker_threads = options
+      .worker_threads
+      .as_ref()
+      .map(|threads| quote::quote!(.worker_threads(#threads)));
+    let pause =
+      options.start_paused.then(|| quote::quote!(#krate::time::pause();));
 
-    let tokens_to_extend = quote::quote! {
+    tokens.extend(quote::quote! {
         #[test]
-        fn #ident(#(#args),*) #return_type {
-            crate::runtime::Runtime::new()
-                .block_on(async #block)
+        #(#attrs)*
+        #vis fn #ident() #output {
+            #krate::runtime::Builder::new()
+                #worker_threads
+                .build()
+                .block_on(async {
+                    #pause
+                    async #block.await
+                })
         }
-    };
-
-    tokens.extend(tokens_to_extend);
+    });
   }
 }
@@ -0,0 +1,167 @@
+use proc_macro2::{Span, TokenStream, TokenTree};
+use quote::{format_ident, quote, ToTokens};
+use syn::{parse::Parse, Expr, Pat, Token};
+
+// Input of `liten::select!`, which passes its own `$crate` in front.
+pub struct Select {
+  krate: TokenStream,
+  biased: bool,
+  branches: Vec<Branch>,
+  otherwise: Option<Expr>,
+}
+
+struct Branch {
+  pattern: Pat,
+  future: Expr,
+  handler: Expr,
+}
+
+mod kw {
+  syn::custom_keyword!(biased);
+}
+
+impl Parse for Select {
+  fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+    let mut krate = TokenStream::new();
+    while !input.peek(Token![;]) {
+      krate.extend([input.parse::<TokenTree>()?]);
+    }
+    input.parse::<Token![;]>()?;
+
+    let biased = input.peek(kw::biased) && input.peek2(Token![;]);
+    if biased {
+      input.parse::<kw::biased>()?;
+      input.parse::<Token![;]>()?;
+    }
+
+    let mut branches = Vec::new();
+    let mut otherwise = None;
+    while !input.is_empty() {
+      if input.peek(Token![else]) {
+        let else_token = input.parse::<Token![else]>()?;
+        if otherwise.is_some() {
+          return Err(syn::Error::new_spanned(
+            else_token,
+            "there can only be one else branch",
+          ));
+        }
+        input.parse::<Token![=>]>()?;
+        otherwise = Some(input.parse::<Expr>()?);
+      } else {
+        let pattern = Pat::parse_multi_with_leading_vert(input)?;
+        input.parse::<Token![=]>()?;
+        let future = input.parse::<Expr>()?;
+        input.parse::<Token![=>]>()?;
+        let handler = input.parse::<Expr>()?;
+        branches.push(Branch { pattern, future, handler });
+      }
+      if !input.is_empty() {
+        input.parse::<Token![,]>()?;
+      }
+    }
+
+    if branches.is_empty() {
+      return Err(syn::Error::new(
+        Span::call_site(),
+        "select! needs at least one branch",
+      ));
+    }
+
+    Ok(Select { krate, biased, branches, otherwise })
+  }
+}
+
+impl ToTokens for Select {
+  fn to_tokens(&self, tokens: &mut TokenStream) {
+    let Select { krate, biased, branches, otherwise } = self;
+    let count = branches.len();
+
+    let variants: Vec<_> =
+      (0..count).map(|index| format_ident!("_{}", index)).collect();
+    let futures: Vec<_> =
+      (0..count).map(|index| format_ident!("__future_{}", index)).collect();
+
+    let init = branches.iter().zip(&futures).map(|(branch, ident)| {
+      let future = &branch.future;
+      quote!(let mut #ident = ::core::pin::pin!(
+        ::core::future::IntoFuture::into_future(#future)
+      );)
+    });
+    let polls = branches.iter().zip(&futures).zip(&variants).enumerate().map(
+      |(index, ((branch, future), variant))| {
+        let pattern = &branch.pattern;
+        quote! {
+          #index => {
+            if __disabled[#index] {
+              continue;
+            }
+            let ::core::task::Poll::Ready(__output) =
+              ::core::future::Future::poll(#future.as_mut(), __cx)
+            else {
+              __pending = true;
+              continue;
+            };
+            // A value not matching the pattern disables the branch.
+            #[allow(unused_variables, unused_mut, irrefutable_let_patterns)]
+            let #pattern = &__output else {
+              __disabled[#index] = true;
+              continue;
+            };
+            return ::core::task::Poll::Ready(__Output::#variant(__output));
+          }
+        }
+      },
+    );
+    let handlers = branches.iter().zip(&variants).map(|(branch, variant)| {
+      let Branch { pattern, handler, .. } = branch;
+      quote!(__Output::#variant(#pattern) => #handler,)
+    });
+    let otherwise = match otherwise {
+      Some(otherwise) => quote!(#otherwise),
+      None => quote!(::core::panic!(
+        "all branches of select! are disabled and there is no else branch"
+      )),
+    };
+    let start = if *biased {
+      quote!(0)
+    } else {
+      quote!(#krate::future::__random_branch(#count))
+    };
+
+    tokens.extend(quote! {{
+      #[allow(non_camel_case_types)]
+      enum __Output<#(#variants),*> {
+        #(#variants(#variants),)*
+        Disabled,
+      }
+
+      #(#init)*
+      let mut __disabled = [false; #count];
+      let __start: usize = #start;
+      let __output = ::core::future::poll_fn(|__cx| {
+        let mut __pending = false;
+        for __offset in 0..#count {
+          #[allow(clippy::modulo_one)]
+          let __branch = (__start + __offset) % #count;
+          match __branch {
+            #(#polls)*
+            _ => ::core::unreachable!(),
+          }
+        }
+        if __pending {
+          ::core::task::Poll::Pending
+        } else {
+          ::core::task::Poll::Ready(__Output::Disabled)
+        }
+      })
+      .await;
+
+      #[allow(unreachable_patterns, unused_variables)]
+      match __output {
+        #(#handlers)*
+        __Output::Disabled => #otherwise,
+        #(__Output::#variants(_) => ::core::unreachable!(),)*
+      }
+    }});
+  }
+}
@@ -0,0 +1,38 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use liten::sync::Mutex;
+use std::{
+  future::Future,
+  pin::pin,
+  task::{Context, Poll, Waker},
+};
+
+// An uncontended lock is ready on the first poll.
+fn now<F: Future>(future: F) -> F::Output {
+  let mut cx = Context::from_waker(Waker::noop());
+  match pin!(future).poll(&mut cx) {
+    Poll::Ready(value) => value,
+    Poll::Pending => unreachable!("the lock isn't held"),
+  }
+}
+
+fn criterion_benchmark(c: &mut Criterion) {
+  let mut group = c.benchmark_group("liten::sync::mutex");
+
+  let mutex = Mutex::new(0u64);
+  group.bench_function("uncontended-lock", |b| {
+    b.iter(|| *now(mutex.lock()).unwrap() += 1)
+  });
+  group.bench_function("uncontended-try-lock", |b| {
+    b.iter(|| *mutex.try_lock().unwrap() += 1)
+  });
+
+  let mutex = std::sync::Mutex::new(0u64);
+  group.bench_function("std-uncontended-lock", |b| {
+    b.iter(|| *mutex.lock().unwrap() += 1)
+  });
+
+  group.finish();
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
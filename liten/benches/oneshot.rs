@@ -0,0 +1,89 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use liten::sync::oneshot;
+use std::{
+  future::Future,
+  pin::pin,
+  sync::{mpsc, Arc},
+  task::{Context, Poll, Wake},
+  thread::{self, Thread},
+};
+
+struct Unpark(Thread);
+
+impl Wake for Unpark {
+  fn wake(self: Arc<Self>) {
+    self.0.unpark();
+  }
+}
+
+// Parks the thread between polls, so every round trip waits on a registered waker.
+fn block_on<F: Future>(future: F) -> F::Output {
+  let waker = Arc::new(Unpark(thread::current())).into();
+  let mut cx = Context::from_waker(&waker);
+  let mut future = pin!(future);
+  loop {
+    match future.as_mut().poll(&mut cx) {
+      Poll::Ready(value) => return value,
+      Poll::Pending => thread::park(),
+    }
+  }
+}
+
+fn criterion_benchmark(c: &mut Criterion) {
+  let mut group = c.benchmark_group("liten::sync::oneshot");
+
+  group.bench_function("send-recv-u64", |b| {
+    b.iter(|| {
+      let (sender, receiver) = oneshot::channel::<u64>();
+      sender.send(criterion::black_box(7)).unwrap();
+      receiver.try_recv().unwrap()
+    })
+  });
+
+  group.bench_function("send-recv-1k", |b| {
+    b.iter(|| {
+      let (sender, receiver) = oneshot::channel::<[u8; 1024]>();
+      sender.send(criterion::black_box([0; 1024])).unwrap();
+      receiver.try_recv().unwrap()
+    })
+  });
+
+  // Answered before it's awaited, like a cached response: the first poll is ready.
+  group.bench_function("sent-before-poll", |b| {
+    b.iter(|| {
+      let (sender, receiver) = oneshot::channel::<u64>();
+      sender.send(criterion::black_box(7)).unwrap();
+      block_on(receiver).unwrap()
+    })
+  });
+
+  // The sender lives on another thread, so sends race with the receiver registering its waker.
+  group.bench_function("ping-pong", |b| {
+    let (ping, pings) = mpsc::channel::<(u64, oneshot::Sender<u64>)>();
+    let ponger = thread::spawn(move || {
+      for (value, pong) in pings {
+        let _ = pong.send(value + 1);
+      }
+    });
+
+    b.iter(|| {
+      let (sender, receiver) = oneshot::channel();
+      ping.send((criterion::black_box(7), sender)).unwrap();
+      block_on(receiver).unwrap()
+    });
+
+    drop(ping);
+    ponger.join().unwrap();
+  });
+
+  group.bench_function("receiver-dropped", |b| {
+    b.iter(|| {
+      let (sender, receiver) = oneshot::channel::<u64>();
+      drop(receiver);
+      sender.send(7).unwrap_err()
+    })
+  });
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
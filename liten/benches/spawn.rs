@@ -0,0 +1,62 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use liten::{runtime::Runtime, sync::oneshot, task};
+use std::{future::Future, sync::mpsc};
+
+// Runs `work` as a task on the workers, and blocks the bench thread until it's done.
+fn run_on_workers<F>(work: F)
+where
+  F: Future<Output = ()> + Send + 'static,
+{
+  let (done, wait) = mpsc::channel();
+  task::spawn(async move {
+    work.await;
+    done.send(()).unwrap();
+  });
+  wait.recv().unwrap();
+}
+
+fn criterion_benchmark(c: &mut Criterion) {
+  let runtime = Runtime::builder().worker_threads(4).build();
+  runtime.block_on(async {
+    let mut group = c.benchmark_group("liten::task::spawn");
+
+    // Every task completes before the next is spawned, so its memory can be reused. The handles
+    // aren't `Send`, the tasks answer through oneshots instead.
+    group.bench_function("spawn-await-1000", |b| {
+      b.iter(|| {
+        run_on_workers(async {
+          for value in 0..1000u64 {
+            let (sender, receiver) = oneshot::channel();
+            let buf = [value; 8];
+            task::spawn(async move { sender.send(buf.iter().sum::<u64>()) });
+            receiver.await.unwrap();
+          }
+        })
+      })
+    });
+
+    // Many alive at once, freed on whichever worker ran them.
+    group.bench_function("spawn-join-1000", |b| {
+      b.iter(|| {
+        run_on_workers(async {
+          let receivers: Vec<_> = (0..1000u64)
+            .map(|value| {
+              let (sender, receiver) = oneshot::channel();
+              let buf = [value; 8];
+              task::spawn(async move { sender.send(buf.iter().sum::<u64>()) });
+              receiver
+            })
+            .collect();
+          for receiver in receivers {
+            receiver.await.unwrap();
+          }
+        })
+      })
+    });
+
+    group.finish();
+  });
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,99 @@
+use std::{
+  io::{Read, Write},
+  net::TcpListener,
+  pin::Pin,
+  time::{Duration, Instant},
+};
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use liten::{
+  io::{AsyncRead, AsyncWrite},
+  net::TcpStream,
+  runtime::Runtime,
+};
+
+const MESSAGE: usize = 64;
+
+// Echoes every message back, until the client disconnects.
+fn echo_server() -> std::net::SocketAddr {
+  let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+  let addr = listener.local_addr().unwrap();
+  std::thread::spawn(move || {
+    for stream in listener.incoming() {
+      let mut stream = stream.unwrap();
+      std::thread::spawn(move || {
+        let mut buf = [0; MESSAGE];
+        while stream.read_exact(&mut buf).is_ok() {
+          stream.write_all(&buf).unwrap();
+        }
+      });
+    }
+  });
+  addr
+}
+
+fn owned(addr: std::net::SocketAddr, iters: u64) -> Duration {
+  Runtime::new().block_on(async move {
+    let mut stream = TcpStream::connect(addr).unwrap().await.unwrap();
+    let mut message = vec![7; MESSAGE];
+    let mut buf = Vec::with_capacity(MESSAGE);
+
+    let start = Instant::now();
+    for _ in 0..iters {
+      let (sent, returned) = stream.send_owned(message).await;
+      assert_eq!(sent.unwrap(), MESSAGE);
+      message = returned;
+
+      buf.clear();
+      while buf.len() < MESSAGE {
+        let (received, returned) = stream.recv_owned(buf).await;
+        received.unwrap();
+        buf = returned;
+      }
+    }
+    start.elapsed()
+  })
+}
+
+fn readiness(addr: std::net::SocketAddr, iters: u64) -> Duration {
+  Runtime::new().block_on(async move {
+    let mut stream = TcpStream::connect(addr).unwrap().await.unwrap();
+    let message = [7; MESSAGE];
+    let mut buf = [0; MESSAGE];
+
+    let start = Instant::now();
+    for _ in 0..iters {
+      let sent = std::future::poll_fn(|cx| {
+        Pin::new(&mut stream).poll_write(cx, &message)
+      })
+      .await;
+      assert_eq!(sent.unwrap(), MESSAGE);
+
+      let mut received = 0;
+      while received < MESSAGE {
+        received += std::future::poll_fn(|cx| {
+          Pin::new(&mut stream).poll_read(cx, &mut buf[received..])
+        })
+        .await
+        .unwrap();
+      }
+    }
+    start.elapsed()
+  })
+}
+
+fn criterion_benchmark(c: &mut Criterion) {
+  let addr = echo_server();
+  let mut group = c.benchmark_group("liten::net::tcp");
+
+  group.bench_function("ping-pong-owned", |b| {
+    b.iter_custom(|iters| owned(addr, iters))
+  });
+
+  group.bench_function("ping-pong-readiness", |b| {
+    b.iter_custom(|iters| readiness(addr, iters))
+  });
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
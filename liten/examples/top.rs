@@ -0,0 +1,75 @@
+//! Prints a top-like view of the runtime once a second, from its instrumentation events.
+
+use std::{collections::BTreeMap, time::Duration};
+
+use liten::{
+  runtime::{Handle, RuntimeEvent},
+  task, time,
+};
+
+#[derive(Default)]
+struct Worker {
+  polls: usize,
+  busy: Duration,
+  parks: usize,
+  local: usize,
+  waiting: usize,
+}
+
+#[liten::main]
+async fn main() {
+  let events = Handle::current().instrument_events();
+
+  // Something to look at: tasks which sleep, and some which spin for a bit.
+  for index in 0..200u64 {
+    task::spawn(async move {
+      for _ in 0..60 {
+        time::sleep(Duration::from_millis(10 + index % 50)).await;
+        if index % 20 == 0 {
+          std::thread::sleep(Duration::from_millis(2));
+        }
+      }
+    });
+  }
+
+  let (mut spawned, mut completed) = (0, 0);
+  for _ in 0..5 {
+    let mut workers = BTreeMap::<usize, Worker>::new();
+    // Read often, the stream only keeps the latest events.
+    for _ in 0..20 {
+      time::sleep(Duration::from_millis(50)).await;
+      for event in events.try_iter() {
+        match event {
+          RuntimeEvent::TaskSpawned { .. } => spawned += 1,
+          RuntimeEvent::TaskCompleted { .. } => completed += 1,
+          RuntimeEvent::TaskPolled { worker, duration, .. } => {
+            let worker = workers.entry(worker).or_default();
+            worker.polls += 1;
+            worker.busy += duration;
+          }
+          RuntimeEvent::WorkerParked { worker } => {
+            workers.entry(worker).or_default().parks += 1
+          }
+          RuntimeEvent::QueueDepth { worker, local, waiting, .. } => {
+            let worker = workers.entry(worker).or_default();
+            (worker.local, worker.waiting) = (local, waiting);
+          }
+          _ => {}
+        }
+      }
+    }
+
+    println!(
+      "tasks: {} alive, {spawned} spawned, {completed} completed",
+      spawned - completed
+    );
+    println!("worker  polls/s  busy      parks/s  ready  waiting");
+    for (id, worker) in workers {
+      println!(
+        "{id:<7} {:<8} {:<9.2?} {:<8} {:<6} {}",
+        worker.polls, worker.busy, worker.parks, worker.local, worker.waiting
+      );
+    }
+    println!();
+  }
+}
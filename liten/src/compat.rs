@@ -0,0 +1,166 @@
+//! Adapters between liten's io traits and tokio's.
+//!
+//! Only the traits are bridged, so byte streams can cross between crates written for either. The
+//! futures are still polled by whichever runtime awaits them, no tokio runtime is started.
+use std::{
+  io,
+  pin::Pin,
+  task::{Context, Poll},
+};
+
+use pin_project_lite::pin_project;
+
+pin_project! {
+  /// Implements tokio's io traits for a type implementing liten's, and liten's for a type
+  /// implementing tokio's.
+  ///
+  /// Reads fill the unfilled part of tokio's `ReadBuf`, and shutting down maps to shutting down on
+  /// both sides.
+  #[derive(Debug)]
+  pub struct TokioIo<T> {
+    #[pin]
+    inner: T,
+  }
+}
+
+impl<T> TokioIo<T> {
+  pub fn new(inner: T) -> Self {
+    TokioIo { inner }
+  }
+
+  pub fn get_ref(&self) -> &T {
+    &self.inner
+  }
+
+  pub fn get_mut(&mut self) -> &mut T {
+    &mut self.inner
+  }
+
+  pub fn into_inner(self) -> T {
+    self.inner
+  }
+}
+
+impl<T: crate::io::AsyncRead> tokio::io::AsyncRead for TokioIo<T> {
+  fn poll_read(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+    buf: &mut tokio::io::ReadBuf<'_>,
+  ) -> Poll<io::Result<()>> {
+    let read = std::task::ready!(self
+      .project()
+      .inner
+      .poll_read(cx, buf.initialize_unfilled()))?;
+    buf.advance(read);
+    Poll::Ready(Ok(()))
+  }
+}
+
+impl<T: crate::io::AsyncWrite> tokio::io::AsyncWrite for TokioIo<T> {
+  fn poll_write(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+    buf: &[u8],
+  ) -> Poll<io::Result<usize>> {
+    self.project().inner.poll_write(cx, buf)
+  }
+
+  fn poll_flush(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<io::Result<()>> {
+    self.project().inner.poll_flush(cx)
+  }
+
+  fn poll_shutdown(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<io::Result<()>> {
+    self.project().inner.poll_shutdown(cx)
+  }
+}
+
+impl<T: tokio::io::AsyncRead> crate::io::AsyncRead for TokioIo<T> {
+  fn poll_read(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+    buf: &mut [u8],
+  ) -> Poll<io::Result<usize>> {
+    let mut buf = tokio::io::ReadBuf::new(buf);
+    std::task::ready!(self.project().inner.poll_read(cx, &mut buf))?;
+    Poll::Ready(Ok(buf.filled().len()))
+  }
+}
+
+impl<T: tokio::io::AsyncWrite> crate::io::AsyncWrite for TokioIo<T> {
+  fn poll_write(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+    buf: &[u8],
+  ) -> Poll<io::Result<usize>> {
+    self.project().inner.poll_write(cx, buf)
+  }
+
+  fn poll_flush(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<io::Result<()>> {
+    self.project().inner.poll_flush(cx)
+  }
+
+  fn poll_shutdown(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<io::Result<()>> {
+    self.project().inner.poll_shutdown(cx)
+  }
+}
+
+#[crate::internal_test]
+async fn framed_over_tcp_stream() {
+  use crate::net::TcpStream;
+  use futures_util::{SinkExt, StreamExt};
+  use tokio_util::codec::{Framed, LengthDelimitedCodec};
+
+  let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+  let addr = listener.local_addr().unwrap();
+  let server = std::thread::spawn(move || {
+    let (mut stream, _) = listener.accept().unwrap();
+    std::io::copy(&mut stream.try_clone().unwrap(), &mut stream).unwrap();
+  });
+
+  let stream = TcpStream::connect(addr).unwrap().await.unwrap();
+  let mut framed =
+    Framed::new(TokioIo::new(stream), LengthDelimitedCodec::new());
+
+  for frame in ["first", "", "third frame"] {
+    framed.send(frame.as_bytes().to_vec().into()).await.unwrap();
+    let echoed = framed.next().await.unwrap().unwrap();
+    assert_eq!(&echoed[..], frame.as_bytes());
+  }
+
+  framed.close().await.unwrap();
+  assert!(framed.next().await.is_none());
+  server.join().unwrap();
+}
+
+#[crate::internal_test]
+async fn liten_traits_over_tokio() {
+  use crate::io::{AsyncRead, AsyncWrite};
+  use std::future::poll_fn;
+
+  let (near, far) = tokio::io::duplex(8);
+  let (mut near, mut far) = (TokioIo::new(near), TokioIo::new(far));
+
+  let written =
+    poll_fn(|cx| Pin::new(&mut near).poll_write(cx, b"0123456789")).await;
+  // The duplex only buffers 8 bytes.
+  assert_eq!(written.unwrap(), 8);
+  poll_fn(|cx| Pin::new(&mut near).poll_shutdown(cx)).await.unwrap();
+
+  let mut buf = [0; 16];
+  let read = poll_fn(|cx| Pin::new(&mut far).poll_read(cx, &mut buf)).await;
+  assert_eq!(&buf[..read.unwrap()], b"01234567");
+  let end = poll_fn(|cx| Pin::new(&mut far).poll_read(cx, &mut buf)).await;
+  assert_eq!(end.unwrap(), 0);
+}
@@ -0,0 +1,297 @@
+use std::{
+  future::Future,
+  io,
+  pin::{pin, Pin},
+  task::{self, Poll},
+  time::{Duration, Instant},
+};
+
+use thiserror::Error;
+
+use crate::{
+  runtime::{scheduler::InFlight, snapshot},
+  sync::CancellationToken,
+  task::TaskLocalFuture,
+  time,
+};
+
+crate::task_local! {
+  static CURRENT: Context;
+}
+
+/// A request-scoped context carrying an optional deadline and a cancellation token.
+///
+/// Contexts form a tree: a child created with [`Context::with_timeout`], [`Context::with_deadline`]
+/// or [`Context::with_cancel`] never outlives its parent, because it inherits the earliest
+/// deadline and is cancelled together with it.
+///
+/// The context is carried through task-local storage with [`Context::scope`], so code deeper down
+/// the call stack can pick it up with [`Context::current`] without it being passed around.
+#[derive(Clone, Debug, Default)]
+pub struct Context {
+  deadline: Option<Instant>,
+  token: CancellationToken,
+}
+
+/// Why a [`Context`] is done.
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContextError {
+  #[error("context cancelled")]
+  Cancelled,
+  #[error("context deadline exceeded")]
+  DeadlineExceeded,
+}
+
+impl From<ContextError> for io::Error {
+  fn from(err: ContextError) -> Self {
+    match err {
+      // `Interrupted` would make callers retry the operation.
+      ContextError::Cancelled => io::Error::other(err),
+      ContextError::DeadlineExceeded => {
+        io::Error::new(io::ErrorKind::TimedOut, err)
+      }
+    }
+  }
+}
+
+impl Context {
+  /// An empty context, without deadline, which is never cancelled unless [`Context::cancel`] is
+  /// called.
+  pub fn background() -> Context {
+    Context::default()
+  }
+
+  /// Returns the context of the current scope, or a [`Context::background`] if there is none.
+  pub fn current() -> Context {
+    Context::try_current().unwrap_or_default()
+  }
+
+  /// Returns the context of the current scope, if there is one.
+  pub fn try_current() -> Option<Context> {
+    CURRENT.try_with(Context::clone).ok()
+  }
+
+  /// Creates a child context which expires after `timeout`, or earlier if the parent does.
+  pub fn with_timeout(&self, timeout: Duration) -> Context {
+    self.with_deadline(time::now() + timeout)
+  }
+
+  /// Creates a child context which expires at `deadline`, or earlier if the parent does.
+  pub fn with_deadline(&self, deadline: Instant) -> Context {
+    let deadline = match self.deadline {
+      Some(parent) => parent.min(deadline),
+      None => deadline,
+    };
+    Context { deadline: Some(deadline), token: self.token.child_token() }
+  }
+
+  /// Creates a child context which can be cancelled without cancelling the parent.
+  pub fn with_cancel(&self) -> Context {
+    Context { deadline: self.deadline, token: self.token.child_token() }
+  }
+
+  /// The instant this context expires at, if any.
+  pub fn deadline(&self) -> Option<Instant> {
+    self.deadline
+  }
+
+  /// Time left until the deadline, `None` if there is no deadline.
+  pub fn remaining(&self) -> Option<Duration> {
+    self
+      .deadline
+      .map(|deadline| deadline.saturating_duration_since(time::now()))
+  }
+
+  /// The token which is cancelled when this context is.
+  pub fn token(&self) -> &CancellationToken {
+    &self.token
+  }
+
+  /// Cancels this context and every context derived from it.
+  pub fn cancel(&self) {
+    self.token.cancel();
+  }
+
+  /// Returns why this context is done, if it is.
+  pub fn err(&self) -> Option<ContextError> {
+    let expired = |deadline| time::now() >= deadline;
+
+    if self.token.is_cancelled() {
+      Some(ContextError::Cancelled)
+    } else if self.deadline.is_some_and(expired) {
+      Some(ContextError::DeadlineExceeded)
+    } else {
+      None
+    }
+  }
+
+  /// Makes this context the current one while `future` runs.
+  pub fn scope<F>(self, future: F) -> TaskLocalFuture<Context, F>
+  where
+    F: Future,
+  {
+    CURRENT.scope(self, future)
+  }
+
+  /// Completes when the context gets cancelled or the deadline is reached.
+  pub async fn done(&self) -> ContextError {
+    let deadline = async {
+      match self.deadline {
+        Some(deadline) => time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+      }
+    };
+    let mut deadline = pin!(deadline);
+    let mut cancelled = pin!(self.token.cancelled());
+
+    std::future::poll_fn(|cx| {
+      if cancelled.as_mut().poll(cx).is_ready() {
+        return Poll::Ready(ContextError::Cancelled);
+      }
+      if deadline.as_mut().poll(cx).is_ready() {
+        return Poll::Ready(ContextError::DeadlineExceeded);
+      }
+      Poll::Pending
+    })
+    .await
+  }
+
+  /// Runs `future` in this context, and stops polling it as soon as the context is done.
+  ///
+  /// This is the helper I/O operations should be wrapped with to respect a deadline.
+  pub async fn run<F>(&self, future: F) -> Result<F::Output, ContextError>
+  where
+    F: Future,
+  {
+    if let Some(err) = self.err() {
+      return Err(err);
+    }
+
+    let mut future = pin!(self.clone().scope(future));
+    let mut done = pin!(self.done());
+
+    std::future::poll_fn(|cx| {
+      if let Poll::Ready(value) = future.as_mut().poll(cx) {
+        return Poll::Ready(Ok(value));
+      }
+      done.as_mut().poll(cx).map(Err)
+    })
+    .await
+  }
+}
+
+/// Watches the context that is current on the first poll.
+///
+/// I/O futures poll this before doing any work, so they wake up and fail when the context
+/// expires or gets cancelled while they are waiting on readiness. Without a deadline from the
+/// context, the [`io_timeout`](crate::runtime::Builder::io_timeout) of the runtime is used.
+#[derive(Default)]
+pub(crate) struct ContextWatch {
+  done: Option<Pin<Box<dyn Future<Output = ContextError> + Send + Sync>>>,
+  initialized: bool,
+  // Held by operations waiting in `poll_io`.
+  in_flight: Option<InFlight>,
+}
+
+impl ContextWatch {
+  pub(crate) fn poll(
+    &mut self,
+    cx: &mut task::Context<'_>,
+  ) -> Poll<ContextError> {
+    if !self.initialized {
+      self.initialized = true;
+      let timeout = super::try_handle().and_then(|handle| handle.io_timeout());
+      let ctx = match (Context::try_current(), timeout) {
+        (Some(ctx), Some(timeout)) if ctx.deadline().is_none() => {
+          Some(ctx.with_timeout(timeout))
+        }
+        (None, Some(timeout)) => {
+          Some(Context::background().with_timeout(timeout))
+        }
+        (ctx, _) => ctx,
+      };
+      if let Some(ctx) = ctx {
+        if let Some(err) = ctx.err() {
+          return Poll::Ready(err);
+        }
+        self.done = Some(Box::pin(async move { ctx.done().await }));
+      }
+    }
+
+    match self.done.as_mut() {
+      Some(done) => done.as_mut().poll(cx),
+      None => Poll::Pending,
+    }
+  }
+
+  /// Fails `poll`, the latest poll of an operation, once the context is done while it's pending.
+  ///
+  /// Unlike polling the watch up front, the context is only picked up once the operation has to
+  /// wait, and it's let go of when the operation completes. This lets one watch cover every
+  /// operation of a stream, each with a deadline of its own.
+  ///
+  /// A pending operation also counts as in flight, for the runtime's
+  /// [`shutdown_timeout`](crate::runtime::Builder::shutdown_timeout) until it completes.
+  pub(crate) fn poll_io<R>(
+    &mut self,
+    cx: &mut task::Context<'_>,
+    poll: Poll<io::Result<R>>,
+  ) -> Poll<io::Result<R>> {
+    let poll = match poll {
+      Poll::Pending => self.poll(cx).map(|err| Err(err.into())),
+      ready => ready,
+    };
+    if poll.is_ready() {
+      if let Some(handle) = super::try_handle() {
+        snapshot::increment(&handle.counters().io_operations);
+      }
+      *self = ContextWatch::default();
+    } else if self.in_flight.is_none() {
+      self.in_flight = super::try_handle().map(InFlight::new);
+    }
+    poll
+  }
+}
+
+#[crate::internal_test]
+async fn child_inherits_parent_deadline() {
+  let parent = Context::background().with_timeout(Duration::from_millis(20));
+
+  let result = parent
+    .clone()
+    .scope(async {
+      // The child asks for more time than the parent has left.
+      let child = Context::current().with_timeout(Duration::from_secs(5));
+      assert_eq!(child.deadline(), parent.deadline());
+
+      child.run(time::sleep(Duration::from_secs(5))).await
+    })
+    .await;
+
+  assert_eq!(result, Err(ContextError::DeadlineExceeded));
+  assert_eq!(parent.err(), Some(ContextError::DeadlineExceeded));
+}
+
+#[test]
+fn io_error_kinds() {
+  let cancelled = io::Error::from(ContextError::Cancelled);
+  assert_eq!(cancelled.kind(), io::ErrorKind::Other);
+  assert_eq!(
+    cancelled.get_ref().and_then(|err| err.downcast_ref()),
+    Some(&ContextError::Cancelled)
+  );
+
+  let expired = io::Error::from(ContextError::DeadlineExceeded);
+  assert_eq!(expired.kind(), io::ErrorKind::TimedOut);
+}
+
+#[crate::internal_test]
+async fn cancelling_parent_cancels_child() {
+  let parent = Context::background();
+  let child = parent.with_timeout(Duration::from_secs(5));
+
+  parent.cancel();
+
+  assert_eq!(child.err(), Some(ContextError::Cancelled));
+  assert_eq!(child.run(async { 1 }).await, Err(ContextError::Cancelled));
+}
@@ -0,0 +1,157 @@
+mod deadline;
+pub use deadline::*;
+
+use std::{cell::RefCell, sync::Arc};
+
+use crate::runtime::{scheduler, NoRuntimeError};
+
+std::thread_local! {
+  static CONTEXT: RuntimeContext = const {
+    RuntimeContext {
+      handle: RefCell::new(None),
+    }
+  };
+}
+
+pub(crate) struct RuntimeContext {
+  handle: RefCell<Option<Arc<scheduler::Handle>>>,
+}
+
+#[cfg(test)]
+static_assertions::assert_impl_all!(RuntimeContext: Send);
+
+impl RuntimeContext {
+  pub fn handle(&self) -> Arc<scheduler::Handle> {
+    self
+      .handle
+      .borrow()
+      .clone()
+      .expect("a liten resource was used outside of the runtime it belongs to")
+  }
+}
+
+pub(crate) fn with_context<F, R>(func: F) -> R
+where
+  F: FnOnce(&RuntimeContext) -> R,
+{
+  CONTEXT.with(func)
+}
+
+/// Returns the handle of the runtime this thread belongs to, if any.
+pub(crate) fn try_handle() -> Option<Arc<scheduler::Handle>> {
+  CONTEXT.with(|ctx| ctx.handle.borrow().clone())
+}
+
+/// Returns the handle of the runtime this thread belongs to, or an error naming `api`, which
+/// needed it.
+pub(crate) fn handle_for(
+  api: &'static str,
+) -> Result<Arc<scheduler::Handle>, NoRuntimeError> {
+  try_handle().ok_or(NoRuntimeError::new(api))
+}
+
+/// Like [`handle_for`], but panics with the error outside of a runtime.
+#[track_caller]
+pub(crate) fn expect_handle(api: &'static str) -> Arc<scheduler::Handle> {
+  handle_for(api).unwrap_or_else(|err| panic!("{err}"))
+}
+
+/// Makes `handle` the current runtime of this thread until the guard is dropped, which puts back
+/// the one before it.
+pub(crate) fn enter(handle: Arc<scheduler::Handle>) -> EnterGuard {
+  let previous = CONTEXT.with(|ctx| ctx.handle.replace(Some(handle)));
+  EnterGuard { previous, _not_send: std::marker::PhantomData }
+}
+
+pub(crate) struct EnterGuard {
+  previous: Option<Arc<scheduler::Handle>>,
+  // The guard restores this thread's context, so it can't move to another thread.
+  _not_send: std::marker::PhantomData<*const ()>,
+}
+
+impl Drop for EnterGuard {
+  fn drop(&mut self) {
+    let previous = self.previous.take();
+    CONTEXT.with(|ctx| *ctx.handle.borrow_mut() = previous);
+  }
+}
+
+pub(crate) fn runtime_enter<F, R>(handle: Arc<scheduler::Handle>, f: F) -> R
+where
+  F: FnOnce(&RuntimeContext) -> R,
+{
+  if try_handle().is_some_and(|x| x.has_entered()) {
+    panic!("nested runtimes is not supported");
+  }
+
+  let guard = enter(handle.clone());
+  let return_type = with_context(f);
+  drop(guard);
+
+  handle.exit();
+
+  return_type
+}
+
+#[test]
+fn without_runtime() {
+  use crate::{
+    net::{TcpListener, TcpStream, UdpSocket},
+    runtime::{Handle, Runtime},
+    task::{self, SpawnError},
+    time,
+  };
+  use std::{io, panic, time::Duration};
+
+  fn check(runtime: Option<&Handle>) {
+    let _guard = runtime.map(Handle::enter);
+    let panicked = |f: fn()| {
+      let payload = panic::catch_unwind(f).err()?;
+      Some(*payload.downcast::<String>().unwrap())
+    };
+    let io_error = |result: io::Result<()>| {
+      let err = result.err()?;
+      Some(*err.into_inner().unwrap().downcast::<NoRuntimeError>().unwrap())
+    };
+    let expected = |api| runtime.is_none().then(|| NoRuntimeError::new(api));
+
+    assert_eq!(
+      task::try_spawn(async {}).err(),
+      expected("task::try_spawn").map(SpawnError::from)
+    );
+    assert_eq!(
+      panicked(|| drop(task::spawn(async {}))),
+      expected("task::spawn").map(|err| err.to_string())
+    );
+    assert_eq!(
+      panicked(|| drop(time::sleep(Duration::ZERO))),
+      expected("time::sleep").map(|err| err.to_string())
+    );
+    assert_eq!(
+      io_error(TcpListener::bind("127.0.0.1:0").map(drop)),
+      expected("TcpListener::bind")
+    );
+    assert_eq!(
+      io_error(UdpSocket::bind("127.0.0.1:0").map(drop)),
+      expected("UdpSocket::bind")
+    );
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    assert_eq!(
+      io_error(TcpStream::connect(listener.local_addr().unwrap()).map(drop)),
+      expected("TcpStream::connect")
+    );
+  }
+
+  check(None);
+  let message = NoRuntimeError::new("task::spawn").to_string();
+  assert_eq!(
+    message,
+    "`task::spawn` needs a liten runtime, but this thread isn't in one. Call it from a task or \
+     from `Runtime::block_on`, or enter a runtime first with `Handle::enter`"
+  );
+
+  Runtime::new().block_on(async {
+    let handle = Handle::current();
+    std::thread::spawn(move || check(Some(&handle))).join().unwrap();
+  });
+}
@@ -1,40 +1,39 @@
 mod registration;
+mod sources;
+#[cfg(all(target_os = "linux", feature = "uring"))]
+pub(crate) mod uring;
 
 pub use registration::EventRegistration;
 
 use std::{
-  collections::{hash_map::Entry, HashMap},
   io,
   sync::{
-    atomic::{AtomicUsize, Ordering},
-    Mutex,
+    atomic::{AtomicU64, Ordering},
+    Mutex, OnceLock,
   },
-  task::{Context, Waker},
+  task::Context,
+  thread,
+  time::Duration,
 };
 
 use mio::{Events, Interest, Token};
 
-struct TokenState(AtomicUsize);
+use sources::Sources;
 
 const SHUTDOWN_SIGNAL_TOKEN: Token = Token(0);
-
-impl TokenState {
-  pub fn new() -> TokenState {
-    TokenState(AtomicUsize::new(1)) // 0 is specialcase
-  }
-  pub fn next_token(&self) -> Token {
-    debug_assert!(
-      self.0.load(Ordering::Relaxed) != 0,
-      "can't call next_token on wakeup token"
-    );
-    Token(self.0.fetch_add(1, Ordering::Acquire))
-  }
-}
+// Failed polls in a row before the driver gives up, with a backoff doubling from
+// `FIRST_BACKOFF` in between.
+const MAX_FAILURES: u32 = 8;
+const FIRST_BACKOFF: Duration = Duration::from_millis(1);
+#[cfg(all(target_os = "linux", feature = "uring"))]
+const URING_TOKEN: Token = Token(1);
 
 /// IO-Driver
 #[derive(Debug)]
 pub struct Driver {
   poll: mio::Poll,
+  // How long events are gathered for before their tasks are woken.
+  coalesce_window: Duration,
 }
 
 /// Reference to the IO driver
@@ -42,14 +41,26 @@ pub struct Handle {
   registry: mio::Registry,
   // Using a stdMutex because events::Handle is not in a async context and doesn't fit a async
   // model.
-  wakers: Mutex<HashMap<Token, Waker>>,
+  // The token of every source and the tasks waiting on it, which can be more than one when it's
+  // shared.
+  sources: Mutex<Sources>,
+  // Tasks woken because of readiness.
+  wakes: AtomicU64,
+  // Set when the driver has given up, what's left of the error which made it.
+  failed: OnceLock<(io::ErrorKind, String)>,
+  // Errors the next polls return instead of polling.
+  #[cfg(test)]
+  faults: Mutex<std::collections::VecDeque<io::Error>>,
 
-  token_state: TokenState,
+  // None when the kernel doesn't support io_uring, everything then goes through mio.
+  #[cfg(all(target_os = "linux", feature = "uring"))]
+  uring: Option<uring::Uring>,
 }
 
 impl Handle {
+  /// Takes a token for a new source, which is freed again by [`Handle::deregister`].
   pub fn next_token(&self) -> Token {
-    self.token_state.next_token()
+    self.sources.lock().unwrap().insert()
   }
   pub fn mio_waker(&self) -> mio::Waker {
     mio::Waker::new(&self.registry, SHUTDOWN_SIGNAL_TOKEN).unwrap()
@@ -57,10 +68,25 @@ impl Handle {
   pub fn from_driver_ref(driver: &Driver) -> io::Result<Self> {
     Ok(Self {
       registry: driver.poll.registry().try_clone()?,
-      wakers: Mutex::new(HashMap::new()),
-      token_state: TokenState::new(),
+      sources: Mutex::default(),
+      wakes: AtomicU64::new(0),
+      failed: OnceLock::new(),
+      #[cfg(test)]
+      faults: Mutex::default(),
+      #[cfg(all(target_os = "linux", feature = "uring"))]
+      uring: uring::Uring::new(driver.poll.registry(), URING_TOKEN)
+        .inspect_err(|_err| {
+          #[cfg(feature = "tracing")]
+          tracing::debug!(err = %_err, "io_uring unavailable")
+        })
+        .ok(),
     })
   }
+
+  #[cfg(all(target_os = "linux", feature = "uring"))]
+  pub(crate) fn uring(&self) -> Option<&uring::Uring> {
+    self.uring.as_ref()
+  }
   pub(self) fn register(
     &self,
     source: &mut dyn mio::event::Source,
@@ -79,56 +105,280 @@ impl Handle {
     self.registry.reregister(source, token, interest)
   }
 
+  // Frees the token even when deregistering fails, the source is going away either way.
   pub(self) fn deregister(
     &self,
     source: &mut dyn mio::event::Source,
+    token: Token,
   ) -> io::Result<()> {
-    self.registry.deregister(source)
+    let result = self.registry.deregister(source);
+    self.sources.lock().unwrap().remove(token);
+    result
   }
 
   /// Registers a waker for io-bound futures that are pending.
   ///
-  /// If token doesn't exist in the registry:
-  ///   Token gets inserted with its waker.
-  pub fn poll(&self, token: Token, cx: &mut Context) {
-    let mut guard = self.wakers.lock().unwrap();
-
-    match guard.entry(token) {
-      Entry::Vacant(vacant) => {
-        vacant.insert(cx.waker().clone());
-      }
-      Entry::Occupied(mut occupied) => {
-        if !occupied.get().will_wake(cx.waker()) {
-          occupied.insert(cx.waker().clone());
-        }
-      }
+  /// The waker is added to the ones already waiting on the token, unless it's one of them. Fails
+  /// once the driver has given up, nothing would wake it, and when the source has been
+  /// deregistered.
+  pub fn poll(&self, token: Token, cx: &mut Context) -> io::Result<()> {
+    let mut guard = self.sources.lock().unwrap();
+    if let Some((kind, message)) = self.failed.get() {
+      return Err(io::Error::new(*kind, message.clone()));
+    }
+
+    let Some(wakers) = guard.wakers(token) else {
+      return Err(io::Error::new(
+        io::ErrorKind::NotConnected,
+        "the source has been deregistered",
+      ));
+    };
+    if !wakers.iter().any(|waker| waker.will_wake(cx.waker())) {
+      wakers.push(cx.waker().clone());
+    }
+    Ok(())
+  }
+
+  /// How many times a task has been woken because its source became ready.
+  pub(crate) fn wakes(&self) -> u64 {
+    self.wakes.load(Ordering::Relaxed)
+  }
+
+  // Wakes every waiting task, which then fail to wait again.
+  fn fail(&self, err: &io::Error) {
+    let mut guard = self.sources.lock().unwrap();
+    let _ = self.failed.set((err.kind(), err.to_string()));
+    for waker in guard.drain_wakers() {
+      waker.wake();
     }
   }
+
+  /// Makes the next poll of the driver fail with `err`. It goes through once the driver is woken
+  /// by any event.
+  #[cfg(test)]
+  pub(crate) fn inject_poll_error(&self, err: io::Error) {
+    self.faults.lock().unwrap().push_back(err);
+  }
 }
 
 impl Driver {
   pub fn new() -> io::Result<(Driver, Handle)> {
-    let driver = Driver { poll: mio::Poll::new().unwrap() };
+    let driver = Driver {
+      poll: mio::Poll::new().unwrap(),
+      coalesce_window: Duration::ZERO,
+    };
 
     let handle = Handle::from_driver_ref(&driver)?;
 
     Ok((driver, handle))
   }
 
-  pub fn turn(&mut self, handle: &Handle) -> bool {
-    // FIXME: This doesn't quit.
+  /// Gathers the events which come in over `window` after the first one, before waking any task,
+  /// see [`Builder::io_coalesce_window`](crate::runtime::Builder::io_coalesce_window).
+  pub fn set_coalesce_window(&mut self, window: Duration) {
+    self.coalesce_window = window;
+  }
+
+  /// Turns the driver until the runtime shuts down, or until polling keeps failing, see
+  /// [`Builder::on_io_error`](crate::runtime::Builder::on_io_error).
+  ///
+  /// A failing poll can't be replaced with a new one, the sources are registered with it.
+  pub fn run(
+    &mut self,
+    handle: &Handle,
+    on_error: Option<&(dyn Fn(&io::Error) + Send + Sync)>,
+  ) {
+    let mut failures = 0;
+    loop {
+      match self.turn(handle) {
+        Ok(true) => return,
+        Ok(false) => failures = 0,
+        Err(err) => {
+          #[cfg(feature = "tracing")]
+          tracing::error!(%err, failures, "polling for io events failed");
+          if let Some(on_error) = on_error {
+            on_error(&err);
+          }
+          failures += 1;
+          if failures == MAX_FAILURES {
+            handle.fail(&err);
+            return;
+          }
+          thread::sleep(FIRST_BACKOFF * 2u32.pow(failures - 1));
+        }
+      }
+    }
+  }
+
+  // Returns `true` once woken to shut down.
+  fn turn(&mut self, handle: &Handle) -> io::Result<bool> {
+    #[cfg(test)]
+    if let Some(err) = handle.faults.lock().unwrap().pop_front() {
+      return Err(err);
+    }
+
     let mut events = Events::with_capacity(1024);
-    self.poll.poll(&mut events, None).unwrap();
+    match self.poll.poll(&mut events, None) {
+      Err(err) if err.kind() == io::ErrorKind::Interrupted => return Ok(false),
+      result => result?,
+    }
+    let mut ready = Vec::new();
+    if Self::gather(handle, &events, &mut ready) {
+      return Ok(true);
+    }
+
+    if !self.coalesce_window.is_zero() {
+      thread::sleep(self.coalesce_window);
+      match self.poll.poll(&mut events, Some(Duration::ZERO)) {
+        Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+        result => result?,
+      }
+      if Self::gather(handle, &events, &mut ready) {
+        return Ok(true);
+      }
+    }
 
-    for event in &events {
+    let mut guard = handle.sources.lock().unwrap();
+    for token in ready {
+      // They all try again, those which find nothing to do wait again. A token which is ready
+      // more than once only wakes them the first time, and one of a source deregistered since
+      // the poll wakes nothing.
+      let wakers = guard.wakers(token).map(std::mem::take);
+      for waker in wakers.into_iter().flatten() {
+        handle.wakes.fetch_add(1, Ordering::Relaxed);
+        waker.wake()
+      }
+    }
+    Ok(false)
+  }
+
+  // Adds the tokens of sources which are ready to `ready`, returns `true` when woken to shut down.
+  fn gather(_handle: &Handle, events: &Events, ready: &mut Vec<Token>) -> bool {
+    for event in events {
       if event.token() == SHUTDOWN_SIGNAL_TOKEN {
         return true; // Wakeup-call
       };
-      let mut guard = handle.wakers.lock().unwrap();
-      if let Some(waker) = guard.remove(&event.token()) {
-        waker.wake()
+      #[cfg(all(target_os = "linux", feature = "uring"))]
+      if event.token() == URING_TOKEN {
+        if let Some(uring) = &_handle.uring {
+          uring.reap();
+        }
+        continue;
       }
+      // Whatever the event is, readable, writable, a hangup or an error, every task waiting on the
+      // source tries again: an operation on a socket which was reset or closed by its peer then
+      // fails or reads EOF instead of waiting for readiness which never comes.
+      ready.push(event.token());
     }
     false
   }
 }
+
+#[test]
+fn recovers_from_failed_poll() {
+  use crate::{io::AsyncReadExt, net::TcpStream, runtime::Builder};
+  use std::{io::Write, sync::Arc};
+
+  let errors = Arc::new(Mutex::new(Vec::new()));
+  let seen = errors.clone();
+  let runtime = Builder::new()
+    .on_io_error(move |err| seen.lock().unwrap().push(err.kind()))
+    .build();
+  runtime.block_on(async {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let mut stream = TcpStream::connect(listener.local_addr().unwrap())
+      .unwrap()
+      .await
+      .unwrap();
+    let (mut peer, _) = listener.accept().unwrap();
+
+    let handle = crate::context::try_handle().unwrap();
+    handle.io().inject_poll_error(io::ErrorKind::OutOfMemory.into());
+    // Wakes the driver, which then fails once.
+    peer.write_all(b"ping").unwrap();
+    while errors.lock().unwrap().is_empty() {
+      crate::time::sleep(Duration::from_millis(1)).await;
+    }
+
+    // And goes on afterwards.
+    let writer = thread::spawn(move || {
+      thread::sleep(Duration::from_millis(20));
+      peer.write_all(b"pong").unwrap();
+    });
+    let mut buf = [0; 8];
+    stream.read_exact(&mut buf).await.unwrap();
+    assert_eq!(&buf, b"pingpong");
+    writer.join().unwrap();
+  });
+  assert_eq!(*errors.lock().unwrap(), [io::ErrorKind::OutOfMemory]);
+}
+
+#[test]
+fn gives_up_on_failing_poll() {
+  use crate::{io::AsyncReadExt, net::TcpStream, runtime::Builder};
+
+  let runtime = Builder::new().build();
+  runtime.block_on(async {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let mut stream = TcpStream::connect(addr).unwrap().await.unwrap();
+    let _peer = listener.accept().unwrap();
+
+    let handle = crate::context::try_handle().unwrap();
+    for _ in 0..MAX_FAILURES {
+      handle.io().inject_poll_error(io::Error::other("poll broke"));
+    }
+    // Only wakes the driver, whether it connects depends on when the driver gives up.
+    let _ = TcpStream::connect(addr).unwrap().await;
+    // The peer never writes, so the read waits until the driver gives up instead of hanging.
+    let mut buf = [0; 4];
+    let err = stream.read(&mut buf).await.unwrap_err();
+    assert_eq!(err.to_string(), "poll broke");
+    // Tasks and timers still work.
+    crate::time::sleep(Duration::from_millis(1)).await;
+    crate::task::spawn(async {}).await.unwrap();
+  });
+}
+
+#[test]
+fn reset_wakes_pending_read() {
+  use crate::{io::AsyncReadExt, net::TcpStream, runtime::Builder};
+  use std::{os::fd::AsRawFd, time::Instant};
+
+  let runtime = Builder::new().build();
+  runtime.block_on(async {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let mut stream = TcpStream::connect(addr).unwrap().await.unwrap();
+    let (peer, _) = listener.accept().unwrap();
+
+    let resetter = thread::spawn(move || {
+      thread::sleep(Duration::from_millis(20));
+      // A linger of zero makes the close send a reset instead of a FIN.
+      let linger = libc::linger { l_onoff: 1, l_linger: 0 };
+      // SAFETY: The value is a `linger`, and its length says so.
+      let result = unsafe {
+        libc::setsockopt(
+          peer.as_raw_fd(),
+          libc::SOL_SOCKET,
+          libc::SO_LINGER,
+          (&linger as *const libc::linger).cast(),
+          std::mem::size_of::<libc::linger>() as libc::socklen_t,
+        )
+      };
+      assert_eq!(result, 0);
+      drop(peer);
+    });
+
+    // Waiting before the reset, which only comes as an error on the socket.
+    let start = Instant::now();
+    let mut buf = [0; 4];
+    let read = crate::select! {
+      read = stream.read(&mut buf) => read,
+      () = crate::time::sleep(Duration::from_secs(5)) => panic!("the read hung"),
+    };
+    assert_eq!(read.unwrap_err().kind(), io::ErrorKind::ConnectionReset);
+    assert!(start.elapsed() < Duration::from_secs(1));
+    resetter.join().unwrap();
+  });
+}
@@ -46,10 +46,13 @@ impl EventRegistration {
   }
 
   pub fn deregister(&self, source: &mut impl Source) -> io::Result<()> {
-    context::with_context(|ctx| ctx.handle().io().deregister(source))
+    // Sources are deregistered when they are dropped, which shouldn't panic on a thread outside of
+    // the runtime. Closing the source takes it out of the poller anyway.
+    let handle = context::handle_for("deregistering")?;
+    handle.io().deregister(source, self.token)
   }
 
-  pub fn register_io_waker(&self, waker: &mut Context) {
-    context::with_context(|ctx| ctx.handle().io().poll(self.token(), waker));
+  pub fn register_io_waker(&self, waker: &mut Context) -> io::Result<()> {
+    context::with_context(|ctx| ctx.handle().io().poll(self.token(), waker))
   }
 }
@@ -0,0 +1,157 @@
+use std::task::Waker;
+
+use mio::Token;
+
+// The tokens below this are the driver's own.
+const RESERVED: usize = 2;
+// The low half of a token is the index into the slab, the high half its generation.
+const INDEX_BITS: u32 = usize::BITS / 2;
+const INDEX_MASK: usize = (1 << INDEX_BITS) - 1;
+
+/// The sources registered with the poller, and the tasks waiting on each, by token.
+///
+/// Indices are reused once their source is deregistered, with the next generation. A poll can
+/// return an event for a source which is deregistered before the event is handled, and its token
+/// then doesn't match the source which got the index since, so the event is dropped instead of
+/// waking the wrong tasks.
+#[derive(Default)]
+pub(super) struct Sources {
+  slots: Vec<Slot>,
+  // Indices of the slots which aren't in use.
+  free: Vec<usize>,
+}
+
+#[derive(Default)]
+struct Slot {
+  generation: usize,
+  in_use: bool,
+  wakers: Vec<Waker>,
+}
+
+impl Sources {
+  pub(super) fn insert(&mut self) -> Token {
+    let index = self.free.pop().unwrap_or_else(|| {
+      self.slots.push(Slot::default());
+      self.slots.len() - 1
+    });
+    assert!(index + RESERVED <= INDEX_MASK, "too many io sources");
+    let slot = &mut self.slots[index];
+    slot.in_use = true;
+    Token(slot.generation << INDEX_BITS | (index + RESERVED))
+  }
+
+  /// Frees the token, and drops the wakers still waiting on it. A token which is already free
+  /// is left alone, so removing one twice doesn't free the index of the source after it.
+  pub(super) fn remove(&mut self, token: Token) {
+    if let Some(slot) = self.get_mut(token) {
+      slot.in_use = false;
+      slot.wakers.clear();
+      slot.generation = (slot.generation + 1) & (usize::MAX >> INDEX_BITS);
+      self.free.push((token.0 & INDEX_MASK) - RESERVED);
+    }
+  }
+
+  /// The wakers waiting on `token`, `None` when it's been removed.
+  pub(super) fn wakers(&mut self, token: Token) -> Option<&mut Vec<Waker>> {
+    self.get_mut(token).map(|slot| &mut slot.wakers)
+  }
+
+  /// Takes the wakers of every source.
+  pub(super) fn drain_wakers(&mut self) -> impl Iterator<Item = Waker> + '_ {
+    self.slots.iter_mut().flat_map(|slot| slot.wakers.drain(..))
+  }
+
+  /// How many sources are registered.
+  #[cfg(test)]
+  pub(super) fn len(&self) -> usize {
+    self.slots.len() - self.free.len()
+  }
+
+  fn get_mut(&mut self, token: Token) -> Option<&mut Slot> {
+    let index = (token.0 & INDEX_MASK).checked_sub(RESERVED)?;
+    self
+      .slots
+      .get_mut(index)
+      .filter(|slot| slot.in_use && slot.generation == token.0 >> INDEX_BITS)
+  }
+}
+
+#[test]
+fn stale_token_after_reuse() {
+  let mut sources = Sources::default();
+  let first = sources.insert();
+  assert!(first.0 >= RESERVED);
+  sources.wakers(first).unwrap().push(Waker::noop().clone());
+
+  sources.remove(first);
+  let second = sources.insert();
+  // The same index, but not the same token.
+  assert_eq!(first.0 & INDEX_MASK, second.0 & INDEX_MASK);
+  assert_ne!(first, second);
+  assert!(sources.wakers(first).is_none());
+  assert_eq!(sources.wakers(second).unwrap().len(), 0);
+
+  // Removing the old one again doesn't free the new one.
+  sources.remove(first);
+  assert!(sources.wakers(second).is_some());
+  assert_eq!(sources.len(), 1);
+}
+
+#[test]
+fn churned_sockets_route_events() {
+  use crate::{
+    io::{AsyncReadExt, AsyncWrite},
+    net::{TcpListener, TcpStream},
+    runtime::Builder,
+    task,
+  };
+  use std::{future::poll_fn, pin::Pin};
+
+  // Small enough to always go in one write.
+  async fn write(stream: &mut TcpStream, buf: &[u8]) {
+    let written = poll_fn(|cx| Pin::new(&mut *stream).poll_write(cx, buf));
+    assert_eq!(written.await.unwrap(), buf.len());
+  }
+
+  let runtime = Builder::new().worker_threads(4).build();
+  runtime.block_on(async {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = task::spawn(async move {
+      loop {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        task::spawn(async move {
+          let mut buf = [0; 8];
+          while let Ok(read @ 1..) = stream.read(&mut buf).await {
+            write(&mut stream, &buf[..read]).await;
+          }
+        });
+      }
+    });
+
+    // Connections come and go while others wait, so indices are reused under events in flight.
+    let clients: Vec<_> = (0..8u32)
+      .map(|client| {
+        task::spawn(async move {
+          for round in 0..50u32 {
+            let mut stream = TcpStream::connect(addr).unwrap().await.unwrap();
+            let message = (client << 16 | round).to_be_bytes();
+            write(&mut stream, &message).await;
+            let mut buf = [0; 4];
+            stream.read_exact(&mut buf).await.unwrap();
+            assert_eq!(buf, message, "a read got another socket's bytes");
+          }
+        })
+      })
+      .collect();
+    for client in clients {
+      client.await.unwrap();
+    }
+    drop(server);
+
+    let handle = crate::context::try_handle().unwrap();
+    // 800 sockets and more came and went, far fewer were ever open at once.
+    let registered = handle.io().sources.lock().unwrap().slots.len();
+    assert!(registered < 400, "{registered} slots, indices aren't reused");
+  });
+}
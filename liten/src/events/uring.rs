@@ -0,0 +1,231 @@
+//! Completion-based io on Linux, used by the operations which have an io_uring version when the
+//! `uring` feature is enabled and the kernel supports it.
+//!
+//! The ring's fd is registered in the mio poll, so completions are reaped on the io thread like
+//! any other event. Operations can be submitted from any thread.
+use std::{
+  any::Any,
+  collections::HashMap,
+  future::Future,
+  io, mem,
+  os::fd::AsRawFd,
+  pin::Pin,
+  sync::{Arc, Mutex},
+  task::{Context, Poll, Waker},
+};
+
+use io_uring::{opcode, squeue, IoUring};
+use mio::{unix::SourceFd, Interest, Registry, Token};
+
+use crate::runtime::scheduler;
+
+// Completions of cancellations are ignored.
+const CANCEL_USER_DATA: u64 = u64::MAX;
+
+pub(crate) struct Uring {
+  // Using stdMutexes because the io thread isn't in a async context.
+  ring: Mutex<IoUring>,
+  ops: Mutex<Ops>,
+}
+
+#[derive(Default)]
+struct Ops {
+  next_id: u64,
+  slots: HashMap<u64, Slot>,
+}
+
+struct Slot {
+  kind: OpKind,
+  state: State,
+}
+
+enum State {
+  Waiting(Option<Waker>),
+  Done(i32),
+  // The future was dropped first, the buffers the kernel may still write to are kept here.
+  Orphaned(#[allow(dead_code)] Box<dyn Any + Send>),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum OpKind {
+  // The result is a new fd, which has to be closed if nothing is left to take it.
+  Accept,
+  Transfer,
+}
+
+impl Uring {
+  pub fn new(registry: &Registry, token: Token) -> io::Result<Uring> {
+    let ring = IoUring::new(256)?;
+    registry.register(
+      &mut SourceFd(&ring.as_raw_fd()),
+      token,
+      Interest::READABLE,
+    )?;
+    Ok(Uring { ring: Mutex::new(ring), ops: Mutex::default() })
+  }
+
+  fn submit(&self, entry: squeue::Entry, kind: OpKind) -> io::Result<u64> {
+    let mut ops = self.ops.lock().unwrap();
+    ops.next_id += 1;
+    let id = ops.next_id;
+    ops.slots.insert(id, Slot { kind, state: State::Waiting(None) });
+    drop(ops);
+
+    let result = self.push(&entry.user_data(id));
+    if result.is_err() {
+      self.ops.lock().unwrap().slots.remove(&id);
+    }
+    result.map(|()| id)
+  }
+
+  fn push(&self, entry: &squeue::Entry) -> io::Result<()> {
+    let mut ring = self.ring.lock().unwrap();
+    // SAFETY: Every caller keeps what the entry points to alive until it has completed.
+    while unsafe { ring.submission().push(entry) }.is_err() {
+      // Full, make room by handing the queued entries to the kernel.
+      ring.submit()?;
+    }
+    ring.submit()?;
+    Ok(())
+  }
+
+  /// Wakes the futures of every completed operation. Called on the io thread.
+  pub fn reap(&self) {
+    let completed: Vec<(u64, i32)> = {
+      let mut ring = self.ring.lock().unwrap();
+      ring.completion().map(|cqe| (cqe.user_data(), cqe.result())).collect()
+    };
+
+    let mut ops = self.ops.lock().unwrap();
+    let mut wakers = Vec::new();
+    for (id, result) in completed {
+      if id == CANCEL_USER_DATA {
+        continue;
+      }
+      let Some(slot) = ops.slots.get_mut(&id) else {
+        continue;
+      };
+      match mem::replace(&mut slot.state, State::Done(result)) {
+        State::Waiting(waker) => wakers.extend(waker),
+        State::Orphaned(_) => {
+          let slot = ops.slots.remove(&id).unwrap();
+          close_unclaimed(slot.kind, result);
+        }
+        State::Done(_) => unreachable!("completed twice"),
+      }
+    }
+    drop(ops);
+
+    wakers.into_iter().for_each(Waker::wake);
+  }
+
+  fn poll_op(&self, id: u64, cx: &mut Context<'_>) -> Poll<i32> {
+    let mut ops = self.ops.lock().unwrap();
+    let slot = ops.slots.get_mut(&id).expect("polled a finished operation");
+    match &mut slot.state {
+      State::Done(result) => {
+        let result = *result;
+        ops.slots.remove(&id);
+        Poll::Ready(result)
+      }
+      State::Waiting(waker) => {
+        if !waker.as_ref().is_some_and(|waker| waker.will_wake(cx.waker())) {
+          *waker = Some(cx.waker().clone());
+        }
+        Poll::Pending
+      }
+      State::Orphaned(_) => unreachable!("polled an orphaned operation"),
+    }
+  }
+
+  // Keeps `buffers` alive until the kernel is done with them, and asks it to cancel.
+  fn orphan(&self, id: u64, buffers: Box<dyn Any + Send>) {
+    let mut ops = self.ops.lock().unwrap();
+    let Some(slot) = ops.slots.get_mut(&id) else {
+      return;
+    };
+    if let State::Done(result) = slot.state {
+      let slot = ops.slots.remove(&id).unwrap();
+      close_unclaimed(slot.kind, result);
+      return;
+    }
+    slot.state = State::Orphaned(buffers);
+    drop(ops);
+
+    let cancel = opcode::AsyncCancel::new(id).build();
+    // If this fails, the operation completes on its own and is cleaned up then.
+    let _ = self.push(&cancel.user_data(CANCEL_USER_DATA));
+  }
+}
+
+// An accept which completed after its future was dropped. The connection can't be handed to
+// anyone.
+fn close_unclaimed(kind: OpKind, result: i32) {
+  if kind == OpKind::Accept && result >= 0 {
+    unsafe { libc::close(result) };
+  }
+}
+
+/// Returns the runtime's handle if its io driver has a ring.
+pub(crate) fn handle() -> Option<Arc<scheduler::Handle>> {
+  crate::context::try_handle().filter(|handle| handle.io().uring().is_some())
+}
+
+/// An operation submitted to the ring, resolving to its result and the buffers given to it.
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub(crate) struct Op<B: Send + 'static> {
+  handle: Arc<scheduler::Handle>,
+  id: u64,
+  // Taken when the operation completes.
+  buffers: Option<B>,
+}
+
+impl<B: Send + 'static> Op<B> {
+  /// Submits `entry`, on error the buffers are given back.
+  ///
+  /// # Safety
+  ///
+  /// `entry` may only point into memory owned by `buffers` which doesn't move when `buffers` is
+  /// moved, like the heap allocation of a `Vec`.
+  pub unsafe fn submit(
+    handle: Arc<scheduler::Handle>,
+    entry: squeue::Entry,
+    kind: OpKind,
+    buffers: B,
+  ) -> Result<Op<B>, (io::Error, B)> {
+    let uring = handle.io().uring().expect("runtime has no io_uring");
+    match uring.submit(entry, kind) {
+      Ok(id) => Ok(Op { handle, id, buffers: Some(buffers) }),
+      Err(err) => Err((err, buffers)),
+    }
+  }
+}
+
+impl<B: Send + 'static> Unpin for Op<B> {}
+
+impl<B: Send + 'static> Future for Op<B> {
+  type Output = (io::Result<u32>, B);
+
+  fn poll(
+    mut self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<Self::Output> {
+    let uring = self.handle.io().uring().unwrap();
+    let result = std::task::ready!(uring.poll_op(self.id, cx));
+    let buffers = self.buffers.take().expect("polled Op after completion");
+
+    let result = match result {
+      result if result < 0 => Err(io::Error::from_raw_os_error(-result)),
+      result => Ok(result as u32),
+    };
+    Poll::Ready((result, buffers))
+  }
+}
+
+impl<B: Send + 'static> Drop for Op<B> {
+  fn drop(&mut self) {
+    if let Some(buffers) = self.buffers.take() {
+      self.handle.io().uring().unwrap().orphan(self.id, Box::new(buffers));
+    }
+  }
+}
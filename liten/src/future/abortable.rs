@@ -0,0 +1,183 @@
+use std::{
+  future::Future,
+  pin::Pin,
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex as StdMutex,
+  },
+  task::{Context, Poll, Waker},
+};
+
+use pin_project_lite::pin_project;
+use thiserror::Error;
+
+/// Wraps `future` so it can be aborted with the returned [`AbortHandle`].
+pub fn abortable<F: Future>(future: F) -> (Abortable<F>, AbortHandle) {
+  let (handle, registration) = AbortHandle::new_pair();
+  (Abortable::new(future, registration), handle)
+}
+
+/// Returned by an [`Abortable`] which has been aborted.
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+#[error("future was aborted")]
+pub struct Aborted;
+
+#[derive(Default)]
+struct Inner {
+  aborted: AtomicBool,
+  // This is not a bottleneck
+  waker: StdMutex<Option<Waker>>,
+}
+
+/// Aborts the [`Abortable`] it was created with. Aborting a future which has completed does
+/// nothing.
+#[derive(Clone)]
+pub struct AbortHandle {
+  inner: Arc<Inner>,
+}
+
+/// The other half of an [`AbortHandle`], used to create an [`Abortable`] later.
+pub struct AbortRegistration {
+  inner: Arc<Inner>,
+}
+
+#[cfg(test)]
+static_assertions::assert_impl_all!(AbortHandle: Send, Sync, Clone);
+#[cfg(test)]
+static_assertions::assert_impl_all!(AbortRegistration: Send, Sync);
+
+impl AbortHandle {
+  /// Creates a handle and the registration it aborts.
+  pub fn new_pair() -> (AbortHandle, AbortRegistration) {
+    let inner = Arc::new(Inner::default());
+    (AbortHandle { inner: inner.clone() }, AbortRegistration { inner })
+  }
+
+  /// Makes the next poll of the future resolve to [`Aborted`], the wrapped future is dropped
+  /// then.
+  pub fn abort(&self) {
+    self.inner.aborted.store(true, Ordering::Release);
+    if let Some(waker) = self.inner.waker.lock().unwrap().take() {
+      waker.wake();
+    }
+  }
+
+  /// Returns `true` if [`AbortHandle::abort`] has been called.
+  pub fn is_aborted(&self) -> bool {
+    self.inner.aborted.load(Ordering::Acquire)
+  }
+}
+
+pin_project! {
+  /// A future which can be aborted, created with [`abortable`] or [`Abortable::new`].
+  #[must_use = "futures do nothing unless you `.await` or poll them"]
+  pub struct Abortable<F> {
+    #[pin]
+    future: Option<F>,
+    inner: Arc<Inner>,
+  }
+}
+
+impl<F: Future> Abortable<F> {
+  /// Wraps `future`, it's aborted by the handle `registration` was created with.
+  pub fn new(future: F, registration: AbortRegistration) -> Self {
+    Abortable { future: Some(future), inner: registration.inner }
+  }
+
+  /// Returns `true` if the future has been aborted.
+  pub fn is_aborted(&self) -> bool {
+    self.inner.aborted.load(Ordering::Acquire)
+  }
+}
+
+impl<F: Future> Future for Abortable<F> {
+  type Output = Result<F::Output, Aborted>;
+
+  fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+    let mut this = self.project();
+
+    if !this.inner.aborted.load(Ordering::Acquire) {
+      if let Some(future) = this.future.as_mut().as_pin_mut() {
+        if let Poll::Ready(output) = future.poll(cx) {
+          this.future.set(None);
+          return Poll::Ready(Ok(output));
+        }
+      }
+
+      let mut waker = this.inner.waker.lock().unwrap();
+      if !waker.as_ref().is_some_and(|waker| waker.will_wake(cx.waker())) {
+        *waker = Some(cx.waker().clone());
+      }
+      drop(waker);
+
+      // Abort could have happened before the waker was registered.
+      if !this.inner.aborted.load(Ordering::Acquire) {
+        return Poll::Pending;
+      }
+    }
+
+    this.future.set(None);
+    Poll::Ready(Err(Aborted))
+  }
+}
+
+#[crate::internal_test]
+async fn abort_before_poll() {
+  let polled = AtomicBool::new(false);
+  let (future, handle) = abortable(async {
+    polled.store(true, Ordering::Relaxed);
+  });
+
+  handle.abort();
+  assert!(future.is_aborted());
+  assert_eq!(future.await, Err(Aborted));
+  assert!(!polled.load(Ordering::Relaxed));
+}
+
+#[crate::internal_test]
+async fn abort_while_pending() {
+  let (future, handle) = abortable(std::future::pending::<()>());
+
+  let thread = std::thread::spawn(move || {
+    std::thread::sleep(std::time::Duration::from_millis(10));
+    handle.abort();
+  });
+
+  assert_eq!(future.await, Err(Aborted));
+  thread.join().unwrap();
+}
+
+#[crate::internal_test]
+async fn abort_racing_completion() {
+  use crate::sync::oneshot;
+
+  for n in 0..200 {
+    let (sender, receiver) = oneshot::channel();
+    let (future, handle) = abortable(receiver);
+
+    let thread = std::thread::spawn(move || {
+      if n % 2 == 0 {
+        sender.send(n).unwrap();
+        handle.abort();
+      } else {
+        handle.abort();
+        let _ = sender.send(n);
+      }
+    });
+
+    // Either outcome is fine, as long as it doesn't hang.
+    if let Ok(value) = future.await {
+      assert_eq!(value.unwrap(), n);
+    }
+    thread.join().unwrap();
+  }
+}
+
+#[crate::internal_test]
+async fn handle_outlives_future() {
+  let (future, handle) = abortable(async { 1 });
+  assert_eq!(future.await, Ok(1));
+
+  handle.abort();
+  assert!(handle.is_aborted());
+}
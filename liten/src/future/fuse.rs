@@ -0,0 +1,62 @@
+use std::{
+  future::Future,
+  pin::Pin,
+  task::{Context, Poll},
+};
+
+use futures_core::FusedFuture;
+use pin_project_lite::pin_project;
+
+pin_project! {
+  /// Future returned by [`FutureExt::fuse`](super::FutureExt::fuse).
+  #[must_use = "futures do nothing unless you `.await` or poll them"]
+  pub struct Fuse<F> {
+    // Dropped once it completes.
+    #[pin]
+    future: Option<F>,
+  }
+}
+
+impl<F> Fuse<F> {
+  pub(super) fn new(future: F) -> Fuse<F> {
+    Fuse { future: Some(future) }
+  }
+}
+
+impl<F: Future> Future for Fuse<F> {
+  type Output = F::Output;
+
+  fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+    let mut this = self.project();
+    let Some(future) = this.future.as_mut().as_pin_mut() else {
+      return Poll::Pending;
+    };
+    let output = std::task::ready!(future.poll(cx));
+    this.future.set(None);
+    Poll::Ready(output)
+  }
+}
+
+impl<F: Future> FusedFuture for Fuse<F> {
+  fn is_terminated(&self) -> bool {
+    self.future.is_none()
+  }
+}
+
+#[crate::internal_test]
+async fn pending_after_completion() {
+  use super::FutureExt;
+  use crate::{sync::oneshot, test_util::task};
+
+  let (sender, receiver) = oneshot::channel();
+  // A receiver panics when it's polled again.
+  let mut future = task::spawn(receiver.fuse());
+  assert!(future.poll().is_pending());
+  assert!(!future.is_terminated());
+  sender.send(3).unwrap();
+  assert_eq!(future.poll(), std::task::Poll::Ready(Ok(3)));
+  for _ in 0..3 {
+    assert!(future.poll().is_pending());
+  }
+  assert!(future.is_terminated());
+}
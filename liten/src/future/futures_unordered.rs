@@ -0,0 +1,288 @@
+use std::{
+  collections::VecDeque,
+  future::Future,
+  pin::Pin,
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex as StdMutex,
+  },
+  task::{Context, Poll, Wake, Waker},
+};
+
+use crate::stream::Stream;
+
+/// A set of futures which yields their outputs in the order they complete.
+///
+/// Every future gets its own waker, so only the futures which have been woken are polled again,
+/// instead of all of them.
+pub struct FuturesUnordered<F: Future> {
+  entries: Vec<Option<Entry<F>>>,
+  // Indices of `entries` which are free to be reused.
+  free: Vec<usize>,
+  len: usize,
+  ready: Arc<ReadyQueue>,
+  // Outputs of the futures completed by `poll_progress`, yielded before polling any other.
+  completed: VecDeque<F::Output>,
+}
+
+// How many futures `poll_progress` polls before it lets the caller run again.
+const POLL_BUDGET: usize = 64;
+
+struct Entry<F> {
+  future: Pin<Box<F>>,
+  waker: Arc<EntryWaker>,
+}
+
+#[derive(Default)]
+struct ReadyQueue {
+  // This is not a bottleneck
+  indices: StdMutex<VecDeque<usize>>,
+  waker: StdMutex<Option<Waker>>,
+}
+
+struct EntryWaker {
+  index: usize,
+  // Set while the index is in the ready queue, to not queue it twice.
+  queued: AtomicBool,
+  ready: Arc<ReadyQueue>,
+}
+
+impl Wake for EntryWaker {
+  fn wake(self: Arc<Self>) {
+    self.wake_by_ref();
+  }
+
+  fn wake_by_ref(self: &Arc<Self>) {
+    if self.queued.swap(true, Ordering::AcqRel) {
+      return;
+    }
+    self.ready.indices.lock().unwrap().push_back(self.index);
+    if let Some(waker) = self.ready.waker.lock().unwrap().as_ref() {
+      waker.wake_by_ref();
+    }
+  }
+}
+
+impl<F: Future> Default for FuturesUnordered<F> {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl<F: Future> Unpin for FuturesUnordered<F> {}
+
+impl<F: Future> FuturesUnordered<F> {
+  pub fn new() -> Self {
+    FuturesUnordered {
+      entries: Vec::new(),
+      free: Vec::new(),
+      len: 0,
+      ready: Arc::default(),
+      completed: VecDeque::new(),
+    }
+  }
+
+  /// Number of futures whose outputs haven't been yielded yet.
+  pub fn len(&self) -> usize {
+    self.len
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.len == 0
+  }
+
+  /// Adds a future to the set, it's first polled by the next poll of the set.
+  pub fn push(&mut self, future: F) {
+    let index = self.free.pop().unwrap_or(self.entries.len());
+    let waker = Arc::new(EntryWaker {
+      index,
+      queued: AtomicBool::new(false),
+      ready: self.ready.clone(),
+    });
+    let entry = Entry { future: Box::pin(future), waker };
+
+    if index == self.entries.len() {
+      self.entries.push(Some(entry));
+    } else {
+      self.entries[index] = Some(entry);
+    }
+    self.len += 1;
+
+    self.entries[index].as_ref().unwrap().waker.wake_by_ref();
+  }
+
+  /// Polls the futures which have been woken, without yielding their outputs: the outputs of the
+  /// futures which complete are kept, and yielded by the next polls of the stream. Returns
+  /// [`Poll::Ready`] once every future has completed.
+  ///
+  /// For a set which is there for what its futures do rather than what they return, which still
+  /// has to make progress between two outputs, or without any.
+  ///
+  /// Every future is polled at most once per call, and no more than 64 in all: past that the
+  /// rest wait for the next call, and the waker of `cx` is woken so there is one. A future which
+  /// is always ready goes behind the ones woken before it, and can't starve them or the caller.
+  pub fn poll_progress(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+    self.register(cx);
+    let mut batch = std::mem::take(&mut *self.ready.indices.lock().unwrap());
+
+    let mut polled = 0;
+    while polled < POLL_BUDGET {
+      let Some(index) = batch.pop_front() else { break };
+      if let Some(poll) = self.poll_index(index) {
+        polled += 1;
+        if let Poll::Ready(output) = poll {
+          self.completed.push_back(output);
+        }
+      }
+    }
+    if !batch.is_empty() {
+      self.requeue(batch);
+      cx.waker().wake_by_ref();
+    }
+
+    match self.completed.len() == self.len {
+      true => Poll::Ready(()),
+      false => Poll::Pending,
+    }
+  }
+
+  fn register(&self, cx: &Context<'_>) {
+    let mut waker = self.ready.waker.lock().unwrap();
+    if !waker.as_ref().is_some_and(|waker| waker.will_wake(cx.waker())) {
+      *waker = Some(cx.waker().clone());
+    }
+  }
+
+  // Polls the future at `index`, and frees its entry once it completes. `None` for a stale index
+  // of a future which has completed.
+  fn poll_index(&mut self, index: usize) -> Option<Poll<F::Output>> {
+    let entry = self.entries[index].as_mut()?;
+    entry.waker.queued.store(false, Ordering::Release);
+    let waker = Waker::from(entry.waker.clone());
+
+    let poll = entry.future.as_mut().poll(&mut Context::from_waker(&waker));
+    if poll.is_ready() {
+      self.entries[index] = None;
+      self.free.push(index);
+    }
+    Some(poll)
+  }
+
+  // Puts back the rest of a batch, they are still queued.
+  fn requeue(&self, batch: VecDeque<usize>) {
+    let mut indices = self.ready.indices.lock().unwrap();
+    for index in batch.into_iter().rev() {
+      indices.push_front(index);
+    }
+  }
+}
+
+impl<F: Future> Stream for FuturesUnordered<F> {
+  type Item = F::Output;
+
+  fn poll_next(
+    mut self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<Option<Self::Item>> {
+    if let Some(output) = self.completed.pop_front() {
+      self.len -= 1;
+      return Poll::Ready(Some(output));
+    }
+    if self.is_empty() {
+      return Poll::Ready(None);
+    }
+
+    self.register(cx);
+
+    // Only the futures which are ready right now, futures woken while polling are left for the
+    // next poll so a future which keeps waking itself can't starve the caller.
+    let mut batch = std::mem::take(&mut *self.ready.indices.lock().unwrap());
+
+    while let Some(index) = batch.pop_front() {
+      if let Some(Poll::Ready(output)) = self.poll_index(index) {
+        self.len -= 1;
+        self.requeue(batch);
+        return Poll::Ready(Some(output));
+      }
+    }
+
+    Poll::Pending
+  }
+
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    (self.len, Some(self.len))
+  }
+}
+
+impl<F: Future> FromIterator<F> for FuturesUnordered<F> {
+  fn from_iter<I: IntoIterator<Item = F>>(iter: I) -> Self {
+    let mut set = FuturesUnordered::new();
+    iter.into_iter().for_each(|future| set.push(future));
+    set
+  }
+}
+
+#[crate::internal_test]
+async fn completion_order() {
+  use crate::{stream::StreamExt, time};
+  use std::time::Duration;
+
+  time::pause();
+
+  let mut set: FuturesUnordered<_> = [3, 1, 2]
+    .into_iter()
+    .map(|n| async move {
+      time::sleep(Duration::from_secs(n)).await;
+      n
+    })
+    .collect();
+  assert_eq!(set.len(), 3);
+
+  let mut completed = Vec::new();
+  while let Some(n) = set.next().await {
+    completed.push(n);
+  }
+  assert_eq!(completed, [1, 2, 3]);
+  assert!(set.is_empty());
+}
+
+#[crate::internal_test]
+async fn progress_without_outputs() {
+  use crate::stream::StreamExt;
+  use std::{
+    future::poll_fn,
+    sync::atomic::{AtomicUsize, Ordering},
+  };
+
+  // Always ready to do more, so each would keep the set busy on its own.
+  let polls: Arc<Vec<AtomicUsize>> =
+    Arc::new((0..100).map(|_| AtomicUsize::new(0)).collect());
+  let mut set: FuturesUnordered<_> = (0..100)
+    .map(|n| {
+      let polls = polls.clone();
+      poll_fn(move |cx| {
+        polls[n].fetch_add(1, Ordering::SeqCst);
+        cx.waker().wake_by_ref();
+        Poll::<()>::Pending
+      })
+    })
+    .collect();
+  let mut cx = Context::from_waker(Waker::noop());
+  for _ in 0..20 {
+    assert!(set.poll_progress(&mut cx).is_pending());
+  }
+  // 20 calls of 64 polls each, shared between all of them.
+  for polls in polls.iter() {
+    assert!(polls.load(Ordering::SeqCst) >= 12);
+  }
+
+  // Outputs are kept for the stream.
+  let mut set: FuturesUnordered<_> = (0..3).map(|n| async move { n }).collect();
+  assert!(set.poll_progress(&mut cx).is_ready());
+  assert_eq!(set.len(), 3);
+  let mut outputs = vec![];
+  while let Some(n) = set.next().await {
+    outputs.push(n);
+  }
+  assert_eq!(outputs, [0, 1, 2]);
+}
@@ -0,0 +1,259 @@
+use std::{
+  future::Future,
+  pin::Pin,
+  task::{Context, Poll},
+};
+
+use pin_project_lite::pin_project;
+
+use super::{FuturesUnordered, MaybeDone};
+use crate::stream::Stream;
+
+// Below this, polling every future on each wake is cheaper than the bookkeeping of
+// `FuturesUnordered`.
+const SMALL_LIMIT: usize = 30;
+
+/// Waits for all futures to complete, and returns their outputs in the same order as the input.
+pub fn join_all<I>(iter: I) -> JoinAll<I::Item>
+where
+  I: IntoIterator,
+  I::Item: Future,
+{
+  JoinAll { inner: Collect::new(iter) }
+}
+
+/// Waits for all futures to complete successfully, and returns their outputs in the same order as
+/// the input.
+///
+/// Returns the first error, in completion order, as soon as it happens. The futures which are
+/// still running are dropped right away.
+pub fn try_join_all<I, T, E>(iter: I) -> TryJoinAll<I::Item>
+where
+  I: IntoIterator,
+  I::Item: Future<Output = Result<T, E>>,
+{
+  TryJoinAll { inner: Collect::new(iter) }
+}
+
+/// Future returned by [`join_all`].
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct JoinAll<F: Future> {
+  inner: Collect<F>,
+}
+
+/// Future returned by [`try_join_all`].
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct TryJoinAll<F: Future> {
+  inner: Collect<F>,
+}
+
+impl<F: Future> Future for JoinAll<F> {
+  type Output = Vec<F::Output>;
+
+  fn poll(
+    mut self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<Self::Output> {
+    self.inner.poll(cx, |_| false).map(|outputs| match outputs {
+      Ok(outputs) => outputs,
+      Err(_) => unreachable!("join_all never stops early"),
+    })
+  }
+}
+
+impl<F, T, E> Future for TryJoinAll<F>
+where
+  F: Future<Output = Result<T, E>>,
+{
+  type Output = Result<Vec<T>, E>;
+
+  fn poll(
+    mut self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<Self::Output> {
+    self.inner.poll(cx, Result::is_err).map(|outputs| match outputs {
+      Ok(outputs) => outputs.into_iter().collect(),
+      Err(Err(err)) => Err(err),
+      Err(Ok(_)) => unreachable!("only errors stop early"),
+    })
+  }
+}
+
+// The outputs are never pinned, and the futures are boxed.
+impl<F: Future> Unpin for JoinAll<F> {}
+impl<F: Future> Unpin for TryJoinAll<F> {}
+
+enum Collect<F: Future> {
+  Small(Pin<Box<[MaybeDone<F>]>>),
+  Big { futures: FuturesUnordered<Indexed<F>>, outputs: Vec<Option<F::Output>> },
+  Done,
+}
+
+impl<F: Future> Collect<F> {
+  fn new<I: IntoIterator<Item = F>>(iter: I) -> Self {
+    let iter = iter.into_iter();
+    if iter.size_hint().1.is_some_and(|upper| upper <= SMALL_LIMIT) {
+      let futures: Box<[_]> = iter.map(MaybeDone::new).collect();
+      return Collect::Small(futures.into());
+    }
+
+    let futures: FuturesUnordered<_> = iter
+      .enumerate()
+      .map(|(index, future)| Indexed { index, future })
+      .collect();
+    let outputs = (0..futures.len()).map(|_| None).collect();
+    Collect::Big { futures, outputs }
+  }
+
+  // Collects the outputs, or stops with the first one `stop` returns true for.
+  fn poll(
+    &mut self,
+    cx: &mut Context<'_>,
+    stop: impl Fn(&F::Output) -> bool,
+  ) -> Poll<Result<Vec<F::Output>, F::Output>> {
+    let result = match self {
+      Collect::Small(futures) => {
+        let mut all_done = true;
+        let mut stopped = None;
+        for mut future in iter_pin_mut(futures.as_mut()) {
+          if future.as_mut().poll(cx).is_pending() {
+            all_done = false;
+          } else if future.as_mut().output_mut().is_some_and(|out| stop(out)) {
+            stopped = future.take_output();
+            break;
+          }
+        }
+        if let Some(output) = stopped {
+          *self = Collect::Done;
+          return Poll::Ready(Err(output));
+        }
+        if !all_done {
+          return Poll::Pending;
+        }
+        iter_pin_mut(futures.as_mut())
+          .map(|future| future.take_output().unwrap())
+          .collect()
+      }
+      Collect::Big { futures, outputs } => loop {
+        match std::task::ready!(Pin::new(&mut *futures).poll_next(cx)) {
+          Some((_, output)) if stop(&output) => {
+            *self = Collect::Done;
+            return Poll::Ready(Err(output));
+          }
+          Some((index, output)) => outputs[index] = Some(output),
+          None => {
+            break outputs.iter_mut().map(|out| out.take().unwrap()).collect()
+          }
+        }
+      },
+      Collect::Done => panic!("JoinAll polled after completion"),
+    };
+
+    *self = Collect::Done;
+    Poll::Ready(Ok(result))
+  }
+}
+
+fn iter_pin_mut<T>(slice: Pin<&mut [T]>) -> impl Iterator<Item = Pin<&mut T>> {
+  // SAFETY: the elements are never moved, only pinned one by one.
+  unsafe { slice.get_unchecked_mut() }
+    .iter_mut()
+    .map(|t| unsafe { Pin::new_unchecked(t) })
+}
+
+pin_project! {
+  // Keeps track of where the output goes in the result.
+  struct Indexed<F> {
+    index: usize,
+    #[pin]
+    future: F,
+  }
+}
+
+impl<F: Future> Future for Indexed<F> {
+  type Output = (usize, F::Output);
+
+  fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+    let this = self.project();
+    this.future.poll(cx).map(|output| (*this.index, output))
+  }
+}
+
+#[cfg(test)]
+async fn sleep_then(secs: u64) -> u64 {
+  crate::time::sleep(std::time::Duration::from_secs(secs)).await;
+  secs
+}
+
+#[crate::internal_test]
+async fn input_order() {
+  crate::time::pause();
+
+  let outputs = join_all([3, 1, 2].map(sleep_then)).await;
+  assert_eq!(outputs, [3, 1, 2]);
+}
+
+#[crate::internal_test]
+async fn empty() {
+  let outputs = join_all(Vec::<std::future::Ready<()>>::new()).await;
+  assert!(outputs.is_empty());
+
+  let outputs =
+    try_join_all(Vec::<std::future::Ready<Result<(), ()>>>::new()).await;
+  assert_eq!(outputs, Ok(Vec::new()));
+}
+
+#[crate::internal_test]
+async fn error_drops_siblings() {
+  use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+  };
+
+  struct CountDrop(Arc<AtomicUsize>);
+
+  impl Drop for CountDrop {
+    fn drop(&mut self) {
+      self.0.fetch_add(1, Ordering::Relaxed);
+    }
+  }
+
+  crate::time::pause();
+  let dropped = Arc::new(AtomicUsize::new(0));
+
+  for count in [3, SMALL_LIMIT * 2] {
+    dropped.store(0, Ordering::Relaxed);
+
+    let futures = (0..count).map(|n| {
+      let guard = CountDrop(dropped.clone());
+      async move {
+        let _guard = guard;
+        if n == 1 {
+          sleep_then(1).await;
+          return Err(n);
+        }
+        std::future::pending::<()>().await;
+        Ok(n)
+      }
+    });
+
+    assert_eq!(try_join_all(futures).await, Err(1));
+    assert_eq!(dropped.load(Ordering::Relaxed), count);
+  }
+}
+
+#[crate::internal_test]
+async fn large_input() {
+  crate::time::pause();
+
+  let count = SMALL_LIMIT as u64 * 4;
+  let join = join_all((0..count).rev().map(sleep_then));
+  assert!(matches!(join.inner, Collect::Big { .. }));
+
+  let outputs = join.await;
+  assert_eq!(outputs, (0..count).rev().collect::<Vec<_>>());
+
+  let join = try_join_all((0..count).map(|n| async move { Ok::<_, ()>(n) }));
+  assert!(matches!(join.inner, Collect::Big { .. }));
+  assert_eq!(join.await, Ok((0..count).collect()));
+}
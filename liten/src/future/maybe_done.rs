@@ -0,0 +1,97 @@
+use std::{
+  future::Future,
+  pin::Pin,
+  task::{Context, Poll},
+};
+
+use pin_project_lite::pin_project;
+
+/// Wraps `future` so its output is kept after it completes, see [`MaybeDone`].
+pub fn maybe_done<F: Future>(future: F) -> MaybeDone<F> {
+  MaybeDone::new(future)
+}
+
+pin_project! {
+  /// A future which keeps its output after completing, until it's taken.
+  ///
+  /// Awaiting it only drives the inner future, the output is taken with
+  /// [`MaybeDone::take_output`]. Polling it again once it's done is a no-op.
+  #[project = MaybeDoneProj]
+  #[project_replace = MaybeDoneProjReplace]
+  pub enum MaybeDone<F: Future> {
+    Future { #[pin] future: F },
+    Done { output: F::Output },
+    // The output has been taken.
+    Gone,
+  }
+}
+
+impl<F: Future> MaybeDone<F> {
+  pub fn new(future: F) -> Self {
+    MaybeDone::Future { future }
+  }
+
+  /// The output, if the future has completed and it hasn't been taken yet.
+  pub fn output_mut(self: Pin<&mut Self>) -> Option<&mut F::Output> {
+    match self.project() {
+      MaybeDoneProj::Done { output } => Some(output),
+      _ => None,
+    }
+  }
+
+  /// Takes the output, if the future has completed. Only the first call after completion returns
+  /// it.
+  pub fn take_output(self: Pin<&mut Self>) -> Option<F::Output> {
+    if !matches!(*self, MaybeDone::Done { .. }) {
+      return None;
+    }
+    match self.project_replace(MaybeDone::Gone) {
+      MaybeDoneProjReplace::Done { output } => Some(output),
+      _ => unreachable!(),
+    }
+  }
+}
+
+impl<F: Future> Future for MaybeDone<F> {
+  type Output = ();
+
+  fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+    let output = match self.as_mut().project() {
+      MaybeDoneProj::Future { future } => std::task::ready!(future.poll(cx)),
+      MaybeDoneProj::Done { .. } => return Poll::Ready(()),
+      // Polling it again could start the future over, or lose the output.
+      MaybeDoneProj::Gone => {
+        panic!("MaybeDone polled after its output was taken")
+      }
+    };
+    self.set(MaybeDone::Done { output });
+    Poll::Ready(())
+  }
+}
+
+#[test]
+fn take_output_once() {
+  let waker = std::task::Waker::noop();
+  let mut cx = Context::from_waker(waker);
+
+  let mut pending = true;
+  let mut future = std::pin::pin!(maybe_done(std::future::poll_fn(|cx| {
+    if std::mem::take(&mut pending) {
+      cx.waker().wake_by_ref();
+      return Poll::Pending;
+    }
+    Poll::Ready(3)
+  })));
+
+  assert!(future.as_mut().poll(&mut cx).is_pending());
+  assert_eq!(future.as_mut().take_output(), None);
+
+  assert!(future.as_mut().poll(&mut cx).is_ready());
+  // Polling a done future again keeps the output.
+  assert!(future.as_mut().poll(&mut cx).is_ready());
+  assert_eq!(future.as_mut().output_mut(), Some(&mut 3));
+
+  assert_eq!(future.as_mut().take_output(), Some(3));
+  assert_eq!(future.as_mut().take_output(), None);
+  assert!(matches!(*future, MaybeDone::Gone));
+}
@@ -0,0 +1,60 @@
+//! Utilities for working with futures.
+mod abortable;
+mod fuse;
+mod futures_unordered;
+mod join_all;
+mod maybe_done;
+mod on_cancel;
+mod select;
+mod select_all;
+mod shared;
+
+pub use abortable::{
+  abortable, AbortHandle, AbortRegistration, Abortable, Aborted,
+};
+pub use fuse::Fuse;
+pub use futures_unordered::FuturesUnordered;
+pub use join_all::{join_all, try_join_all, JoinAll, TryJoinAll};
+pub use maybe_done::{maybe_done, MaybeDone};
+pub use on_cancel::OnCancel;
+#[doc(hidden)]
+pub use select::__random_branch;
+pub use select_all::{select_all, SelectAll};
+pub use shared::Shared;
+
+use std::future::Future;
+
+/// Combinators for [`Future`]s, implemented for every future.
+pub trait FutureExt: Future {
+  /// Turns the future into one which can be cloned, every clone completes with a clone of the
+  /// output of a single execution.
+  fn shared(self) -> Shared<Self>
+  where
+    Self: Sized,
+    Self::Output: Clone,
+  {
+    Shared::new(self)
+  }
+
+  /// Calls `cleanup` if the future is dropped before it completes, like to give back something
+  /// it has reserved. A future which completes drops `cleanup` without calling it.
+  fn on_cancel<C>(self, cleanup: C) -> OnCancel<Self, C>
+  where
+    Self: Sized,
+    C: FnOnce(),
+  {
+    OnCancel::new(self, cleanup)
+  }
+
+  /// Makes the future return `Pending` forever once it has completed, instead of whatever it
+  /// does when it's polled again, which for many futures is to panic. Needed to poll a future
+  /// from a `select!` in a loop after its branch has run.
+  fn fuse(self) -> Fuse<Self>
+  where
+    Self: Sized,
+  {
+    Fuse::new(self)
+  }
+}
+
+impl<F: Future + ?Sized> FutureExt for F {}
@@ -0,0 +1,72 @@
+use std::{
+  future::Future,
+  pin::Pin,
+  task::{Context, Poll},
+};
+
+use pin_project_lite::pin_project;
+
+pin_project! {
+  /// Future returned by [`FutureExt::on_cancel`](super::FutureExt::on_cancel).
+  #[must_use = "futures do nothing unless you `.await` or poll them"]
+  pub struct OnCancel<F, C>
+  where
+    C: FnOnce(),
+  {
+    #[pin]
+    future: F,
+    // Taken once the future completes.
+    cleanup: Option<C>,
+  }
+
+  impl<F, C> PinnedDrop for OnCancel<F, C>
+  where
+    C: FnOnce(),
+  {
+    fn drop(this: Pin<&mut Self>) {
+      if let Some(cleanup) = this.project().cleanup.take() {
+        cleanup();
+      }
+    }
+  }
+}
+
+impl<F, C: FnOnce()> OnCancel<F, C> {
+  pub(super) fn new(future: F, cleanup: C) -> OnCancel<F, C> {
+    OnCancel { future, cleanup: Some(cleanup) }
+  }
+}
+
+impl<F: Future, C: FnOnce()> Future for OnCancel<F, C> {
+  type Output = F::Output;
+
+  fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+    let this = self.project();
+    let output = std::task::ready!(this.future.poll(cx));
+    this.cleanup.take();
+    Poll::Ready(output)
+  }
+}
+
+#[crate::internal_test]
+async fn only_when_cancelled() {
+  use super::FutureExt;
+  use crate::{sync::oneshot, test_util::task};
+  use std::{cell::Cell, rc::Rc};
+
+  let cancelled = Rc::new(Cell::new(false));
+  let (_sender, receiver) = oneshot::channel::<()>();
+  let reservation = cancelled.clone();
+  let mut future =
+    task::spawn(receiver.on_cancel(move || reservation.set(true)));
+  assert!(future.poll().is_pending());
+  assert!(!cancelled.get());
+  drop(future);
+  assert!(cancelled.get());
+
+  let cancelled = Rc::new(Cell::new(false));
+  let reservation = cancelled.clone();
+  let output = async { 7 }.on_cancel(move || reservation.set(true)).await;
+  assert_eq!(output, 7);
+  assert!(!cancelled.get());
+}
@@ -0,0 +1,130 @@
+use std::{
+  cell::Cell,
+  hash::{BuildHasher, RandomState},
+};
+
+/// Waits on several futures at once, and runs the handler of the first one to complete.
+///
+/// Every branch is `pattern = future => handler`, where the future can be anything implementing
+/// [`IntoFuture`](std::future::IntoFuture). The futures are evaluated up front and polled in
+/// place, the ones which don't win are dropped once a handler runs. A value which doesn't match
+/// its pattern disables that branch, and the rest keep going. When every branch is disabled, the
+/// `else => handler` branch runs, which panics if there isn't one.
+///
+/// Branches are polled starting from a random one on every poll, so a future which is always
+/// ready can't starve the others. With `biased;` ahead of the branches they are polled in the
+/// order they are written instead, which gives a branch like a shutdown signal priority over
+/// the others.
+///
+/// Biased mode means that an earlier branch which is always ready wins every time, and the ones
+/// below it never run. Only put branches first which are rarely ready.
+///
+/// ```
+/// use liten::sync::oneshot;
+///
+/// # liten::runtime::Runtime::new().block_on(async {
+/// let (stop, stopped) = oneshot::channel::<()>();
+/// stop.send(()).unwrap();
+///
+/// let message = liten::select! {
+///   biased;
+///   _ = stopped => None,
+///   message = std::future::ready("data") => Some(message),
+/// };
+/// assert_eq!(message, None);
+/// # });
+/// ```
+#[macro_export]
+macro_rules! select {
+  ($($input:tt)*) => {
+    $crate::__select!($crate; $($input)*)
+  };
+}
+
+/// Picks the branch `select!` starts polling from.
+#[doc(hidden)]
+pub fn __random_branch(branches: usize) -> usize {
+  std::thread_local! {
+    static STATE: Cell<u64> =
+      Cell::new(RandomState::new().hash_one(std::thread::current().id()) | 1);
+  }
+
+  STATE.with(|state| {
+    // xorshift64
+    let mut x = state.get();
+    x ^= x << 13;
+    x ^= x >> 7;
+    x ^= x << 17;
+    state.set(x);
+    (x % branches as u64) as usize
+  })
+}
+
+#[crate::internal_test]
+async fn biased_picks_first_ready() {
+  use std::future::ready;
+
+  for _ in 0..100 {
+    let picked = crate::select! {
+      biased;
+      first = ready(1) => first,
+      second = ready(2) => second,
+      third = ready(3) => third,
+    };
+    assert_eq!(picked, 1);
+  }
+}
+
+#[crate::internal_test]
+async fn fair_picks_every_branch() {
+  use std::future::ready;
+
+  let mut picked = [0; 3];
+  for _ in 0..300 {
+    let index = crate::select! {
+      index = ready(0) => index,
+      index = ready(1) => index,
+      index = ready(2) => index,
+    };
+    picked[index] += 1;
+  }
+  assert!(picked.iter().all(|count| *count > 0), "{picked:?}");
+}
+
+#[crate::internal_test]
+async fn pending_branch_loses() {
+  use std::{future::pending, time::Duration};
+
+  let picked = crate::select! {
+    () = pending::<()>() => "pending",
+    () = crate::time::sleep(Duration::from_millis(1)) => "sleep",
+  };
+  assert_eq!(picked, "sleep");
+}
+
+#[crate::internal_test]
+async fn mismatch_disables_branch() {
+  use std::future::{pending, ready};
+
+  let picked = crate::select! {
+    Some(value) = ready(None) => value,
+    () = pending::<()>() => 0,
+    Some(value) = ready(Some(2)) => value,
+  };
+  assert_eq!(picked, 2);
+
+  let picked = crate::select! {
+    Some(value) = ready(None::<i32>) => value,
+    else => -1,
+  };
+  assert_eq!(picked, -1);
+}
+
+#[crate::internal_test]
+async fn into_future_branch() {
+  let picked = crate::select! {
+    value = crate::task::spawn(async { 1 }) => value.unwrap(),
+    () = std::future::pending::<()>() => 0,
+  };
+  assert_eq!(picked, 1);
+}
@@ -0,0 +1,118 @@
+use std::{
+  future::Future,
+  pin::Pin,
+  task::{Context, Poll},
+};
+
+/// Waits for the first of `iter` to complete.
+///
+/// Resolves to its output, its index, and the futures which are still running, in their original
+/// order without the completed one. Those are only moved, so they keep the wakers they registered,
+/// and can be passed to `select_all` again.
+///
+/// # Panics
+///
+/// Panics if `iter` is empty.
+pub fn select_all<I>(iter: I) -> SelectAll<I::Item>
+where
+  I: IntoIterator,
+  I::Item: Future + Unpin,
+{
+  let inner: Vec<_> = iter.into_iter().collect();
+  assert!(!inner.is_empty(), "select_all needs at least one future");
+  SelectAll { inner }
+}
+
+/// Future returned by [`select_all`].
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+#[derive(Debug)]
+pub struct SelectAll<F> {
+  inner: Vec<F>,
+}
+
+impl<F: Unpin> Unpin for SelectAll<F> {}
+
+impl<F: Future + Unpin> SelectAll<F> {
+  /// The futures still running.
+  pub fn into_inner(self) -> Vec<F> {
+    self.inner
+  }
+}
+
+impl<F: Future + Unpin> Future for SelectAll<F> {
+  type Output = (F::Output, usize, Vec<F>);
+
+  fn poll(
+    mut self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<Self::Output> {
+    let ready =
+      self.inner.iter_mut().enumerate().find_map(|(index, future)| {
+        match Pin::new(future).poll(cx) {
+          Poll::Ready(output) => Some((index, output)),
+          Poll::Pending => None,
+        }
+      });
+
+    match ready {
+      Some((index, output)) => {
+        let mut rest = std::mem::take(&mut self.inner);
+        rest.remove(index);
+        Poll::Ready((output, index, rest))
+      }
+      None => Poll::Pending,
+    }
+  }
+}
+
+#[crate::internal_test]
+async fn drains_until_empty() {
+  use crate::time;
+  use std::time::Duration;
+
+  time::pause();
+
+  // Complete in the order 2, 0, 3, 1.
+  let delays = [20, 40, 10, 30];
+  let mut futures: Vec<_> = delays
+    .iter()
+    .map(|&ms| {
+      Box::pin(async move {
+        time::sleep(Duration::from_millis(ms)).await;
+        ms
+      })
+    })
+    .collect();
+
+  // Kept in step with the remaining futures, to check every index.
+  let mut remaining = delays.to_vec();
+  let mut order = Vec::new();
+  while !futures.is_empty() {
+    let (ms, index, rest) = select_all(futures).await;
+    assert_eq!(remaining.remove(index), ms);
+    order.push(ms);
+    futures = rest;
+  }
+
+  assert_eq!(order, [10, 20, 30, 40]);
+}
+
+#[crate::internal_test]
+async fn index_into_input() {
+  let futures = vec![
+    Box::pin(std::future::pending::<u8>())
+      as Pin<Box<dyn Future<Output = u8> + Send>>,
+    Box::pin(std::future::pending()),
+    Box::pin(std::future::ready(7)),
+    Box::pin(std::future::pending()),
+  ];
+
+  let (output, index, rest) = select_all(futures).await;
+  assert_eq!((output, index, rest.len()), (7, 2, 3));
+}
+
+#[test]
+#[should_panic = "select_all needs at least one future"]
+fn empty() {
+  drop(select_all(Vec::<std::future::Ready<()>>::new()));
+}
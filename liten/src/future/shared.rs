@@ -0,0 +1,236 @@
+use std::{
+  collections::HashMap,
+  future::Future,
+  pin::Pin,
+  sync::{Arc, Mutex as StdMutex},
+  task::{Context, Poll, Wake, Waker},
+};
+
+/// Future returned by [`FutureExt::shared`](super::FutureExt::shared).
+///
+/// Every clone waits for the same execution of the inner future. Whichever clone is polled drives
+/// the inner future, so it keeps going as long as any clone is waiting on it, even if the clone
+/// which started it is dropped.
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Shared<F: Future> {
+  inner: Arc<Inner<F>>,
+  // Id of this clone's waker, once registered.
+  slot: Option<usize>,
+}
+
+struct Inner<F: Future> {
+  // This is not a bottleneck
+  slot: StdMutex<Slot<F>>,
+  // Given to the inner future as its waker. Waiters register while holding the `slot` lock, so
+  // they can't miss the wake after the output is set.
+  notifier: Arc<Notifier>,
+}
+
+enum Slot<F: Future> {
+  Future(Pin<Box<F>>),
+  // Taken out by the clone polling it right now.
+  Polling,
+  Done(F::Output),
+  Panicked,
+}
+
+#[derive(Default)]
+struct Notifier {
+  waiters: StdMutex<Waiters>,
+}
+
+#[derive(Default)]
+struct Waiters {
+  wakers: HashMap<usize, Waker>,
+  next_id: usize,
+}
+
+impl Notifier {
+  fn register(&self, slot: &mut Option<usize>, waker: &Waker) {
+    let mut waiters = self.waiters.lock().unwrap();
+    let id = *slot.get_or_insert_with(|| {
+      waiters.next_id += 1;
+      waiters.next_id
+    });
+    match waiters.wakers.get(&id) {
+      Some(registered) if registered.will_wake(waker) => {}
+      _ => {
+        waiters.wakers.insert(id, waker.clone());
+      }
+    }
+  }
+
+  fn remove(&self, slot: &mut Option<usize>) {
+    if let Some(id) = slot.take() {
+      self.waiters.lock().unwrap().wakers.remove(&id);
+    }
+  }
+}
+
+impl Wake for Notifier {
+  fn wake(self: Arc<Self>) {
+    self.wake_by_ref();
+  }
+
+  fn wake_by_ref(self: &Arc<Self>) {
+    let wakers = std::mem::take(&mut self.waiters.lock().unwrap().wakers);
+    wakers.into_values().for_each(Waker::wake);
+  }
+}
+
+impl<F: Future> Shared<F>
+where
+  F::Output: Clone,
+{
+  pub(super) fn new(future: F) -> Self {
+    let inner = Inner {
+      slot: StdMutex::new(Slot::Future(Box::pin(future))),
+      notifier: Arc::default(),
+    };
+    Shared { inner: Arc::new(inner), slot: None }
+  }
+
+  /// The output, if the inner future has completed.
+  pub fn peek(&self) -> Option<F::Output> {
+    match &*self.inner.slot.lock().unwrap() {
+      Slot::Done(output) => Some(output.clone()),
+      _ => None,
+    }
+  }
+}
+
+impl<F: Future> Clone for Shared<F> {
+  fn clone(&self) -> Self {
+    Shared { inner: self.inner.clone(), slot: None }
+  }
+}
+
+impl<F: Future> Unpin for Shared<F> {}
+
+impl<F> Future for Shared<F>
+where
+  F: Future,
+  F::Output: Clone,
+{
+  type Output = F::Output;
+
+  fn poll(
+    mut self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<Self::Output> {
+    let this = &mut *self;
+    let inner = &*this.inner;
+    let mut slot = inner.slot.lock().unwrap();
+
+    let mut future = match std::mem::replace(&mut *slot, Slot::Polling) {
+      Slot::Future(future) => future,
+      Slot::Done(output) => {
+        *slot = Slot::Done(output.clone());
+        inner.notifier.remove(&mut this.slot);
+        return Poll::Ready(output);
+      }
+      // Another clone is driving it, and wakes every waiter when it's done.
+      Slot::Polling => {
+        inner.notifier.register(&mut this.slot, cx.waker());
+        return Poll::Pending;
+      }
+      Slot::Panicked => {
+        *slot = Slot::Panicked;
+        panic!("Shared future panicked while being polled");
+      }
+    };
+
+    // Registered before the inner future is polled, so a wake while it's being polled isn't
+    // missed.
+    inner.notifier.register(&mut this.slot, cx.waker());
+    drop(slot);
+
+    // Other clones would wait forever on `Polling` if this panics.
+    struct Bomb<'a, F: Future>(Option<&'a Inner<F>>);
+    impl<F: Future> Drop for Bomb<'_, F> {
+      fn drop(&mut self) {
+        if let Some(inner) = self.0 {
+          *inner.slot.lock().unwrap() = Slot::Panicked;
+          inner.notifier.wake_by_ref();
+        }
+      }
+    }
+
+    let waker = Waker::from(inner.notifier.clone());
+    let mut bomb = Bomb(Some(inner));
+    let poll = future.as_mut().poll(&mut Context::from_waker(&waker));
+    bomb.0 = None;
+
+    let mut slot = inner.slot.lock().unwrap();
+    match poll {
+      Poll::Ready(output) => {
+        *slot = Slot::Done(output.clone());
+        drop(slot);
+        inner.notifier.remove(&mut this.slot);
+        inner.notifier.wake_by_ref();
+        Poll::Ready(output)
+      }
+      Poll::Pending => {
+        *slot = Slot::Future(future);
+        Poll::Pending
+      }
+    }
+  }
+}
+
+impl<F: Future> Drop for Shared<F> {
+  fn drop(&mut self) {
+    self.inner.notifier.remove(&mut self.slot);
+  }
+}
+
+#[crate::internal_test]
+async fn one_execution() {
+  use super::FutureExt;
+  use std::sync::atomic::{AtomicUsize, Ordering};
+
+  let executions = Arc::new(AtomicUsize::new(0));
+  let counter = executions.clone();
+  let shared = async move {
+    counter.fetch_add(1, Ordering::Relaxed);
+    crate::time::sleep(std::time::Duration::from_millis(10)).await;
+    String::from("config")
+  }
+  .shared();
+
+  let handles: Vec<_> =
+    (0..16).map(|_| crate::task::spawn(shared.clone())).collect();
+  for handle in handles {
+    assert_eq!(handle.await.unwrap(), "config");
+  }
+
+  assert_eq!(shared.peek().as_deref(), Some("config"));
+  assert_eq!(shared.await, "config");
+  assert_eq!(executions.load(Ordering::Relaxed), 1);
+}
+
+#[crate::internal_test]
+async fn driver_dropped() {
+  use super::FutureExt;
+  use crate::sync::oneshot;
+
+  let (sender, receiver) = oneshot::channel();
+  let shared = async move { receiver.await.unwrap() }.shared();
+
+  // The first clone starts the inner future, and is dropped while it's pending.
+  let mut driver = shared.clone();
+  let waker = Waker::noop();
+  assert!(Pin::new(&mut driver)
+    .poll(&mut Context::from_waker(waker))
+    .is_pending());
+  drop(driver);
+
+  let other = crate::task::spawn(shared.clone());
+  std::thread::spawn(move || {
+    std::thread::sleep(std::time::Duration::from_millis(10));
+    sender.send(7).unwrap();
+  });
+
+  assert_eq!(other.await.unwrap(), 7);
+  assert_eq!(shared.peek(), Some(7));
+}
@@ -0,0 +1,223 @@
+use std::{
+  future::Future,
+  io,
+  pin::Pin,
+  task::{Context, Poll},
+};
+
+use super::AsyncWrite;
+
+const DEFAULT_CAPACITY: usize = 8 * 1024;
+
+/// Gathers small writes into a buffer, and writes them to the inner writer together.
+///
+/// # Ordering
+///
+/// Bytes reach the inner writer in the order they were written, and only in two cases: when a
+/// write doesn't fit in what's left of the buffer, which writes the buffer out first, and on a
+/// flush. Until then, everything written stays in the buffer.
+///
+/// So when several writers share a downstream, like clones of a
+/// [`TcpStreamHandle`](crate::net::TcpStreamHandle), the order of their output downstream is the
+/// order they're flushed in, as long as what each writes between two flushes fits in its buffer.
+/// [`flush_in_order`] flushes a set of them as a barrier, which gives frames multiplexed over one
+/// connection an order which doesn't depend on which writer got to run first.
+pub struct BufWriter<W> {
+  inner: W,
+  buf: Vec<u8>,
+  capacity: usize,
+  // How much of the front of `buf` has been written to `inner` already.
+  written: usize,
+}
+
+impl<W> BufWriter<W> {
+  /// Wraps `inner` with a buffer of 8 KiB.
+  pub fn new(inner: W) -> BufWriter<W> {
+    BufWriter::with_capacity(DEFAULT_CAPACITY, inner)
+  }
+
+  pub fn with_capacity(capacity: usize, inner: W) -> BufWriter<W> {
+    BufWriter { inner, buf: Vec::with_capacity(capacity), capacity, written: 0 }
+  }
+
+  pub fn get_ref(&self) -> &W {
+    &self.inner
+  }
+
+  /// Writing to the inner writer directly skips over what's still buffered.
+  pub fn get_mut(&mut self) -> &mut W {
+    &mut self.inner
+  }
+
+  /// The bytes which haven't been written to the inner writer yet.
+  pub fn buffer(&self) -> &[u8] {
+    &self.buf[self.written..]
+  }
+
+  /// Returns the inner writer, and drops what's still buffered.
+  pub fn into_inner(self) -> W {
+    self.inner
+  }
+}
+
+impl<W: AsyncWrite + Unpin> BufWriter<W> {
+  /// Writes everything buffered so far to the inner writer, and flushes it.
+  ///
+  /// This is a barrier: every byte written before it has reached the inner writer once it
+  /// completes, and none written after it can get there first.
+  pub fn flush_barrier(&mut self) -> FlushBarrier<'_, W> {
+    FlushBarrier { writer: self }
+  }
+
+  fn poll_write_buf(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+    while self.written < self.buf.len() {
+      let buf = &self.buf[self.written..];
+      match std::task::ready!(Pin::new(&mut self.inner).poll_write(cx, buf)) {
+        Ok(0) => return Poll::Ready(Err(io::ErrorKind::WriteZero.into())),
+        Ok(written) => self.written += written,
+        Err(err) => return Poll::Ready(Err(err)),
+      }
+    }
+    self.buf.clear();
+    self.written = 0;
+    Poll::Ready(Ok(()))
+  }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for BufWriter<W> {
+  fn poll_write(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+    buf: &[u8],
+  ) -> Poll<io::Result<usize>> {
+    let this = self.get_mut();
+    if this.buf.len() + buf.len() > this.capacity {
+      std::task::ready!(this.poll_write_buf(cx))?;
+    }
+    // Too large to be worth a copy.
+    if buf.len() >= this.capacity {
+      return Pin::new(&mut this.inner).poll_write(cx, buf);
+    }
+    this.buf.extend_from_slice(buf);
+    Poll::Ready(Ok(buf.len()))
+  }
+
+  fn poll_flush(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<io::Result<()>> {
+    let this = self.get_mut();
+    std::task::ready!(this.poll_write_buf(cx))?;
+    Pin::new(&mut this.inner).poll_flush(cx)
+  }
+
+  fn poll_shutdown(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<io::Result<()>> {
+    let this = self.get_mut();
+    std::task::ready!(this.poll_write_buf(cx))?;
+    Pin::new(&mut this.inner).poll_shutdown(cx)
+  }
+}
+
+/// Future returned by [`BufWriter::flush_barrier`].
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct FlushBarrier<'a, W> {
+  writer: &'a mut BufWriter<W>,
+}
+
+impl<W: AsyncWrite + Unpin> Future for FlushBarrier<'_, W> {
+  type Output = io::Result<()>;
+
+  fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+    let this = self.get_mut();
+    Pin::new(&mut *this.writer).poll_flush(cx)
+  }
+}
+
+/// Flushes `writers` one after the other, in the order of the slice, see the
+/// [ordering](BufWriter#ordering) of a [`BufWriter`].
+///
+/// Stops at the first writer which fails, the ones after it are left as they were.
+pub async fn flush_in_order<W: AsyncWrite + Unpin>(
+  writers: &mut [&mut BufWriter<W>],
+) -> io::Result<()> {
+  for writer in writers {
+    writer.flush_barrier().await?;
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+async fn write(writer: &mut BufWriter<Sink>, buf: &[u8]) -> usize {
+  std::future::poll_fn(|cx| Pin::new(&mut *writer).poll_write(cx, buf))
+    .await
+    .unwrap()
+}
+
+// A downstream shared by several writers.
+#[cfg(test)]
+#[derive(Clone, Default)]
+struct Sink(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+#[cfg(test)]
+impl AsyncWrite for Sink {
+  fn poll_write(
+    self: Pin<&mut Self>,
+    _: &mut Context<'_>,
+    buf: &[u8],
+  ) -> Poll<io::Result<usize>> {
+    // A little at a time, like a socket with a full buffer.
+    let len = buf.len().min(3);
+    self.0.lock().unwrap().extend_from_slice(&buf[..len]);
+    Poll::Ready(Ok(len))
+  }
+
+  fn poll_flush(
+    self: Pin<&mut Self>,
+    _: &mut Context<'_>,
+  ) -> Poll<io::Result<()>> {
+    Poll::Ready(Ok(()))
+  }
+
+  fn poll_shutdown(
+    self: Pin<&mut Self>,
+    _: &mut Context<'_>,
+  ) -> Poll<io::Result<()>> {
+    Poll::Ready(Ok(()))
+  }
+}
+
+#[crate::internal_test]
+async fn barrier_orders_shared_downstream() {
+  let sink = Sink::default();
+  let mut frames = BufWriter::new(sink.clone());
+  let mut control = BufWriter::new(sink.clone());
+
+  // Interleaved, but nothing gets downstream before the barrier.
+  write(&mut frames, b"[frame 1]").await;
+  write(&mut control, b"[ping]").await;
+  write(&mut frames, b"[frame 2]").await;
+  write(&mut control, b"[window]").await;
+  assert!(sink.0.lock().unwrap().is_empty());
+  assert_eq!(frames.buffer(), b"[frame 1][frame 2]");
+
+  flush_in_order(&mut [&mut control, &mut frames]).await.unwrap();
+  assert_eq!(*sink.0.lock().unwrap(), b"[ping][window][frame 1][frame 2]");
+  assert!(frames.buffer().is_empty() && control.buffer().is_empty());
+}
+
+#[crate::internal_test]
+async fn full_buffer_writes_through() {
+  let sink = Sink::default();
+  let mut writer = BufWriter::with_capacity(8, sink.clone());
+
+  write(&mut writer, b"abcde").await;
+  // Doesn't fit, so the buffer goes first.
+  write(&mut writer, b"fghi").await;
+  assert_eq!(*sink.0.lock().unwrap(), b"abcde");
+  // Larger than the buffer, straight to the sink once the buffer is out.
+  assert_eq!(write(&mut writer, b"0123456789").await, 3);
+  assert_eq!(*sink.0.lock().unwrap(), b"abcdefghi012");
+}
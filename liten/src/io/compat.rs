@@ -0,0 +1,161 @@
+use std::{
+  io,
+  pin::Pin,
+  task::{Context, Poll},
+};
+
+use pin_project_lite::pin_project;
+
+use super::{AsyncRead, AsyncWrite};
+use crate::net::TcpStream;
+
+pin_project! {
+  /// Implements the [`futures_io`] traits for a type implementing liten's [`AsyncRead`] and
+  /// [`AsyncWrite`].
+  ///
+  /// liten's own io types implement the [`futures_io`] traits directly, this is for the types which
+  /// can't, like the ones in other crates.
+  #[derive(Debug)]
+  pub struct Compat<T> {
+    #[pin]
+    inner: T,
+  }
+}
+
+impl<T> Compat<T> {
+  pub fn new(inner: T) -> Self {
+    Compat { inner }
+  }
+
+  pub fn get_ref(&self) -> &T {
+    &self.inner
+  }
+
+  pub fn get_mut(&mut self) -> &mut T {
+    &mut self.inner
+  }
+
+  pub fn into_inner(self) -> T {
+    self.inner
+  }
+}
+
+impl<T: AsyncRead> futures_io::AsyncRead for Compat<T> {
+  fn poll_read(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+    buf: &mut [u8],
+  ) -> Poll<io::Result<usize>> {
+    self.project().inner.poll_read(cx, buf)
+  }
+}
+
+impl<T: AsyncWrite> futures_io::AsyncWrite for Compat<T> {
+  fn poll_write(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+    buf: &[u8],
+  ) -> Poll<io::Result<usize>> {
+    self.project().inner.poll_write(cx, buf)
+  }
+
+  fn poll_flush(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<io::Result<()>> {
+    self.project().inner.poll_flush(cx)
+  }
+
+  fn poll_close(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<io::Result<()>> {
+    self.project().inner.poll_shutdown(cx)
+  }
+}
+
+impl futures_io::AsyncRead for TcpStream {
+  fn poll_read(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+    buf: &mut [u8],
+  ) -> Poll<io::Result<usize>> {
+    AsyncRead::poll_read(self, cx, buf)
+  }
+}
+
+impl futures_io::AsyncWrite for TcpStream {
+  fn poll_write(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+    buf: &[u8],
+  ) -> Poll<io::Result<usize>> {
+    AsyncWrite::poll_write(self, cx, buf)
+  }
+
+  fn poll_flush(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<io::Result<()>> {
+    AsyncWrite::poll_flush(self, cx)
+  }
+
+  fn poll_close(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<io::Result<()>> {
+    AsyncWrite::poll_shutdown(self, cx)
+  }
+}
+
+// Written like a library which only knows about `futures_io`.
+#[cfg(test)]
+async fn echo<S>(mut stream: S, message: &[u8]) -> io::Result<Vec<u8>>
+where
+  S: futures_io::AsyncRead + futures_io::AsyncWrite + Unpin,
+{
+  use std::future::poll_fn;
+
+  let mut written = 0;
+  while written < message.len() {
+    written +=
+      poll_fn(|cx| Pin::new(&mut stream).poll_write(cx, &message[written..]))
+        .await?;
+  }
+  poll_fn(|cx| Pin::new(&mut stream).poll_close(cx)).await?;
+
+  let mut echoed = Vec::new();
+  let mut buf = [0; 16];
+  loop {
+    match poll_fn(|cx| Pin::new(&mut stream).poll_read(cx, &mut buf)).await? {
+      0 => return Ok(echoed),
+      n => echoed.extend_from_slice(&buf[..n]),
+    }
+  }
+}
+
+#[cfg(test)]
+fn echo_server() -> std::net::SocketAddr {
+  let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+  let addr = listener.local_addr().unwrap();
+  std::thread::spawn(move || {
+    let (mut stream, _) = listener.accept().unwrap();
+    std::io::copy(&mut stream.try_clone().unwrap(), &mut stream).unwrap();
+  });
+  addr
+}
+
+#[crate::internal_test]
+async fn tcp_stream_echo() {
+  let message = b"hello over futures-io, longer than one read";
+  let stream = TcpStream::connect(echo_server()).unwrap().await.unwrap();
+
+  assert_eq!(echo(stream, message).await.unwrap(), message);
+}
+
+#[crate::internal_test]
+async fn compat_echo() {
+  let stream = TcpStream::connect(echo_server()).unwrap().await.unwrap();
+
+  assert_eq!(echo(Compat::new(stream), b"wrapped").await.unwrap(), b"wrapped");
+}
@@ -0,0 +1,193 @@
+use std::{
+  collections::VecDeque,
+  io,
+  pin::Pin,
+  sync::{Arc, Mutex as StdMutex},
+  task::{Context, Poll, Waker},
+};
+
+use super::{AsyncRead, AsyncWrite};
+
+/// Creates two connected in-memory streams: what's written to one is read from the other.
+///
+/// At most `capacity` bytes wait in each direction, writes past that wait for the other end to
+/// read, like writes to a socket with a full buffer. Dropping or shutting down one end makes the
+/// other read EOF once it has read everything written before, and writing to an end whose other
+/// end is dropped fails with [`io::ErrorKind::BrokenPipe`].
+///
+/// Meant for testing protocol code without sockets.
+///
+/// # Panics
+///
+/// Panics if `capacity` is 0.
+pub fn duplex(capacity: usize) -> (DuplexStream, DuplexStream) {
+  assert!(capacity > 0, "duplex capacity can't be 0");
+  let one = Arc::new(StdMutex::new(Pipe::new(capacity)));
+  let two = Arc::new(StdMutex::new(Pipe::new(capacity)));
+  (
+    DuplexStream { read: one.clone(), write: two.clone() },
+    DuplexStream { read: two, write: one },
+  )
+}
+
+/// One end of a [`duplex`].
+pub struct DuplexStream {
+  // This is not a bottleneck
+  read: Arc<StdMutex<Pipe>>,
+  write: Arc<StdMutex<Pipe>>,
+}
+
+// The bytes going one way.
+struct Pipe {
+  buf: VecDeque<u8>,
+  capacity: usize,
+  // The writing end is shut down or dropped.
+  closed: bool,
+  reader_dropped: bool,
+  read_waker: Option<Waker>,
+  write_waker: Option<Waker>,
+}
+
+impl Pipe {
+  fn new(capacity: usize) -> Pipe {
+    Pipe {
+      buf: VecDeque::with_capacity(capacity),
+      capacity,
+      closed: false,
+      reader_dropped: false,
+      read_waker: None,
+      write_waker: None,
+    }
+  }
+
+  fn close(&mut self) {
+    self.closed = true;
+    if let Some(waker) = self.read_waker.take() {
+      waker.wake();
+    }
+  }
+}
+
+impl Drop for DuplexStream {
+  fn drop(&mut self) {
+    self.write.lock().unwrap().close();
+    let mut read = self.read.lock().unwrap();
+    read.reader_dropped = true;
+    if let Some(waker) = read.write_waker.take() {
+      waker.wake();
+    }
+  }
+}
+
+impl AsyncRead for DuplexStream {
+  fn poll_read(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+    buf: &mut [u8],
+  ) -> Poll<io::Result<usize>> {
+    let mut pipe = self.read.lock().unwrap();
+    if buf.is_empty() || (pipe.buf.is_empty() && pipe.closed) {
+      return Poll::Ready(Ok(0));
+    }
+    if pipe.buf.is_empty() {
+      pipe.read_waker = Some(cx.waker().clone());
+      return Poll::Pending;
+    }
+
+    let len = buf.len().min(pipe.buf.len());
+    for (byte, read) in buf.iter_mut().zip(pipe.buf.drain(..len)) {
+      *byte = read;
+    }
+    if let Some(waker) = pipe.write_waker.take() {
+      waker.wake();
+    }
+    Poll::Ready(Ok(len))
+  }
+}
+
+impl AsyncWrite for DuplexStream {
+  fn poll_write(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+    buf: &[u8],
+  ) -> Poll<io::Result<usize>> {
+    let mut pipe = self.write.lock().unwrap();
+    if pipe.reader_dropped || pipe.closed {
+      return Poll::Ready(Err(io::ErrorKind::BrokenPipe.into()));
+    }
+    if buf.is_empty() {
+      return Poll::Ready(Ok(0));
+    }
+    let room = pipe.capacity - pipe.buf.len();
+    if room == 0 {
+      pipe.write_waker = Some(cx.waker().clone());
+      return Poll::Pending;
+    }
+
+    let len = buf.len().min(room);
+    pipe.buf.extend(&buf[..len]);
+    if let Some(waker) = pipe.read_waker.take() {
+      waker.wake();
+    }
+    Poll::Ready(Ok(len))
+  }
+
+  fn poll_flush(
+    self: Pin<&mut Self>,
+    _: &mut Context<'_>,
+  ) -> Poll<io::Result<()>> {
+    Poll::Ready(Ok(()))
+  }
+
+  fn poll_shutdown(
+    self: Pin<&mut Self>,
+    _: &mut Context<'_>,
+  ) -> Poll<io::Result<()>> {
+    self.write.lock().unwrap().close();
+    Poll::Ready(Ok(()))
+  }
+}
+
+#[crate::internal_test]
+async fn frames_round_trip() {
+  use super::AsyncReadExt;
+  use crate::task;
+  use std::future::poll_fn;
+
+  async fn write_all(stream: &mut DuplexStream, mut buf: &[u8]) {
+    while !buf.is_empty() {
+      let written = poll_fn(|cx| Pin::new(&mut *stream).poll_write(cx, buf));
+      buf = &buf[written.await.unwrap()..];
+    }
+  }
+
+  // Smaller than a frame, so every frame waits on the reader.
+  let (mut client, mut server) = duplex(4);
+  let frames: Vec<Vec<u8>> =
+    vec![b"hello".to_vec(), vec![], b"a longer frame".to_vec()];
+  let sent = frames.clone();
+  let writer = task::spawn(async move {
+    for frame in &sent {
+      write_all(&mut client, &(frame.len() as u16).to_be_bytes()).await;
+      write_all(&mut client, frame).await;
+    }
+    poll_fn(|cx| Pin::new(&mut client).poll_shutdown(cx)).await.unwrap();
+    client
+  });
+
+  let mut received = vec![];
+  let mut len = [0; 2];
+  while server.read(&mut len[..1]).await.unwrap() == 1 {
+    server.read_exact(&mut len[1..]).await.unwrap();
+    let mut frame = vec![0; u16::from_be_bytes(len) as usize];
+    server.read_exact(&mut frame).await.unwrap();
+    received.push(frame);
+  }
+  assert_eq!(received, frames);
+
+  // Writing to a dropped end fails.
+  let mut client = writer.await.unwrap();
+  drop(server);
+  let write = poll_fn(|cx| Pin::new(&mut client).poll_write(cx, b"late"));
+  assert_eq!(write.await.unwrap_err().kind(), io::ErrorKind::BrokenPipe);
+}
@@ -0,0 +1,350 @@
+use std::{
+  io::{self, ErrorKind, Read, Write},
+  mem::ManuallyDrop,
+  os::fd::{AsRawFd, RawFd},
+  pin::Pin,
+  task::{Context, Poll},
+};
+
+use mio::{unix::SourceFd, Interest};
+
+use super::{AsyncRead, AsyncWrite};
+use crate::{context, events::EventRegistration};
+
+/// Makes any file descriptor pollable by the runtime, like a timerfd, an inotify fd or a character
+/// device.
+///
+/// [`Async::new`] sets the fd non-blocking and registers it with the runtime, it's deregistered
+/// when the `Async` is dropped. An fd can only be registered once: wrapping the same fd in a second
+/// `Async` fails with [`ErrorKind::AlreadyExists`].
+pub struct Async<T: AsRawFd> {
+  inner: T,
+  registration: EventRegistration,
+}
+
+/// [`Async`], by the name other runtimes give it.
+pub type AsyncFd<T> = Async<T>;
+
+impl<T: AsRawFd> Async<T> {
+  pub fn new(inner: T) -> io::Result<Async<T>> {
+    context::handle_for("io::Async::new")?;
+    let fd = inner.as_raw_fd();
+    set_nonblocking(fd)?;
+
+    let registration =
+      EventRegistration::new(Interest::READABLE | Interest::WRITABLE);
+    registration.register(&mut SourceFd(&fd))?;
+    Ok(Async { inner, registration })
+  }
+
+  pub fn get_ref(&self) -> &T {
+    &self.inner
+  }
+
+  /// The fd must stay the same, it's what is registered with the runtime.
+  pub fn get_mut(&mut self) -> &mut T {
+    &mut self.inner
+  }
+
+  /// Deregisters the fd and gives it back. It's left non-blocking.
+  pub fn into_inner(self) -> io::Result<T> {
+    let this = ManuallyDrop::new(self);
+    let result = this.deregister();
+    // SAFETY: `this` is never used or dropped after this.
+    let inner = unsafe { std::ptr::read(&this.inner) };
+    result.map(|()| inner)
+  }
+
+  /// Calls `f` until it returns something else than [`ErrorKind::WouldBlock`], waiting for the fd
+  /// to become readable in between.
+  pub async fn read_with<R>(
+    &self,
+    mut f: impl FnMut(&T) -> io::Result<R>,
+  ) -> io::Result<R> {
+    std::future::poll_fn(|cx| self.poll_with(cx, &mut f)).await
+  }
+
+  /// Like [`Async::read_with`], but waits for the fd to become writable.
+  pub async fn write_with<R>(
+    &self,
+    mut f: impl FnMut(&T) -> io::Result<R>,
+  ) -> io::Result<R> {
+    std::future::poll_fn(|cx| self.poll_with(cx, &mut f)).await
+  }
+
+  /// Waits until the fd is readable, for io which [`Async::read_with`] can't express, like a call
+  /// into a C library which reads from the fd itself.
+  ///
+  /// The fd can stop being readable before the guard is used, see [`ReadyGuard::try_io`].
+  pub async fn readable(&self) -> io::Result<ReadyGuard<'_, T>> {
+    self.ready(libc::POLLIN).await
+  }
+
+  /// Like [`Async::readable`], but waits until the fd is writable.
+  pub async fn writable(&self) -> io::Result<ReadyGuard<'_, T>> {
+    self.ready(libc::POLLOUT).await
+  }
+
+  async fn ready(
+    &self,
+    events: libc::c_short,
+  ) -> io::Result<ReadyGuard<'_, T>> {
+    let fd = self.inner.as_raw_fd();
+    std::future::poll_fn(|cx| {
+      if is_ready(fd, events)? {
+        return Poll::Ready(Ok::<(), io::Error>(()));
+      }
+      self.registration.register_io_waker(cx)?;
+      // Readiness is edge-triggered, check again in case it changed before the waker was
+      // registered.
+      match is_ready(fd, events)? {
+        true => Poll::Ready(Ok(())),
+        false => Poll::Pending,
+      }
+    })
+    .await?;
+    Ok(ReadyGuard { fd: self })
+  }
+
+  // One waker is registered per fd, so reads and writes wait the same way.
+  fn poll_with<R>(
+    &self,
+    cx: &mut Context<'_>,
+    mut f: impl FnMut(&T) -> io::Result<R>,
+  ) -> Poll<io::Result<R>> {
+    poll_io(&self.registration, cx, || f(&self.inner))
+  }
+
+  fn poll_with_mut<R>(
+    &mut self,
+    cx: &mut Context<'_>,
+    mut f: impl FnMut(&mut T) -> io::Result<R>,
+  ) -> Poll<io::Result<R>> {
+    poll_io(&self.registration, cx, || f(&mut self.inner))
+  }
+
+  fn deregister(&self) -> io::Result<()> {
+    self.registration.deregister(&mut SourceFd(&self.inner.as_raw_fd()))
+  }
+}
+
+impl<T: AsRawFd> Drop for Async<T> {
+  fn drop(&mut self) {
+    // Ignore errors.
+    let _ = self.deregister();
+  }
+}
+
+impl<T: AsRawFd> AsRawFd for Async<T> {
+  fn as_raw_fd(&self) -> RawFd {
+    self.inner.as_raw_fd()
+  }
+}
+
+// Tries `f`, and registers the waker if the fd isn't ready.
+fn poll_io<R>(
+  registration: &EventRegistration,
+  cx: &mut Context<'_>,
+  mut f: impl FnMut() -> io::Result<R>,
+) -> Poll<io::Result<R>> {
+  match f() {
+    Err(err) if err.kind() == ErrorKind::WouldBlock => {}
+    result => return Poll::Ready(result),
+  }
+
+  registration.register_io_waker(cx)?;
+
+  // Readiness is edge-triggered, try again in case it changed before the waker was registered.
+  match f() {
+    Err(err) if err.kind() == ErrorKind::WouldBlock => Poll::Pending,
+    result => Poll::Ready(result),
+  }
+}
+
+// Whether the fd is ready for `events` right now. An error or a hangup counts as ready, so the io
+// which is tried next fails or finds the end.
+fn is_ready(fd: RawFd, events: libc::c_short) -> io::Result<bool> {
+  let mut pollfd = libc::pollfd { fd, events, revents: 0 };
+  // SAFETY: One `pollfd`, which lives through the call.
+  match unsafe { libc::poll(&mut pollfd, 1, 0) } {
+    -1 => Err(io::Error::last_os_error()),
+    _ => Ok(pollfd.revents != 0),
+  }
+}
+
+/// Proof that an [`Async`] was ready, returned by [`Async::readable`] and [`Async::writable`].
+pub struct ReadyGuard<'a, T: AsRawFd> {
+  fd: &'a Async<T>,
+}
+
+/// Returned by [`ReadyGuard::try_io`] when the fd wasn't ready after all.
+#[derive(Debug)]
+pub struct TryIoError(());
+
+impl<T: AsRawFd> ReadyGuard<'_, T> {
+  pub fn get_ref(&self) -> &T {
+    &self.fd.inner
+  }
+
+  /// Runs `f`, which does io on the fd.
+  ///
+  /// If it fails with [`ErrorKind::WouldBlock`], the readiness the guard was given for is gone,
+  /// and `try_io` returns [`TryIoError`] instead: the next [`Async::readable`] or
+  /// [`Async::writable`] waits for the runtime to see the fd become ready again.
+  pub fn try_io<R>(
+    &mut self,
+    f: impl FnOnce(&T) -> io::Result<R>,
+  ) -> Result<io::Result<R>, TryIoError> {
+    match f(&self.fd.inner) {
+      Err(err) if err.kind() == ErrorKind::WouldBlock => Err(TryIoError(())),
+      result => Ok(result),
+    }
+  }
+}
+
+fn set_nonblocking(fd: RawFd) -> io::Result<()> {
+  // SAFETY: fcntl doesn't touch memory, an invalid fd is reported as an error.
+  unsafe {
+    let flags = libc::fcntl(fd, libc::F_GETFL);
+    if flags < 0 || libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK) < 0
+    {
+      return Err(io::Error::last_os_error());
+    }
+  }
+  Ok(())
+}
+
+impl<T: AsRawFd + Read + Unpin> AsyncRead for Async<T> {
+  fn poll_read(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+    buf: &mut [u8],
+  ) -> Poll<io::Result<usize>> {
+    self.get_mut().poll_with_mut(cx, |inner| inner.read(buf))
+  }
+}
+
+impl<T: AsRawFd + Write + Unpin> AsyncWrite for Async<T> {
+  fn poll_write(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+    buf: &[u8],
+  ) -> Poll<io::Result<usize>> {
+    self.get_mut().poll_with_mut(cx, |inner| inner.write(buf))
+  }
+
+  fn poll_flush(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<io::Result<()>> {
+    self.get_mut().poll_with_mut(cx, |inner| inner.flush())
+  }
+
+  // Closing the fd is up to `T`, when it's dropped.
+  fn poll_shutdown(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<io::Result<()>> {
+    self.poll_flush(cx)
+  }
+}
+
+#[crate::internal_test]
+async fn pipe() {
+  use std::{fs::File, os::fd::FromRawFd, time::Duration};
+
+  let mut fds = [0; 2];
+  assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+  // SAFETY: Both fds were just created and aren't owned by anything else.
+  let (reader, writer) =
+    unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) };
+  let mut reader = Async::new(reader).unwrap();
+
+  let writes = std::thread::spawn(move || {
+    let mut writer = writer;
+    for chunk in [&b"hel"[..], b"lo"] {
+      std::thread::sleep(Duration::from_millis(10));
+      writer.write_all(chunk).unwrap();
+    }
+  });
+
+  // Both reads find the pipe empty first, and are retried once the chunk is written.
+  let mut received = Vec::new();
+  let mut buf = [0; 8];
+  while received.len() < 5 {
+    let read =
+      std::future::poll_fn(|cx| Pin::new(&mut reader).poll_read(cx, &mut buf))
+        .await
+        .unwrap();
+    received.extend_from_slice(&buf[..read]);
+  }
+  assert_eq!(received, b"hello");
+  writes.join().unwrap();
+}
+
+#[crate::internal_test]
+async fn socketpair() {
+  use std::os::unix::net::UnixStream;
+
+  let (left, right) = UnixStream::pair().unwrap();
+  let left = Async::new(left).unwrap();
+  let right = Async::new(right).unwrap();
+
+  let reader = crate::task::spawn(async move {
+    let mut buf = [0; 4];
+    let read = right.read_with(|stream| (&*stream).read(&mut buf)).await;
+    (read.unwrap(), buf)
+  });
+
+  crate::time::sleep(std::time::Duration::from_millis(10)).await;
+  let written =
+    left.write_with(|stream| (&*stream).write(b"ping")).await.unwrap();
+  assert_eq!(written, 4);
+  assert_eq!(reader.await.unwrap(), (4, *b"ping"));
+}
+
+#[crate::internal_test]
+async fn registers_once() {
+  use std::os::{fd::AsFd, unix::net::UnixStream};
+
+  let (stream, _other) = UnixStream::pair().unwrap();
+  let first = Async::new(stream.as_fd()).unwrap();
+  let err = Async::new(stream.as_fd()).err().unwrap();
+  assert_eq!(err.kind(), ErrorKind::AlreadyExists);
+
+  // The failed registration left the first one alone.
+  drop(first);
+  let again = Async::new(stream.as_fd()).unwrap();
+  assert!(again.into_inner().is_ok());
+}
+
+#[crate::internal_test]
+async fn readable_guard() {
+  use std::{fs::File, os::fd::FromRawFd, time::Duration};
+
+  let mut fds = [0; 2];
+  assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+  // SAFETY: Both fds were just created and aren't owned by anything else.
+  let (reader, writer) =
+    unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) };
+  let reader = AsyncFd::new(reader).unwrap();
+
+  let writes = std::thread::spawn(move || {
+    std::thread::sleep(Duration::from_millis(10));
+    (&writer).write_all(b"abc").unwrap();
+    writer
+  });
+
+  let mut buf = [0; 8];
+  let mut guard = reader.readable().await.unwrap();
+  let read = guard.try_io(|file| (&*file).read(&mut buf)).unwrap().unwrap();
+  assert_eq!(&buf[..read], b"abc");
+  // Drained, so the readiness is gone.
+  assert!(guard.try_io(|file| (&*file).read(&mut buf)).is_err());
+
+  // An empty pipe can be written to right away.
+  let writer = AsyncFd::new(writes.join().unwrap()).unwrap();
+  let mut guard = writer.writable().await.unwrap();
+  let written = guard.try_io(|file| (&*file).write(b"d")).unwrap();
+  assert_eq!(written.unwrap(), 1);
+}
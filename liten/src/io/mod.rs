@@ -0,0 +1,183 @@
+//! Asynchronous versions of [`std::io::Read`] and [`std::io::Write`].
+//!
+//! The traits take the same arguments as their std counterparts, so adapting them to other
+//! asynchronous io traits only means forwarding the calls. With the `futures-compat` feature,
+//! liten's io types implement the [`futures-io`](https://docs.rs/futures-io) traits too, and
+//! [`Compat`] does the same for any other type implementing the traits here.
+mod buf_writer;
+pub use buf_writer::{flush_in_order, BufWriter, FlushBarrier};
+#[cfg(feature = "futures-compat")]
+mod compat;
+mod duplex;
+#[cfg(feature = "futures-compat")]
+pub use compat::Compat;
+pub use duplex::{duplex, DuplexStream};
+#[cfg(unix)]
+mod fd;
+#[cfg(unix)]
+pub use fd::{Async, AsyncFd, ReadyGuard, TryIoError};
+mod read_ext;
+pub use read_ext::{
+  AsyncReadExt, Read, ReadExact, ReadToEnd, ReadToEndOptions,
+};
+mod send_file;
+pub use send_file::send_file;
+mod stream;
+pub use stream::AsyncStream;
+mod write_ext;
+pub use write_ext::{AsyncWriteExt, Flush, Write, WriteAll};
+
+use std::{
+  io,
+  ops::DerefMut,
+  pin::Pin,
+  task::{Context, Poll},
+};
+
+/// Reads bytes from a source without blocking the thread.
+pub trait AsyncRead {
+  /// Reads into `buf`, and returns how many bytes were read. `Ok(0)` means the end of the source,
+  /// or an empty `buf`.
+  ///
+  /// When no data is available, the waker of `cx` is registered and [`Poll::Pending`] returned.
+  fn poll_read(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+    buf: &mut [u8],
+  ) -> Poll<io::Result<usize>>;
+}
+
+/// Writes bytes to a sink without blocking the thread.
+pub trait AsyncWrite {
+  /// Writes from `buf`, and returns how many bytes were written.
+  ///
+  /// When the sink can't take any data, the waker of `cx` is registered and [`Poll::Pending`]
+  /// returned.
+  fn poll_write(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+    buf: &[u8],
+  ) -> Poll<io::Result<usize>>;
+
+  /// Flushes every buffered byte to the sink.
+  fn poll_flush(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<io::Result<()>>;
+
+  /// Flushes and shuts down the writing half, after which nothing more can be written.
+  fn poll_shutdown(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<io::Result<()>>;
+}
+
+impl<T: AsyncRead + Unpin + ?Sized> AsyncRead for &mut T {
+  fn poll_read(
+    mut self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+    buf: &mut [u8],
+  ) -> Poll<io::Result<usize>> {
+    Pin::new(&mut **self).poll_read(cx, buf)
+  }
+}
+
+impl<T: AsyncRead + Unpin + ?Sized> AsyncRead for Box<T> {
+  fn poll_read(
+    mut self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+    buf: &mut [u8],
+  ) -> Poll<io::Result<usize>> {
+    Pin::new(&mut **self).poll_read(cx, buf)
+  }
+}
+
+impl<P> AsyncRead for Pin<P>
+where
+  P: DerefMut + Unpin,
+  P::Target: AsyncRead,
+{
+  fn poll_read(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+    buf: &mut [u8],
+  ) -> Poll<io::Result<usize>> {
+    self.get_mut().as_mut().poll_read(cx, buf)
+  }
+}
+
+impl<T: AsyncWrite + Unpin + ?Sized> AsyncWrite for &mut T {
+  fn poll_write(
+    mut self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+    buf: &[u8],
+  ) -> Poll<io::Result<usize>> {
+    Pin::new(&mut **self).poll_write(cx, buf)
+  }
+
+  fn poll_flush(
+    mut self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<io::Result<()>> {
+    Pin::new(&mut **self).poll_flush(cx)
+  }
+
+  fn poll_shutdown(
+    mut self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<io::Result<()>> {
+    Pin::new(&mut **self).poll_shutdown(cx)
+  }
+}
+
+impl<T: AsyncWrite + Unpin + ?Sized> AsyncWrite for Box<T> {
+  fn poll_write(
+    mut self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+    buf: &[u8],
+  ) -> Poll<io::Result<usize>> {
+    Pin::new(&mut **self).poll_write(cx, buf)
+  }
+
+  fn poll_flush(
+    mut self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<io::Result<()>> {
+    Pin::new(&mut **self).poll_flush(cx)
+  }
+
+  fn poll_shutdown(
+    mut self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<io::Result<()>> {
+    Pin::new(&mut **self).poll_shutdown(cx)
+  }
+}
+
+impl<P> AsyncWrite for Pin<P>
+where
+  P: DerefMut + Unpin,
+  P::Target: AsyncWrite,
+{
+  fn poll_write(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+    buf: &[u8],
+  ) -> Poll<io::Result<usize>> {
+    self.get_mut().as_mut().poll_write(cx, buf)
+  }
+
+  fn poll_flush(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<io::Result<()>> {
+    self.get_mut().as_mut().poll_flush(cx)
+  }
+
+  fn poll_shutdown(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<io::Result<()>> {
+    self.get_mut().as_mut().poll_shutdown(cx)
+  }
+}
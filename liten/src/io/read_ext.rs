@@ -0,0 +1,331 @@
+use std::{
+  future::Future,
+  io,
+  num::NonZero,
+  pin::Pin,
+  task::{Context, Poll},
+};
+
+use super::AsyncRead;
+
+/// Reads as futures, for every [`AsyncRead`].
+///
+/// # Cancel safety
+///
+/// [`read`](AsyncReadExt::read) is cancel safe: bytes are only taken from the source in the poll
+/// which completes the future, so dropping it before then, like when another branch of a
+/// [`select!`](crate::select) wins, loses nothing. The next read gets the same bytes.
+///
+/// [`read_exact`](AsyncReadExt::read_exact) isn't. It reads in several steps, and the bytes of
+/// the steps which were done when it's dropped are in the buffer, but there's no telling how many
+/// there were. Wait with `read` in a loop instead when a read has to be raced against something.
+/// Neither is [`read_to_end`](AsyncReadExt::read_to_end), though the bytes it read before it was
+/// dropped are left at the end of the `Vec`.
+pub trait AsyncReadExt: AsyncRead {
+  /// Reads into `buf`, and returns how many bytes were read, see [`AsyncRead::poll_read`].
+  fn read<'a>(&'a mut self, buf: &'a mut [u8]) -> Read<'a, Self>
+  where
+    Self: Unpin,
+  {
+    Read { reader: self, buf }
+  }
+
+  /// Reads until `buf` is full. Fails with [`io::ErrorKind::UnexpectedEof`] if the source ends
+  /// first, with an unknown part of `buf` filled.
+  fn read_exact<'a>(&'a mut self, buf: &'a mut [u8]) -> ReadExact<'a, Self>
+  where
+    Self: Unpin,
+  {
+    ReadExact { reader: self, buf, filled: 0 }
+  }
+
+  /// Reads until the end of the source, and appends the bytes to `buf`. Returns how many bytes
+  /// were read.
+  ///
+  /// The buffer grows as [`ReadToEndOptions::default`] says, see
+  /// [`read_to_end_with`](AsyncReadExt::read_to_end_with).
+  fn read_to_end<'a>(&'a mut self, buf: &'a mut Vec<u8>) -> ReadToEnd<'a, Self>
+  where
+    Self: Unpin,
+  {
+    self.read_to_end_with(buf, ReadToEndOptions::default())
+  }
+
+  /// Like [`read_to_end`](AsyncReadExt::read_to_end), with the buffer grown as `options` says.
+  fn read_to_end_with<'a>(
+    &'a mut self,
+    buf: &'a mut Vec<u8>,
+    options: ReadToEndOptions,
+  ) -> ReadToEnd<'a, Self>
+  where
+    Self: Unpin,
+  {
+    let chunk = options.initial_chunk.min(options.max_chunk);
+    ReadToEnd {
+      reader: self,
+      buf,
+      max_chunk: options.max_chunk,
+      chunk,
+      read: 0,
+    }
+  }
+}
+
+impl<R: AsyncRead + ?Sized> AsyncReadExt for R {}
+
+/// How [`AsyncReadExt::read_to_end_with`] grows its buffer.
+///
+/// Each read asks for at most a chunk, in the room left at the end of the buffer or a chunk more
+/// when there's none. The first chunk is small, so a short response doesn't take much memory, and
+/// every read which fills its chunk doubles the next one, up to the largest chunk, so a long one
+/// doesn't take a read per few bytes either. The `Vec` itself grows like it always does, its
+/// capacity at least doubles when it's full.
+///
+/// Defaults to chunks of 512 bytes growing up to 64 KiB.
+#[derive(Clone, Copy, Debug)]
+pub struct ReadToEndOptions {
+  initial_chunk: usize,
+  max_chunk: usize,
+}
+
+impl Default for ReadToEndOptions {
+  fn default() -> Self {
+    ReadToEndOptions { initial_chunk: 512, max_chunk: 64 * 1024 }
+  }
+}
+
+impl ReadToEndOptions {
+  pub fn new() -> Self {
+    ReadToEndOptions::default()
+  }
+
+  /// How many bytes the first read asks for.
+  ///
+  /// # Panics
+  ///
+  /// Panics if `size` is 0.
+  pub fn initial_chunk(mut self, size: usize) -> Self {
+    let size = NonZero::new(size).expect("initial_chunk can't be 0");
+    self.initial_chunk = size.get();
+    self
+  }
+
+  /// The most bytes a read asks for. A smaller initial chunk is lowered to it.
+  ///
+  /// # Panics
+  ///
+  /// Panics if `size` is 0.
+  pub fn max_chunk(mut self, size: usize) -> Self {
+    let size = NonZero::new(size).expect("max_chunk can't be 0");
+    self.max_chunk = size.get();
+    self
+  }
+}
+
+/// Future returned by [`AsyncReadExt::read`].
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Read<'a, R: ?Sized> {
+  reader: &'a mut R,
+  buf: &'a mut [u8],
+}
+
+impl<R: AsyncRead + Unpin + ?Sized> Future for Read<'_, R> {
+  type Output = io::Result<usize>;
+
+  fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+    let this = self.get_mut();
+    Pin::new(&mut *this.reader).poll_read(cx, this.buf)
+  }
+}
+
+/// Future returned by [`AsyncReadExt::read_exact`].
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct ReadExact<'a, R: ?Sized> {
+  reader: &'a mut R,
+  buf: &'a mut [u8],
+  filled: usize,
+}
+
+impl<R: AsyncRead + Unpin + ?Sized> Future for ReadExact<'_, R> {
+  type Output = io::Result<()>;
+
+  fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+    let this = self.get_mut();
+    while this.filled < this.buf.len() {
+      let buf = &mut this.buf[this.filled..];
+      match std::task::ready!(Pin::new(&mut *this.reader).poll_read(cx, buf)) {
+        Ok(0) => {
+          return Poll::Ready(Err(io::ErrorKind::UnexpectedEof.into()));
+        }
+        Ok(read) => this.filled += read,
+        Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+        Err(err) => return Poll::Ready(Err(err)),
+      }
+    }
+    Poll::Ready(Ok(()))
+  }
+}
+
+/// Future returned by [`AsyncReadExt::read_to_end`] and [`AsyncReadExt::read_to_end_with`].
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct ReadToEnd<'a, R: ?Sized> {
+  reader: &'a mut R,
+  buf: &'a mut Vec<u8>,
+  max_chunk: usize,
+  // How much room the next read asks for.
+  chunk: usize,
+  read: usize,
+}
+
+impl<R: AsyncRead + Unpin + ?Sized> Future for ReadToEnd<'_, R> {
+  type Output = io::Result<usize>;
+
+  fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+    let this = self.get_mut();
+    loop {
+      let len = this.buf.len();
+      // Room left from the last growth is used first, so finding the end doesn't grow the buffer.
+      let room = match this.buf.capacity() - len {
+        0 => this.chunk,
+        spare => spare.min(this.chunk),
+      };
+      // `poll_read` takes initialised bytes. Within the capacity there already, this only sets
+      // them.
+      this.buf.resize(len + room, 0);
+      let poll =
+        Pin::new(&mut *this.reader).poll_read(cx, &mut this.buf[len..]);
+      let read = match poll {
+        Poll::Ready(Ok(read)) => read,
+        Poll::Ready(Err(err)) => {
+          this.buf.truncate(len);
+          match err.kind() {
+            io::ErrorKind::Interrupted => continue,
+            _ => return Poll::Ready(Err(err)),
+          }
+        }
+        Poll::Pending => {
+          this.buf.truncate(len);
+          return Poll::Pending;
+        }
+      };
+      this.buf.truncate(len + read);
+
+      if read == 0 {
+        return Poll::Ready(Ok(this.read));
+      }
+      this.read += read;
+      // The source has more than the chunk could take, ask for more next time.
+      if read == this.chunk {
+        this.chunk = (this.chunk * 2).min(this.max_chunk);
+      }
+    }
+  }
+}
+
+#[crate::internal_test]
+async fn read_is_cancel_safe() {
+  use crate::{test_util::io::Builder, time};
+  use std::time::Duration;
+
+  time::pause();
+  let mut mock =
+    Builder::new().wait(Duration::from_secs(1)).read(b"hello").build();
+  let mut buf = [0; 5];
+
+  let timed_out = crate::select! {
+    _ = mock.read(&mut buf) => false,
+    () = time::sleep(Duration::from_millis(10)) => true,
+  };
+  assert!(timed_out);
+  // The dropped read took nothing.
+  assert_eq!(mock.read(&mut buf).await.unwrap(), 5);
+  assert_eq!(&buf, b"hello");
+  time::resume();
+}
+
+#[crate::internal_test]
+async fn read_exact_loses_progress() {
+  use crate::{test_util::io::Builder, time};
+  use std::time::Duration;
+
+  time::pause();
+  let mut mock = Builder::new()
+    .read(b"he")
+    .wait(Duration::from_secs(1))
+    .read(b"llo")
+    .build();
+  let mut buf = [0; 5];
+
+  let timed_out = crate::select! {
+    _ = mock.read_exact(&mut buf) => false,
+    () = time::sleep(Duration::from_millis(10)) => true,
+  };
+  assert!(timed_out);
+  // The first two bytes were read into `buf` by the dropped future, a new `read_exact` can't
+  // know about them and waits for five more.
+  assert_eq!(&buf[..2], b"he");
+  let mut rest = [0; 3];
+  mock.read_exact(&mut rest).await.unwrap();
+  assert_eq!(&rest, b"llo");
+
+  let err = mock.read_exact(&mut buf).await.unwrap_err();
+  assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+  time::resume();
+}
+
+#[test]
+fn read_to_end_grows_geometrically() {
+  use crate::test_util::alloc::allocations;
+  use std::task::Waker;
+
+  // Gives as much as it's asked for, like a socket with a full buffer.
+  struct Source {
+    left: usize,
+    // The most bytes a read asked for.
+    largest: usize,
+  }
+  impl AsyncRead for Source {
+    fn poll_read(
+      mut self: Pin<&mut Self>,
+      _: &mut Context<'_>,
+      buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+      let read = buf.len().min(self.left);
+      buf[..read].fill(7);
+      self.left -= read;
+      self.largest = self.largest.max(buf.len());
+      Poll::Ready(Ok(read))
+    }
+  }
+
+  // Returns the bytes, the largest read and how many allocations it took.
+  fn read_all(
+    len: usize,
+    options: ReadToEndOptions,
+  ) -> (Vec<u8>, usize, usize) {
+    let mut buf = Vec::new();
+    let mut source = Source { left: len, largest: 0 };
+    let before = allocations();
+    let read = std::pin::pin!(source.read_to_end_with(&mut buf, options))
+      .poll(&mut Context::from_waker(Waker::noop()));
+    let allocations = allocations() - before;
+    assert!(matches!(read, Poll::Ready(Ok(read)) if read == len));
+    (buf, source.largest, allocations)
+  }
+
+  // A short response stays in the first chunk.
+  let (buf, _, allocations) = read_all(100, ReadToEndOptions::default());
+  assert_eq!((buf.len(), buf.capacity(), allocations), (100, 512, 1));
+
+  // A long one takes a reallocation per doubling, not one per chunk.
+  let (buf, _, allocations) = read_all(4 << 20, ReadToEndOptions::default());
+  assert_eq!(buf.len(), 4 << 20);
+  assert!(buf.iter().all(|&byte| byte == 7));
+  assert!(allocations <= 15, "{allocations} allocations");
+  assert!(buf.capacity() <= 2 * buf.len() + 64 * 1024);
+
+  // The chunk doesn't grow past its cap.
+  let options = ReadToEndOptions::new().initial_chunk(16).max_chunk(64);
+  let (buf, largest, _) = read_all(1000, options);
+  assert_eq!((buf.len(), largest), (1000, 64));
+}
@@ -0,0 +1,150 @@
+use std::{fs::File, io};
+
+use crate::net::TcpStream;
+
+/// Sends `len` bytes of `file`, starting at `offset`, to `stream`. Returns how many bytes were
+/// sent, which is less than `len` only if the file ended first.
+///
+/// On Linux this is `sendfile`, so the bytes go from the page cache to the socket without being
+/// copied through userspace. Elsewhere the file is read in chunks and written to the socket. The
+/// file's cursor isn't used or moved either way.
+pub async fn send_file(
+  stream: &mut TcpStream,
+  file: &File,
+  offset: u64,
+  len: usize,
+) -> io::Result<usize> {
+  let mut sent = 0;
+  #[cfg(not(target_os = "linux"))]
+  let mut chunk = Vec::new();
+
+  while sent < len {
+    let position = offset + sent as u64;
+    let remaining = len - sent;
+
+    #[cfg(target_os = "linux")]
+    let result = std::future::poll_fn(|cx| {
+      stream.poll_io(cx, |socket| sendfile(socket, file, position, remaining))
+    })
+    .await;
+    #[cfg(not(target_os = "linux"))]
+    let result =
+      send_chunk(stream, file, position, remaining, &mut chunk).await;
+
+    match result? {
+      0 => break,
+      count => sent += count,
+    }
+  }
+
+  Ok(sent)
+}
+
+#[cfg(target_os = "linux")]
+fn sendfile(
+  socket: &mut mio::net::TcpStream,
+  file: &File,
+  position: u64,
+  len: usize,
+) -> io::Result<usize> {
+  use std::os::fd::AsRawFd;
+
+  let mut offset = libc::off_t::try_from(position)
+    .map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))?;
+  // SAFETY: Both fds are open for the duration of the call, and `offset` is a valid pointer.
+  let sent = unsafe {
+    libc::sendfile(socket.as_raw_fd(), file.as_raw_fd(), &mut offset, len)
+  };
+  if sent < 0 {
+    return Err(io::Error::last_os_error());
+  }
+  Ok(sent as usize)
+}
+
+// Reads the next chunk unless the socket hasn't taken all of the last one, then writes as much of
+// it as the socket takes.
+#[cfg(not(target_os = "linux"))]
+async fn send_chunk(
+  stream: &mut TcpStream,
+  file: &File,
+  position: u64,
+  len: usize,
+  chunk: &mut Vec<u8>,
+) -> io::Result<usize> {
+  use std::io::Write;
+
+  const CHUNK_SIZE: usize = 64 * 1024;
+
+  if chunk.is_empty() {
+    chunk.resize(len.min(CHUNK_SIZE), 0);
+    let read = read_at(file, chunk, position)?;
+    chunk.truncate(read);
+    if read == 0 {
+      return Ok(0);
+    }
+  }
+
+  let written =
+    std::future::poll_fn(|cx| stream.poll_io(cx, |socket| socket.write(chunk)))
+      .await?;
+  chunk.drain(..written);
+  Ok(written)
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn read_at(file: &File, buf: &mut [u8], position: u64) -> io::Result<usize> {
+  std::os::unix::fs::FileExt::read_at(file, buf, position)
+}
+
+#[cfg(windows)]
+fn read_at(file: &File, buf: &mut [u8], position: u64) -> io::Result<usize> {
+  std::os::windows::fs::FileExt::seek_read(file, buf, position)
+}
+
+#[crate::internal_test]
+async fn serves_file() {
+  use crate::io::AsyncRead;
+  use std::{io::Write, pin::Pin};
+
+  // Large enough to need more than one send through the loopback socket buffers.
+  let contents: Vec<u8> =
+    (0..4 * 1024 * 1024).map(|n| (n % 251) as u8).collect();
+  let path = std::env::temp_dir()
+    .join(format!("liten-send-file-{}", std::process::id()));
+  File::create(&path).unwrap().write_all(&contents).unwrap();
+  let file = File::open(&path).unwrap();
+
+  let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+  let mut client =
+    TcpStream::connect(listener.local_addr().unwrap()).unwrap().await.unwrap();
+  let (server, _) = listener.accept().unwrap();
+  server.set_nonblocking(true).unwrap();
+  let mut server =
+    TcpStream::inherit_mio_stream(mio::net::TcpStream::from_std(server));
+
+  let offset = 1000;
+  let len = contents.len() - 2000;
+  let end = contents.len() as u64;
+  let sender = crate::task::spawn(async move {
+    let sent = send_file(&mut server, &file, offset as u64, len).await;
+    // Past the end, only what's left of the file is sent.
+    let rest = send_file(&mut server, &file, end - 10, 100).await;
+    (sent.unwrap(), rest.unwrap())
+  });
+
+  let mut received = Vec::new();
+  let mut buf = vec![0; 64 * 1024];
+  while received.len() < len + 10 {
+    let read =
+      std::future::poll_fn(|cx| Pin::new(&mut client).poll_read(cx, &mut buf))
+        .await
+        .unwrap();
+    assert_ne!(read, 0);
+    received.extend_from_slice(&buf[..read]);
+  }
+
+  assert_eq!(sender.await.unwrap(), (len, 10));
+  std::fs::remove_file(&path).unwrap();
+  assert_eq!(&received[..len], &contents[offset..offset + len]);
+  assert_eq!(&received[len..], &contents[contents.len() - 10..]);
+}
@@ -0,0 +1,97 @@
+use super::{AsyncRead, AsyncWrite};
+
+/// A byte stream which can be both read and written, like a
+/// [`TcpStream`](crate::net::TcpStream).
+///
+/// Everything which implements [`AsyncRead`] and [`AsyncWrite`] implements it too. A TLS layer
+/// wraps any `S: AsyncStream`, and is one itself, so a server written over `impl AsyncStream`
+/// serves plaintext and encrypted connections with the same code, without liten depending on a
+/// TLS library.
+pub trait AsyncStream: AsyncRead + AsyncWrite {}
+
+impl<T: AsyncRead + AsyncWrite + ?Sized> AsyncStream for T {}
+
+#[crate::internal_test]
+async fn generic_over_layers() {
+  use super::{duplex, AsyncReadExt};
+  use crate::task;
+  use std::{
+    future::poll_fn,
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+  };
+
+  // Stands in for a TLS layer: it only knows its inner stream as an `AsyncStream`.
+  struct Xor<S>(S);
+
+  impl<S: AsyncStream + Unpin> AsyncRead for Xor<S> {
+    fn poll_read(
+      mut self: Pin<&mut Self>,
+      cx: &mut Context<'_>,
+      buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+      let read = std::task::ready!(Pin::new(&mut self.0).poll_read(cx, buf))?;
+      buf[..read].iter_mut().for_each(|byte| *byte ^= 0xff);
+      Poll::Ready(Ok(read))
+    }
+  }
+
+  impl<S: AsyncStream + Unpin> AsyncWrite for Xor<S> {
+    fn poll_write(
+      mut self: Pin<&mut Self>,
+      cx: &mut Context<'_>,
+      buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+      // One byte at a time, so the bytes written are the ones encoded.
+      let Some(byte) = buf.first() else { return Poll::Ready(Ok(0)) };
+      Pin::new(&mut self.0).poll_write(cx, &[byte ^ 0xff])
+    }
+
+    fn poll_flush(
+      mut self: Pin<&mut Self>,
+      cx: &mut Context<'_>,
+    ) -> Poll<io::Result<()>> {
+      Pin::new(&mut self.0).poll_flush(cx)
+    }
+
+    fn poll_shutdown(
+      mut self: Pin<&mut Self>,
+      cx: &mut Context<'_>,
+    ) -> Poll<io::Result<()>> {
+      Pin::new(&mut self.0).poll_shutdown(cx)
+    }
+  }
+
+  // Server code which doesn't know which layers it runs over.
+  async fn echo(mut stream: impl AsyncStream + Unpin) {
+    let mut buf = [0; 16];
+    while let Ok(read @ 1..) = stream.read(&mut buf).await {
+      let mut buf = &buf[..read];
+      while !buf.is_empty() {
+        let written = poll_fn(|cx| Pin::new(&mut stream).poll_write(cx, buf));
+        buf = &buf[written.await.unwrap()..];
+      }
+    }
+  }
+
+  async fn round_trip(mut stream: impl AsyncStream + Unpin) -> Vec<u8> {
+    for byte in b"ping" {
+      let written =
+        poll_fn(|cx| Pin::new(&mut stream).poll_write(cx, &[*byte]));
+      assert_eq!(written.await.unwrap(), 1);
+    }
+    let mut buf = [0; 4];
+    stream.read_exact(&mut buf).await.unwrap();
+    buf.to_vec()
+  }
+
+  let (client, server) = duplex(64);
+  task::spawn(echo(server));
+  assert_eq!(round_trip(client).await, b"ping");
+
+  // The same code, with both ends wrapped.
+  let (client, server) = duplex(64);
+  task::spawn(echo(Xor(server)));
+  assert_eq!(round_trip(Xor(client)).await, b"ping");
+}
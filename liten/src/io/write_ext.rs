@@ -0,0 +1,138 @@
+use std::{
+  future::Future,
+  io,
+  pin::Pin,
+  task::{Context, Poll},
+};
+
+use super::AsyncWrite;
+
+/// Writes as futures, for every [`AsyncWrite`].
+///
+/// # Partial writes
+///
+/// [`write`](AsyncWriteExt::write) is one write, like [`std::io::Write::write`]: it returns how
+/// many bytes the writer took, which can be fewer than `buf`, and leaves the rest to the caller.
+/// A flow-controlled protocol can then decide what to send next with what it knows was sent.
+/// [`write_all`](AsyncWriteExt::write_all) loops until all of `buf` is written.
+///
+/// `write` is cancel safe, like [`read`](super::AsyncReadExt::read). `write_all` isn't: the bytes
+/// it wrote before it was dropped are gone, and there's no telling how many there were.
+pub trait AsyncWriteExt: AsyncWrite {
+  /// Writes from `buf`, and returns how many bytes were written, see [`AsyncWrite::poll_write`].
+  fn write<'a>(&'a mut self, buf: &'a [u8]) -> Write<'a, Self>
+  where
+    Self: Unpin,
+  {
+    Write { writer: self, buf }
+  }
+
+  /// Writes until all of `buf` is written. Fails with [`io::ErrorKind::WriteZero`] if the writer
+  /// takes nothing, with an unknown part of `buf` written.
+  fn write_all<'a>(&'a mut self, buf: &'a [u8]) -> WriteAll<'a, Self>
+  where
+    Self: Unpin,
+  {
+    WriteAll { writer: self, buf }
+  }
+
+  /// Flushes the writer, see [`AsyncWrite::poll_flush`].
+  fn flush(&mut self) -> Flush<'_, Self>
+  where
+    Self: Unpin,
+  {
+    Flush { writer: self }
+  }
+}
+
+impl<W: AsyncWrite + ?Sized> AsyncWriteExt for W {}
+
+/// Future returned by [`AsyncWriteExt::write`].
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Write<'a, W: ?Sized> {
+  writer: &'a mut W,
+  buf: &'a [u8],
+}
+
+impl<W: AsyncWrite + Unpin + ?Sized> Future for Write<'_, W> {
+  type Output = io::Result<usize>;
+
+  fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+    let this = self.get_mut();
+    Pin::new(&mut *this.writer).poll_write(cx, this.buf)
+  }
+}
+
+/// Future returned by [`AsyncWriteExt::write_all`].
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct WriteAll<'a, W: ?Sized> {
+  writer: &'a mut W,
+  // What's left to write.
+  buf: &'a [u8],
+}
+
+impl<W: AsyncWrite + Unpin + ?Sized> Future for WriteAll<'_, W> {
+  type Output = io::Result<()>;
+
+  fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+    let this = self.get_mut();
+    while !this.buf.is_empty() {
+      let poll = Pin::new(&mut *this.writer).poll_write(cx, this.buf);
+      match std::task::ready!(poll) {
+        Ok(0) => return Poll::Ready(Err(io::ErrorKind::WriteZero.into())),
+        Ok(written) => this.buf = &this.buf[written..],
+        Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+        Err(err) => return Poll::Ready(Err(err)),
+      }
+    }
+    Poll::Ready(Ok(()))
+  }
+}
+
+/// Future returned by [`AsyncWriteExt::flush`].
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Flush<'a, W: ?Sized> {
+  writer: &'a mut W,
+}
+
+impl<W: AsyncWrite + Unpin + ?Sized> Future for Flush<'_, W> {
+  type Output = io::Result<()>;
+
+  fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+    Pin::new(&mut *self.get_mut().writer).poll_flush(cx)
+  }
+}
+
+#[crate::internal_test]
+async fn partial_write_leaves_rest() {
+  use crate::net::TcpStream;
+  use std::io::Read;
+
+  let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+  let mut stream =
+    TcpStream::connect(listener.local_addr().unwrap()).unwrap().await.unwrap();
+  let (mut peer, _) = listener.accept().unwrap();
+  // Far less room than the write, while the peer isn't reading.
+  stream.set_send_buffer_size(4096).unwrap();
+
+  let data: Vec<u8> = (0..1 << 20).map(|n| n as u8).collect();
+  let written = stream.write(&data).await.unwrap();
+  assert!(0 < written && written < data.len(), "wrote {written}");
+
+  let reader = std::thread::spawn(move || {
+    let mut received = vec![];
+    peer.read_to_end(&mut received).unwrap();
+    received
+  });
+  // Each write sends part of what's left, until all of it is written.
+  let mut written = written;
+  while written < data.len() {
+    let left = data.len() - written;
+    let n = stream.write(&data[written..]).await.unwrap();
+    assert!(0 < n && n <= left, "wrote {n} of {left}");
+    written += n;
+  }
+  stream.flush().await.unwrap();
+  drop(stream);
+  assert!(reader.join().unwrap() == data);
+}
@@ -1,9 +1,21 @@
 use liten_macros::internal_test;
+#[doc(hidden)]
+pub use liten_macros::select as __select;
 pub use liten_macros::{main, test};
-mod context;
+#[cfg(feature = "tokio-compat")]
+pub mod compat;
+pub mod context;
 mod events;
+pub mod future;
+pub mod io;
+mod loom;
+#[cfg(feature = "metrics")]
+pub mod metrics;
 pub mod net;
 pub mod runtime;
+pub mod stream;
 pub mod sync;
 pub mod task;
+pub mod test_util;
 pub mod time;
+pub mod util;
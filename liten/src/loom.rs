@@ -0,0 +1,53 @@
+//! The synchronization primitives the sync module is built on. Building with `--cfg loom` swaps
+//! them for [loom](https://docs.rs/loom)'s, so the model checker sees every access, other builds
+//! use std's.
+//!
+//! Run the model checks with `RUSTFLAGS="--cfg loom" cargo test -p liten --release --lib loom_`.
+
+#[cfg(not(loom))]
+pub(crate) mod sync {
+  pub(crate) use std::sync::{Arc, Mutex, RwLock};
+
+  pub(crate) mod atomic {
+    pub(crate) use std::sync::atomic::{
+      AtomicU16, AtomicU8, AtomicUsize, Ordering,
+    };
+  }
+}
+
+#[cfg(loom)]
+pub(crate) mod sync {
+  pub(crate) use loom::sync::{Arc, Mutex, RwLock};
+
+  pub(crate) mod atomic {
+    pub(crate) use loom::sync::atomic::{
+      AtomicU16, AtomicU8, AtomicUsize, Ordering,
+    };
+  }
+}
+
+pub(crate) mod cell {
+  #[cfg(loom)]
+  pub(crate) use loom::cell::UnsafeCell;
+
+  /// [`std::cell::UnsafeCell`] with loom's api, which only hands out the pointer to a closure so
+  /// loom can tell when it's accessed.
+  #[cfg(not(loom))]
+  #[repr(transparent)]
+  pub(crate) struct UnsafeCell<T>(std::cell::UnsafeCell<T>);
+
+  #[cfg(not(loom))]
+  impl<T> UnsafeCell<T> {
+    pub(crate) const fn new(value: T) -> UnsafeCell<T> {
+      UnsafeCell(std::cell::UnsafeCell::new(value))
+    }
+
+    pub(crate) fn with<R>(&self, f: impl FnOnce(*const T) -> R) -> R {
+      f(self.0.get())
+    }
+
+    pub(crate) fn with_mut<R>(&self, f: impl FnOnce(*mut T) -> R) -> R {
+      f(self.0.get())
+    }
+  }
+}
@@ -0,0 +1,244 @@
+//! Publishes what the runtime is doing to the [`metrics`](https://docs.rs/metrics) facade.
+//!
+//! [`install`] spawns a task which follows the runtime's
+//! [instrumentation events](crate::runtime::Handle::instrument_events), and updates these every
+//! interval, with the prefix in front:
+//!
+//! | Name                   | Kind      | Labels   |                                        |
+//! |------------------------|-----------|----------|----------------------------------------|
+//! | `tasks_spawned`        | counter   |          |                                        |
+//! | `tasks_completed`      | counter   |          | Including the ones which panicked.     |
+//! | `tasks_panicked`       | counter   |          |                                        |
+//! | `tasks_alive`          | gauge     |          | Spawned and not completed since then.  |
+//! | `workers_busy`         | gauge     |          | How many workers were polling, on average over the interval. |
+//! | `polls`                | counter   | `worker` |                                        |
+//! | `poll_duration`        | histogram | `worker` | In seconds, for percentiles like the p99. |
+//! | `park_count`           | counter   | `worker` |                                        |
+//! | `queue_depth`          | gauge     | `worker` | Tasks ready to run on the worker, when it was last sampled. |
+//! | `global_queue_depth`   | gauge     |          | Tasks no worker has picked up yet.     |
+//!
+//! The events are buffered, so a runtime doing more than a few thousand polls between two
+//! reads of the exporter loses some of them and undercounts. Task counts only cover tasks spawned
+//! after the exporter was installed.
+
+use std::{collections::BTreeMap, time::Duration};
+
+use metrics::{Counter, Gauge, Histogram};
+
+use crate::{
+  runtime::{Handle, RuntimeEvent},
+  task, time,
+};
+
+// How often the events are read, the buffer of the subscription has to last this long.
+const DRAIN_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Installs the exporter with the prefix `liten`, see [`Exporter`].
+pub fn install(handle: &Handle, interval: Duration) {
+  Exporter::new(interval).install(handle);
+}
+
+/// Configures the task which publishes the runtime's metrics.
+#[derive(Clone, Debug)]
+pub struct Exporter {
+  interval: Duration,
+  prefix: String,
+}
+
+impl Exporter {
+  /// Publishes every `interval`.
+  pub fn new(interval: Duration) -> Exporter {
+    Exporter { interval, prefix: "liten".to_string() }
+  }
+
+  /// Put in front of every metric name, separated with a dot. Defaults to `liten`.
+  pub fn prefix(mut self, prefix: impl Into<String>) -> Exporter {
+    self.prefix = prefix.into();
+    self
+  }
+
+  /// Spawns the exporter on the runtime of `handle`. It runs until the runtime shuts down.
+  pub fn install(self, handle: &Handle) {
+    let events = handle.instrument_events();
+    let _guard = handle.enter();
+    task::Builder::new().name("liten-metrics").build(async move {
+      let mut metrics = Metrics::new(self.prefix);
+      let mut publish_at = time::now() + self.interval;
+      loop {
+        time::sleep(DRAIN_INTERVAL.min(self.interval)).await;
+        for event in events.try_iter() {
+          metrics.record(event);
+        }
+        if time::now() >= publish_at {
+          metrics.publish(self.interval);
+          publish_at += self.interval;
+        }
+      }
+    });
+  }
+}
+
+struct Metrics {
+  prefix: String,
+  spawned: Counter,
+  completed: Counter,
+  panicked: Counter,
+  alive: Gauge,
+  busy: Gauge,
+  global_queue: Gauge,
+  workers: BTreeMap<usize, Worker>,
+
+  spawned_count: u64,
+  completed_count: u64,
+  // Since the last publish.
+  busy_time: Duration,
+}
+
+struct Worker {
+  polls: Counter,
+  poll_duration: Histogram,
+  parks: Counter,
+  queue: Gauge,
+}
+
+impl Metrics {
+  fn new(prefix: String) -> Metrics {
+    Metrics {
+      spawned: metrics::counter!(format!("{prefix}.tasks_spawned")),
+      completed: metrics::counter!(format!("{prefix}.tasks_completed")),
+      panicked: metrics::counter!(format!("{prefix}.tasks_panicked")),
+      alive: metrics::gauge!(format!("{prefix}.tasks_alive")),
+      busy: metrics::gauge!(format!("{prefix}.workers_busy")),
+      global_queue: metrics::gauge!(format!("{prefix}.global_queue_depth")),
+      workers: BTreeMap::new(),
+      spawned_count: 0,
+      completed_count: 0,
+      busy_time: Duration::ZERO,
+      prefix,
+    }
+  }
+
+  fn worker(&mut self, index: usize) -> &Worker {
+    let prefix = &self.prefix;
+    self.workers.entry(index).or_insert_with(|| {
+      let label = [("worker", index.to_string())];
+      Worker {
+        polls: metrics::counter!(format!("{prefix}.polls"), &label),
+        poll_duration: metrics::histogram!(
+          format!("{prefix}.poll_duration"),
+          &label
+        ),
+        parks: metrics::counter!(format!("{prefix}.park_count"), &label),
+        queue: metrics::gauge!(format!("{prefix}.queue_depth"), &label),
+      }
+    })
+  }
+
+  fn record(&mut self, event: RuntimeEvent) {
+    match event {
+      RuntimeEvent::TaskSpawned { .. } => {
+        self.spawned.increment(1);
+        self.spawned_count += 1;
+      }
+      RuntimeEvent::TaskCompleted { .. } => {
+        self.completed.increment(1);
+        self.completed_count += 1;
+      }
+      RuntimeEvent::TaskPanicked { .. } => {
+        self.completed.increment(1);
+        self.panicked.increment(1);
+        self.completed_count += 1;
+      }
+      RuntimeEvent::TaskPolled { worker, duration, .. } => {
+        let worker = self.worker(worker);
+        worker.polls.increment(1);
+        worker.poll_duration.record(duration.as_secs_f64());
+        self.busy_time += duration;
+      }
+      RuntimeEvent::WorkerParked { worker } => {
+        self.worker(worker).parks.increment(1);
+      }
+      RuntimeEvent::QueueDepth { worker, local, global, .. } => {
+        self.worker(worker).queue.set(local as f64);
+        self.global_queue.set(global as f64);
+      }
+      _ => {}
+    }
+  }
+
+  fn publish(&mut self, interval: Duration) {
+    let alive = self.spawned_count.saturating_sub(self.completed_count);
+    self.alive.set(alive as f64);
+    self.busy.set(self.busy_time.as_secs_f64() / interval.as_secs_f64());
+    self.busy_time = Duration::ZERO;
+  }
+}
+
+#[test]
+fn known_workload() {
+  use metrics_util::{
+    debugging::{DebugValue, DebuggingRecorder},
+    MetricKind,
+  };
+  use std::collections::HashMap;
+
+  let recorder = DebuggingRecorder::new();
+  let snapshotter = recorder.snapshotter();
+  recorder.install().unwrap();
+  let snapshot = || {
+    let mut values = HashMap::new();
+    for (key, _, _, value) in snapshotter.snapshot().into_vec() {
+      let labels: Vec<_> =
+        key.key().labels().map(|label| label.key().to_string()).collect();
+      values.insert((key.kind(), key.key().name().to_string(), labels), value);
+    }
+    values
+  };
+  let counter = |values: &HashMap<_, DebugValue>, name: &str| {
+    let key = (MetricKind::Counter, name.to_string(), Vec::new());
+    match values.get(&key) {
+      Some(DebugValue::Counter(value)) => *value,
+      other => panic!("{name}: {other:?}"),
+    }
+  };
+
+  let runtime = crate::runtime::Builder::new().worker_threads(2).build();
+  runtime.block_on(async {
+    let workload = async || {
+      let tasks: Vec<_> = (0..10)
+        .map(|_| task::spawn(time::sleep(Duration::from_millis(1))))
+        .collect();
+      for task in tasks {
+        task.await.unwrap();
+      }
+      // Published at least once since.
+      time::sleep(Duration::from_millis(100)).await;
+    };
+
+    Exporter::new(Duration::from_millis(20))
+      .prefix("test")
+      .install(&Handle::current());
+    workload().await;
+    let first = snapshot();
+    // It counts itself too.
+    assert!(counter(&first, "test.tasks_spawned") >= 11);
+    assert!(counter(&first, "test.tasks_completed") >= 10);
+    assert_eq!(counter(&first, "test.tasks_panicked"), 0);
+    for (kind, name, labels) in [
+      (MetricKind::Gauge, "test.tasks_alive", &[][..]),
+      (MetricKind::Gauge, "test.workers_busy", &[]),
+      (MetricKind::Counter, "test.polls", &["worker"]),
+      (MetricKind::Histogram, "test.poll_duration", &["worker"]),
+    ] {
+      let labels = labels.iter().map(|label| label.to_string()).collect();
+      assert!(first.contains_key(&(kind, name.to_string(), labels)), "{name}");
+    }
+
+    // The snapshots take what the counters went up by since the last one.
+    workload().await;
+    let second = snapshot();
+    for name in ["test.tasks_spawned", "test.tasks_completed"] {
+      assert!(counter(&second, name) >= 10, "{name}");
+    }
+  });
+}
@@ -7,12 +7,15 @@ pub struct Http1Listener {
 
 pub struct Http1Request {
   stream: TcpStream,
-  readable: bool,
 }
 
 impl Http1Request {
   pub fn from_stream(tcp: TcpStream) -> Self {
-    Http1Request { stream: tcp, readable: false }
+    Http1Request { stream: tcp }
+  }
+
+  pub fn into_stream(self) -> TcpStream {
+    self.stream
   }
 }
 
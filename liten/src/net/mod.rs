@@ -1,6 +1,8 @@
 #[cfg(feature = "http1")]
 mod http1;
 mod tcp;
+mod udp;
 #[cfg(feature = "http1")]
 pub use http1::*;
 pub use tcp::*;
+pub use udp::*;
@@ -8,7 +8,7 @@ use std::{
 
 use mio::net as mionet;
 
-use crate::{events::EventRegistration, net::TcpStream};
+use crate::{context::ContextWatch, events::EventRegistration, net::TcpStream};
 
 #[must_use = "futures do nothing unless you `.await` or poll them"]
 pub struct Accept<'a> {
@@ -17,6 +17,13 @@ pub struct Accept<'a> {
   // We don't drop this after accepts lifetime because it's a reference and it's TcpListeners job
   // to drop this.
   registration: &'a EventRegistration,
+  context: ContextWatch,
+
+  // Set when the accept goes through io_uring, `false` keeps it on readiness.
+  #[cfg(all(target_os = "linux", feature = "uring"))]
+  uring: bool,
+  #[cfg(all(target_os = "linux", feature = "uring"))]
+  op: Option<crate::events::uring::Op<()>>,
 }
 
 impl<'a> Accept<'a> {
@@ -24,22 +31,116 @@ impl<'a> Accept<'a> {
     listener: &'a mionet::TcpListener,
     registration: &'a EventRegistration,
   ) -> Accept<'a> {
-    Self { inner: listener, registration }
+    Self {
+      inner: listener,
+      registration,
+      context: ContextWatch::default(),
+      #[cfg(all(target_os = "linux", feature = "uring"))]
+      uring: true,
+      #[cfg(all(target_os = "linux", feature = "uring"))]
+      op: None,
+    }
+  }
+
+  /// Only waits for readiness. An accept in flight on io_uring takes a connection even if this
+  /// future is dropped, which loses it when the future doesn't outlive one poll.
+  #[cfg_attr(
+    not(all(target_os = "linux", feature = "uring")),
+    allow(unused_mut)
+  )]
+  pub(crate) fn readiness_only(mut self) -> Self {
+    #[cfg(all(target_os = "linux", feature = "uring"))]
+    {
+      self.uring = false;
+    }
+    self
+  }
+
+  #[cfg(all(target_os = "linux", feature = "uring"))]
+  fn poll_uring(
+    &mut self,
+    cx: &mut Context<'_>,
+  ) -> Option<Poll<io::Result<(TcpStream, SocketAddr)>>> {
+    use crate::events::uring::{self, Op, OpKind};
+    use std::os::fd::{AsRawFd, FromRawFd};
+
+    if !self.uring {
+      return None;
+    }
+    if self.op.is_none() {
+      let handle = uring::handle()?;
+      let fd = io_uring::types::Fd(self.inner.as_raw_fd());
+      let entry = io_uring::opcode::Accept::new(
+        fd,
+        std::ptr::null_mut(),
+        std::ptr::null_mut(),
+      )
+      .flags(libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC)
+      .build();
+      // SAFETY: The entry doesn't point to any memory.
+      match unsafe { Op::submit(handle, entry, OpKind::Accept, ()) } {
+        Ok(op) => self.op = Some(op),
+        Err((err, ())) => return Some(Poll::Ready(Err(err))),
+      }
+    }
+
+    let op = self.op.as_mut().unwrap();
+    let Poll::Ready((result, ())) = Pin::new(op).poll(cx) else {
+      return Some(Poll::Pending);
+    };
+    self.op = None;
+
+    Some(Poll::Ready(result.and_then(|fd| {
+      // SAFETY: The fd was just accepted and isn't owned by anything else.
+      let stream = unsafe { std::net::TcpStream::from_raw_fd(fd as i32) };
+      let addr = stream.peer_addr()?;
+      #[cfg(feature = "tracing")]
+      tracing::trace!(peer = %addr, "accepted");
+      let stream = mionet::TcpStream::from_std(stream);
+      Ok((TcpStream::inherit_mio_stream(stream), addr))
+    })))
   }
 }
 
 impl Future for Accept<'_> {
   type Output = io::Result<(TcpStream, SocketAddr)>;
-  fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+  fn poll(
+    mut self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<Self::Output> {
+    if let Poll::Ready(err) = self.context.poll(cx) {
+      return Poll::Ready(Err(err.into()));
+    }
+
+    #[cfg(all(target_os = "linux", feature = "uring"))]
+    if let Some(poll) = self.poll_uring(cx) {
+      return poll;
+    }
+
     match self.inner.accept() {
       Ok((stream, addr)) => {
+        #[cfg(feature = "tracing")]
+        tracing::trace!(peer = %addr, "accepted");
         Poll::Ready(Ok((TcpStream::inherit_mio_stream(stream), addr)))
       }
       Err(kind) if kind.kind() == io::ErrorKind::WouldBlock => {
-        self.registration.register_io_waker(cx);
+        self.registration.register_io_waker(cx)?;
         Poll::Pending
       }
       Err(err) => Poll::Ready(Err(err)),
     }
   }
 }
+
+#[crate::internal_test]
+async fn expires_while_pending() {
+  use crate::{context::Context, net::TcpListener};
+  use std::time::Duration;
+
+  let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+
+  let ctx = Context::background().with_timeout(Duration::from_millis(20));
+  let err = ctx.scope(listener.accept()).await.err().unwrap();
+
+  assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+}
@@ -0,0 +1,87 @@
+use std::{io, net::SocketAddr};
+
+use mio::net as mionet;
+
+/// The addresses of a connection, returned by [`TcpListener::accept_with_info`].
+///
+/// [`TcpListener::accept_with_info`]: super::TcpListener::accept_with_info
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ConnectionInfo {
+  pub local_addr: SocketAddr,
+  pub peer_addr: SocketAddr,
+  /// The destination the peer connected to before it was redirected to this listener, as seen
+  /// by a transparent proxy behind an iptables `REDIRECT` or `DNAT` rule (`SO_ORIGINAL_DST`).
+  /// `None` when the connection wasn't redirected, or on platforms other than Linux.
+  pub original_dst: Option<SocketAddr>,
+}
+
+impl ConnectionInfo {
+  pub(super) fn of(
+    stream: &mionet::TcpStream,
+    peer_addr: SocketAddr,
+  ) -> io::Result<ConnectionInfo> {
+    Ok(ConnectionInfo {
+      local_addr: stream.local_addr()?,
+      peer_addr,
+      original_dst: original_dst(stream),
+    })
+  }
+}
+
+#[cfg(target_os = "linux")]
+fn original_dst(stream: &mionet::TcpStream) -> Option<SocketAddr> {
+  use std::{
+    mem::{size_of, MaybeUninit},
+    net::{SocketAddrV4, SocketAddrV6},
+    os::fd::AsRawFd,
+  };
+
+  let (level, name) = match stream.local_addr().ok()? {
+    SocketAddr::V4(_) => (libc::SOL_IP, libc::SO_ORIGINAL_DST),
+    SocketAddr::V6(_) => (libc::SOL_IPV6, libc::IP6T_SO_ORIGINAL_DST),
+  };
+  let mut addr = MaybeUninit::<libc::sockaddr_storage>::zeroed();
+  let mut len = size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+  // SAFETY: `addr` is large enough for any address, and `len` says so.
+  let result = unsafe {
+    libc::getsockopt(
+      stream.as_raw_fd(),
+      level,
+      name,
+      addr.as_mut_ptr().cast(),
+      &mut len,
+    )
+  };
+  // Fails when netfilter has no redirect for the connection.
+  if result != 0 {
+    return None;
+  }
+
+  // SAFETY: The kernel has written an address of the family it says.
+  let addr = unsafe { addr.assume_init() };
+  match addr.ss_family as libc::c_int {
+    libc::AF_INET => {
+      let addr = unsafe { *(&addr as *const _ as *const libc::sockaddr_in) };
+      Some(SocketAddr::V4(SocketAddrV4::new(
+        u32::from_be(addr.sin_addr.s_addr).into(),
+        u16::from_be(addr.sin_port),
+      )))
+    }
+    libc::AF_INET6 => {
+      let addr = unsafe { *(&addr as *const _ as *const libc::sockaddr_in6) };
+      Some(SocketAddr::V6(SocketAddrV6::new(
+        addr.sin6_addr.s6_addr.into(),
+        u16::from_be(addr.sin6_port),
+        addr.sin6_flowinfo,
+        addr.sin6_scope_id,
+      )))
+    }
+    _ => None,
+  }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn original_dst(_: &mionet::TcpStream) -> Option<SocketAddr> {
+  None
+}
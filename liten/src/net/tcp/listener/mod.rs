@@ -1,19 +1,23 @@
 mod accept;
+mod info;
+mod serve;
 use std::{
   io,
   net::{SocketAddr, ToSocketAddrs},
+  os::fd::AsRawFd,
   pin::Pin,
   task::{Context, Poll},
 };
 
 pub use accept::*;
+pub use info::ConnectionInfo;
 
 use mio::{net as mionet, Interest};
 use std::net as stdnet;
 
-use crate::events::EventRegistration;
+use crate::{context, events::EventRegistration};
 
-use super::TcpStream;
+use super::{sockopt, TcpStream};
 
 pub struct TcpListener {
   registration: EventRegistration,
@@ -33,15 +37,14 @@ impl TcpListener {
   where
     A: ToSocketAddrs,
   {
+    context::handle_for("TcpListener::bind")?;
     let tcp = stdnet::TcpListener::bind(addr)?;
     tcp.set_nonblocking(true)?;
 
     let mut listener = mionet::TcpListener::from_std(tcp);
 
     // This is only readable because this IoRegistration is only used for listening for incoming
-    // connections. TcpStream read and write operations are all blocking.
-    //
-    // This is because some trange bugs happen when trying for async io.
+    // connections. Every accepted TcpStream has its own.
     let registration = EventRegistration::new(Interest::READABLE);
     let _ = registration.register(&mut listener);
     Ok(TcpListener { registration, listener })
@@ -50,6 +53,45 @@ impl TcpListener {
   pub fn accept(&self) -> Accept<'_> {
     Accept::new(&self.listener, &self.registration)
   }
+
+  /// Like [`TcpListener::accept`], but also returns the local address of the connection, and the
+  /// original destination if it was redirected to this listener.
+  pub async fn accept_with_info(
+    &self,
+  ) -> io::Result<(TcpStream, ConnectionInfo)> {
+    let (stream, peer_addr) = self.accept().await?;
+    let info = ConnectionInfo::of(stream.as_mio(), peer_addr)?;
+    Ok((stream, info))
+  }
+
+  pub fn local_addr(&self) -> io::Result<SocketAddr> {
+    self.listener.local_addr()
+  }
+
+  /// Sets the receive buffer size of the connections accepted from now on, see
+  /// [`TcpStream::set_recv_buffer_size`].
+  ///
+  /// The window scale of a connection is settled during its handshake, before it's accepted, so
+  /// this is how a connection gets a receive buffer larger than 64 KiB to be of any use.
+  pub fn set_recv_buffer_size(&self, size: usize) -> io::Result<()> {
+    sockopt::set_buffer_size(self.listener.as_raw_fd(), libc::SO_RCVBUF, size)
+  }
+
+  /// The receive buffer size accepted connections start with.
+  pub fn recv_buffer_size(&self) -> io::Result<usize> {
+    sockopt::buffer_size(self.listener.as_raw_fd(), libc::SO_RCVBUF)
+  }
+
+  /// Sets the send buffer size of the connections accepted from now on, see
+  /// [`TcpStream::set_send_buffer_size`].
+  pub fn set_send_buffer_size(&self, size: usize) -> io::Result<()> {
+    sockopt::set_buffer_size(self.listener.as_raw_fd(), libc::SO_SNDBUF, size)
+  }
+
+  /// The send buffer size accepted connections start with.
+  pub fn send_buffer_size(&self) -> io::Result<usize> {
+    sockopt::buffer_size(self.listener.as_raw_fd(), libc::SO_SNDBUF)
+  }
 }
 
 impl futures_core::Stream for TcpListener {
@@ -59,10 +101,44 @@ impl futures_core::Stream for TcpListener {
     self: Pin<&mut Self>,
     cx: &mut Context<'_>,
   ) -> Poll<Option<Self::Item>> {
-    let fut = std::pin::pin!(self.accept());
+    // A new accept is made for every poll.
+    let fut = std::pin::pin!(self.accept().readiness_only());
     match std::future::Future::poll(fut, cx) {
       Poll::Ready(value) => Poll::Ready(Some(value)),
       Poll::Pending => Poll::Pending,
     }
   }
 }
+
+#[crate::internal_test]
+async fn accept_with_info() {
+  let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+  let addr = listener.local_addr().unwrap();
+  let client = std::net::TcpStream::connect(addr).unwrap();
+
+  let (stream, info) = listener.accept_with_info().await.unwrap();
+  assert_eq!(info.peer_addr, client.local_addr().unwrap());
+  assert_eq!(info.local_addr, addr);
+  assert_eq!(stream.peer_addr().unwrap(), info.peer_addr);
+  // Not redirected, there's no netfilter rule in the way.
+  assert_eq!(info.original_dst, None);
+}
+
+#[crate::internal_test]
+async fn buffer_sizes() {
+  let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+  listener.set_send_buffer_size(64 * 1024).unwrap();
+  let addr = listener.local_addr().unwrap();
+  let _client = std::net::TcpStream::connect(addr).unwrap();
+  let (stream, _) = listener.accept().await.unwrap();
+
+  // Linux doubles what it's given.
+  let inherited = stream.send_buffer_size().unwrap();
+  assert!(inherited == 64 * 1024 || inherited == 128 * 1024, "{inherited}");
+
+  stream.set_send_buffer_size(32 * 1024).unwrap();
+  let size = stream.send_buffer_size().unwrap();
+  assert!(size == 32 * 1024 || size == 64 * 1024, "{size}");
+  stream.set_recv_buffer_size(32 * 1024).unwrap();
+  assert!(stream.recv_buffer_size().unwrap() >= 32 * 1024);
+}
@@ -0,0 +1,125 @@
+use std::{
+  future::{poll_fn, Future},
+  io,
+  net::SocketAddr,
+  pin::pin,
+  task::Poll,
+};
+
+use crate::{
+  net::TcpStream,
+  sync::CancellationToken,
+  task::{JoinSet, TaskHandleError},
+};
+
+use super::TcpListener;
+
+impl TcpListener {
+  /// Accepts connections until `shutdown` is cancelled, and spawns `handler(stream, addr)` for
+  /// each of them.
+  ///
+  /// Handlers which finish are reaped as the loop goes, one which panics is logged and doesn't
+  /// stop the others. Once `shutdown` is cancelled no more connections are accepted, and this
+  /// waits for the handlers still running before returning. Handlers which should stop early can
+  /// watch a clone of `shutdown` themselves.
+  ///
+  /// An error accepting a connection is returned right away, the handlers already running are
+  /// left detached.
+  pub async fn serve<F, Fut>(
+    &self,
+    shutdown: &CancellationToken,
+    mut handler: F,
+  ) -> io::Result<()>
+  where
+    F: FnMut(TcpStream, SocketAddr) -> Fut,
+    Fut: Future<Output = ()> + Send + 'static,
+  {
+    let mut handlers = JoinSet::new();
+    let mut cancelled = pin!(shutdown.cancelled());
+    // Kept between polls, an accept on io_uring can't be dropped without losing its connection.
+    let mut accept = Box::pin(self.accept());
+
+    let accepted = poll_fn(|cx| loop {
+      while let Poll::Ready(Some(result)) = handlers.poll_join_next(cx) {
+        log_handler(result);
+      }
+      if cancelled.as_mut().poll(cx).is_ready() {
+        return Poll::Ready(Ok(()));
+      }
+      let (stream, addr) = std::task::ready!(accept.as_mut().poll(cx))?;
+      accept.set(self.accept());
+      handlers.spawn(handler(stream, addr));
+    })
+    .await;
+
+    while let Some(result) = handlers.join_next().await {
+      log_handler(result);
+    }
+    accepted
+  }
+}
+
+fn log_handler(result: Result<(), TaskHandleError>) {
+  #[cfg(feature = "tracing")]
+  if let Err(err) = result {
+    tracing::error!("connection handler failed: {err}");
+  }
+  #[cfg(not(feature = "tracing"))]
+  let _ = result;
+}
+
+#[crate::internal_test]
+async fn echo_connections() {
+  use crate::{
+    io::{AsyncReadExt, AsyncWrite},
+    task,
+  };
+  use std::{pin::Pin, sync::Arc, thread};
+
+  let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+  let addr = listener.local_addr().unwrap();
+  let shutdown = CancellationToken::new();
+  let server = task::spawn({
+    let shutdown = shutdown.clone();
+    async move {
+      listener
+        .serve(&shutdown, |mut stream, _| async move {
+          let mut buf = [0; 8];
+          while let Ok(read @ 1..) = stream.read(&mut buf).await {
+            let write =
+              poll_fn(|cx| Pin::new(&mut stream).poll_write(cx, &buf[..read]));
+            write.await.unwrap();
+          }
+        })
+        .await
+        .unwrap();
+      listener
+    }
+  });
+
+  // Every client is connected before any of them writes, so the handlers run at once.
+  let barrier = Arc::new(std::sync::Barrier::new(4));
+  let clients: Vec<_> = (0..4u8)
+    .map(|client| {
+      let barrier = barrier.clone();
+      thread::spawn(move || {
+        use std::io::{Read, Write};
+
+        let mut stream = std::net::TcpStream::connect(addr).unwrap();
+        barrier.wait();
+        stream.write_all(&[client; 4]).unwrap();
+        let mut buf = [0; 4];
+        stream.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, [client; 4]);
+      })
+    })
+    .collect();
+  for client in clients {
+    client.join().unwrap();
+  }
+
+  shutdown.cancel();
+  // Every client has hung up, so each handler was reaped and the listener comes back.
+  let listener = server.await.unwrap();
+  assert_eq!(listener.local_addr().unwrap(), addr);
+}
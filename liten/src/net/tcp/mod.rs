@@ -1,4 +1,5 @@
 mod listener;
+mod sockopt;
 pub use listener::*;
 mod stream;
 pub use stream::*;
@@ -0,0 +1,95 @@
+use std::{io, mem::size_of, os::fd::RawFd};
+
+// Socket options which are a `c_int`, shared by streams and listeners.
+pub(super) fn set(
+  fd: RawFd,
+  level: libc::c_int,
+  name: libc::c_int,
+  value: libc::c_int,
+) -> io::Result<()> {
+  // SAFETY: The value is a `c_int`, and its length says so.
+  let result = unsafe {
+    libc::setsockopt(
+      fd,
+      level,
+      name,
+      (&value as *const libc::c_int).cast(),
+      size_of::<libc::c_int>() as libc::socklen_t,
+    )
+  };
+  if result != 0 {
+    return Err(io::Error::last_os_error());
+  }
+  Ok(())
+}
+
+pub(super) fn get(
+  fd: RawFd,
+  level: libc::c_int,
+  name: libc::c_int,
+) -> io::Result<libc::c_int> {
+  let mut value: libc::c_int = 0;
+  let mut len = size_of::<libc::c_int>() as libc::socklen_t;
+  // SAFETY: `value` is a `c_int`, and `len` says so.
+  let result = unsafe {
+    libc::getsockopt(
+      fd,
+      level,
+      name,
+      (&mut value as *mut libc::c_int).cast(),
+      &mut len,
+    )
+  };
+  if result != 0 {
+    return Err(io::Error::last_os_error());
+  }
+  Ok(value)
+}
+
+// `SO_RCVBUF` and `SO_SNDBUF`.
+pub(super) fn set_buffer_size(
+  fd: RawFd,
+  name: libc::c_int,
+  size: usize,
+) -> io::Result<()> {
+  let size = libc::c_int::try_from(size).map_err(|_| {
+    io::Error::new(io::ErrorKind::InvalidInput, "buffer size is too large")
+  })?;
+  set(fd, libc::SOL_SOCKET, name, size)
+}
+
+pub(super) fn buffer_size(fd: RawFd, name: libc::c_int) -> io::Result<usize> {
+  get(fd, libc::SOL_SOCKET, name).map(|size| size as usize)
+}
+
+// `TCP_CORK` on Linux, `TCP_NOPUSH` on the BSDs, which does the same.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(super) fn set_cork(fd: RawFd, cork: bool) -> io::Result<()> {
+  set(fd, libc::IPPROTO_TCP, libc::TCP_CORK, cork.into())
+}
+
+#[cfg(any(
+  target_os = "macos",
+  target_os = "ios",
+  target_os = "freebsd",
+  target_os = "netbsd",
+  target_os = "openbsd",
+  target_os = "dragonfly"
+))]
+pub(super) fn set_cork(fd: RawFd, cork: bool) -> io::Result<()> {
+  set(fd, libc::IPPROTO_TCP, libc::TCP_NOPUSH, cork.into())
+}
+
+#[cfg(not(any(
+  target_os = "linux",
+  target_os = "android",
+  target_os = "macos",
+  target_os = "ios",
+  target_os = "freebsd",
+  target_os = "netbsd",
+  target_os = "openbsd",
+  target_os = "dragonfly"
+)))]
+pub(super) fn set_cork(_: RawFd, _: bool) -> io::Result<()> {
+  Ok(())
+}
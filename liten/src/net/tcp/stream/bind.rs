@@ -0,0 +1,186 @@
+use std::{
+  io, mem,
+  net::{self as stdnet, SocketAddr, ToSocketAddrs},
+  os::fd::{AsRawFd, FromRawFd, OwnedFd},
+};
+
+use mio::net as mionet;
+
+use crate::context;
+
+use super::{Connect, TcpStream};
+
+impl TcpStream {
+  /// Like [`TcpStream::connect`], but from `local`: the socket is bound to it before it connects,
+  /// which picks the source address of the connection, and on a host with several interfaces the
+  /// one it leaves from. A port of 0 lets the system pick one.
+  pub fn connect_from(
+    local: SocketAddr,
+    remote: impl ToSocketAddrs,
+  ) -> io::Result<Connect> {
+    context::handle_for("TcpStream::connect_from")?;
+    let remote = first_addr(remote)?;
+    let socket = socket(&remote)?;
+    let (addr, len) = to_sockaddr(&local);
+    // SAFETY: `addr` is a sockaddr of `len` bytes.
+    if unsafe { libc::bind(socket.as_raw_fd(), addr.as_ptr(), len) } != 0 {
+      return Err(io::Error::last_os_error());
+    }
+    connect(socket, &remote)
+  }
+
+  /// Like [`TcpStream::connect`], but the connection leaves from the network interface named
+  /// `interface` (`SO_BINDTODEVICE`), whatever the routing table says. Linux only allows it to
+  /// processes with `CAP_NET_RAW` before 5.7.
+  #[cfg(any(target_os = "linux", target_os = "android"))]
+  pub fn connect_from_device(
+    interface: &str,
+    remote: impl ToSocketAddrs,
+  ) -> io::Result<Connect> {
+    context::handle_for("TcpStream::connect_from_device")?;
+    let remote = first_addr(remote)?;
+    let socket = socket(&remote)?;
+    // SAFETY: The name is `interface.len()` bytes, which doesn't need a nul at the end.
+    let result = unsafe {
+      libc::setsockopt(
+        socket.as_raw_fd(),
+        libc::SOL_SOCKET,
+        libc::SO_BINDTODEVICE,
+        interface.as_ptr().cast(),
+        interface.len() as libc::socklen_t,
+      )
+    };
+    if result != 0 {
+      return Err(io::Error::last_os_error());
+    }
+    connect(socket, &remote)
+  }
+}
+
+fn first_addr(addr: impl ToSocketAddrs) -> io::Result<SocketAddr> {
+  addr.to_socket_addrs()?.next().ok_or_else(|| {
+    io::Error::new(io::ErrorKind::InvalidInput, "Address not valid")
+  })
+}
+
+// A TCP socket of the family of `remote`, which isn't connected yet.
+fn socket(remote: &SocketAddr) -> io::Result<OwnedFd> {
+  let family = match remote {
+    SocketAddr::V4(_) => libc::AF_INET,
+    SocketAddr::V6(_) => libc::AF_INET6,
+  };
+  // Close on exec and non-blocking, like every other socket of the runtime. Set at creation where
+  // possible, so a fork meanwhile can't leak the socket.
+  #[cfg(any(target_os = "linux", target_os = "android"))]
+  let ty = libc::SOCK_STREAM | libc::SOCK_CLOEXEC | libc::SOCK_NONBLOCK;
+  #[cfg(not(any(target_os = "linux", target_os = "android")))]
+  let ty = libc::SOCK_STREAM;
+  // SAFETY: No pointers.
+  let fd = unsafe { libc::socket(family, ty, 0) };
+  if fd == -1 {
+    return Err(io::Error::last_os_error());
+  }
+  // SAFETY: Just created, and not owned by anything else.
+  let socket = unsafe { OwnedFd::from_raw_fd(fd) };
+  #[cfg(not(any(target_os = "linux", target_os = "android")))]
+  set_flags(&socket)?;
+  Ok(socket)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn set_flags(socket: &OwnedFd) -> io::Result<()> {
+  let fd = socket.as_raw_fd();
+  // SAFETY: No pointers.
+  let set = unsafe {
+    libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) != -1 && {
+      let flags = libc::fcntl(fd, libc::F_GETFL);
+      flags != -1
+        && libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK) != -1
+    }
+  };
+  match set {
+    true => Ok(()),
+    false => Err(io::Error::last_os_error()),
+  }
+}
+
+fn connect(socket: OwnedFd, remote: &SocketAddr) -> io::Result<Connect> {
+  let (addr, len) = to_sockaddr(remote);
+  // SAFETY: `addr` is a sockaddr of `len` bytes.
+  if unsafe { libc::connect(socket.as_raw_fd(), addr.as_ptr(), len) } != 0 {
+    let err = io::Error::last_os_error();
+    if err.raw_os_error() != Some(libc::EINPROGRESS) {
+      return Err(err);
+    }
+  }
+  let stream = mionet::TcpStream::from_std(stdnet::TcpStream::from(socket));
+  Ok(Connect::inherit_stream(stream))
+}
+
+// The address as the `sockaddr` the system calls take, with its length.
+fn to_sockaddr(addr: &SocketAddr) -> (SockAddr, libc::socklen_t) {
+  // SAFETY: All zeroes is a valid sockaddr_storage.
+  let mut storage: libc::sockaddr_storage = unsafe { mem::zeroed() };
+  let len = match addr {
+    SocketAddr::V4(addr) => {
+      // SAFETY: A sockaddr_in fits in a sockaddr_storage, which is aligned for it.
+      let sin = unsafe {
+        &mut *std::ptr::from_mut(&mut storage).cast::<libc::sockaddr_in>()
+      };
+      sin.sin_family = libc::AF_INET as libc::sa_family_t;
+      sin.sin_port = addr.port().to_be();
+      sin.sin_addr.s_addr = u32::from(*addr.ip()).to_be();
+      mem::size_of::<libc::sockaddr_in>()
+    }
+    SocketAddr::V6(addr) => {
+      // SAFETY: A sockaddr_in6 fits in a sockaddr_storage, which is aligned for it.
+      let sin6 = unsafe {
+        &mut *std::ptr::from_mut(&mut storage).cast::<libc::sockaddr_in6>()
+      };
+      sin6.sin6_family = libc::AF_INET6 as libc::sa_family_t;
+      sin6.sin6_port = addr.port().to_be();
+      sin6.sin6_addr.s6_addr = addr.ip().octets();
+      sin6.sin6_flowinfo = addr.flowinfo();
+      sin6.sin6_scope_id = addr.scope_id();
+      mem::size_of::<libc::sockaddr_in6>()
+    }
+  };
+  (SockAddr(storage), len as libc::socklen_t)
+}
+
+struct SockAddr(libc::sockaddr_storage);
+
+impl SockAddr {
+  fn as_ptr(&self) -> *const libc::sockaddr {
+    std::ptr::from_ref(&self.0).cast()
+  }
+}
+
+// All of 127/8 is only loopback on Linux.
+#[cfg(target_os = "linux")]
+#[crate::internal_test]
+async fn source_address_is_chosen() {
+  let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+  let remote = listener.local_addr().unwrap();
+
+  // Another source address than the listener's.
+  let local: SocketAddr = "127.0.0.2:0".parse().unwrap();
+  let stream = TcpStream::connect_from(local, remote).unwrap().await.unwrap();
+  let (_peer, source) = listener.accept().unwrap();
+  assert_eq!(source.ip(), local.ip());
+  assert_eq!(stream.local_addr().unwrap(), source);
+  assert_eq!(stream.peer_addr().unwrap(), remote);
+
+  // A port which is taken can't be bound again.
+  let err = TcpStream::connect_from(source, remote).err().unwrap();
+  assert_eq!(err.kind(), io::ErrorKind::AddrInUse);
+
+  match TcpStream::connect_from_device("lo", remote) {
+    Ok(connect) => {
+      connect.await.unwrap();
+      listener.accept().unwrap();
+    }
+    // Without the capability it takes on older kernels.
+    Err(err) => assert_eq!(err.kind(), io::ErrorKind::PermissionDenied),
+  }
+}
@@ -7,7 +7,10 @@ use std::{
 
 use mio::{net as mionet, Interest};
 
-use crate::{context, events::EventRegistration};
+use crate::{
+  context::{self, ContextWatch},
+  events::EventRegistration,
+};
 
 use super::TcpStream;
 
@@ -15,25 +18,29 @@ use super::TcpStream;
 pub struct Connect {
   socket: Option<mionet::TcpStream>,
   registration: EventRegistration,
+  context: ContextWatch,
 }
 
 impl Connect {
   /// Registration and it's management is passed on
   pub(crate) fn inherit_stream(mut stream: mionet::TcpStream) -> Self {
-    let registration = EventRegistration::new(Interest::READABLE);
+    // The socket becomes writable once the connection is established, or has failed.
+    let registration = EventRegistration::new(Interest::WRITABLE);
     registration.register(&mut stream).expect("internal 'liten' error: failed to register liten::net::tcp::stream::Connect's IoRegistration");
-    Self { socket: Some(stream), registration }
+    Self {
+      socket: Some(stream),
+      registration,
+      context: ContextWatch::default(),
+    }
   }
 }
 
 impl Drop for Connect {
   fn drop(&mut self) {
-    match self.socket {
-      Some(ref mut v) => {
-        // Ignore error
-        let _ = self.registration.deregister(v);
-      }
-      None => {} // Future was dropped without polling
+    // None: Future was dropped without polling
+    if let Some(ref mut v) = self.socket {
+      // Ignore error
+      let _ = self.registration.deregister(v);
     }
   }
 }
@@ -42,8 +49,12 @@ impl Future for Connect {
   type Output = io::Result<TcpStream>;
   fn poll(
     mut self: Pin<&mut Self>,
-    _cx: &mut Context<'_>,
+    cx: &mut Context<'_>,
   ) -> Poll<Self::Output> {
+    if let Poll::Ready(err) = self.context.poll(cx) {
+      return Poll::Ready(Err(err.into()));
+    }
+
     match self.socket.take() {
       Some(socket) => {
         if let Ok(Some(err)) | Err(err) = socket.take_error() {
@@ -51,7 +62,12 @@ impl Future for Connect {
         }
 
         match socket.peer_addr() {
-          Ok(_) => {
+          Ok(_peer) => {
+            #[cfg(feature = "tracing")]
+            tracing::trace!(peer = %_peer, "connected");
+            let mut socket = socket;
+            // The stream registers the socket again with its own interests.
+            self.registration.deregister(&mut socket)?;
             let stream = TcpStream::inherit_mio_stream(socket);
             Poll::Ready(Ok(stream))
           }
@@ -60,9 +76,9 @@ impl Future for Connect {
               || err.raw_os_error()
                 == Some(115 /* = libc::EINPROGRESS */) =>
           {
-            let _ = context::with_context(|ctx| {
-              ctx.handle().io().poll(self.registration.token(), _cx)
-            });
+            context::with_context(|ctx| {
+              ctx.handle().io().poll(self.registration.token(), cx)
+            })?;
             self.socket = Some(socket);
 
             Poll::Pending
@@ -74,3 +90,13 @@ impl Future for Connect {
     }
   }
 }
+
+#[crate::internal_test]
+async fn connected_stream_is_registered() {
+  let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+  let addr = listener.local_addr().unwrap();
+
+  let stream = TcpStream::connect(addr).unwrap().await;
+  listener.accept().unwrap();
+  assert!(stream.is_ok());
+}
@@ -1,17 +1,34 @@
+mod bind;
 mod connect;
 pub use connect::*;
+mod shared;
+pub use shared::TcpStreamHandle;
 
-use crate::events::EventRegistration;
+use super::sockopt;
+use crate::{
+  context::{self, ContextWatch},
+  events::EventRegistration,
+  io::{AsyncRead, AsyncWrite},
+  runtime::scheduler::OpenStream,
+};
 
 use mio::{net as mionet, Interest};
 use std::{
-  io::{self, ErrorKind},
+  io::{self, ErrorKind, Read, Write},
   net::{self as stdnet, ToSocketAddrs},
+  os::fd::AsRawFd,
+  pin::Pin,
+  task::{Context, Poll},
 };
 
 pub struct TcpStream {
   inner: mionet::TcpStream,
   registration: EventRegistration,
+  // The deadlines of the read and the write in progress, if any.
+  read_watch: ContextWatch,
+  write_watch: ContextWatch,
+  // Keeps a draining runtime going while the stream is open.
+  _open: Option<OpenStream>,
 }
 
 impl Drop for TcpStream {
@@ -25,8 +42,9 @@ impl TcpStream {
   /// Create a new TCP stream and issue a non-blocking connect to the
   /// specified address.
   pub fn connect(addr: impl ToSocketAddrs) -> io::Result<Connect> {
-    let addrs = addr.to_socket_addrs()?;
-    for addr in addrs {
+    context::handle_for("TcpStream::connect")?;
+    let mut addrs = addr.to_socket_addrs()?;
+    if let Some(addr) = addrs.next() {
       let mio_stream = mionet::TcpStream::connect(addr)?;
       return Ok(Connect::inherit_stream(mio_stream));
     }
@@ -34,6 +52,52 @@ impl TcpStream {
     Err(io::Error::new(io::ErrorKind::InvalidInput, "Address not valid"))
   }
 
+  pub fn local_addr(&self) -> io::Result<stdnet::SocketAddr> {
+    self.inner.local_addr()
+  }
+
+  pub fn peer_addr(&self) -> io::Result<stdnet::SocketAddr> {
+    self.inner.peer_addr()
+  }
+
+  /// Sets the size of the kernel's receive buffer (`SO_RCVBUF`), which bounds the TCP window. A
+  /// connection with a large bandwidth-delay product needs a larger one to use its bandwidth.
+  ///
+  /// Linux doubles the size to make room for its own bookkeeping, and caps it at
+  /// `net.core.rmem_max`, so [`TcpStream::recv_buffer_size`] can return something else.
+  pub fn set_recv_buffer_size(&self, size: usize) -> io::Result<()> {
+    sockopt::set_buffer_size(self.inner.as_raw_fd(), libc::SO_RCVBUF, size)
+  }
+
+  /// The size of the kernel's receive buffer.
+  pub fn recv_buffer_size(&self) -> io::Result<usize> {
+    sockopt::buffer_size(self.inner.as_raw_fd(), libc::SO_RCVBUF)
+  }
+
+  /// Sets the size of the kernel's send buffer (`SO_SNDBUF`), like
+  /// [`TcpStream::set_recv_buffer_size`]. Linux caps it at `net.core.wmem_max`.
+  pub fn set_send_buffer_size(&self, size: usize) -> io::Result<()> {
+    sockopt::set_buffer_size(self.inner.as_raw_fd(), libc::SO_SNDBUF, size)
+  }
+
+  /// The size of the kernel's send buffer.
+  pub fn send_buffer_size(&self) -> io::Result<usize> {
+    sockopt::buffer_size(self.inner.as_raw_fd(), libc::SO_SNDBUF)
+  }
+
+  /// Holds back partial segments while `cork` is set (`TCP_CORK` on Linux, `TCP_NOPUSH` on the
+  /// BSDs), so writes like a header and then a body go out together. Unsetting it sends what's
+  /// held back right away.
+  ///
+  /// Linux sends a held back segment after 200ms anyway. This does nothing on other platforms.
+  pub fn set_cork(&self, cork: bool) -> io::Result<()> {
+    sockopt::set_cork(self.inner.as_raw_fd(), cork)
+  }
+
+  pub(crate) fn as_mio(&self) -> &mionet::TcpStream {
+    &self.inner
+  }
+
   // Partially to maintain compatibility with std.
   pub fn shutdown(&mut self, how: stdnet::Shutdown) -> io::Result<()> {
     match how {
@@ -72,7 +136,174 @@ impl TcpStream {
     let registration =
       EventRegistration::new(Interest::READABLE | Interest::WRITABLE);
     registration.register(&mut mio).expect("Couldn't register TcpStream");
-    TcpStream { inner: mio, registration }
+    TcpStream {
+      inner: mio,
+      registration,
+      read_watch: ContextWatch::default(),
+      write_watch: ContextWatch::default(),
+      _open: context::try_handle().map(OpenStream::new),
+    }
+  }
+
+  /// Receives into the spare capacity of `buf`, and returns how many bytes were received along with
+  /// the buffer. `Ok(0)` means the peer has shut down writing, or that `buf` had no spare capacity.
+  ///
+  /// The buffer is owned by the operation while it runs, which lets it be a single completion-based
+  /// operation when the runtime uses io_uring (the `uring` feature on Linux). Otherwise it waits for
+  /// the socket to become readable, like [`AsyncRead`].
+  pub async fn recv_owned(
+    &mut self,
+    mut buf: Vec<u8>,
+  ) -> (io::Result<usize>, Vec<u8>) {
+    #[cfg(all(target_os = "linux", feature = "uring"))]
+    if let Some(handle) = crate::events::uring::handle() {
+      use crate::events::uring::{Op, OpKind};
+      use std::os::fd::AsRawFd;
+
+      let len = buf.len();
+      let spare = (buf.capacity() - len).min(u32::MAX as usize) as u32;
+      let fd = io_uring::types::Fd(self.inner.as_raw_fd());
+      // SAFETY: The spare capacity is on the heap, and stays where it is when the `Vec` moves.
+      let entry = io_uring::opcode::Recv::new(
+        fd,
+        buf.as_mut_ptr().wrapping_add(len),
+        spare,
+      )
+      .build();
+      let (result, mut buf) =
+        match unsafe { Op::submit(handle, entry, OpKind::Transfer, buf) } {
+          Ok(op) => op.await,
+          Err((err, buf)) => return (Err(err), buf),
+        };
+      if let Ok(received) = result {
+        // SAFETY: The kernel has initialized that many bytes of the spare capacity.
+        unsafe { buf.set_len(len + received as usize) };
+      }
+      return (result.map(|received| received as usize), buf);
+    }
+
+    let len = buf.len();
+    buf.resize(buf.capacity(), 0);
+    let result = std::future::poll_fn(|cx| {
+      let poll = self.poll_io(cx, |inner| inner.read(&mut buf[len..]));
+      self.read_watch.poll_io(cx, poll)
+    })
+    .await;
+    buf.truncate(len + result.as_ref().map_or(0, |received| *received));
+    (result, buf)
+  }
+
+  /// Sends from `buf`, and returns how many bytes were sent along with the buffer.
+  ///
+  /// Like [`TcpStream::recv_owned`], this is a single io_uring operation when the runtime uses
+  /// io_uring.
+  pub async fn send_owned(
+    &mut self,
+    buf: Vec<u8>,
+  ) -> (io::Result<usize>, Vec<u8>) {
+    #[cfg(all(target_os = "linux", feature = "uring"))]
+    if let Some(handle) = crate::events::uring::handle() {
+      use crate::events::uring::{Op, OpKind};
+      use std::os::fd::AsRawFd;
+
+      let len = buf.len().min(u32::MAX as usize) as u32;
+      let fd = io_uring::types::Fd(self.inner.as_raw_fd());
+      let entry = io_uring::opcode::Send::new(fd, buf.as_ptr(), len)
+        .flags(libc::MSG_NOSIGNAL)
+        .build();
+      // SAFETY: The entry only points to the heap allocation of `buf`.
+      let (result, buf) =
+        match unsafe { Op::submit(handle, entry, OpKind::Transfer, buf) } {
+          Ok(op) => op.await,
+          Err((err, buf)) => return (Err(err), buf),
+        };
+      return (result.map(|sent| sent as usize), buf);
+    }
+
+    let result = std::future::poll_fn(|cx| {
+      let poll = self.poll_io(cx, |inner| inner.write(&buf));
+      self.write_watch.poll_io(cx, poll)
+    })
+    .await;
+    (result, buf)
+  }
+
+  /// Receives into `buf` without taking the bytes off the socket, the next peek or read returns
+  /// them again. Like a read, `Ok(0)` means the peer has shut down writing.
+  pub async fn peek(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    std::future::poll_fn(|cx| self.poll_peek(cx, buf)).await
+  }
+
+  /// The poll version of [`TcpStream::peek`], for futures which look at what comes first before
+  /// deciding how to read the rest, like one telling TLS apart from plain text.
+  ///
+  /// When nothing has arrived yet, the waker in `cx` is woken once the socket is readable.
+  pub fn poll_peek(
+    &mut self,
+    cx: &mut Context<'_>,
+    buf: &mut [u8],
+  ) -> Poll<io::Result<usize>> {
+    let poll = self.poll_io(cx, |inner| inner.peek(buf));
+    self.read_watch.poll_io(cx, poll)
+  }
+
+  // Tries `f`, and registers the waker if the socket isn't ready.
+  pub(crate) fn poll_io<R>(
+    &mut self,
+    cx: &mut Context<'_>,
+    mut f: impl FnMut(&mut mionet::TcpStream) -> io::Result<R>,
+  ) -> Poll<io::Result<R>> {
+    match f(&mut self.inner) {
+      Err(err) if err.kind() == ErrorKind::WouldBlock => {}
+      result => return Poll::Ready(result),
+    }
+
+    self.registration.register_io_waker(cx)?;
+
+    // Readiness is edge-triggered, try again in case it changed before the waker was registered.
+    match f(&mut self.inner) {
+      Err(err) if err.kind() == ErrorKind::WouldBlock => Poll::Pending,
+      result => Poll::Ready(result),
+    }
+  }
+}
+
+impl AsyncRead for TcpStream {
+  fn poll_read(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+    buf: &mut [u8],
+  ) -> Poll<io::Result<usize>> {
+    let this = self.get_mut();
+    let poll = this.poll_io(cx, |inner| inner.read(buf));
+    this.read_watch.poll_io(cx, poll)
+  }
+}
+
+impl AsyncWrite for TcpStream {
+  fn poll_write(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+    buf: &[u8],
+  ) -> Poll<io::Result<usize>> {
+    let this = self.get_mut();
+    let poll = this.poll_io(cx, |inner| inner.write(buf));
+    this.write_watch.poll_io(cx, poll)
+  }
+
+  // Writes go straight to the socket, there is nothing to flush.
+  fn poll_flush(
+    self: Pin<&mut Self>,
+    _: &mut Context<'_>,
+  ) -> Poll<io::Result<()>> {
+    Poll::Ready(Ok(()))
+  }
+
+  fn poll_shutdown(
+    self: Pin<&mut Self>,
+    _: &mut Context<'_>,
+  ) -> Poll<io::Result<()>> {
+    Poll::Ready(self.inner.shutdown(stdnet::Shutdown::Write))
   }
 }
 
@@ -109,3 +340,118 @@ impl io::Read for TcpStream {
     }
   }
 }
+
+#[crate::internal_test]
+async fn poll_read_waits_for_data() {
+  use std::future::poll_fn;
+
+  let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+  let mut stream =
+    TcpStream::connect(listener.local_addr().unwrap()).unwrap().await.unwrap();
+  let (mut peer, _) = listener.accept().unwrap();
+
+  let mut buf = [0; 4];
+  let waker = std::task::Waker::noop();
+  let poll =
+    Pin::new(&mut stream).poll_read(&mut Context::from_waker(waker), &mut buf);
+  assert!(poll.is_pending());
+
+  peer.write_all(b"ping").unwrap();
+  let read = poll_fn(|cx| Pin::new(&mut stream).poll_read(cx, &mut buf)).await;
+  assert_eq!(read.unwrap(), 4);
+  assert_eq!(&buf, b"ping");
+}
+
+#[crate::internal_test]
+async fn sniff_with_poll_peek() {
+  use std::future::poll_fn;
+
+  let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+  let mut stream =
+    TcpStream::connect(listener.local_addr().unwrap()).unwrap().await.unwrap();
+  let (mut peer, _) = listener.accept().unwrap();
+
+  let mut first = [0; 1];
+  let waker = std::task::Waker::noop();
+  let poll = stream.poll_peek(&mut Context::from_waker(waker), &mut first);
+  assert!(poll.is_pending());
+
+  // A TLS record starts with 0x16, this is plain text.
+  peer.write_all(b"GET /").unwrap();
+  let peeked = poll_fn(|cx| stream.poll_peek(cx, &mut first)).await;
+  assert_eq!(peeked.unwrap(), 1);
+  assert_ne!(first[0], 0x16);
+
+  // The peeked byte is still there for the read.
+  let mut buf = [0; 5];
+  let read = poll_fn(|cx| Pin::new(&mut stream).poll_read(cx, &mut buf)).await;
+  assert_eq!(read.unwrap(), 5);
+  assert_eq!(&buf, b"GET /");
+}
+
+#[crate::internal_test]
+async fn corked_writes() {
+  use std::future::poll_fn;
+
+  let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+  let mut stream =
+    TcpStream::connect(listener.local_addr().unwrap()).unwrap().await.unwrap();
+  let (mut peer, _) = listener.accept().unwrap();
+
+  stream.set_cork(true).unwrap();
+  for chunk in [&b"head"[..], b"body"] {
+    let written =
+      poll_fn(|cx| Pin::new(&mut stream).poll_write(cx, chunk)).await;
+    assert_eq!(written.unwrap(), 4);
+  }
+  stream.set_cork(false).unwrap();
+
+  // Whether it was one segment can't be seen from here, only that nothing got lost.
+  let mut buf = [0; 8];
+  io::Read::read_exact(&mut peer, &mut buf).unwrap();
+  assert_eq!(&buf, b"headbody");
+}
+
+#[crate::internal_test]
+async fn owned_buffers() {
+  let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+  let mut stream =
+    TcpStream::connect(listener.local_addr().unwrap()).unwrap().await.unwrap();
+  let (mut peer, _) = listener.accept().unwrap();
+
+  let (sent, _) = stream.send_owned(b"ping".to_vec()).await;
+  assert_eq!(sent.unwrap(), 4);
+  let mut buf = [0; 4];
+  io::Read::read_exact(&mut peer, &mut buf).unwrap();
+  assert_eq!(&buf, b"ping");
+
+  peer.write_all(b"pong").unwrap();
+  let mut buf = Vec::with_capacity(16);
+  buf.extend_from_slice(b">");
+  let (received, buf) = stream.recv_owned(buf).await;
+  assert_eq!(received.unwrap(), 4);
+  assert_eq!(buf, b">pong");
+}
+
+#[crate::internal_test]
+async fn dropped_recv_owned() {
+  use std::future::Future;
+
+  let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+  let mut stream =
+    TcpStream::connect(listener.local_addr().unwrap()).unwrap().await.unwrap();
+  let (mut peer, _) = listener.accept().unwrap();
+
+  // Nothing is sent yet, so this is dropped with the receive in flight.
+  {
+    let recv = std::pin::pin!(stream.recv_owned(Vec::with_capacity(8)));
+    let waker = std::task::Waker::noop();
+    assert!(recv.poll(&mut Context::from_waker(waker)).is_pending());
+  }
+
+  peer.write_all(b"late").unwrap();
+  let (received, buf) = stream.recv_owned(Vec::with_capacity(8)).await;
+  // The cancelled receive didn't take any of it.
+  assert_eq!(received.unwrap(), 4);
+  assert_eq!(buf, b"late");
+}
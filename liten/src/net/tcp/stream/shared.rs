@@ -0,0 +1,183 @@
+use std::{
+  io::{self, ErrorKind, Read, Write},
+  net as stdnet,
+  ops::Deref,
+  pin::Pin,
+  sync::Arc,
+  task::{Context, Poll},
+};
+
+use mio::net as mionet;
+
+use crate::{
+  context::ContextWatch,
+  io::{AsyncRead, AsyncWrite},
+};
+
+use super::TcpStream;
+
+impl TcpStream {
+  /// Turns the stream into a handle which can be cloned, and used from several tasks at once.
+  ///
+  /// Every clone can read and write. A task waiting to read or write is woken along with every
+  /// other task waiting on the stream, and those which find nothing to do wait again.
+  ///
+  /// Nothing keeps the bytes of different clones apart: a write which is partial, which a large
+  /// one can always be, can have the write of another task land right after it. Tasks which
+  /// write from several clones have to take turns themselves, with a
+  /// [`Mutex`](crate::sync::Mutex) for example. The same goes for reads, every read gets
+  /// whatever bytes are next.
+  ///
+  /// The socket is closed when the last clone is dropped.
+  pub fn clone_handle(self) -> TcpStreamHandle {
+    TcpStreamHandle {
+      stream: Arc::new(self),
+      read_watch: ContextWatch::default(),
+      write_watch: ContextWatch::default(),
+    }
+  }
+
+  // Like `poll_io`, but only needs a shared reference.
+  fn poll_shared<R>(
+    &self,
+    cx: &mut Context<'_>,
+    mut f: impl FnMut(&mionet::TcpStream) -> io::Result<R>,
+  ) -> Poll<io::Result<R>> {
+    match f(&self.inner) {
+      Err(err) if err.kind() == ErrorKind::WouldBlock => {}
+      result => return Poll::Ready(result),
+    }
+
+    self.registration.register_io_waker(cx)?;
+
+    // Readiness is edge-triggered, try again in case it changed before the waker was registered.
+    match f(&self.inner) {
+      Err(err) if err.kind() == ErrorKind::WouldBlock => Poll::Pending,
+      result => Poll::Ready(result),
+    }
+  }
+}
+
+/// A [`TcpStream`] shared between tasks, see [`TcpStream::clone_handle`].
+pub struct TcpStreamHandle {
+  stream: Arc<TcpStream>,
+  // Every clone has deadlines of its own.
+  read_watch: ContextWatch,
+  write_watch: ContextWatch,
+}
+
+impl Clone for TcpStreamHandle {
+  fn clone(&self) -> Self {
+    TcpStreamHandle {
+      stream: self.stream.clone(),
+      read_watch: ContextWatch::default(),
+      write_watch: ContextWatch::default(),
+    }
+  }
+}
+
+impl Deref for TcpStreamHandle {
+  type Target = TcpStream;
+
+  fn deref(&self) -> &TcpStream {
+    &self.stream
+  }
+}
+
+impl AsyncRead for TcpStreamHandle {
+  fn poll_read(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+    buf: &mut [u8],
+  ) -> Poll<io::Result<usize>> {
+    let this = self.get_mut();
+    let poll = this.stream.poll_shared(cx, |mut inner| inner.read(buf));
+    this.read_watch.poll_io(cx, poll)
+  }
+}
+
+impl AsyncWrite for TcpStreamHandle {
+  fn poll_write(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+    buf: &[u8],
+  ) -> Poll<io::Result<usize>> {
+    let this = self.get_mut();
+    let poll = this.stream.poll_shared(cx, |mut inner| inner.write(buf));
+    this.write_watch.poll_io(cx, poll)
+  }
+
+  fn poll_flush(
+    self: Pin<&mut Self>,
+    _: &mut Context<'_>,
+  ) -> Poll<io::Result<()>> {
+    Poll::Ready(Ok(()))
+  }
+
+  /// Shuts down writing for every clone.
+  fn poll_shutdown(
+    self: Pin<&mut Self>,
+    _: &mut Context<'_>,
+  ) -> Poll<io::Result<()>> {
+    Poll::Ready(self.stream.inner.shutdown(stdnet::Shutdown::Write))
+  }
+}
+
+#[crate::internal_test]
+async fn shared_between_tasks() {
+  use crate::{task, time};
+  use std::{future::poll_fn, thread, time::Duration};
+
+  let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+  let stream =
+    TcpStream::connect(listener.local_addr().unwrap()).unwrap().await.unwrap();
+  let (mut peer, _) = listener.accept().unwrap();
+  let handle = stream.clone_handle();
+
+  // Both tasks wait on the stream at once, each has to be woken.
+  let tasks: Vec<_> = (*b"ab")
+    .into_iter()
+    .map(|name| {
+      let mut handle = handle.clone();
+      task::spawn(async move {
+        let written =
+          poll_fn(|cx| Pin::new(&mut handle).poll_write(cx, &[name])).await;
+        assert_eq!(written.unwrap(), 1);
+
+        let mut buf = [0; 4];
+        let mut read = 0;
+        while read < buf.len() {
+          let poll =
+            poll_fn(|cx| Pin::new(&mut handle).poll_read(cx, &mut buf[read..]));
+          read += poll.await.unwrap();
+        }
+        buf
+      })
+    })
+    .collect();
+
+  let peer = thread::spawn(move || {
+    let mut names = [0; 2];
+    peer.read_exact(&mut names).unwrap();
+    for message in [b"1111", b"2222"] {
+      thread::sleep(Duration::from_millis(20));
+      peer.write_all(message).unwrap();
+    }
+    names
+  });
+
+  let mut received = Vec::new();
+  for task in tasks {
+    let buf = crate::select! {
+      buf = task => buf.unwrap(),
+      () = time::sleep(Duration::from_secs(5)) => panic!("a reader wasn't woken"),
+    };
+    received.extend(buf);
+  }
+  received.sort();
+  assert_eq!(received, b"11112222");
+
+  let mut names = peer.join().unwrap();
+  names.sort();
+  assert_eq!(&names, b"ab");
+}
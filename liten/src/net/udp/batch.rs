@@ -0,0 +1,170 @@
+use std::{io, net::SocketAddr};
+
+use mio::net as mionet;
+
+#[cfg(target_os = "linux")]
+pub(super) fn recv(
+  socket: &mionet::UdpSocket,
+  batch: &mut [(Vec<u8>, SocketAddr)],
+) -> io::Result<usize> {
+  use std::{mem, os::fd::AsRawFd, ptr};
+
+  let mut iovecs: Vec<libc::iovec> = batch
+    .iter_mut()
+    .map(|(buf, _)| {
+      buf.resize(buf.capacity(), 0);
+      libc::iovec { iov_base: buf.as_mut_ptr().cast(), iov_len: buf.len() }
+    })
+    .collect();
+  // SAFETY: all zeroes is a valid sockaddr_storage.
+  let mut names: Vec<libc::sockaddr_storage> =
+    vec![unsafe { mem::zeroed() }; batch.len()];
+
+  let mut messages: Vec<libc::mmsghdr> = iovecs
+    .iter_mut()
+    .zip(names.iter_mut())
+    .map(|(iovec, name)| {
+      // SAFETY: all zeroes is a valid msghdr.
+      let mut header: libc::msghdr = unsafe { mem::zeroed() };
+      header.msg_name = ptr::from_mut(name).cast();
+      header.msg_namelen = mem::size_of::<libc::sockaddr_storage>() as _;
+      header.msg_iov = iovec;
+      header.msg_iovlen = 1;
+      libc::mmsghdr { msg_hdr: header, msg_len: 0 }
+    })
+    .collect();
+
+  // SAFETY: every message points to a buffer and an address which outlive the call.
+  let received = unsafe {
+    libc::recvmmsg(
+      socket.as_raw_fd(),
+      messages.as_mut_ptr(),
+      messages.len() as _,
+      libc::MSG_DONTWAIT,
+      ptr::null_mut(),
+    )
+  };
+  if received < 0 {
+    batch.iter_mut().for_each(|(buf, _)| buf.clear());
+    return Err(io::Error::last_os_error());
+  }
+
+  let received = received as usize;
+  for (index, (buf, addr)) in batch.iter_mut().enumerate() {
+    if index >= received {
+      buf.clear();
+      continue;
+    }
+    buf.truncate(messages[index].msg_len as usize);
+    *addr = to_socket_addr(&names[index])?;
+  }
+
+  Ok(received)
+}
+
+#[cfg(any(not(target_os = "linux"), test))]
+#[cfg_attr(target_os = "linux", allow(dead_code))]
+pub(super) fn recv_each(
+  socket: &mionet::UdpSocket,
+  batch: &mut [(Vec<u8>, SocketAddr)],
+) -> io::Result<usize> {
+  let mut received = 0;
+
+  for (buf, addr) in batch.iter_mut() {
+    buf.resize(buf.capacity(), 0);
+    match socket.recv_from(buf) {
+      Ok((len, from)) => {
+        buf.truncate(len);
+        *addr = from;
+        received += 1;
+      }
+      // Nothing more to drain, errors after the first datagram are left for the next call.
+      Err(_) if received > 0 => break,
+      Err(err) => {
+        buf.clear();
+        return Err(err);
+      }
+    }
+  }
+  batch[received..].iter_mut().for_each(|(buf, _)| buf.clear());
+
+  Ok(received)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(super) use recv_each as recv;
+
+#[cfg(target_os = "linux")]
+fn to_socket_addr(storage: &libc::sockaddr_storage) -> io::Result<SocketAddr> {
+  use std::net::{Ipv4Addr, Ipv6Addr, SocketAddrV4, SocketAddrV6};
+
+  match storage.ss_family as libc::c_int {
+    libc::AF_INET => {
+      // SAFETY: the family says this is a sockaddr_in, which fits in sockaddr_storage.
+      let addr =
+        unsafe { &*std::ptr::from_ref(storage).cast::<libc::sockaddr_in>() };
+      let ip = Ipv4Addr::from(u32::from_be(addr.sin_addr.s_addr));
+      Ok(SocketAddrV4::new(ip, u16::from_be(addr.sin_port)).into())
+    }
+    libc::AF_INET6 => {
+      // SAFETY: the family says this is a sockaddr_in6, which fits in sockaddr_storage.
+      let addr =
+        unsafe { &*std::ptr::from_ref(storage).cast::<libc::sockaddr_in6>() };
+      let ip = Ipv6Addr::from(addr.sin6_addr.s6_addr);
+      Ok(
+        SocketAddrV6::new(
+          ip,
+          u16::from_be(addr.sin6_port),
+          addr.sin6_flowinfo,
+          addr.sin6_scope_id,
+        )
+        .into(),
+      )
+    }
+    _ => Err(io::Error::new(
+      io::ErrorKind::InvalidData,
+      "unsupported address family",
+    )),
+  }
+}
+
+#[cfg(test)]
+fn sent_datagrams(
+  count: u8,
+) -> (super::UdpSocket, std::net::UdpSocket, Vec<(Vec<u8>, SocketAddr)>) {
+  let receiver = super::UdpSocket::bind("127.0.0.1:0").unwrap();
+  let sender = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+  for n in 0..count {
+    sender.send_to(&[n; 3], receiver.local_addr().unwrap()).unwrap();
+  }
+
+  let unspecified = SocketAddr::from(([0, 0, 0, 0], 0));
+  // Not `vec![..; n]`, which would clone the buffers without their capacity.
+  let batch =
+    (0..=count).map(|_| (Vec::with_capacity(64), unspecified)).collect();
+  (receiver, sender, batch)
+}
+
+#[crate::internal_test]
+async fn one_batch() {
+  let (receiver, sender, mut batch) = sent_datagrams(4);
+
+  assert_eq!(receiver.recv_batch(&mut batch).await.unwrap(), 4);
+  for (n, (buf, addr)) in batch[..4].iter().enumerate() {
+    assert_eq!(buf, &[n as u8; 3]);
+    assert_eq!(*addr, sender.local_addr().unwrap());
+  }
+  assert!(batch[4].0.is_empty());
+}
+
+#[crate::internal_test]
+async fn one_batch_without_recvmmsg() {
+  let (receiver, sender, mut batch) = sent_datagrams(4);
+
+  assert_eq!(recv_each(&receiver.inner, &mut batch).unwrap(), 4);
+  for (n, (buf, addr)) in batch[..4].iter().enumerate() {
+    assert_eq!(buf, &[n as u8; 3]);
+    assert_eq!(*addr, sender.local_addr().unwrap());
+  }
+  assert!(batch[4].0.is_empty());
+}
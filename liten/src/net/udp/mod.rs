@@ -0,0 +1,133 @@
+mod batch;
+
+use std::{
+  future::poll_fn,
+  io,
+  net::{self as stdnet, SocketAddr, ToSocketAddrs},
+  task::{Context, Poll},
+};
+
+use mio::{net as mionet, Interest};
+
+use crate::{
+  context::{self, ContextWatch},
+  events::EventRegistration,
+};
+
+pub struct UdpSocket {
+  inner: mionet::UdpSocket,
+  registration: EventRegistration,
+}
+
+impl Drop for UdpSocket {
+  fn drop(&mut self) {
+    // Ignore errors.
+    let _ = self.registration.deregister(&mut self.inner);
+  }
+}
+
+impl UdpSocket {
+  pub fn bind<A>(addr: A) -> io::Result<UdpSocket>
+  where
+    A: ToSocketAddrs,
+  {
+    context::handle_for("UdpSocket::bind")?;
+    let socket = stdnet::UdpSocket::bind(addr)?;
+    socket.set_nonblocking(true)?;
+
+    let mut inner = mionet::UdpSocket::from_std(socket);
+    let registration =
+      EventRegistration::new(Interest::READABLE | Interest::WRITABLE);
+    registration.register(&mut inner)?;
+    Ok(UdpSocket { inner, registration })
+  }
+
+  pub fn local_addr(&self) -> io::Result<SocketAddr> {
+    self.inner.local_addr()
+  }
+
+  pub async fn send_to(
+    &self,
+    buf: &[u8],
+    target: SocketAddr,
+  ) -> io::Result<usize> {
+    self
+      .watched(|cx| self.poll_io(cx, || self.inner.send_to(buf, target)))
+      .await
+  }
+
+  pub async fn recv_from(
+    &self,
+    buf: &mut [u8],
+  ) -> io::Result<(usize, SocketAddr)> {
+    self.watched(|cx| self.poll_io(cx, || self.inner.recv_from(buf))).await
+  }
+
+  /// Receives as many datagrams as are available, up to one per entry of `batch`, and returns how
+  /// many were received.
+  ///
+  /// Each datagram is written to the spare capacity of the entry's buffer, so buffers should be
+  /// created with [`Vec::with_capacity`]. The buffer's length is set to the size of the datagram
+  /// and the address to where it came from, the buffers of the remaining entries are cleared.
+  ///
+  /// This waits for the socket to become readable once, and then drains everything queued on it
+  /// in as few system calls as possible: a single `recvmmsg` on Linux, a `recv_from` per datagram
+  /// elsewhere.
+  pub async fn recv_batch(
+    &self,
+    batch: &mut [(Vec<u8>, SocketAddr)],
+  ) -> io::Result<usize> {
+    if batch.is_empty() {
+      return Ok(0);
+    }
+    self
+      .watched(|cx| self.poll_io(cx, || batch::recv(&self.inner, batch)))
+      .await
+  }
+
+  // Runs the operation polled by `poll`, until it's done or its deadline is reached.
+  async fn watched<R>(
+    &self,
+    mut poll: impl FnMut(&mut Context<'_>) -> Poll<io::Result<R>>,
+  ) -> io::Result<R> {
+    let mut watch = ContextWatch::default();
+    poll_fn(|cx| {
+      let poll = poll(cx);
+      watch.poll_io(cx, poll)
+    })
+    .await
+  }
+
+  // Tries `f`, and registers the waker if the socket isn't ready.
+  fn poll_io<R>(
+    &self,
+    cx: &mut Context<'_>,
+    mut f: impl FnMut() -> io::Result<R>,
+  ) -> Poll<io::Result<R>> {
+    match f() {
+      Err(err) if err.kind() == io::ErrorKind::WouldBlock => {}
+      result => return Poll::Ready(result),
+    }
+
+    self.registration.register_io_waker(cx)?;
+
+    // Readiness is edge-triggered, try again in case it changed before the waker was registered.
+    match f() {
+      Err(err) if err.kind() == io::ErrorKind::WouldBlock => Poll::Pending,
+      result => Poll::Ready(result),
+    }
+  }
+}
+
+#[crate::internal_test]
+async fn send_and_receive() {
+  let a = UdpSocket::bind("127.0.0.1:0").unwrap();
+  let b = UdpSocket::bind("127.0.0.1:0").unwrap();
+
+  a.send_to(b"hello", b.local_addr().unwrap()).await.unwrap();
+
+  let mut buf = [0; 16];
+  let (len, from) = b.recv_from(&mut buf).await.unwrap();
+  assert_eq!(&buf[..len], b"hello");
+  assert_eq!(from, a.local_addr().unwrap());
+}
@@ -0,0 +1,491 @@
+use std::{num::NonZero, sync::Arc, time::Duration};
+
+use super::{
+  scheduler::{Elastic, Hooks, Scheduler},
+  Runtime,
+};
+
+/// What [`task::spawn`](crate::task::spawn) does when the task limit of the runtime is reached,
+/// see [`Builder::max_concurrent_tasks`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OnLimit {
+  /// Spawning fails with [`SpawnError::LimitReached`](crate::task::SpawnError::LimitReached).
+  #[default]
+  Reject,
+  /// The spawning thread blocks until a task completes.
+  ///
+  /// This blocks a worker when spawning from a task, so a limit which every worker waits on at
+  /// once deadlocks the runtime.
+  Wait,
+}
+
+/// Configures a [`Runtime`].
+#[derive(Clone, Debug, Default)]
+pub struct Builder {
+  worker_threads: Option<NonZero<usize>>,
+  max_concurrent_tasks: Option<(usize, OnLimit)>,
+  io_timeout: Option<Duration>,
+  shutdown_timeout: Option<Duration>,
+  min_workers: Option<NonZero<usize>>,
+  worker_idle_timeout: Option<Duration>,
+  hooks: Hooks,
+  io_coalesce_window: Duration,
+  blocking_thread_stack_size: Option<usize>,
+}
+
+impl Builder {
+  pub fn new() -> Self {
+    Builder::default()
+  }
+
+  /// How many worker threads run the spawned tasks. Defaults to the available parallelism.
+  ///
+  /// # Panics
+  ///
+  /// Panics if `threads` is 0.
+  pub fn worker_threads(mut self, threads: usize) -> Self {
+    let threads = NonZero::new(threads).expect("worker_threads can't be 0");
+    self.worker_threads = Some(threads);
+    self
+  }
+
+  /// The most worker threads the runtime runs at once, the same as [`Builder::worker_threads`].
+  ///
+  /// # Panics
+  ///
+  /// Panics if `max` is 0.
+  pub fn max_workers(self, max: usize) -> Self {
+    self.worker_threads(max)
+  }
+
+  /// Lets workers stop when they're idle, down to `min` of them, so a runtime which is mostly
+  /// idle doesn't keep a thread per core. Stopped workers are started again when tasks are spawned
+  /// faster than the running ones take them, up to [`Builder::max_workers`].
+  ///
+  /// A worker stops once it has had nothing to do for the
+  /// [`worker_idle_timeout`](Builder::worker_idle_timeout), and only if no task is waiting to be
+  /// woken on it. Has no effect unless `min` is below the number of worker threads.
+  ///
+  /// # Panics
+  ///
+  /// Panics if `min` is 0.
+  pub fn min_workers(mut self, min: usize) -> Self {
+    let min = NonZero::new(min).expect("min_workers can't be 0");
+    self.min_workers = Some(min);
+    self
+  }
+
+  /// How long a worker has to be idle before it stops, see [`Builder::min_workers`]. Defaults to
+  /// 10 seconds.
+  pub fn worker_idle_timeout(mut self, timeout: Duration) -> Self {
+    self.worker_idle_timeout = Some(timeout);
+    self
+  }
+
+  /// Limits how many spawned tasks can be live at once, the future given to
+  /// [`Runtime::block_on`] isn't counted. What happens to spawns past the limit is up to
+  /// `on_limit`.
+  pub fn max_concurrent_tasks(mut self, max: usize, on_limit: OnLimit) -> Self {
+    self.max_concurrent_tasks = Some((max, on_limit));
+    self
+  }
+
+  /// A deadline for every socket read, write, accept and connect which isn't already limited by
+  /// the deadline of its [`Context`](crate::context::Context). An operation fails with
+  /// [`ErrorKind::TimedOut`](std::io::ErrorKind::TimedOut) once it has waited this long.
+  ///
+  /// This is a safety net against connections which hang forever: to give an operation more (or
+  /// less) time, run it in a context with its own deadline.
+  pub fn io_timeout(mut self, timeout: Duration) -> Self {
+    self.io_timeout = Some(timeout);
+    self
+  }
+
+  /// Once the future given to [`Runtime::block_on`] completes, keeps the runtime going for up to
+  /// `timeout` while TCP streams are open or socket reads and writes are still waiting, instead
+  /// of dropping their tasks right away. This lets a server finish the requests it's in the
+  /// middle of.
+  ///
+  /// A stream counts from the moment it's connected or accepted until it's dropped, so a handler
+  /// which was just handed a connection, or which works on a request between reading it and
+  /// writing the response, is waited on too. Streams still open after `timeout`, like idle
+  /// keep-alive connections, are dropped along with their tasks. Accepts don't count, the
+  /// listener can be waiting forever.
+  pub fn shutdown_timeout(mut self, timeout: Duration) -> Self {
+    self.shutdown_timeout = Some(timeout);
+    self
+  }
+
+  /// Calls `f` with the index of a worker right before it parks, when it has found nothing to do.
+  ///
+  /// Along with [`Builder::on_worker_unpark`], this shows when workers go idle and busy again,
+  /// like to see whether the runtime has more workers than it needs. It's called on the worker
+  /// thread in the middle of the scheduler loop, so it has to be quick and must not block.
+  pub fn on_worker_park(
+    mut self,
+    f: impl Fn(usize) + Send + Sync + 'static,
+  ) -> Self {
+    self.hooks.on_park = Some(Arc::new(f));
+    self
+  }
+
+  /// Calls `f` with the index of a worker right after it's unparked, see
+  /// [`Builder::on_worker_park`].
+  pub fn on_worker_unpark(
+    mut self,
+    f: impl Fn(usize) + Send + Sync + 'static,
+  ) -> Self {
+    self.hooks.on_unpark = Some(Arc::new(f));
+    self
+  }
+
+  /// Calls `f` when waiting for socket readiness fails, like when the process is out of file
+  /// descriptors.
+  ///
+  /// The runtime waits a little and tries again after each failure. If it fails several times in
+  /// a row without recovering, it gives up on socket readiness: socket operations which have to
+  /// wait, now or later, fail with the last error instead of hanging, while tasks and timers keep
+  /// running. Without `f`, failures are only logged with the `tracing` feature.
+  pub fn on_io_error(
+    mut self,
+    f: impl Fn(&std::io::Error) + Send + Sync + 'static,
+  ) -> Self {
+    self.hooks.on_io_error = Some(Arc::new(f));
+    self
+  }
+
+  /// Once a socket is ready, waits for `window` to gather the events which come in meanwhile
+  /// before waking the tasks waiting on them. Defaults to zero, which wakes them right away.
+  ///
+  /// This trades a little latency for fewer wakes when there are many connections: a task whose
+  /// socket gets ready more than once within the window is woken once, and runs once for all of
+  /// it. A window of more than tens of microseconds shows up in the latency of every request.
+  pub fn io_coalesce_window(mut self, window: Duration) -> Self {
+    self.io_coalesce_window = window;
+    self
+  }
+
+  /// The stack size, in bytes, of the threads which
+  /// [`BlockScope::spawn_blocking`](crate::task::BlockScope::spawn_blocking) starts from inside
+  /// the runtime. Defaults to the stack size of any new thread, see
+  /// [`std::thread::Builder::stack_size`].
+  ///
+  /// Blocking work like deep recursive parsing can need more stack than tasks do, this gives it
+  /// that without making the stack of every worker larger.
+  pub fn blocking_thread_stack_size(mut self, size: usize) -> Self {
+    self.blocking_thread_stack_size = Some(size);
+    self
+  }
+
+  /// Calls `span` around every poll of a task, see [`PollSpan`](super::PollSpan).
+  pub fn poll_span(mut self, span: impl super::PollSpan) -> Self {
+    self.hooks.poll_span = Some(Arc::new(span));
+    self
+  }
+
+  pub fn build(self) -> Runtime {
+    Runtime {
+      scheduler: Scheduler::new(
+        self.worker_threads,
+        self.max_concurrent_tasks,
+        self.io_timeout,
+        self.shutdown_timeout,
+        self.min_workers.map(|min| Elastic {
+          min: min.get(),
+          idle_timeout: self
+            .worker_idle_timeout
+            .unwrap_or(Duration::from_secs(10)),
+        }),
+        self.hooks,
+        self.io_coalesce_window,
+        self.blocking_thread_stack_size,
+      ),
+    }
+  }
+}
+
+#[test]
+fn rejects_past_limit() {
+  use crate::{
+    sync::oneshot,
+    task::{self, SpawnError},
+  };
+
+  let runtime = Builder::new().max_concurrent_tasks(2, OnLimit::Reject).build();
+  runtime.block_on(async {
+    let (first, first_done) = oneshot::channel::<()>();
+    let (second, second_done) = oneshot::channel::<()>();
+    let first_task =
+      task::try_spawn(async move { first_done.await.unwrap() }).unwrap();
+    let _second_task =
+      task::try_spawn(async move { second_done.await.unwrap() }).unwrap();
+
+    let rejected = task::try_spawn(async {});
+    assert_eq!(rejected.err(), Some(SpawnError::LimitReached));
+
+    first.send(()).unwrap();
+    first_task.await.unwrap();
+    task::try_spawn(async {}).unwrap().await.unwrap();
+    second.send(()).unwrap();
+  });
+}
+
+#[test]
+fn one_worker_thread() {
+  use std::{collections::HashSet, sync::Arc, thread};
+
+  let threads = Arc::new(std::sync::Mutex::new(HashSet::new()));
+  let seen = threads.clone();
+  Builder::new().worker_threads(1).build().block_on(async move {
+    let handles: Vec<_> = (0..8)
+      .map(|_| {
+        let seen = seen.clone();
+        crate::task::spawn(async move {
+          seen.lock().unwrap().insert(thread::current().id());
+        })
+      })
+      .collect();
+    for handle in handles {
+      handle.await.unwrap();
+    }
+  });
+  assert_eq!(threads.lock().unwrap().len(), 1);
+}
+
+#[test]
+fn waits_for_slot() {
+  use std::time::{Duration, Instant};
+
+  let runtime = Builder::new().max_concurrent_tasks(1, OnLimit::Wait).build();
+  runtime.block_on(async {
+    let start = Instant::now();
+    let first = crate::task::spawn(async {
+      std::thread::sleep(Duration::from_millis(20));
+    });
+    // Blocks until the first task is done.
+    let second = crate::task::spawn(async {});
+    assert!(start.elapsed() >= Duration::from_millis(20));
+    first.await.unwrap();
+    second.await.unwrap();
+  });
+}
+
+#[test]
+fn io_timeout() {
+  use crate::{context::Context, io::AsyncRead, net::TcpStream};
+  use std::{future::poll_fn, io, io::Write, pin::Pin, thread};
+
+  let runtime = Builder::new().io_timeout(Duration::from_millis(20)).build();
+  runtime.block_on(async {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let mut stream = TcpStream::connect(listener.local_addr().unwrap())
+      .unwrap()
+      .await
+      .unwrap();
+    let (mut peer, _) = listener.accept().unwrap();
+
+    // The peer never sends anything.
+    let mut buf = [0; 4];
+    let read = poll_fn(|cx| Pin::new(&mut stream).poll_read(cx, &mut buf));
+    assert_eq!(read.await.unwrap_err().kind(), io::ErrorKind::TimedOut);
+
+    // A deadline of the context overrides it.
+    let sender = thread::spawn(move || {
+      thread::sleep(Duration::from_millis(50));
+      peer.write_all(b"late").unwrap();
+    });
+    let read = poll_fn(|cx| Pin::new(&mut stream).poll_read(cx, &mut buf));
+    let ctx = Context::background().with_timeout(Duration::from_secs(5));
+    assert_eq!(ctx.scope(read).await.unwrap(), 4);
+    assert_eq!(&buf, b"late");
+    sender.join().unwrap();
+  });
+}
+
+#[test]
+fn drains_on_shutdown() {
+  use crate::{
+    io::{AsyncRead, AsyncWrite},
+    net::TcpListener,
+    sync::oneshot,
+    task, time,
+  };
+  use std::{
+    future::poll_fn,
+    io::{Read, Write},
+    pin::Pin,
+    sync::{
+      atomic::{AtomicBool, Ordering},
+      Arc,
+    },
+    thread,
+  };
+
+  let answered = Arc::new(AtomicBool::new(false));
+  let (addr_sender, addr) = std::sync::mpsc::channel();
+  // A client which takes its time to send the request.
+  let client = thread::spawn(move || {
+    let mut stream =
+      std::net::TcpStream::connect(addr.recv().unwrap()).unwrap();
+    thread::sleep(Duration::from_millis(50));
+    stream.write_all(b"ping").unwrap();
+    let mut response = [0; 4];
+    stream.read_exact(&mut response).unwrap();
+    response
+  });
+
+  let runtime = Builder::new().shutdown_timeout(Duration::from_secs(5)).build();
+  let handler_answered = answered.clone();
+  runtime.block_on(async move {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    addr_sender.send(listener.local_addr().unwrap()).unwrap();
+    let (accepted, has_accepted) = oneshot::channel();
+    task::spawn(async move {
+      let (mut stream, _) = listener.accept().await.unwrap();
+      accepted.send(()).unwrap();
+      let mut request = [0; 4];
+      let mut read = 0;
+      while read < request.len() {
+        let poll = poll_fn(|cx| {
+          Pin::new(&mut stream).poll_read(cx, &mut request[read..])
+        });
+        read += poll.await.unwrap();
+      }
+      assert_eq!(&request, b"ping");
+      // Work which doesn't wait on the socket, the open stream keeps the runtime going.
+      time::sleep(Duration::from_millis(20)).await;
+      poll_fn(|cx| Pin::new(&mut stream).poll_write(cx, b"pong"))
+        .await
+        .unwrap();
+      handler_answered.store(true, Ordering::SeqCst);
+    });
+    // Shuts down before the handler has read anything.
+    has_accepted.await.unwrap();
+  });
+
+  assert!(answered.load(Ordering::SeqCst));
+  assert_eq!(&client.join().unwrap(), b"pong");
+}
+
+#[test]
+fn idle_workers_stop() {
+  use crate::{runtime::Handle, task, time};
+  use std::time::Instant;
+
+  let runtime = Builder::new()
+    .max_workers(4)
+    .min_workers(1)
+    .worker_idle_timeout(Duration::from_millis(20))
+    .build();
+  runtime.block_on(async {
+    let handle = Handle::current();
+    let workers = || handle.metrics_snapshot().workers;
+    let shrunk = async || {
+      let start = Instant::now();
+      while workers() > 1 {
+        assert!(start.elapsed() < Duration::from_secs(5), "{}", workers());
+        time::sleep(Duration::from_millis(5)).await;
+      }
+    };
+    assert_eq!(workers(), 4);
+    shrunk().await;
+
+    // A burst of tasks which keep their workers busy.
+    let tasks: Vec<_> = (0..8)
+      .map(|_| {
+        task::spawn(async { std::thread::sleep(Duration::from_millis(20)) })
+      })
+      .collect();
+    assert!(workers() > 1);
+    for task in tasks {
+      task.await.unwrap();
+    }
+    shrunk().await;
+    assert_eq!(workers(), 1);
+  });
+}
+
+#[test]
+fn park_hooks() {
+  use std::sync::atomic::{AtomicUsize, Ordering};
+
+  static PARKS: AtomicUsize = AtomicUsize::new(0);
+  static UNPARKS: AtomicUsize = AtomicUsize::new(0);
+  let runtime = Builder::new()
+    .worker_threads(1)
+    .on_worker_park(|worker| {
+      assert_eq!(worker, 0);
+      PARKS.fetch_add(1, Ordering::SeqCst);
+    })
+    .on_worker_unpark(|_| {
+      UNPARKS.fetch_add(1, Ordering::SeqCst);
+    })
+    .build();
+  runtime.block_on(async {
+    // The worker has nothing to do during every sleep.
+    crate::task::spawn(async {
+      for _ in 0..5 {
+        crate::time::sleep(Duration::from_millis(10)).await;
+      }
+    })
+    .await
+    .unwrap();
+  });
+
+  // The worker is unparked to shut down, so every park has its unpark.
+  let parks = PARKS.load(Ordering::SeqCst);
+  assert!(parks >= 5, "{parks}");
+  assert_eq!(UNPARKS.load(Ordering::SeqCst), parks);
+}
+
+#[test]
+fn coalesced_wakes() {
+  use crate::{io::AsyncReadExt, net::TcpStream, runtime::Handle};
+  use std::{io::Write, thread};
+
+  // A peer which writes a byte every millisecond, and how many wakes it takes to read them.
+  let wakes = |window| {
+    Builder::new().io_coalesce_window(window).build().block_on(async {
+      let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+      let mut stream = TcpStream::connect(listener.local_addr().unwrap())
+        .unwrap()
+        .await
+        .unwrap();
+      let (mut peer, _) = listener.accept().unwrap();
+      let before = Handle::current().metrics_snapshot().io_wakes;
+      let writer = thread::spawn(move || {
+        for _ in 0..40 {
+          peer.write_all(b"x").unwrap();
+          thread::sleep(Duration::from_millis(1));
+        }
+      });
+      let mut buf = [0; 40];
+      stream.read_exact(&mut buf).await.unwrap();
+      writer.join().unwrap();
+      Handle::current().metrics_snapshot().io_wakes - before
+    })
+  };
+
+  let immediate = wakes(Duration::ZERO);
+  let coalesced = wakes(Duration::from_millis(10));
+  assert!(coalesced < immediate, "{coalesced} wakes, {immediate} without");
+}
+
+#[test]
+fn blocking_thread_stack_size() {
+  use crate::task::block_scope;
+
+  // Far more than the 2 MiB a thread gets by default.
+  const LARGE: usize = 16 << 20;
+  let runtime = Builder::new().blocking_thread_stack_size(4 * LARGE).build();
+  runtime.block_on(async {
+    let sum = block_scope(|scope| {
+      let handle = scope.spawn_blocking(|| {
+        let buf = std::hint::black_box([1u8; LARGE]);
+        buf.iter().map(|&byte| byte as usize).sum::<usize>()
+      });
+      handle.join().unwrap()
+    });
+    assert_eq!(sum, LARGE);
+  });
+}
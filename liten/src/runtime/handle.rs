@@ -0,0 +1,184 @@
+use std::{
+  future::Future,
+  io,
+  marker::PhantomData,
+  pin::Pin,
+  sync::Arc,
+  task::{Context, Poll},
+};
+
+use pin_project_lite::pin_project;
+use thiserror::Error;
+
+use super::{scheduler, MetricsSnapshot, RuntimeEvent};
+use crate::{context, sync::mpsc};
+
+/// A reference to a running runtime, which can be used to enter it from other threads.
+#[derive(Clone)]
+pub struct Handle {
+  inner: Arc<scheduler::Handle>,
+}
+
+/// Returned by [`Handle::try_current`] outside of a runtime.
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+#[error("there is no liten runtime running on this thread")]
+pub struct TryCurrentError;
+
+/// Returned when something which needs a runtime, like spawning a task or binding a socket, is
+/// done on a thread outside of one.
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+#[error(
+  "`{api}` needs a liten runtime, but this thread isn't in one. Call it from a task or from \
+   `Runtime::block_on`, or enter a runtime first with `Handle::enter`"
+)]
+pub struct NoRuntimeError {
+  api: &'static str,
+}
+
+impl NoRuntimeError {
+  pub(crate) fn new(api: &'static str) -> NoRuntimeError {
+    NoRuntimeError { api }
+  }
+
+  /// The API which was called, like `task::spawn`.
+  pub fn api(&self) -> &'static str {
+    self.api
+  }
+}
+
+impl From<NoRuntimeError> for io::Error {
+  fn from(err: NoRuntimeError) -> Self {
+    io::Error::other(err)
+  }
+}
+
+impl Handle {
+  /// Returns the handle of the current runtime.
+  ///
+  /// # Panics
+  ///
+  /// Panics if called outside of a runtime, see [`Handle::try_current`].
+  pub fn current() -> Handle {
+    Handle::try_current().unwrap_or_else(|err| panic!("{err}"))
+  }
+
+  /// Returns the handle of the current runtime, if there is one.
+  pub fn try_current() -> Result<Handle, TryCurrentError> {
+    context::try_handle().map(|inner| Handle { inner }).ok_or(TryCurrentError)
+  }
+
+  /// Makes this the current runtime of the thread until the guard is dropped.
+  ///
+  /// Code which expects a runtime, like [`time::sleep`](crate::time::sleep) or
+  /// [`task::spawn`](crate::task::spawn), can then be called from threads the runtime doesn't
+  /// know about.
+  pub fn enter(&self) -> EnterGuard<'_> {
+    EnterGuard {
+      _guard: context::enter(self.inner.clone()),
+      _handle: PhantomData,
+    }
+  }
+
+  /// Returns a [`Spawner`](crate::task::Spawner) for this runtime, which can be handed to code
+  /// written against [`Spawn`](crate::task::Spawn).
+  pub fn spawner(&self) -> crate::task::Spawner {
+    crate::task::Spawner::new(self.inner.clone())
+  }
+
+  /// Subscribes to a live stream of what the runtime is doing: tasks being spawned, woken, polled
+  /// and completed, workers parking, and samples of the queue depths.
+  ///
+  /// Nothing is recorded until the first subscription. The stream holds the latest 4096 events,
+  /// older ones are dropped when the receiver falls behind, so reading slowly never holds up the
+  /// runtime. Dropping the receiver ends the subscription.
+  pub fn instrument_events(&self) -> mpsc::Receiver<RuntimeEvent> {
+    self.inner.instrument().subscribe()
+  }
+
+  /// Returns the runtime's counters and gauges as they are now, to be exported in whatever format
+  /// a scraper wants. With the `serde` feature the snapshot is serializable.
+  ///
+  /// The counters are always kept, unlike [instrumentation events](Handle::instrument_events),
+  /// and cost an atomic add each. Each value is read once and on its own, so a snapshot taken
+  /// while tasks run can be a few events apart between fields. Task counts are consistent with
+  /// each other: `tasks_completed` never exceeds `tasks_spawned`.
+  pub fn metrics_snapshot(&self) -> MetricsSnapshot {
+    MetricsSnapshot::of(&self.inner)
+  }
+
+  /// Wraps `future` so this runtime is entered around every poll of it.
+  ///
+  /// This lets a future which depends on liten be driven by a different executor.
+  pub fn wrap<F: Future>(&self, future: F) -> WithHandle<F> {
+    WithHandle { handle: self.clone(), future }
+  }
+}
+
+/// Guard returned by [`Handle::enter`], which leaves the runtime when dropped.
+#[must_use = "the runtime is left once the guard is dropped"]
+pub struct EnterGuard<'a> {
+  _guard: context::EnterGuard,
+  _handle: PhantomData<&'a Handle>,
+}
+
+pin_project! {
+  /// Future returned by [`Handle::wrap`].
+  #[must_use = "futures do nothing unless you `.await` or poll them"]
+  pub struct WithHandle<F> {
+    handle: Handle,
+    #[pin]
+    future: F,
+  }
+}
+
+impl<F: Future> Future for WithHandle<F> {
+  type Output = F::Output;
+
+  fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+    let this = self.project();
+    let _guard = this.handle.enter();
+    this.future.poll(cx)
+  }
+}
+
+#[test]
+fn outside_runtime() {
+  assert!(Handle::try_current().is_err());
+}
+
+#[crate::internal_test]
+async fn foreign_poll_loop() {
+  use std::task::{Wake, Waker};
+
+  struct Unpark(std::thread::Thread);
+
+  impl Wake for Unpark {
+    fn wake(self: Arc<Self>) {
+      self.0.unpark();
+    }
+  }
+
+  let handle = Handle::current();
+
+  // A bare executor on a thread the runtime doesn't know about.
+  let value = std::thread::spawn(move || {
+    let future = handle.wrap(async {
+      assert!(Handle::try_current().is_ok());
+      crate::task::spawn(async { 1 }).await.unwrap()
+    });
+    let mut future = std::pin::pin!(future);
+    let waker = Waker::from(Arc::new(Unpark(std::thread::current())));
+    let mut cx = Context::from_waker(&waker);
+
+    loop {
+      match future.as_mut().poll(&mut cx) {
+        Poll::Ready(value) => break value,
+        Poll::Pending => std::thread::park(),
+      }
+    }
+  })
+  .join()
+  .unwrap();
+
+  assert_eq!(value, 1);
+}
@@ -0,0 +1,147 @@
+use std::{
+  num::NonZero,
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    Mutex as StdMutex,
+  },
+  time::Duration,
+};
+
+use crate::{sync::mpsc, task::TaskId};
+
+/// How many events a subscriber can fall behind before the oldest ones are dropped.
+pub(crate) const CAPACITY: NonZero<usize> = NonZero::new(4096).unwrap();
+/// How often a busy worker reports the depth of its queues.
+pub(crate) const SAMPLE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// What the runtime is doing, see [`Handle::instrument_events`](super::Handle::instrument_events).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum RuntimeEvent {
+  TaskSpawned {
+    task: TaskId,
+  },
+  /// The task was woken and is queued to be polled again.
+  TaskWoken {
+    task: TaskId,
+  },
+  TaskPolled {
+    task: TaskId,
+    worker: usize,
+    duration: Duration,
+  },
+  TaskCompleted {
+    task: TaskId,
+  },
+  TaskPanicked {
+    task: TaskId,
+  },
+  WorkerParked {
+    worker: usize,
+  },
+  WorkerUnparked {
+    worker: usize,
+  },
+  /// How many tasks a worker has, sampled while it's busy.
+  QueueDepth {
+    worker: usize,
+    /// Ready to run on the worker.
+    local: usize,
+    /// Waiting to be woken.
+    waiting: usize,
+    /// Spawned and not picked up by any worker yet.
+    global: usize,
+  },
+}
+
+// Sends events to every subscriber. Costs a single load while there are none.
+#[derive(Default)]
+pub(crate) struct Instrument {
+  enabled: AtomicBool,
+  // This is not a bottleneck
+  subscribers: StdMutex<Vec<mpsc::Sender<RuntimeEvent>>>,
+}
+
+impl Instrument {
+  pub fn subscribe(&self) -> mpsc::Receiver<RuntimeEvent> {
+    let (sender, receiver) = mpsc::lossy(CAPACITY);
+    self.subscribers.lock().unwrap().push(sender);
+    self.enabled.store(true, Ordering::Release);
+    receiver
+  }
+
+  pub fn is_enabled(&self) -> bool {
+    self.enabled.load(Ordering::Acquire)
+  }
+
+  /// Sends the event made by `event`, which is only called if there are subscribers.
+  pub fn emit(&self, event: impl FnOnce() -> RuntimeEvent) {
+    if !self.is_enabled() {
+      return;
+    }
+    let event = event();
+    let mut subscribers = self.subscribers.lock().unwrap();
+    subscribers.retain(|sender| sender.send(event.clone()).is_ok());
+    if subscribers.is_empty() {
+      self.enabled.store(false, Ordering::Release);
+    }
+  }
+}
+
+#[crate::internal_test]
+async fn known_workload() {
+  use crate::{task, time};
+
+  let events = super::Handle::current().instrument_events();
+  let sleepers: Vec<_> = (0..20)
+    .map(|_| task::spawn(time::sleep(Duration::from_millis(1))))
+    .collect();
+  for sleeper in sleepers {
+    sleeper.await.unwrap();
+  }
+  // Polls long enough for the worker's queues to be sampled.
+  task::spawn(async {
+    std::thread::sleep(SAMPLE_INTERVAL);
+    task::yield_now().await;
+  })
+  .await
+  .unwrap();
+
+  let completed =
+    |e: &RuntimeEvent| matches!(e, RuntimeEvent::TaskCompleted { .. });
+  // A worker reports a completion right after the handle has the output, so it can still be on
+  // its way.
+  let mut received = Vec::new();
+  while received.iter().filter(|event| completed(event)).count() < 21 {
+    received.push(events.recv().await.unwrap());
+  }
+  received.extend(events.try_iter());
+
+  let count = |kind: fn(&RuntimeEvent) -> bool| {
+    received.iter().filter(|event| kind(event)).count()
+  };
+  assert_eq!(count(|e| matches!(e, RuntimeEvent::TaskSpawned { .. })), 21);
+  assert_eq!(count(completed), 21);
+  assert!(count(|e| matches!(e, RuntimeEvent::TaskWoken { .. })) >= 20);
+  assert!(count(|e| matches!(e, RuntimeEvent::TaskPolled { .. })) >= 41);
+  assert!(count(|e| matches!(e, RuntimeEvent::WorkerParked { .. })) >= 1);
+  assert!(count(|e| matches!(e, RuntimeEvent::QueueDepth { .. })) >= 1);
+}
+
+#[crate::internal_test]
+async fn slow_subscriber() {
+  use crate::{context, task};
+
+  let events = super::Handle::current().instrument_events();
+  // Far more events than the subscriber keeps, and it doesn't read any of them meanwhile.
+  let tasks: Vec<_> = (0..5_000).map(|_| task::spawn(async {})).collect();
+  for task in tasks {
+    task.await.unwrap();
+  }
+  assert_eq!(events.try_iter().count(), CAPACITY.get());
+
+  drop(events);
+  task::spawn(async {}).await.unwrap();
+  let handle = context::try_handle().unwrap();
+  assert!(!handle.instrument().is_enabled());
+}
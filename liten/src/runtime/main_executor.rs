@@ -6,6 +6,7 @@ use std::{
 };
 
 use super::waker::RuntimeWaker;
+use crate::context;
 
 pub struct GlobalExecutor;
 
@@ -14,13 +15,19 @@ impl GlobalExecutor {
   where
     F: Future<Output = R>,
   {
-    let runtime_waker = Arc::new(RuntimeWaker::new(thread::current())).into();
-    let mut context = StdContext::from_waker(&runtime_waker);
+    let runtime_waker = Arc::new(RuntimeWaker::new(thread::current()));
+    let waker = runtime_waker.clone().into();
+    let mut context = StdContext::from_waker(&waker);
     let mut pinned = std::pin::pin!(f);
+    let handle = context::with_context(|ctx| ctx.handle());
 
     loop {
       match pinned.as_mut().poll(&mut context) {
         Poll::Ready(value) => return value,
+        // Nothing to do but wait for a timer: a paused clock can skip ahead to it.
+        Poll::Pending
+          if !runtime_waker.take_woken() && handle.time().advance_to_next() => {
+        }
         Poll::Pending => thread::park(),
       };
     }
@@ -1,17 +1,38 @@
+mod builder;
+mod handle;
+mod instrument;
 mod main_executor;
 pub(crate) mod scheduler;
+pub(crate) mod snapshot;
+mod span;
 mod waker;
 
+pub use builder::{Builder, OnLimit};
+pub use handle::*;
+pub use instrument::RuntimeEvent;
 use scheduler::Scheduler;
+pub use snapshot::MetricsSnapshot;
+pub(crate) use snapshot::{Counters, WorkerCounters};
+pub use span::PollSpan;
 use std::future::Future;
 
 pub struct Runtime {
   scheduler: Scheduler,
 }
 
+impl Default for Runtime {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
 impl Runtime {
   pub fn new() -> Self {
-    Runtime { scheduler: Scheduler }
+    Builder::new().build()
+  }
+
+  pub fn builder() -> Builder {
+    Builder::new()
   }
 
   pub fn block_on<F, Res>(self, fut: F) -> Res
@@ -0,0 +1,80 @@
+use std::sync::{
+  atomic::{AtomicUsize, Ordering},
+  Arc, Condvar, Mutex as StdMutex,
+};
+
+use crate::runtime::OnLimit;
+
+/// Counts the live tasks of a runtime with a task limit.
+pub(crate) struct TaskLimit {
+  max: usize,
+  on_limit: OnLimit,
+  live: AtomicUsize,
+  // Only used with `OnLimit::Wait`. This is not a bottleneck
+  lock: StdMutex<()>,
+  released: Condvar,
+}
+
+/// Held by a task while it's live, and gives the slot back when dropped.
+pub(crate) struct TaskPermit(Arc<TaskLimit>);
+
+impl TaskLimit {
+  pub(crate) fn new(max: usize, on_limit: OnLimit) -> TaskLimit {
+    TaskLimit {
+      max,
+      on_limit,
+      live: AtomicUsize::new(0),
+      lock: StdMutex::new(()),
+      released: Condvar::new(),
+    }
+  }
+
+  /// Takes a slot, or waits for one with `OnLimit::Wait`. Returns `None` if the limit is reached
+  /// with `OnLimit::Reject`.
+  pub(crate) fn acquire(self: &Arc<Self>) -> Option<TaskPermit> {
+    if let Some(permit) = self.try_acquire() {
+      return Some(permit);
+    }
+    if self.on_limit == OnLimit::Reject {
+      return None;
+    }
+
+    let mut guard = self.lock.lock().unwrap();
+    loop {
+      // Tried again under the lock, so a release can't be missed in between.
+      if let Some(permit) = self.try_acquire() {
+        return Some(permit);
+      }
+      guard = self.released.wait(guard).unwrap();
+    }
+  }
+
+  fn try_acquire(self: &Arc<Self>) -> Option<TaskPermit> {
+    let mut live = self.live.load(Ordering::Acquire);
+    loop {
+      if live >= self.max {
+        return None;
+      }
+      match self.live.compare_exchange_weak(
+        live,
+        live + 1,
+        Ordering::AcqRel,
+        Ordering::Acquire,
+      ) {
+        Ok(_) => return Some(TaskPermit(self.clone())),
+        Err(actual) => live = actual,
+      }
+    }
+  }
+}
+
+impl Drop for TaskPermit {
+  fn drop(&mut self) {
+    let limit = &self.0;
+    limit.live.fetch_sub(1, Ordering::AcqRel);
+    if limit.on_limit == OnLimit::Wait {
+      let _guard = limit.lock.lock().unwrap();
+      limit.released.notify_one();
+    }
+  }
+}
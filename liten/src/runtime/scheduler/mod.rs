@@ -1,24 +1,101 @@
+mod limit;
 pub mod worker;
 use crate::runtime::scheduler::worker::shared::Shared;
 
 use std::{
+  fmt,
   future::Future,
+  io,
+  num::NonZero,
+  panic::{self, AssertUnwindSafe},
   sync::{
     atomic::{AtomicBool, AtomicUsize, Ordering},
-    Arc, OnceLock,
+    Arc, Condvar, Mutex as StdMutex, OnceLock,
   },
+  time::{Duration, Instant},
 };
 
+pub(crate) use limit::{TaskLimit, TaskPermit};
 use worker::Workers;
 
-use crate::context;
+use crate::{
+  context,
+  runtime::{instrument::Instrument, Counters, OnLimit, PollSpan},
+  task::SpawnError,
+};
 
-use super::{super::events, main_executor::GlobalExecutor};
+use super::{
+  super::{events, time},
+  main_executor::GlobalExecutor,
+};
 
 #[derive(Debug)]
-pub struct Scheduler;
+pub struct Scheduler {
+  worker_threads: Option<NonZero<usize>>,
+  max_concurrent_tasks: Option<(usize, OnLimit)>,
+  io_timeout: Option<Duration>,
+  shutdown_timeout: Option<Duration>,
+  elastic: Option<Elastic>,
+  hooks: Hooks,
+  io_coalesce_window: Duration,
+  blocking_stack_size: Option<usize>,
+}
+
+/// Lets idle workers stop, see [`Builder::min_workers`](crate::runtime::Builder::min_workers).
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Elastic {
+  pub(crate) min: usize,
+  pub(crate) idle_timeout: Duration,
+}
+
+// Called with the index of the worker.
+pub(crate) type WorkerHook = Arc<dyn Fn(usize) + Send + Sync>;
+pub(crate) type IoErrorHook = Arc<dyn Fn(&io::Error) + Send + Sync>;
+
+/// Callbacks set on the [`Builder`](crate::runtime::Builder).
+#[derive(Clone, Default)]
+pub(crate) struct Hooks {
+  pub(crate) on_park: Option<WorkerHook>,
+  pub(crate) on_unpark: Option<WorkerHook>,
+  pub(crate) on_io_error: Option<IoErrorHook>,
+  pub(crate) poll_span: Option<Arc<dyn PollSpan>>,
+}
+
+impl fmt::Debug for Hooks {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("Hooks")
+      .field("on_park", &self.on_park.is_some())
+      .field("on_unpark", &self.on_unpark.is_some())
+      .field("on_io_error", &self.on_io_error.is_some())
+      .field("poll_span", &self.poll_span.is_some())
+      .finish()
+  }
+}
 
 impl Scheduler {
+  #[allow(clippy::too_many_arguments)]
+  pub fn new(
+    worker_threads: Option<NonZero<usize>>,
+    max_concurrent_tasks: Option<(usize, OnLimit)>,
+    io_timeout: Option<Duration>,
+    shutdown_timeout: Option<Duration>,
+    elastic: Option<Elastic>,
+    hooks: Hooks,
+    io_coalesce_window: Duration,
+    blocking_stack_size: Option<usize>,
+  ) -> Scheduler {
+    Scheduler {
+      worker_threads,
+      max_concurrent_tasks,
+      io_timeout,
+      shutdown_timeout,
+      elastic,
+      hooks,
+      io_coalesce_window,
+      blocking_stack_size,
+    }
+  }
+
   pub fn block_on<F, Res>(self, fut: F) -> Res
   where
     F: Future<Output = Res>,
@@ -26,49 +103,84 @@ impl Scheduler {
     let (io_driver, io_handle) = events::Driver::new().unwrap();
 
     let mut driver = Driver { io: io_driver };
-    let handle = Arc::new(Handle::without_shared(io_handle));
+    driver.io.set_coalesce_window(self.io_coalesce_window);
+    let mut handle = Handle::without_shared(io_handle);
+    if let Some((max, on_limit)) = self.max_concurrent_tasks {
+      handle.task_limit = Some(Arc::new(TaskLimit::new(max, on_limit)));
+    }
+    handle.io_timeout = self.io_timeout;
+    handle.blocking_stack_size = self.blocking_stack_size;
+    handle.hooks = self.hooks;
 
-    let cpus = std::thread::available_parallelism().unwrap();
+    let cpus = self
+      .worker_threads
+      .unwrap_or_else(|| std::thread::available_parallelism().unwrap());
+    handle.elastic = self.elastic.filter(|elastic| elastic.min < cpus.get());
+    let handle = Arc::new(handle);
 
-    let workers = Workers::new(cpus, handle.clone());
+    let mut workers = Workers::new(cpus, handle.clone());
 
     let shared = Shared::from_workers(&workers);
     handle.set_handle(shared);
 
-    let mut shutdown = workers.as_shutdown_workers();
+    let shutdown = workers.as_shutdown_workers();
+    workers.launch(handle.clone());
 
-    let handles = workers.launch(handle.clone());
-    shutdown.fill_handle(handles);
-
-    let span = tracing::trace_span!("liten runtime");
-    let _span = span.enter();
+    #[cfg(feature = "tracing")]
+    let _span = tracing::trace_span!("liten runtime").entered();
 
     // NOTE: Has to be over the mio join handle.
     let mio_waker = handle.io().mio_waker();
 
     let thread_handle = handle.clone();
 
-    let join_handle = std::thread::spawn(move || loop {
-      if driver.io.turn(thread_handle.io()) {
-        break;
-      }
+    let join_handle = std::thread::spawn(move || {
+      let on_error = thread_handle.hooks().on_io_error.as_deref();
+      driver.io.run(thread_handle.io(), on_error)
     });
 
-    let return_type =
-      context::runtime_enter(handle, move |_| GlobalExecutor::block_on(fut));
+    let time_handle = handle.clone();
+    let time_join_handle = std::thread::spawn(move || time_handle.time().run());
+
+    // A panic of the root future gets to the caller once the runtime has shut down, instead of
+    // leaving its threads running.
+    let return_type = context::runtime_enter(handle.clone(), move |_| {
+      panic::catch_unwind(AssertUnwindSafe(|| GlobalExecutor::block_on(fut)))
+    });
 
-    shutdown.shutdown();
+    if let Some(timeout) = self.shutdown_timeout {
+      handle.drain(timeout);
+    }
+    shutdown.shutdown(handle.state());
 
     mio_waker.wake().expect("noo :(");
     join_handle.join().unwrap();
 
-    return_type
+    handle.time().shutdown();
+    time_join_handle.join().unwrap();
+
+    return_type.unwrap_or_else(|payload| panic::resume_unwind(payload))
   }
 }
 
 pub struct Handle {
   pub io: events::Handle,
+  time: time::driver::Handle,
   pub shared: OnceLock<Arc<Shared>>,
+  task_limit: Option<Arc<TaskLimit>>,
+  instrument: Instrument,
+  counters: Counters,
+  io_timeout: Option<Duration>,
+  blocking_stack_size: Option<usize>,
+  elastic: Option<Elastic>,
+  hooks: Hooks,
+  // Socket reads and writes waiting on readiness, see `InFlight`.
+  in_flight: AtomicUsize,
+  // Connected and accepted streams which are still open, see `OpenStream`.
+  open_streams: AtomicUsize,
+  // This is not a bottleneck
+  drain_lock: StdMutex<()>,
+  drained: Condvar,
 
   current_task_id: AtomicUsize,
   has_exited: AtomicBool,
@@ -78,11 +190,31 @@ impl Handle {
   pub fn without_shared(io: events::Handle) -> Handle {
     Handle {
       io,
+      time: time::driver::Handle::new(),
       shared: OnceLock::new(),
+      task_limit: None,
+      instrument: Instrument::default(),
+      counters: Counters::default(),
+      io_timeout: None,
+      blocking_stack_size: None,
+      elastic: None,
+      hooks: Hooks::default(),
+      in_flight: AtomicUsize::new(0),
+      open_streams: AtomicUsize::new(0),
+      drain_lock: StdMutex::new(()),
+      drained: Condvar::new(),
       has_exited: AtomicBool::new(false),
       current_task_id: AtomicUsize::new(0),
     }
   }
+  /// Takes a slot for a new task. `None` means the runtime has no task limit.
+  pub(crate) fn acquire_task(&self) -> Result<Option<TaskPermit>, SpawnError> {
+    match &self.task_limit {
+      Some(limit) => limit.acquire().map(Some).ok_or(SpawnError::LimitReached),
+      None => Ok(None),
+    }
+  }
+
   /// Returns the previous value
   pub fn task_id_inc(&self) -> usize {
     self.current_task_id.fetch_add(1, Ordering::SeqCst)
@@ -110,7 +242,7 @@ pub struct Driver {
 
 impl Handle {
   pub fn state(&self) -> &Shared {
-    &self.shared.get().expect("state not set")
+    self.shared.get().expect("state not set")
   }
 }
 
@@ -118,4 +250,134 @@ impl Handle {
   pub fn io(&self) -> &events::Handle {
     &self.io
   }
+
+  pub fn time(&self) -> &time::driver::Handle {
+    &self.time
+  }
+
+  pub(crate) fn instrument(&self) -> &Instrument {
+    &self.instrument
+  }
+
+  pub(crate) fn elastic(&self) -> Option<Elastic> {
+    self.elastic
+  }
+
+  pub(crate) fn hooks(&self) -> &Hooks {
+    &self.hooks
+  }
+
+  pub(crate) fn counters(&self) -> &Counters {
+    &self.counters
+  }
+
+  /// How many socket operations are in flight, see `InFlight`.
+  pub(crate) fn in_flight(&self) -> usize {
+    self.in_flight.load(Ordering::SeqCst)
+  }
+
+  /// The deadline of socket operations which don't have one from their [`Context`](crate::context::Context).
+  pub(crate) fn io_timeout(&self) -> Option<Duration> {
+    self.io_timeout
+  }
+
+  /// See [`Builder::blocking_thread_stack_size`](super::Builder::blocking_thread_stack_size).
+  pub(crate) fn blocking_stack_size(&self) -> Option<usize> {
+    self.blocking_stack_size
+  }
+
+  // Waits for at most `timeout` until no socket operation is in flight and every stream is closed.
+  fn drain(&self, timeout: Duration) {
+    let deadline = Instant::now() + timeout;
+    let mut guard = self.drain_lock.lock().unwrap();
+    while self.in_flight.load(Ordering::SeqCst) != 0
+      || self.open_streams.load(Ordering::SeqCst) != 0
+    {
+      let Some(left) = deadline.checked_duration_since(Instant::now()) else {
+        return;
+      };
+      guard = self.drained.wait_timeout(guard, left).unwrap().0;
+    }
+  }
+}
+
+/// Counts a socket read or write as in flight for as long as it's alive, which keeps a runtime
+/// with a [`shutdown_timeout`](crate::runtime::Builder::shutdown_timeout) running.
+pub(crate) struct InFlight(Arc<Handle>);
+
+impl InFlight {
+  pub(crate) fn new(handle: Arc<Handle>) -> InFlight {
+    handle.in_flight.fetch_add(1, Ordering::SeqCst);
+    InFlight(handle)
+  }
+}
+
+impl Drop for InFlight {
+  fn drop(&mut self) {
+    self.0.release(&self.0.in_flight);
+  }
+}
+
+/// Counts a stream as live work from the moment it's connected or accepted until it's dropped,
+/// for the [`shutdown_timeout`](crate::runtime::Builder::shutdown_timeout) too. A handler is then
+/// waited on while it isn't waiting on the socket, like before its first read or between reading
+/// a request and writing the response.
+pub(crate) struct OpenStream(Arc<Handle>);
+
+impl OpenStream {
+  pub(crate) fn new(handle: Arc<Handle>) -> OpenStream {
+    handle.open_streams.fetch_add(1, Ordering::SeqCst);
+    OpenStream(handle)
+  }
+}
+
+impl Drop for OpenStream {
+  fn drop(&mut self) {
+    self.0.release(&self.0.open_streams);
+  }
+}
+
+impl Handle {
+  // Takes one off `count`, one of the counts `drain` waits on.
+  fn release(&self, count: &AtomicUsize) {
+    if count.fetch_sub(1, Ordering::SeqCst) == 1 {
+      // Taking the lock makes sure the drain is either waiting, or yet to look at the count.
+      drop(self.drain_lock.lock().unwrap());
+      self.drained.notify_all();
+    }
+  }
+}
+
+#[test]
+fn repeated_shutdown() {
+  // Workers used to be unparked before the shutdown signal was sent, which could leave one parked
+  // forever and hang the join.
+  for _ in 0..2000 {
+    std::thread::spawn(|| crate::runtime::Runtime::new().block_on(async {}))
+      .join()
+      .unwrap();
+  }
+}
+
+#[test]
+fn root_future_panic() {
+  use crate::{runtime::Runtime, sync::oneshot, task};
+
+  let (sender, receiver) = oneshot::channel::<()>();
+  let panicked = panic::catch_unwind(AssertUnwindSafe(|| {
+    Runtime::new().block_on(async move {
+      // Still waiting when the runtime shuts down.
+      task::spawn(receiver);
+      panic!("root future failed");
+    })
+  }));
+  let payload = panicked.unwrap_err();
+  assert_eq!(payload.downcast_ref::<&str>(), Some(&"root future failed"));
+  // The task was dropped with the runtime.
+  assert!(sender.send(()).is_err());
+
+  // The thread isn't left inside the runtime which panicked.
+  assert!(context::try_handle().is_none());
+  let value = Runtime::new().block_on(async { task::spawn(async { 2 }).await });
+  assert_eq!(value.unwrap(), 2);
 }
@@ -1,85 +1,117 @@
 use std::{
   num::NonZero,
   ops::Deref,
-  sync::{Arc, OnceLock},
+  sync::{atomic::Ordering, Arc, Mutex as StdMutex},
   thread::{Builder, JoinHandle},
 };
 
 use crossbeam_deque::Stealer;
 use crossbeam_utils::sync::Unparker;
-use worker::Worker;
+use shared::Shared;
+use worker::{Stop, Worker};
 
-use crate::{context, sync::oneshot::Sender, task::ArcTask};
+use crate::{
+  context, runtime::WorkerCounters, sync::oneshot::Sender, task::ArcTask,
+};
 
 use super::Handle;
 
 pub mod shared;
+#[allow(clippy::module_inception)]
 pub mod worker;
 
 pub struct WorkerShutdown {
   worker_id: usize,
   signal_sender: Sender<()>,
   unparker: Unparker,
-  handle: OnceLock<JoinHandle<()>>,
 }
 
 pub struct ShutdownWorkers(Vec<WorkerShutdown>);
 
 impl ShutdownWorkers {
   pub fn before_starting_workers<'a>(
-    workers: impl Iterator<Item = &'a Worker>,
+    workers: impl Iterator<Item = &'a mut Worker>,
   ) -> Self {
     ShutdownWorkers(
       workers
         .map(|x| WorkerShutdown {
           worker_id: x.id(),
-          signal_sender: x.get_shutdown_sender(),
+          signal_sender: x.take_shutdown_sender(),
           unparker: x.parker().unparker().clone(),
-          handle: OnceLock::new(),
         })
         .collect(),
     )
   }
 
-  pub fn fill_handle(&mut self, handle: Vec<JoinHandle<()>>) {
-    assert!(
-      self.0.len() == handle.len(),
-      "joinhandle len is not equal to workers"
-    );
-
-    for (index, handle) in handle.into_iter().enumerate() {
-      self.0[index].handle.set(handle).unwrap();
-    }
-  }
-  pub fn shutdown(self) {
-    for WorkerShutdown { signal_sender, unparker, handle, worker_id } in self.0
-    {
-      unparker.unpark();
+  pub fn shutdown(self, shared: &Shared) {
+    // No worker is started again from here on.
+    shared.shutting_down.store(true, Ordering::SeqCst);
+    for WorkerShutdown { signal_sender, unparker, worker_id } in self.0 {
+      // Signal has to be sent before unparking, otherwise the worker can wake up, miss the
+      // signal and park again.
       signal_sender.send(()).unwrap();
+      unparker.unpark();
 
-      handle
-        .into_inner()
-        .expect("worker-handle not initialied")
-        .join()
-        .unwrap();
+      let remote = &shared.remotes[worker_id];
+      let join = remote.thread.lock().unwrap().join.take();
+      if let Some(join) = join {
+        join.join().unwrap();
+      }
+      // A stopped worker holds on to the runtime, which holds on to it.
+      drop(remote.thread.lock().unwrap().stopped.take());
 
+      #[cfg(feature = "tracing")]
       tracing::trace!(worker_id, "worker has shutdown");
     }
   }
 }
 
+// Runs `worker` on a thread of its own. A worker which stops because it's idle puts itself back
+// in its slot, to be started again later.
+pub(super) fn spawn_worker(mut worker: Worker) -> JoinHandle<()> {
+  let builder = Builder::new().name(format!("liten-worker-{}", worker.id()));
+  builder
+    .spawn(move || {
+      #[cfg(feature = "tracing")]
+      let _span =
+        tracing::trace_span!("worker", worker_id = worker.id()).entered();
+      let handle = worker.handle().clone();
+      let stop = context::runtime_enter(handle.clone(), |_| worker.launch());
+      if stop == Stop::Idle {
+        let remote = &handle.state().remotes[worker.id()];
+        remote.thread.lock().unwrap().stopped = Some(worker);
+      }
+    })
+    .unwrap()
+}
+
 // One remote worker.
-#[derive(Clone)]
 pub struct Remote {
   stealer: Stealer<ArcTask>,
   unparker: crossbeam_utils::sync::Unparker,
+  counters: Arc<WorkerCounters>,
+  // This is not a bottleneck
+  thread: StdMutex<WorkerThread>,
 }
+
+// The thread running a worker, or the worker itself while it's stopped.
+#[derive(Default)]
+pub(super) struct WorkerThread {
+  pub(super) join: Option<JoinHandle<()>>,
+  pub(super) stopped: Option<Worker>,
+}
+
 impl Remote {
   pub fn from_stealer(
     stealer: Stealer<ArcTask>,
     unparker: crossbeam_utils::sync::Unparker,
+    counters: Arc<WorkerCounters>,
   ) -> Self {
-    Remote { stealer, unparker }
+    Remote { stealer, unparker, counters, thread: StdMutex::default() }
+  }
+
+  pub(crate) fn counters(&self) -> &WorkerCounters {
+    &self.counters
   }
 
   pub fn unpark(&self) {
@@ -113,27 +145,17 @@ impl Workers {
     Workers(worker_vec)
   }
 
-  pub fn as_shutdown_workers(&self) -> ShutdownWorkers {
-    ShutdownWorkers::before_starting_workers(self.0.iter())
+  pub fn as_shutdown_workers(&mut self) -> ShutdownWorkers {
+    ShutdownWorkers::before_starting_workers(self.0.iter_mut())
   }
 
-  pub fn launch(self, handle: Arc<Handle>) -> Vec<JoinHandle<()>> {
+  pub fn launch(self, handle: Arc<Handle>) {
+    #[cfg(feature = "tracing")]
     tracing::trace!(len = self.0.len(), "launching threads");
-    let join_handles: Vec<JoinHandle<()>> = self
-      .0
-      .into_iter()
-      .map(|mut worker| {
-        let builder =
-          Builder::new().name(format!("liten-worker-{}", worker.id()));
-        let another_handle = handle.clone();
-        builder
-          .spawn(move || {
-            context::runtime_enter(another_handle, move |_| worker.launch());
-          })
-          .unwrap()
-      })
-      .collect();
-
-    join_handles
+    let shared = handle.state();
+    for worker in self.0 {
+      let remote = &shared.remotes[worker.id()];
+      remote.thread.lock().unwrap().join = Some(spawn_worker(worker));
+    }
   }
 }
@@ -1,6 +1,9 @@
-use std::sync::Arc;
+use std::sync::{
+  atomic::{AtomicBool, AtomicUsize, Ordering},
+  Arc,
+};
 
-use super::{worker::Worker, Remote};
+use super::{spawn_worker, worker::Worker, Remote};
 use crossbeam_deque::Injector;
 
 use crate::task::ArcTask;
@@ -8,6 +11,10 @@ use crate::task::ArcTask;
 pub struct Shared {
   pub remotes: Box<[Remote]>,
   pub injector: Injector<ArcTask>,
+  // Workers which are running, the others are stopped until there's work for them again.
+  live: AtomicUsize,
+  parked: AtomicUsize,
+  pub(super) shutting_down: AtomicBool,
 }
 
 impl Shared {
@@ -17,6 +24,71 @@ impl Shared {
     for remote in self.remotes.iter() {
       remote.unpark();
     }
+    self.grow();
+  }
+
+  /// How many workers are running.
+  pub(crate) fn live_workers(&self) -> usize {
+    self.live.load(Ordering::SeqCst)
+  }
+
+  pub(super) fn parking(&self) {
+    self.parked.fetch_add(1, Ordering::SeqCst);
+  }
+
+  pub(super) fn unparked(&self) {
+    self.parked.fetch_sub(1, Ordering::SeqCst);
+  }
+
+  // Starts a stopped worker when more tasks are waiting than there are parked workers to take
+  // them.
+  fn grow(&self) {
+    let waiting = self.injector.len();
+    if self.live_workers() == self.remotes.len()
+      || waiting <= self.parked.load(Ordering::SeqCst)
+    {
+      return;
+    }
+    for remote in self.remotes.iter() {
+      let mut thread = remote.thread.lock().unwrap();
+      if self.shutting_down.load(Ordering::SeqCst) {
+        return;
+      }
+      if let Some(worker) = thread.stopped.take() {
+        self.live.fetch_add(1, Ordering::SeqCst);
+        // It has put the worker back, and is about to exit.
+        if let Some(stopped) = thread.join.take() {
+          stopped.join().unwrap();
+        }
+        thread.join = Some(spawn_worker(worker));
+        return;
+      }
+    }
+  }
+
+  // Counts an idle worker out, unless that leaves fewer than `min` running or tasks are waiting.
+  pub(super) fn try_stop(&self, min: usize) -> bool {
+    let mut live = self.live.load(Ordering::SeqCst);
+    loop {
+      if live <= min {
+        return false;
+      }
+      match self.live.compare_exchange_weak(
+        live,
+        live - 1,
+        Ordering::SeqCst,
+        Ordering::SeqCst,
+      ) {
+        Ok(_) => break,
+        Err(actual) => live = actual,
+      }
+    }
+    // A task pushed meanwhile could have counted on this worker being parked.
+    if !self.injector.is_empty() {
+      self.live.fetch_add(1, Ordering::SeqCst);
+      return false;
+    }
+    true
   }
 
   pub fn from_workers(
@@ -28,12 +100,18 @@ impl Shared {
       .map(|worker| {
         let stealer = worker.stealer();
         let unparker = worker.parker().unparker().clone();
-        Remote::from_stealer(stealer, unparker)
+        Remote::from_stealer(stealer, unparker, worker.counters().clone())
       })
       .collect::<Vec<_>>()
       .into_boxed_slice();
 
-    let shared = Shared { remotes, injector: Injector::new() };
+    let shared = Shared {
+      live: AtomicUsize::new(remotes.len()),
+      remotes,
+      injector: Injector::new(),
+      parked: AtomicUsize::new(0),
+      shutting_down: AtomicBool::new(false),
+    };
 
     Arc::new(shared)
 
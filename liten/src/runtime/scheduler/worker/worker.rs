@@ -1,56 +1,31 @@
-use std::{collections::HashMap, sync::Arc, task::Poll};
+use std::{
+  collections::{HashMap, VecDeque},
+  sync::Arc,
+  task::Poll,
+  time::Instant,
+};
 
 use crossbeam_deque::{Steal, Worker as WorkerQueue};
 use crossbeam_utils::sync::Parker;
 
 use crate::{
-  runtime::{scheduler::Handle, waker::TaskWaker},
+  runtime::{
+    instrument::SAMPLE_INTERVAL, scheduler::Handle, snapshot, waker::TaskWaker,
+    RuntimeEvent, WorkerCounters,
+  },
   sync::{
     mpsc,
     oneshot::{self, Receiver},
   },
-  task::{ArcTask, TaskId},
+  task::{ArcTask, Priority, TaskId},
 };
 
-pub struct WorkerBuilder {
-  worker_id: usize,
-  handle: Option<Arc<Handle>>,
-  parker: Option<Parker>,
-
-  queue: Option<WorkerQueue<ArcTask>>,
-}
-
-impl WorkerBuilder {
-  pub fn with_id(worker_id: usize) -> Self {
-    WorkerBuilder { worker_id, handle: None, parker: None, queue: None }
-  }
-
-  pub fn handle(mut self, handle: Arc<Handle>) -> Self {
-    self.handle = Some(handle);
-    self
-  }
-
-  pub fn parker(mut self, parker: Parker) -> Self {
-    self.parker = Some(parker);
-    self
-  }
-
-  pub fn queue(mut self, queue: WorkerQueue<ArcTask>) -> Self {
-    self.queue = Some(queue);
-    self
-  }
-
-  pub fn build(self, receiver: Receiver<()>) -> Worker {
-    Worker {
-      worker_id: self.worker_id,
-      handle: self.handle.expect("handle is required"),
-      parker: self.parker.expect("parker is required"),
-
-      local_queue: self.queue.expect("queue is required"),
-      cold_queue: HashMap::new(),
-      receiver,
-    }
-  }
+/// Why [`Worker::launch`] returned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stop {
+  Shutdown,
+  /// Idle for the idle timeout, the worker can be launched again.
+  Idle,
 }
 
 // Local worker.
@@ -60,22 +35,35 @@ pub struct Worker {
   parker: Parker,
 
   local_queue: WorkerQueue<ArcTask>,
+  // Woken tasks above normal priority, run before the local queue. Not stealable.
+  urgent_queue: VecDeque<ArcTask>,
   cold_queue: HashMap<TaskId, ArcTask>,
 
   receiver: Receiver<()>,
+  // Taken once by the shutdown handle, before the worker starts.
+  shutdown_sender: Option<oneshot::Sender<()>>,
+  // Wakers send the id of their task here.
+  woken: (mpsc::Sender<TaskId>, mpsc::Receiver<TaskId>),
+  // When the queue depths were last sent to the instrumentation.
+  sampled: Instant,
+  counters: Arc<WorkerCounters>,
 }
 
 impl Worker {
   pub fn new(id: usize, handle: Arc<Handle>) -> Worker {
     let (sender, receiver) = oneshot::channel();
-    drop(sender);
     Worker {
       worker_id: id,
       handle,
       parker: Parker::new(),
       receiver,
+      shutdown_sender: Some(sender),
       cold_queue: HashMap::new(),
       local_queue: WorkerQueue::new_fifo(),
+      urgent_queue: VecDeque::new(),
+      woken: mpsc::unbounded(),
+      sampled: Instant::now(),
+      counters: Arc::default(),
     }
   }
 
@@ -83,19 +71,30 @@ impl Worker {
     self.worker_id
   }
 
+  pub fn handle(&self) -> &Arc<Handle> {
+    &self.handle
+  }
+
   pub fn parker(&self) -> &Parker {
     &self.parker
   }
 
+  pub(crate) fn counters(&self) -> &Arc<WorkerCounters> {
+    &self.counters
+  }
+
   pub fn stealer(&self) -> crossbeam_deque::Stealer<ArcTask> {
     self.local_queue.stealer()
   }
 
-  pub fn get_shutdown_sender(&self) -> oneshot::Sender<()> {
-    self.receiver.try_get_sender().unwrap()
+  pub fn take_shutdown_sender(&mut self) -> oneshot::Sender<()> {
+    self.shutdown_sender.take().expect("shutdown sender taken twice")
   }
 
-  fn fetch_task(&self) -> Option<ArcTask> {
+  fn fetch_task(&mut self) -> Option<ArcTask> {
+    if let Some(task) = self.urgent_queue.pop_front() {
+      return Some(task);
+    }
     if let Some(task) = self.local_queue.pop() {
       return Some(task);
       // Fill local queue from the global tasks
@@ -123,7 +122,8 @@ impl Worker {
           Steal::Empty => break,
           // Break immediately and return task
           Steal::Success(task) => {
-            tracing::trace!("hehe stole task");
+            #[cfg(feature = "tracing")]
+            tracing::trace!(worker_id = self.id(), "stole task");
             return Some(task);
           }
         }
@@ -136,43 +136,180 @@ impl Worker {
   fn steal_from_global_queue(&self) -> Steal<ArcTask> {
     self.handle.state().injector.steal_batch_and_pop(&self.local_queue)
   }
-  pub fn launch(&mut self) {
-    let (sender, receiver) = mpsc::unbounded();
+  pub fn launch(&mut self) -> Stop {
+    #[cfg(feature = "tracing")]
     tracing::trace!(worker_id = self.id(), "starting");
+    // Since when there has been nothing to do.
+    let mut idle_since = None;
     loop {
-      if self.receiver.try_recv().is_ok() {
+      if let Ok(Some(())) = self.receiver.try_recv() {
+        #[cfg(feature = "tracing")]
         tracing::trace!(worker_id = self.id(), "shutting down");
-        break;
-      }
-      for now_active_task_id in receiver.try_iter() {
-        let task = self
-          .cold_queue
-          .remove(&now_active_task_id)
-          .expect("invalid waker called, TaskId doesn't exist");
-
-        self.local_queue.push(task);
+        return Stop::Shutdown;
       }
+      self.wake_tasks();
 
       let Some(task) = self.fetch_task() else {
-        self.parker.park();
+        if self.park_or_stop(&mut idle_since) {
+          #[cfg(feature = "tracing")]
+          tracing::trace!(worker_id = self.id(), "stopping while idle");
+          return Stop::Idle;
+        }
         continue;
       };
-      let id = task.id();
-      let liten_waker = Arc::new(TaskWaker::new(id, sender.clone())).into();
-      let mut context = std::task::Context::from_waker(&liten_waker);
-
-      let unwind_task = task.clone();
-      let poll_result = match std::panic::catch_unwind(move || {
-        unwind_task.poll(&mut context)
-      }) {
-        Ok(value) => value,
-        Err(_) => continue,
-      };
+      idle_since = None;
+      self.run_task(task);
+      self.sample();
+    }
+  }
+
+  // Parks until there's something to do. With a minimum of workers, a worker which no task is
+  // waiting on stops instead once it has been idle for long enough, and `true` is returned.
+  fn park_or_stop(&mut self, idle_since: &mut Option<Instant>) -> bool {
+    let shared = self.handle.state();
+    let mut timeout = None;
+    if let Some(elastic) = self.handle.elastic() {
+      if self.cold_queue.is_empty() {
+        let idle = idle_since.get_or_insert_with(Instant::now).elapsed();
+        match elastic.idle_timeout.checked_sub(idle) {
+          Some(left) if !left.is_zero() => timeout = Some(left),
+          _ if shared.try_stop(elastic.min) => return true,
+          // Already down to the minimum.
+          _ => {}
+        }
+      }
+    }
+
+    #[cfg(feature = "tracing")]
+    tracing::trace!(worker_id = self.id(), "parking");
+    let worker = self.id();
+    let instrument = self.handle.instrument();
+    snapshot::increment(&self.counters.parks);
+    instrument.emit(|| RuntimeEvent::WorkerParked { worker });
+    shared.parking();
+    let hooks = self.handle.hooks();
+    if let Some(on_park) = &hooks.on_park {
+      on_park(worker);
+    }
+    match timeout {
+      Some(timeout) => self.parker.park_timeout(timeout),
+      None => self.parker.park(),
+    }
+    if let Some(on_unpark) = &hooks.on_unpark {
+      on_unpark(worker);
+    }
+    shared.unparked();
+    instrument.emit(|| RuntimeEvent::WorkerUnparked { worker });
+    #[cfg(feature = "tracing")]
+    tracing::trace!(worker_id = self.id(), "unparked");
+    false
+  }
+
+  /// Polls every task that is ready once without parking, and returns how many were polled.
+  ///
+  /// Tasks woken or spawned meanwhile are left for the next tick.
+  pub fn tick(&mut self) -> usize {
+    self.wake_tasks();
+    let ready: Vec<ArcTask> =
+      std::iter::from_fn(|| self.fetch_task()).collect();
+    let polled = ready.len();
+    for task in ready {
+      self.run_task(task);
+    }
+    self.sample();
+    polled
+  }
+
+  /// Polls the next ready task, if there is one.
+  pub fn step(&mut self) -> bool {
+    self.wake_tasks();
+    let Some(task) = self.fetch_task() else {
+      return false;
+    };
+    self.run_task(task);
+    self.sample();
+    true
+  }
 
-      if Poll::Pending == poll_result {
+  fn wake_tasks(&mut self) {
+    for now_active_task_id in self.woken.1.try_iter() {
+      // A task can be woken more than once, or after it has completed, then it's not waiting
+      // here anymore.
+      if let Some(task) = self.cold_queue.remove(&now_active_task_id) {
+        let instrument = self.handle.instrument();
+        instrument.emit(|| RuntimeEvent::TaskWoken { task: task.id() });
+        if task.priority() > Priority::Normal {
+          self.urgent_queue.push_back(task);
+        } else {
+          self.local_queue.push(task);
+        }
+      }
+    }
+  }
+
+  fn run_task(&mut self, task: ArcTask) {
+    let id = task.id();
+    let liten_waker = Arc::new(TaskWaker::new(
+      id,
+      self.woken.0.clone(),
+      self.parker.unparker().clone(),
+    ))
+    .into();
+    let mut context = std::task::Context::from_waker(&liten_waker);
+
+    let unwind_task = task.clone();
+    let started = self.handle.instrument().is_enabled().then(Instant::now);
+    let span = self.handle.hooks().poll_span.as_deref();
+    if let Some(span) = span {
+      span.enter(id);
+    }
+    let poll_result =
+      std::panic::catch_unwind(move || unwind_task.poll(&mut context));
+    if let Some(span) = span {
+      span.exit(id);
+    }
+    let instrument = self.handle.instrument();
+    snapshot::increment(&self.counters.polls);
+    if let Some(started) = started {
+      let (worker, duration) = (self.worker_id, started.elapsed());
+      instrument.emit(|| RuntimeEvent::TaskPolled {
+        task: id,
+        worker,
+        duration,
+      });
+    }
+
+    match poll_result {
+      Ok(Poll::Pending) => {
         let old_value = self.cold_queue.insert(id, task);
         assert!(old_value.is_none(), "logic error of inserted cold_queue task");
       }
+      Ok(Poll::Ready(())) => {
+        snapshot::increment(&self.handle.counters().tasks_completed);
+        instrument.emit(|| RuntimeEvent::TaskCompleted { task: id })
+      }
+      Err(_) => {
+        snapshot::increment(&self.handle.counters().tasks_panicked);
+        snapshot::increment(&self.handle.counters().tasks_completed);
+        #[cfg(feature = "tracing")]
+        tracing::trace!(parent: task.span(), "panicked");
+        instrument.emit(|| RuntimeEvent::TaskPanicked { task: id });
+      }
+    }
+  }
+
+  // Sends the queue depths to the instrumentation, at most once per interval.
+  fn sample(&mut self) {
+    let instrument = self.handle.instrument();
+    if !instrument.is_enabled() || self.sampled.elapsed() < SAMPLE_INTERVAL {
+      return;
     }
+    self.sampled = Instant::now();
+    instrument.emit(|| RuntimeEvent::QueueDepth {
+      worker: self.worker_id,
+      local: self.local_queue.len() + self.urgent_queue.len(),
+      waiting: self.cold_queue.len(),
+      global: self.handle.state().injector.len(),
+    });
   }
 }
@@ -0,0 +1,140 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crossbeam_utils::CachePadded;
+
+use super::scheduler;
+
+/// The runtime's counters at one point in time, returned by
+/// [`Handle::metrics_snapshot`](super::Handle::metrics_snapshot).
+///
+/// Counters start at zero when the runtime starts and only go up, so rates come from the
+/// difference between two snapshots. Gauges are what they were when the snapshot was taken.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[non_exhaustive]
+pub struct MetricsSnapshot {
+  /// Gauge, how many worker threads are running, see
+  /// [`Builder::min_workers`](super::Builder::min_workers).
+  pub workers: usize,
+  /// Counter.
+  pub tasks_spawned: u64,
+  /// Counter, including the tasks which panicked.
+  pub tasks_completed: u64,
+  /// Counter.
+  pub tasks_panicked: u64,
+  /// Gauge, tasks which were spawned and aren't completed.
+  pub tasks_alive: u64,
+  /// Counter, of polls by every worker together.
+  pub polls: u64,
+  /// Counter, of how many times a worker ran out of tasks and went to sleep.
+  pub worker_parks: u64,
+  /// Gauge, tasks no worker has picked up yet.
+  pub global_queue_depth: usize,
+  /// Counter, of socket reads and writes which completed, successfully or not.
+  pub io_operations: u64,
+  /// Gauge, socket reads and writes waiting on readiness.
+  pub io_in_flight: usize,
+  /// Counter, of how many times a task was woken because its socket became ready, see
+  /// [`Builder::io_coalesce_window`](super::Builder::io_coalesce_window).
+  pub io_wakes: u64,
+}
+
+// Counted on every spawn and completion, which cost more than an atomic add already.
+#[derive(Default)]
+pub(crate) struct Counters {
+  pub(crate) tasks_spawned: AtomicU64,
+  pub(crate) tasks_completed: AtomicU64,
+  pub(crate) tasks_panicked: AtomicU64,
+  pub(crate) io_operations: AtomicU64,
+}
+
+// Counted on every poll, so every worker has its own and they don't share a cache line.
+#[derive(Default)]
+pub(crate) struct WorkerCounters {
+  pub(crate) polls: CachePadded<AtomicU64>,
+  pub(crate) parks: AtomicU64,
+}
+
+pub(crate) fn increment(counter: &AtomicU64) {
+  counter.fetch_add(1, Ordering::Relaxed);
+}
+
+impl MetricsSnapshot {
+  pub(crate) fn of(handle: &scheduler::Handle) -> MetricsSnapshot {
+    let counters = handle.counters();
+    // Completions are read first, so a task which completes meanwhile is counted as spawned
+    // either way, and there are never more completed tasks than spawned ones.
+    let tasks_completed = counters.tasks_completed.load(Ordering::SeqCst);
+    let tasks_panicked = counters.tasks_panicked.load(Ordering::SeqCst);
+    let tasks_spawned = counters.tasks_spawned.load(Ordering::SeqCst);
+
+    let remotes = &handle.state().remotes;
+    let worker = |count: fn(&WorkerCounters) -> &AtomicU64| {
+      let sum = remotes.iter().map(|remote| count(remote.counters()));
+      sum.map(|counter| counter.load(Ordering::Relaxed)).sum()
+    };
+
+    MetricsSnapshot {
+      workers: handle.state().live_workers(),
+      tasks_spawned,
+      tasks_completed,
+      tasks_panicked,
+      tasks_alive: tasks_spawned - tasks_completed,
+      polls: worker(|counters| &counters.polls),
+      worker_parks: worker(|counters| &counters.parks),
+      global_queue_depth: handle.state().injector.len(),
+      io_operations: counters.io_operations.load(Ordering::Relaxed),
+      io_in_flight: handle.in_flight(),
+      io_wakes: handle.io().wakes(),
+    }
+  }
+}
+
+#[test]
+fn spawned_tasks_and_io() {
+  use crate::{io::AsyncRead, net::TcpStream, runtime::Builder, task};
+  use std::{future::poll_fn, io::Write, pin::Pin};
+
+  let runtime = Builder::new().worker_threads(2).build();
+  runtime.block_on(async {
+    let handle = super::Handle::current();
+    let before = handle.metrics_snapshot();
+    assert_eq!(before.workers, 2);
+
+    let tasks: Vec<_> = (0..10).map(|_| task::spawn(async {})).collect();
+    for task in tasks {
+      task.await.unwrap();
+    }
+    assert!(task::spawn(async { panic!() }).await.is_err());
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let mut stream = TcpStream::connect(listener.local_addr().unwrap())
+      .unwrap()
+      .await
+      .unwrap();
+    let (mut peer, _) = listener.accept().unwrap();
+    let mut buf = [0; 4];
+    let read = task::spawn(async move {
+      poll_fn(|cx| Pin::new(&mut stream).poll_read(cx, &mut buf)).await
+    });
+    // The read waits until the peer writes.
+    while handle.metrics_snapshot().io_in_flight == 0 {
+      task::yield_now().await;
+    }
+    peer.write_all(b"ping").unwrap();
+    assert_eq!(read.await.unwrap().unwrap(), 4);
+
+    // A worker counts a completion right after the handle has the output.
+    let mut after = handle.metrics_snapshot();
+    while after.tasks_completed - before.tasks_completed < 12 {
+      task::yield_now().await;
+      after = handle.metrics_snapshot();
+    }
+    assert_eq!(after.tasks_spawned - before.tasks_spawned, 12);
+    assert_eq!(after.tasks_panicked - before.tasks_panicked, 1);
+    assert_eq!(after.tasks_alive, 0);
+    assert!(after.polls - before.polls >= 12);
+    assert!(after.io_operations > before.io_operations);
+    assert_eq!(after.io_in_flight, 0);
+  });
+}
@@ -0,0 +1,59 @@
+use crate::task::TaskId;
+
+/// Opens a span of its own around every poll of a task, see
+/// [`Builder::poll_span`](super::Builder::poll_span).
+///
+/// This is how a logging or tracing library follows which task is running, without liten
+/// depending on it: like entering a span stored per task in `enter`, and leaving it in `exit`.
+/// Both are called on the worker thread polling the task, so they have to be quick.
+pub trait PollSpan: Send + Sync + 'static {
+  /// Called right before `task` is polled.
+  fn enter(&self, task: TaskId);
+
+  /// Called right after the poll of `task` returns, or panics.
+  fn exit(&self, task: TaskId);
+}
+
+#[test]
+fn enter_exit_per_poll() {
+  use crate::{runtime::Builder, task};
+  use std::sync::{Arc, Mutex};
+
+  #[derive(Default)]
+  struct Recorder(Mutex<Vec<(TaskId, bool)>>);
+  impl PollSpan for Arc<Recorder> {
+    fn enter(&self, task: TaskId) {
+      self.0.lock().unwrap().push((task, true));
+    }
+    fn exit(&self, task: TaskId) {
+      self.0.lock().unwrap().push((task, false));
+    }
+  }
+
+  let recorder = Arc::new(Recorder::default());
+  let runtime =
+    Builder::new().worker_threads(1).poll_span(recorder.clone()).build();
+  let yielding = runtime.block_on(async {
+    let yielding = task::spawn(async {
+      task::yield_now().await;
+      task::current().id()
+    });
+    let id = yielding.await.unwrap();
+    assert!(task::spawn(async { panic!("in a poll") }).await.is_err());
+    id
+  });
+
+  let events = recorder.0.lock().unwrap();
+  let of = |same: bool| {
+    let polls = events.iter().filter(|(id, _)| (*id == yielding) == same);
+    polls.map(|(_, enter)| *enter).collect::<Vec<_>>()
+  };
+  // Yielding is pending three times, so four polls, each one entered and then left.
+  assert_eq!(of(true), [true, false].repeat(4));
+  // A panicking poll is left too.
+  assert_eq!(of(false), [true, false]);
+  // Polls on one worker don't overlap.
+  for pair in events.chunks(2) {
+    assert!(pair[0].1 && !pair[1].1 && pair[0].0 == pair[1].0);
+  }
+}
@@ -1,35 +1,102 @@
-use std::{sync::Arc, task::Wake, thread::Thread};
+use std::{
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+  },
+  task::Wake,
+  thread::Thread,
+};
+
+use crossbeam_utils::sync::Unparker;
 
 use crate::{sync::mpsc, task::TaskId};
 
 pub struct TaskWaker {
   task_id: TaskId,
   sender: mpsc::Sender<TaskId>,
+  // The worker owning the task could be parked.
+  unparker: Unparker,
 }
 
 impl TaskWaker {
-  pub(crate) fn new(task: TaskId, sender: mpsc::Sender<TaskId>) -> Self {
-    Self { task_id: task, sender }
+  pub(crate) fn new(
+    task: TaskId,
+    sender: mpsc::Sender<TaskId>,
+    unparker: Unparker,
+  ) -> Self {
+    Self { task_id: task, sender, unparker }
   }
 }
 
 impl Wake for TaskWaker {
   fn wake(self: Arc<Self>) {
-    self.sender.send(self.task_id).unwrap();
+    self.wake_by_ref();
+  }
+
+  fn wake_by_ref(self: &Arc<Self>) {
+    // The worker is gone once the runtime has shut down, there is nothing left to wake then.
+    if self.sender.send(self.task_id).is_ok() {
+      #[cfg(feature = "tracing")]
+      tracing::trace!(task.id = self.task_id.0, "woken");
+      self.unparker.unpark();
+    }
   }
 }
 
 // Waker implementation to notify the runtime
-pub struct RuntimeWaker(Thread);
+pub struct RuntimeWaker {
+  thread: Thread,
+  woken: AtomicBool,
+}
 
 impl RuntimeWaker {
   pub fn new(thread: Thread) -> Self {
-    Self(thread)
+    Self { thread, woken: AtomicBool::new(false) }
+  }
+
+  /// Returns `true` if the waker has been woken since the last call.
+  pub fn take_woken(&self) -> bool {
+    self.woken.swap(false, Ordering::AcqRel)
   }
 }
 
 impl Wake for RuntimeWaker {
   fn wake(self: Arc<Self>) {
-    self.0.unpark();
+    self.woken.store(true, Ordering::Release);
+    self.thread.unpark();
+  }
+}
+
+#[test]
+fn woken_after_shutdown() {
+  let waker = crate::runtime::Runtime::new().block_on(async {
+    let (sender, receiver) =
+      crate::sync::oneshot::channel::<std::task::Waker>();
+    crate::task::spawn(async move {
+      sender.send(crate::task::current().waker()).unwrap();
+    });
+    receiver.await.unwrap()
+  });
+  // Used to panic, since the worker's receiver was dropped.
+  waker.wake();
+}
+
+#[crate::internal_test]
+async fn tasks_woken_from_other_threads() {
+  use crate::sync::oneshot;
+
+  let handles: Vec<_> = (0..64)
+    .map(|n| {
+      let (sender, receiver) = oneshot::channel();
+      std::thread::spawn(move || {
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        sender.send(n).unwrap();
+      });
+      crate::task::spawn(async move { receiver.await.unwrap() })
+    })
+    .collect();
+
+  for (n, handle) in handles.into_iter().enumerate() {
+    assert_eq!(handle.await.unwrap(), n);
   }
 }
@@ -0,0 +1,127 @@
+use std::{
+  future::Future,
+  pin::Pin,
+  task::{Context, Poll},
+  time::Duration,
+};
+
+use pin_project_lite::pin_project;
+
+use super::Stream;
+use crate::time::{self, Sleep};
+
+pin_project! {
+  /// Stream returned by [`StreamExt::debounce`](super::StreamExt::debounce).
+  #[must_use = "streams do nothing unless polled"]
+  pub struct Debounce<S: Stream> {
+    #[pin]
+    stream: S,
+    duration: Duration,
+    // The latest item and the end of its quiet period.
+    latest: Option<(S::Item, Sleep)>,
+    ended: bool,
+  }
+}
+
+impl<S: Stream> Debounce<S> {
+  pub(super) fn new(stream: S, duration: Duration) -> Self {
+    Debounce { stream, duration, latest: None, ended: false }
+  }
+}
+
+impl<S: Stream> Stream for Debounce<S> {
+  type Item = S::Item;
+
+  fn poll_next(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<Option<Self::Item>> {
+    let mut this = self.project();
+
+    while !*this.ended {
+      match this.stream.as_mut().poll_next(cx) {
+        Poll::Ready(Some(item)) => {
+          let deadline = time::now() + *this.duration;
+          match this.latest {
+            Some((latest, quiet)) => {
+              *latest = item;
+              quiet.reset(deadline);
+            }
+            None => *this.latest = Some((item, time::sleep_until(deadline))),
+          }
+        }
+        Poll::Ready(None) => *this.ended = true,
+        Poll::Pending => break,
+      }
+    }
+
+    match this.latest {
+      // Flush the pending item instead of dropping it when the stream has ended.
+      Some(_) if *this.ended => {
+        Poll::Ready(this.latest.take().map(|(item, _)| item))
+      }
+      Some((_, quiet)) => {
+        std::task::ready!(Pin::new(quiet).poll(cx));
+        Poll::Ready(this.latest.take().map(|(item, _)| item))
+      }
+      None if *this.ended => Poll::Ready(None),
+      None => Poll::Pending,
+    }
+  }
+}
+
+#[cfg(test)]
+pin_project! {
+  // Yields each item once its offset from the first poll has passed.
+  struct Scheduled<T> {
+    items: std::collections::VecDeque<(Duration, T)>,
+    start: Option<std::time::Instant>,
+    sleep: Option<Sleep>,
+  }
+}
+
+#[cfg(test)]
+impl<T> Stream for Scheduled<T> {
+  type Item = T;
+
+  fn poll_next(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<Option<Self::Item>> {
+    let this = self.project();
+    let start = *this.start.get_or_insert_with(time::now);
+    let Some((offset, _)) = this.items.front() else {
+      return Poll::Ready(None);
+    };
+
+    let sleep = this.sleep.get_or_insert_with(|| time::sleep_until(start));
+    sleep.reset(start + *offset);
+    std::task::ready!(Pin::new(sleep).poll(cx));
+    Poll::Ready(this.items.pop_front().map(|(_, item)| item))
+  }
+}
+
+#[crate::internal_test]
+async fn collapses_bursts() {
+  use super::StreamExt;
+
+  time::pause();
+  let start = time::now();
+  let ms = Duration::from_millis;
+
+  let scheduled = Scheduled {
+    items: [(ms(0), 1), (ms(10), 2), (ms(20), 3), (ms(200), 4), (ms(210), 5)]
+      .into(),
+    start: None,
+    sleep: None,
+  };
+  let mut stream = std::pin::pin!(scheduled.debounce(ms(50)));
+
+  assert_eq!(stream.next().await, Some(3));
+  assert_eq!(time::now() - start, ms(70));
+
+  // The stream ends during the quiet period of 5, which is flushed right away.
+  assert_eq!(stream.next().await, Some(5));
+  assert_eq!(time::now() - start, ms(210));
+  assert_eq!(stream.next().await, None);
+}
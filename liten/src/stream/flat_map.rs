@@ -0,0 +1,145 @@
+use std::{
+  pin::Pin,
+  task::{Context, Poll},
+};
+
+use pin_project_lite::pin_project;
+
+use super::Stream;
+
+pin_project! {
+  /// Stream returned by [`StreamExt::flat_map`](super::StreamExt::flat_map).
+  #[must_use = "streams do nothing unless polled"]
+  pub struct FlatMap<S, F, U> {
+    #[pin]
+    stream: S,
+    f: F,
+    // The sub-stream of the last item, until it ends.
+    #[pin]
+    inner: Option<U>,
+  }
+}
+
+impl<S, F, U> FlatMap<S, F, U> {
+  pub(super) fn new(stream: S, f: F) -> Self {
+    FlatMap { stream, f, inner: None }
+  }
+}
+
+impl<S, F, U> Stream for FlatMap<S, F, U>
+where
+  S: Stream,
+  F: FnMut(S::Item) -> U,
+  U: Stream,
+{
+  type Item = U::Item;
+
+  fn poll_next(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<Option<Self::Item>> {
+    let this = self.project();
+    poll_flat(this.stream, this.inner, cx, this.f)
+  }
+}
+
+pin_project! {
+  /// Stream returned by [`StreamExt::flatten`](super::StreamExt::flatten).
+  #[must_use = "streams do nothing unless polled"]
+  pub struct Flatten<S, U> {
+    #[pin]
+    stream: S,
+    #[pin]
+    inner: Option<U>,
+  }
+}
+
+impl<S, U> Flatten<S, U> {
+  pub(super) fn new(stream: S) -> Self {
+    Flatten { stream, inner: None }
+  }
+}
+
+impl<S, U> Stream for Flatten<S, U>
+where
+  S: Stream<Item = U>,
+  U: Stream,
+{
+  type Item = U::Item;
+
+  fn poll_next(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<Option<Self::Item>> {
+    let this = self.project();
+    poll_flat(this.stream, this.inner, cx, &mut |stream| stream)
+  }
+}
+
+// Yields the items of `inner` until it ends, then makes the next one out of the next item of
+// `stream`.
+fn poll_flat<S: Stream, U: Stream>(
+  mut stream: Pin<&mut S>,
+  mut inner: Pin<&mut Option<U>>,
+  cx: &mut Context<'_>,
+  f: &mut impl FnMut(S::Item) -> U,
+) -> Poll<Option<U::Item>> {
+  loop {
+    if let Some(current) = inner.as_mut().as_pin_mut() {
+      match std::task::ready!(current.poll_next(cx)) {
+        Some(item) => return Poll::Ready(Some(item)),
+        None => inner.set(None),
+      }
+    }
+    match std::task::ready!(stream.as_mut().poll_next(cx)) {
+      Some(item) => inner.set(Some(f(item))),
+      None => return Poll::Ready(None),
+    }
+  }
+}
+
+#[test]
+fn digits() {
+  use super::{iter, StreamExt};
+  use crate::{assert_ready_eq, test_util::task};
+
+  let digits = |number: u32| {
+    let digits: Vec<_> = number
+      .to_string()
+      .chars()
+      .map(|digit| digit.to_digit(10).unwrap())
+      .collect();
+    iter(digits)
+  };
+  let mut flat = task::spawn(iter([12, 0, 345]).flat_map(digits));
+  for expected in [1, 2, 0, 3, 4, 5] {
+    assert_ready_eq!(flat.poll_next(), Some(expected));
+  }
+  assert_ready_eq!(flat.poll_next(), None);
+
+  let mut flat = task::spawn(
+    iter([iter(vec![1, 2]), iter(vec![]), iter(vec![3])]).flatten(),
+  );
+  for expected in [1, 2, 3] {
+    assert_ready_eq!(flat.poll_next(), Some(expected));
+  }
+  assert_ready_eq!(flat.poll_next(), None);
+}
+
+#[crate::internal_test]
+async fn waits_on_sub_stream() {
+  use super::StreamExt;
+  use crate::sync::mpsc;
+
+  // The sub-stream of the first item ends before the second item is looked at.
+  let (first, first_items) = mpsc::unbounded();
+  let (second, second_items) = mpsc::unbounded();
+  let mut flat = super::iter([first_items, second_items]).flatten();
+  second.send("second").unwrap();
+  first.send("first").unwrap();
+  assert_eq!(flat.next().await, Some("first"));
+  drop(first);
+  assert_eq!(flat.next().await, Some("second"));
+  drop(second);
+  assert_eq!(flat.next().await, None);
+}
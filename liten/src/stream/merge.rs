@@ -0,0 +1,114 @@
+use std::{
+  pin::Pin,
+  task::{Context, Poll},
+};
+
+use pin_project_lite::pin_project;
+
+use super::Stream;
+
+pin_project! {
+  /// Stream returned by [`StreamExt::merge`](super::StreamExt::merge).
+  #[must_use = "streams do nothing unless polled"]
+  pub struct Merge<A, B> {
+    #[pin]
+    first: A,
+    #[pin]
+    second: B,
+    // Which one is polled first next time, flipped on every poll.
+    second_first: bool,
+    first_done: bool,
+    second_done: bool,
+  }
+}
+
+impl<A, B> Merge<A, B> {
+  pub(super) fn new(first: A, second: B) -> Self {
+    Merge {
+      first,
+      second,
+      second_first: false,
+      first_done: false,
+      second_done: false,
+    }
+  }
+}
+
+impl<A, B> Stream for Merge<A, B>
+where
+  A: Stream,
+  B: Stream<Item = A::Item>,
+{
+  type Item = A::Item;
+
+  fn poll_next(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<Option<Self::Item>> {
+    let mut this = self.project();
+    let second_first = *this.second_first;
+    *this.second_first = !second_first;
+
+    for second in [second_first, !second_first] {
+      let (poll, done) = if second {
+        if *this.second_done {
+          continue;
+        }
+        (this.second.as_mut().poll_next(cx), &mut *this.second_done)
+      } else {
+        if *this.first_done {
+          continue;
+        }
+        (this.first.as_mut().poll_next(cx), &mut *this.first_done)
+      };
+      match poll {
+        Poll::Ready(Some(item)) => return Poll::Ready(Some(item)),
+        Poll::Ready(None) => *done = true,
+        Poll::Pending => {}
+      }
+    }
+
+    if *this.first_done && *this.second_done {
+      Poll::Ready(None)
+    } else {
+      Poll::Pending
+    }
+  }
+}
+
+#[crate::internal_test]
+async fn merges_channels() {
+  use super::StreamExt;
+  use crate::{sync::mpsc, task};
+
+  let (data, data_stream) = mpsc::unbounded();
+  let (control, control_stream) = mpsc::unbounded();
+  let producer = task::spawn(async move {
+    for value in 0..5 {
+      data.send(value).unwrap();
+      task::yield_now().await;
+    }
+    control.send(100).unwrap();
+  });
+
+  let mut merged = data_stream.merge(control_stream);
+  let mut items = Vec::new();
+  while let Some(item) = merged.next().await {
+    items.push(item);
+  }
+  producer.await.unwrap();
+  items.sort();
+  assert_eq!(items, [0, 1, 2, 3, 4, 100]);
+}
+
+#[test]
+fn alternates() {
+  use super::{iter, StreamExt};
+  use crate::{assert_ready_eq, test_util::task};
+
+  let mut merged = task::spawn(iter([1, 2, 3]).merge(iter([10, 20])));
+  for expected in [1, 10, 2, 20, 3] {
+    assert_ready_eq!(merged.poll_next(), Some(expected));
+  }
+  assert_ready_eq!(merged.poll_next(), None);
+}
@@ -0,0 +1,119 @@
+//! Asynchronous sequences of values and combinators over them.
+mod debounce;
+mod flat_map;
+mod iter;
+mod merge;
+mod next;
+mod scan;
+mod take_until;
+mod throttle;
+
+pub use debounce::Debounce;
+pub use flat_map::{FlatMap, Flatten};
+pub use futures_core::Stream;
+pub use iter::{iter, Iter};
+pub use merge::Merge;
+pub use next::Next;
+pub use scan::Scan;
+pub use take_until::TakeUntil;
+pub use throttle::Throttle;
+
+use std::{future::Future, time::Duration};
+
+/// Combinators for [`Stream`]s, implemented for every stream.
+pub trait StreamExt: Stream {
+  /// Returns the next item of the stream, `None` when it has ended.
+  fn next(&mut self) -> Next<'_, Self>
+  where
+    Self: Unpin,
+  {
+    Next::new(self)
+  }
+
+  /// Yields at most one item per `period`.
+  ///
+  /// The first item is yielded right away, every item after it waits until `period` has passed
+  /// since the previous one. No items are dropped: the stream is only polled again once the delay
+  /// is over, so if it ends during a delay, the end is seen after the delay.
+  fn throttle(self, period: Duration) -> Throttle<Self>
+  where
+    Self: Sized,
+  {
+    Throttle::new(self, period)
+  }
+
+  /// Yields an item only after the stream has been quiet for `duration`, dropping the items
+  /// which were superseded before that.
+  ///
+  /// If the stream ends while an item is waiting for the quiet period, that item is yielded right
+  /// away before the end.
+  fn debounce(self, duration: Duration) -> Debounce<Self>
+  where
+    Self: Sized,
+  {
+    Debounce::new(self, duration)
+  }
+
+  /// Yields the items of both streams as they come, and ends once both have ended.
+  ///
+  /// The streams take turns being polled first, so one which always has an item ready can't
+  /// starve the other.
+  fn merge<S>(self, other: S) -> Merge<Self, S>
+  where
+    Self: Sized,
+    S: Stream<Item = Self::Item>,
+  {
+    Merge::new(self, other)
+  }
+
+  /// Yields what `f` makes of every item, with a state it's given alongside each one.
+  ///
+  /// The state starts out as `init`. Once `f` returns `None`, the stream ends without polling the
+  /// underlying one again.
+  fn scan<St, B, F>(self, init: St, f: F) -> Scan<Self, St, F>
+  where
+    Self: Sized,
+    F: FnMut(&mut St, Self::Item) -> Option<B>,
+  {
+    Scan::new(self, init, f)
+  }
+
+  /// Makes a stream out of every item with `f`, and yields the items of those streams one after
+  /// the other.
+  ///
+  /// A sub-stream is yielded until it ends before the next item is taken, so the order of the
+  /// items is kept.
+  fn flat_map<U, F>(self, f: F) -> FlatMap<Self, F, U>
+  where
+    Self: Sized,
+    F: FnMut(Self::Item) -> U,
+    U: Stream,
+  {
+    FlatMap::new(self, f)
+  }
+
+  /// Yields the items of every stream this stream yields, one stream after the other, like
+  /// [`StreamExt::flat_map`].
+  fn flatten(self) -> Flatten<Self, Self::Item>
+  where
+    Self: Sized,
+    Self::Item: Stream,
+  {
+    Flatten::new(self)
+  }
+
+  /// Yields items until `signal` completes, then ends, like a connection's reads bounded by a
+  /// shutdown signal or a timeout.
+  ///
+  /// The signal is polled before the stream on every poll, so once it has completed no more items
+  /// are yielded, even ready ones. Its output is dropped.
+  fn take_until<F>(self, signal: F) -> TakeUntil<Self, F>
+  where
+    Self: Sized,
+    F: Future,
+  {
+    TakeUntil::new(self, signal)
+  }
+}
+
+impl<S: Stream + ?Sized> StreamExt for S {}
@@ -0,0 +1,81 @@
+use std::{
+  pin::Pin,
+  task::{Context, Poll},
+};
+
+use pin_project_lite::pin_project;
+
+use super::Stream;
+
+pin_project! {
+  /// Stream returned by [`StreamExt::scan`](super::StreamExt::scan).
+  #[must_use = "streams do nothing unless polled"]
+  pub struct Scan<S, St, F> {
+    #[pin]
+    stream: S,
+    state: St,
+    f: F,
+    // Set once `f` has returned `None`, the stream isn't polled after that.
+    done: bool,
+  }
+}
+
+impl<S, St, F> Scan<S, St, F> {
+  pub(super) fn new(stream: S, init: St, f: F) -> Self {
+    Scan { stream, state: init, f, done: false }
+  }
+}
+
+impl<S, St, F, B> Stream for Scan<S, St, F>
+where
+  S: Stream,
+  F: FnMut(&mut St, S::Item) -> Option<B>,
+{
+  type Item = B;
+
+  fn poll_next(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<Option<Self::Item>> {
+    let this = self.project();
+    if *this.done {
+      return Poll::Ready(None);
+    }
+
+    let item = std::task::ready!(this.stream.poll_next(cx));
+    let output = item.and_then(|item| (this.f)(this.state, item));
+    *this.done = output.is_none();
+    Poll::Ready(output)
+  }
+}
+
+#[test]
+fn running_sum() {
+  use super::{iter, StreamExt};
+  use crate::{assert_ready_eq, test_util::task};
+
+  let mut sums = task::spawn(iter([1, 2, 3, 4]).scan(0, |sum, item| {
+    *sum += item;
+    Some(*sum)
+  }));
+  for expected in [1, 3, 6, 10] {
+    assert_ready_eq!(sums.poll_next(), Some(expected));
+  }
+  assert_ready_eq!(sums.poll_next(), None);
+}
+
+#[test]
+fn ends_on_none() {
+  use super::{iter, StreamExt};
+  use crate::{assert_ready_eq, test_util::task};
+
+  let mut sums = task::spawn(iter(1..).scan(0, |sum, item| {
+    *sum += item;
+    (*sum < 6).then_some(*sum)
+  }));
+  assert_ready_eq!(sums.poll_next(), Some(1));
+  assert_ready_eq!(sums.poll_next(), Some(3));
+  assert_ready_eq!(sums.poll_next(), None);
+  // The rest of the stream is left alone.
+  assert_ready_eq!(sums.poll_next(), None);
+}
@@ -0,0 +1,74 @@
+use std::{
+  future::Future,
+  pin::Pin,
+  task::{Context, Poll},
+};
+
+use pin_project_lite::pin_project;
+
+use super::Stream;
+
+pin_project! {
+  /// Stream returned by [`StreamExt::take_until`](super::StreamExt::take_until).
+  #[must_use = "streams do nothing unless polled"]
+  pub struct TakeUntil<S, F> {
+    #[pin]
+    stream: S,
+    #[pin]
+    signal: F,
+    // Once the signal, or the stream, is done.
+    ended: bool,
+  }
+}
+
+impl<S, F> TakeUntil<S, F> {
+  pub(super) fn new(stream: S, signal: F) -> Self {
+    TakeUntil { stream, signal, ended: false }
+  }
+}
+
+impl<S: Stream, F: Future> Stream for TakeUntil<S, F> {
+  type Item = S::Item;
+
+  fn poll_next(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<Option<Self::Item>> {
+    let this = self.project();
+    if *this.ended {
+      return Poll::Ready(None);
+    }
+
+    // First, so an item which is ready doesn't get past a signal which is too.
+    if this.signal.poll(cx).is_ready() {
+      *this.ended = true;
+      return Poll::Ready(None);
+    }
+    let poll = this.stream.poll_next(cx);
+    if let Poll::Ready(None) = poll {
+      *this.ended = true;
+    }
+    poll
+  }
+}
+
+#[crate::internal_test]
+async fn ended_by_signal() {
+  use super::StreamExt;
+  use crate::sync::{mpsc, oneshot};
+
+  let (items, receiver) = mpsc::unbounded();
+  let (shutdown, signal) = oneshot::channel::<()>();
+  let mut stream = receiver.take_until(signal);
+
+  items.send(1).unwrap();
+  items.send(2).unwrap();
+  assert_eq!(stream.next().await, Some(1));
+  assert_eq!(stream.next().await, Some(2));
+
+  // The stream is still open, and has an item ready, but the signal ends it.
+  items.send(3).unwrap();
+  shutdown.send(()).unwrap();
+  assert_eq!(stream.next().await, None);
+  assert_eq!(stream.next().await, None);
+}
@@ -0,0 +1,76 @@
+use std::{
+  future::Future,
+  pin::Pin,
+  task::{Context, Poll},
+  time::Duration,
+};
+
+use pin_project_lite::pin_project;
+
+use super::Stream;
+use crate::time::{self, Sleep};
+
+pin_project! {
+  /// Stream returned by [`StreamExt::throttle`](super::StreamExt::throttle).
+  #[must_use = "streams do nothing unless polled"]
+  pub struct Throttle<S> {
+    #[pin]
+    stream: S,
+    period: Duration,
+    // Set after an item has been yielded, until the period is over.
+    delay: Option<Sleep>,
+  }
+}
+
+impl<S> Throttle<S> {
+  pub(super) fn new(stream: S, period: Duration) -> Self {
+    Throttle { stream, period, delay: None }
+  }
+}
+
+impl<S: Stream> Stream for Throttle<S> {
+  type Item = S::Item;
+
+  fn poll_next(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<Option<Self::Item>> {
+    let this = self.project();
+
+    if let Some(delay) = this.delay {
+      if Pin::new(delay).poll(cx).is_pending() {
+        return Poll::Pending;
+      }
+      *this.delay = None;
+    }
+
+    let item = std::task::ready!(this.stream.poll_next(cx));
+    if item.is_some() {
+      *this.delay = Some(time::sleep(*this.period));
+    }
+    Poll::Ready(item)
+  }
+}
+
+#[crate::internal_test]
+async fn emission_times() {
+  use super::StreamExt;
+
+  time::pause();
+  let start = time::now();
+
+  let mut stream = std::pin::pin!(super::iter(1..=3).throttle(period()));
+  let mut emitted = Vec::new();
+  while let Some(item) = stream.next().await {
+    emitted.push((item, time::now() - start));
+  }
+
+  assert_eq!(emitted, [(1, Duration::ZERO), (2, period()), (3, period() * 2)]);
+  // The end is only seen after the last delay.
+  assert_eq!(time::now() - start, period() * 3);
+}
+
+#[cfg(test)]
+fn period() -> Duration {
+  Duration::from_millis(100)
+}
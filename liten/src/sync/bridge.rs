@@ -0,0 +1,331 @@
+//! A bounded channel between threads and tasks.
+//!
+//! Each side can be used from async code, with [`Sender::send`] and [`Receiver::recv`], or from a
+//! plain thread, with [`Sender::blocking_send`] and [`Receiver::blocking_recv`]. A thread outside
+//! of the runtime can thus feed tasks, or be fed by them, and a full channel holds the senders
+//! back until the receiver catches up.
+//!
+//! The blocking methods block the whole thread, calling them from a task blocks its worker.
+
+use std::{
+  collections::VecDeque,
+  fmt,
+  future::Future,
+  num::NonZero,
+  pin::Pin,
+  sync::{Arc, Condvar, Mutex as StdMutex, MutexGuard},
+  task::{Context, Poll, Waker},
+};
+
+use futures_core::Stream;
+use thiserror::Error;
+
+/// Creates a channel which holds at most `capacity` values.
+pub fn channel<T>(capacity: NonZero<usize>) -> (Sender<T>, Receiver<T>) {
+  let channel = Arc::new(Channel {
+    state: StdMutex::new(State {
+      values: VecDeque::with_capacity(capacity.get()),
+      senders: 1,
+      receiver_dropped: false,
+      receiver: None,
+      senders_waiting: Vec::new(),
+    }),
+    capacity: capacity.get(),
+    not_empty: Condvar::new(),
+    not_full: Condvar::new(),
+  });
+  (Sender { channel: channel.clone() }, Receiver { channel })
+}
+
+/// Returned by a send once the receiver is dropped, with the value which couldn't be sent.
+#[derive(Error, PartialEq, Eq)]
+#[error("the receiver was dropped")]
+pub struct SendError<T>(pub T);
+
+impl<T> fmt::Debug for SendError<T> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str("SendError(..)")
+  }
+}
+
+/// Returned by a receive once every sender is dropped and every value has been received.
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+#[error("every sender was dropped")]
+pub struct RecvError;
+
+struct Channel<T> {
+  // Blocking senders and receivers wait on the condvars, which need a std mutex.
+  state: StdMutex<State<T>>,
+  capacity: usize,
+  not_empty: Condvar,
+  not_full: Condvar,
+}
+
+struct State<T> {
+  values: VecDeque<T>,
+  senders: usize,
+  receiver_dropped: bool,
+  // The waker of a pending recv.
+  receiver: Option<Waker>,
+  // The wakers of pending sends, all woken when there's room since any of them might be gone.
+  senders_waiting: Vec<Waker>,
+}
+
+impl<T> Channel<T> {
+  fn lock(&self) -> MutexGuard<'_, State<T>> {
+    self.state.lock().unwrap()
+  }
+
+  // Sends `value` if there's room, otherwise gives it back.
+  fn try_send(
+    &self,
+    state: &mut State<T>,
+    value: T,
+  ) -> Result<Result<(), T>, SendError<T>> {
+    if state.receiver_dropped {
+      return Err(SendError(value));
+    }
+    if state.values.len() >= self.capacity {
+      return Ok(Err(value));
+    }
+    state.values.push_back(value);
+    if let Some(waker) = state.receiver.take() {
+      waker.wake();
+    }
+    self.not_empty.notify_one();
+    Ok(Ok(()))
+  }
+
+  // `None` while the channel is empty and a sender is left.
+  fn try_recv(&self, state: &mut State<T>) -> Option<Result<T, RecvError>> {
+    match state.values.pop_front() {
+      Some(value) => {
+        for waker in state.senders_waiting.drain(..) {
+          waker.wake();
+        }
+        self.not_full.notify_all();
+        Some(Ok(value))
+      }
+      None if state.senders == 0 => Some(Err(RecvError)),
+      None => None,
+    }
+  }
+}
+
+/// Sends values into a [`channel`], it can be cloned for more senders.
+pub struct Sender<T> {
+  channel: Arc<Channel<T>>,
+}
+
+impl<T> Sender<T> {
+  /// Waits for room in the channel and sends `value`.
+  pub fn send(&self, value: T) -> SendFuture<'_, T> {
+    SendFuture { sender: self, value: Some(value) }
+  }
+
+  /// Blocks the thread until there's room in the channel, and sends `value`.
+  pub fn blocking_send(&self, value: T) -> Result<(), SendError<T>> {
+    let mut state = self.channel.lock();
+    let mut value = value;
+    loop {
+      match self.channel.try_send(&mut state, value)? {
+        Ok(()) => return Ok(()),
+        Err(back) => value = back,
+      }
+      state = self.channel.not_full.wait(state).unwrap();
+    }
+  }
+}
+
+impl<T> Clone for Sender<T> {
+  fn clone(&self) -> Self {
+    self.channel.lock().senders += 1;
+    Sender { channel: self.channel.clone() }
+  }
+}
+
+impl<T> Drop for Sender<T> {
+  fn drop(&mut self) {
+    let mut state = self.channel.lock();
+    state.senders -= 1;
+    if state.senders == 0 {
+      // A waiting receiver has to see the disconnect.
+      if let Some(waker) = state.receiver.take() {
+        waker.wake();
+      }
+      self.channel.not_empty.notify_all();
+    }
+  }
+}
+
+/// Future returned by [`Sender::send`].
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct SendFuture<'a, T> {
+  sender: &'a Sender<T>,
+  // Taken once it's sent.
+  value: Option<T>,
+}
+
+// The value is never pinned.
+impl<T> Unpin for SendFuture<'_, T> {}
+
+impl<T> Future for SendFuture<'_, T> {
+  type Output = Result<(), SendError<T>>;
+
+  fn poll(
+    mut self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<Self::Output> {
+    let channel = &self.sender.channel;
+    let mut state = channel.lock();
+    let value = self.value.take().expect("SendFuture polled after completion");
+    match channel.try_send(&mut state, value) {
+      Ok(Ok(())) => Poll::Ready(Ok(())),
+      Err(err) => Poll::Ready(Err(err)),
+      Ok(Err(value)) => {
+        if !state
+          .senders_waiting
+          .iter()
+          .any(|waker| waker.will_wake(cx.waker()))
+        {
+          state.senders_waiting.push(cx.waker().clone());
+        }
+        drop(state);
+        self.value = Some(value);
+        Poll::Pending
+      }
+    }
+  }
+}
+
+/// Receives the values of a [`channel`].
+pub struct Receiver<T> {
+  channel: Arc<Channel<T>>,
+}
+
+impl<T> Receiver<T> {
+  /// Waits for the next value. Values sent before the last sender was dropped are still received.
+  pub fn recv(&self) -> RecvFuture<'_, T> {
+    RecvFuture { receiver: self }
+  }
+
+  /// Blocks the thread until the next value is there.
+  pub fn blocking_recv(&self) -> Result<T, RecvError> {
+    let mut state = self.channel.lock();
+    loop {
+      if let Some(result) = self.channel.try_recv(&mut state) {
+        return result;
+      }
+      state = self.channel.not_empty.wait(state).unwrap();
+    }
+  }
+
+  /// Takes the next value if there is one, `Ok(None)` means the channel is empty.
+  pub fn try_recv(&self) -> Result<Option<T>, RecvError> {
+    let mut state = self.channel.lock();
+    self.channel.try_recv(&mut state).transpose()
+  }
+
+  fn poll_recv(&self, cx: &mut Context<'_>) -> Poll<Result<T, RecvError>> {
+    let mut state = self.channel.lock();
+    match self.channel.try_recv(&mut state) {
+      Some(result) => Poll::Ready(result),
+      None => {
+        state.receiver = Some(cx.waker().clone());
+        Poll::Pending
+      }
+    }
+  }
+}
+
+/// Yields the values until every sender is dropped, like [`Receiver::recv`].
+impl<T> Stream for Receiver<T> {
+  type Item = T;
+
+  fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+    self.poll_recv(cx).map(Result::ok)
+  }
+}
+
+impl<T> Drop for Receiver<T> {
+  fn drop(&mut self) {
+    let mut state = self.channel.lock();
+    state.receiver_dropped = true;
+    for waker in state.senders_waiting.drain(..) {
+      waker.wake();
+    }
+    self.channel.not_full.notify_all();
+  }
+}
+
+/// Future returned by [`Receiver::recv`].
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct RecvFuture<'a, T> {
+  receiver: &'a Receiver<T>,
+}
+
+impl<T> Future for RecvFuture<'_, T> {
+  type Output = Result<T, RecvError>;
+
+  fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+    self.receiver.poll_recv(cx)
+  }
+}
+
+#[crate::internal_test]
+async fn thread_feeds_task() {
+  use crate::time;
+  use std::{
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+    time::Duration,
+  };
+
+  let (sender, receiver) = channel(NonZero::new(2).unwrap());
+  let sent = Arc::new(AtomicUsize::new(0));
+  let feeder_sent = sent.clone();
+  let feeder = thread::spawn(move || {
+    for value in 0..10 {
+      sender.blocking_send(value).unwrap();
+      feeder_sent.fetch_add(1, Ordering::SeqCst);
+    }
+  });
+
+  // The thread blocks once the channel is full.
+  time::sleep(Duration::from_millis(20)).await;
+  assert_eq!(sent.load(Ordering::SeqCst), 2);
+
+  let mut received = Vec::new();
+  while let Ok(value) = receiver.recv().await {
+    received.push(value);
+  }
+  assert_eq!(received, (0..10).collect::<Vec<_>>());
+  feeder.join().unwrap();
+}
+
+#[crate::internal_test]
+async fn task_feeds_thread() {
+  use crate::task;
+  use std::thread;
+
+  let (sender, receiver) = channel(NonZero::new(1).unwrap());
+  let consumer = thread::spawn(move || {
+    let mut received = Vec::new();
+    while let Ok(value) = receiver.blocking_recv() {
+      received.push(value);
+    }
+    received
+  });
+
+  let producer = task::spawn(async move {
+    for value in 0..10 {
+      sender.send(value).await.unwrap();
+    }
+  });
+  producer.await.unwrap();
+  assert_eq!(consumer.join().unwrap(), (0..10).collect::<Vec<_>>());
+
+  let (sender, receiver) = channel::<u32>(NonZero::new(1).unwrap());
+  drop(receiver);
+  assert_eq!(sender.send(1).await, Err(SendError(1)));
+}
@@ -0,0 +1,314 @@
+//! A multi-producer, multi-consumer channel where every receiver sees every value.
+//!
+//! The channel keeps the last `capacity` values. A receiver which falls further behind than that
+//! gets [`RecvError::Lagged`] and skips to the oldest value still buffered. When every
+//! [`Sender`] has been dropped, or [`Sender::close`] is called, receivers first drain what is left
+//! in the buffer and then get [`RecvError::Closed`].
+use std::{
+  collections::{HashMap, VecDeque},
+  future::Future,
+  pin::Pin,
+  sync::{Arc, Mutex as StdMutex},
+  task::{Context, Poll, Waker},
+};
+
+use thiserror::Error;
+
+/// Creates a channel which buffers up to `capacity` values.
+///
+/// # Panics
+///
+/// Panics if `capacity` is zero.
+pub fn channel<T: Clone>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+  assert!(capacity > 0, "broadcast channel capacity must be at least 1");
+
+  let shared = Arc::new(Shared {
+    state: StdMutex::new(State {
+      buffer: VecDeque::with_capacity(capacity),
+      capacity,
+      tail: 0,
+      senders: 1,
+      receivers: 1,
+      closed: false,
+      next_waiter: 0,
+      waiters: HashMap::new(),
+    }),
+  });
+
+  (Sender { shared: shared.clone() }, Receiver { shared, next: 0 })
+}
+
+struct Shared<T> {
+  // This is not a bottleneck
+  state: StdMutex<State<T>>,
+}
+
+struct State<T> {
+  buffer: VecDeque<T>,
+  capacity: usize,
+  // Position of the next value to be sent, the buffer holds the ones right before it.
+  tail: u64,
+  senders: usize,
+  receivers: usize,
+  closed: bool,
+
+  next_waiter: usize,
+  waiters: HashMap<usize, Waker>,
+}
+
+impl<T> State<T> {
+  fn head(&self) -> u64 {
+    self.tail - self.buffer.len() as u64
+  }
+
+  fn recv_at(&self, next: &mut u64) -> Result<T, TryRecvError>
+  where
+    T: Clone,
+  {
+    let head = self.head();
+    if *next < head {
+      let skipped = head - *next;
+      *next = head;
+      return Err(TryRecvError::Lagged(skipped));
+    }
+
+    match self.buffer.get((*next - head) as usize) {
+      Some(value) => {
+        *next += 1;
+        Ok(value.clone())
+      }
+      None if self.closed => Err(TryRecvError::Closed),
+      None => Err(TryRecvError::Empty),
+    }
+  }
+
+  fn close(&mut self) -> Vec<Waker> {
+    self.closed = true;
+    self.waiters.drain().map(|(_, waker)| waker).collect()
+  }
+}
+
+/// Returned by [`Sender::send`] when the value can't reach any receiver.
+#[derive(Error, Debug, PartialEq, Eq)]
+#[error("broadcast channel has no receivers or is closed")]
+pub struct SendError<T>(pub T);
+
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecvError {
+  /// The receiver fell behind and the given number of values were skipped.
+  #[error("receiver lagged behind by {0} values")]
+  Lagged(u64),
+  /// Every sender is gone and the buffer has been drained.
+  #[error("broadcast channel closed")]
+  Closed,
+}
+
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TryRecvError {
+  #[error("broadcast channel is empty")]
+  Empty,
+  #[error("receiver lagged behind by {0} values")]
+  Lagged(u64),
+  #[error("broadcast channel closed")]
+  Closed,
+}
+
+pub struct Sender<T> {
+  shared: Arc<Shared<T>>,
+}
+
+impl<T: Clone> Sender<T> {
+  /// Sends `value` to every receiver, and returns how many there are.
+  ///
+  /// The oldest value is dropped if the buffer is full.
+  pub fn send(&self, value: T) -> Result<usize, SendError<T>> {
+    let mut state = self.shared.state.lock().unwrap();
+    if state.closed || state.receivers == 0 {
+      return Err(SendError(value));
+    }
+
+    if state.buffer.len() == state.capacity {
+      state.buffer.pop_front();
+    }
+    state.buffer.push_back(value);
+    state.tail += 1;
+
+    let receivers = state.receivers;
+    let wakers: Vec<Waker> =
+      state.waiters.drain().map(|(_, waker)| waker).collect();
+    drop(state);
+
+    wakers.into_iter().for_each(Waker::wake);
+    Ok(receivers)
+  }
+
+  /// Creates a receiver which gets every value sent after this call.
+  pub fn subscribe(&self) -> Receiver<T> {
+    let mut state = self.shared.state.lock().unwrap();
+    state.receivers += 1;
+    let next = state.tail;
+    drop(state);
+
+    Receiver { shared: self.shared.clone(), next }
+  }
+
+  /// Closes the channel for every sender. Receivers drain the buffered values and then get
+  /// [`RecvError::Closed`].
+  pub fn close(&self) {
+    let wakers = self.shared.state.lock().unwrap().close();
+    wakers.into_iter().for_each(Waker::wake);
+  }
+
+  /// Returns `true` if the channel has been closed.
+  pub fn is_closed(&self) -> bool {
+    self.shared.state.lock().unwrap().closed
+  }
+
+  /// Number of receivers alive.
+  pub fn receiver_count(&self) -> usize {
+    self.shared.state.lock().unwrap().receivers
+  }
+}
+
+impl<T> Clone for Sender<T> {
+  fn clone(&self) -> Self {
+    self.shared.state.lock().unwrap().senders += 1;
+    Sender { shared: self.shared.clone() }
+  }
+}
+
+impl<T> Drop for Sender<T> {
+  fn drop(&mut self) {
+    let mut state = self.shared.state.lock().unwrap();
+    state.senders -= 1;
+    if state.senders == 0 {
+      let wakers = state.close();
+      drop(state);
+      wakers.into_iter().for_each(Waker::wake);
+    }
+  }
+}
+
+pub struct Receiver<T> {
+  shared: Arc<Shared<T>>,
+  // Position of the next value this receiver gets.
+  next: u64,
+}
+
+impl<T: Clone> Receiver<T> {
+  /// Receives the next value, waiting until one is sent.
+  pub fn recv(&mut self) -> Recv<'_, T> {
+    Recv { receiver: self, slot: None }
+  }
+
+  pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+    let state = self.shared.state.lock().unwrap();
+    state.recv_at(&mut self.next)
+  }
+}
+
+impl<T> Clone for Receiver<T> {
+  /// The clone starts at the same position as this receiver.
+  fn clone(&self) -> Self {
+    self.shared.state.lock().unwrap().receivers += 1;
+    Receiver { shared: self.shared.clone(), next: self.next }
+  }
+}
+
+impl<T> Drop for Receiver<T> {
+  fn drop(&mut self) {
+    self.shared.state.lock().unwrap().receivers -= 1;
+  }
+}
+
+/// Future returned by [`Receiver::recv`].
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Recv<'a, T> {
+  receiver: &'a mut Receiver<T>,
+  // Waiter id, once registered.
+  slot: Option<usize>,
+}
+
+impl<T: Clone> Future for Recv<'_, T> {
+  type Output = Result<T, RecvError>;
+
+  fn poll(
+    mut self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<Self::Output> {
+    let this = &mut *self;
+    let mut state = this.receiver.shared.state.lock().unwrap();
+
+    match state.recv_at(&mut this.receiver.next) {
+      Ok(value) => Poll::Ready(Ok(value)),
+      Err(TryRecvError::Lagged(skipped)) => {
+        Poll::Ready(Err(RecvError::Lagged(skipped)))
+      }
+      Err(TryRecvError::Closed) => Poll::Ready(Err(RecvError::Closed)),
+      Err(TryRecvError::Empty) => {
+        let id = *this.slot.get_or_insert_with(|| {
+          state.next_waiter += 1;
+          state.next_waiter
+        });
+        state.waiters.insert(id, cx.waker().clone());
+        Poll::Pending
+      }
+    }
+  }
+}
+
+impl<T> Drop for Recv<'_, T> {
+  fn drop(&mut self) {
+    if let Some(id) = self.slot {
+      self.receiver.shared.state.lock().unwrap().waiters.remove(&id);
+    }
+  }
+}
+
+#[test]
+fn drains_then_closed() {
+  let (sender, mut first) = channel(4);
+  let mut second = sender.subscribe();
+
+  for n in 0..3 {
+    assert_eq!(sender.send(n), Ok(2));
+  }
+  drop(sender);
+
+  for receiver in [&mut first, &mut second] {
+    for n in 0..3 {
+      assert_eq!(receiver.try_recv(), Ok(n));
+    }
+    assert_eq!(receiver.try_recv(), Err(TryRecvError::Closed));
+  }
+}
+
+#[test]
+fn lagged_is_not_closed() {
+  let (sender, mut receiver) = channel(2);
+  for n in 0..5 {
+    sender.send(n).unwrap();
+  }
+  sender.close();
+  assert!(sender.send(5).is_err());
+
+  assert_eq!(receiver.try_recv(), Err(TryRecvError::Lagged(3)));
+  assert_eq!(receiver.try_recv(), Ok(3));
+  assert_eq!(receiver.try_recv(), Ok(4));
+  assert_eq!(receiver.try_recv(), Err(TryRecvError::Closed));
+}
+
+#[crate::internal_test]
+async fn pending_recv_sees_close() {
+  let (sender, mut receiver) = channel::<u8>(1);
+
+  let thread = std::thread::spawn(move || {
+    std::thread::sleep(std::time::Duration::from_millis(10));
+    sender.send(1).unwrap();
+    drop(sender);
+  });
+
+  assert_eq!(receiver.recv().await, Ok(1));
+  assert_eq!(receiver.recv().await, Err(RecvError::Closed));
+  thread.join().unwrap();
+}
@@ -0,0 +1,189 @@
+use std::{
+  collections::HashMap,
+  future::Future,
+  pin::Pin,
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex as StdMutex, Weak,
+  },
+  task::{Context, Poll, Waker},
+};
+
+/// A token which can be used to signal cancellation to everyone holding a clone of it.
+///
+/// Cancelling a token also cancels all tokens created with [`CancellationToken::child_token`],
+/// but cancelling a child doesn't affect the parent.
+#[derive(Clone, Default)]
+pub struct CancellationToken {
+  inner: Arc<Inner>,
+}
+
+impl std::fmt::Debug for CancellationToken {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("CancellationToken")
+      .field("is_cancelled", &self.is_cancelled())
+      .finish()
+  }
+}
+
+#[derive(Default)]
+struct Inner {
+  cancelled: AtomicBool,
+  // This is not a bottleneck
+  waiters: StdMutex<Waiters>,
+  children: StdMutex<Vec<Weak<Inner>>>,
+}
+
+// Every `Cancelled` future gets its own slot, which it removes when dropped.
+#[derive(Default)]
+struct Waiters {
+  next_id: usize,
+  wakers: HashMap<usize, Waker>,
+}
+
+impl Inner {
+  fn cancel(&self) {
+    if self.cancelled.swap(true, Ordering::AcqRel) {
+      return;
+    }
+
+    let wakers = std::mem::take(&mut self.waiters.lock().unwrap().wakers);
+    for waker in wakers.into_values() {
+      waker.wake();
+    }
+
+    for child in self.children.lock().unwrap().drain(..) {
+      if let Some(child) = child.upgrade() {
+        child.cancel();
+      }
+    }
+  }
+}
+
+impl CancellationToken {
+  /// Creates a token which isn't cancelled.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Creates a token which is cancelled when this one is.
+  pub fn child_token(&self) -> CancellationToken {
+    let child = CancellationToken::new();
+
+    let mut children = self.inner.children.lock().unwrap();
+    // Checked with the lock held so a concurrent cancel can't miss this child.
+    if self.is_cancelled() {
+      child.inner.cancelled.store(true, Ordering::Release);
+    } else {
+      children.retain(|child| child.strong_count() > 0);
+      children.push(Arc::downgrade(&child.inner));
+    }
+
+    child
+  }
+
+  /// Cancels this token and all of its children.
+  pub fn cancel(&self) {
+    self.inner.cancel();
+  }
+
+  /// Returns `true` if the token has been cancelled.
+  pub fn is_cancelled(&self) -> bool {
+    self.inner.cancelled.load(Ordering::Acquire)
+  }
+
+  /// Completes when the token is cancelled.
+  pub fn cancelled(&self) -> Cancelled<'_> {
+    Cancelled { token: self, slot: None }
+  }
+
+  fn poll_cancelled(
+    &self,
+    slot: &mut Option<usize>,
+    cx: &mut Context<'_>,
+  ) -> Poll<()> {
+    if self.is_cancelled() {
+      return Poll::Ready(());
+    }
+
+    let mut waiters = self.inner.waiters.lock().unwrap();
+    // Cancel could have happened before the lock was taken.
+    if self.is_cancelled() {
+      return Poll::Ready(());
+    }
+
+    let id = *slot.get_or_insert_with(|| {
+      waiters.next_id += 1;
+      waiters.next_id
+    });
+    match waiters.wakers.get_mut(&id) {
+      Some(waker) if waker.will_wake(cx.waker()) => {}
+      Some(waker) => *waker = cx.waker().clone(),
+      None => {
+        waiters.wakers.insert(id, cx.waker().clone());
+      }
+    }
+
+    Poll::Pending
+  }
+
+  fn remove_waiter(&self, id: usize) {
+    self.inner.waiters.lock().unwrap().wakers.remove(&id);
+  }
+}
+
+/// Future returned by [`CancellationToken::cancelled`].
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Cancelled<'a> {
+  token: &'a CancellationToken,
+  slot: Option<usize>,
+}
+
+impl Future for Cancelled<'_> {
+  type Output = ();
+
+  fn poll(
+    mut self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<Self::Output> {
+    let this = &mut *self;
+    this.token.poll_cancelled(&mut this.slot, cx)
+  }
+}
+
+impl Drop for Cancelled<'_> {
+  fn drop(&mut self) {
+    if let Some(id) = self.slot {
+      self.token.remove_waiter(id);
+    }
+  }
+}
+
+#[test]
+fn child_cancelled_by_parent() {
+  let parent = CancellationToken::new();
+  let child = parent.child_token();
+  let grandchild = child.child_token();
+
+  grandchild.cancel();
+  assert!(!child.is_cancelled());
+
+  parent.cancel();
+  assert!(child.is_cancelled());
+  assert!(parent.child_token().is_cancelled());
+}
+
+#[test]
+fn dropped_waiters_are_removed() {
+  let waker = std::task::Waker::noop();
+  let mut cx = Context::from_waker(waker);
+  let token = CancellationToken::new();
+
+  for _ in 0..100 {
+    let mut cancelled = std::pin::pin!(token.cancelled());
+    assert!(cancelled.as_mut().poll(&mut cx).is_pending());
+    assert!(cancelled.as_mut().poll(&mut cx).is_pending());
+  }
+
+  assert!(token.inner.waiters.lock().unwrap().wakers.is_empty());
+}
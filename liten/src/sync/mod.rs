@@ -1,6 +1,13 @@
+pub mod bridge;
+pub mod broadcast;
+mod cancellation;
+pub use cancellation::*;
 pub mod mpsc;
 mod mutex;
+mod once_cell;
 mod semaphore;
 pub use mutex::*;
+pub use once_cell::OnceCell;
 pub use semaphore::*;
 pub mod oneshot;
+mod state;
@@ -1,17 +1,27 @@
 use std::{
   collections::VecDeque,
   future::Future,
-  sync::{
-    atomic::{AtomicU16, Ordering},
-    Arc, RwLock,
-  },
+  num::NonZero,
   task::{Poll, Waker},
 };
 
-use crossbeam_utils::atomic::AtomicCell;
 use futures_core::{FusedFuture, Stream};
 
-use super::Mutex;
+use super::state::AtomicState;
+use crate::loom::sync::{
+  atomic::{AtomicU16, AtomicUsize, Ordering},
+  Arc, Mutex as StdMutex, RwLock,
+};
+
+mod priority;
+pub use priority::{
+  priority_channel, PriorityReceiver, PriorityRecv, PrioritySend,
+  PrioritySender,
+};
+mod sized;
+pub use sized::{
+  sized_channel, SizedReceiver, SizedRecv, SizedSend, SizedSender,
+};
 
 pub fn unbounded<T>() -> (Sender<T>, Receiver<T>) {
   let channel = Arc::new(UnboundedChannel::default());
@@ -23,6 +33,34 @@ pub fn unbounded_with_capacity<T>(num: usize) -> (Sender<T>, Receiver<T>) {
   (Sender::from(channel.clone()), Receiver::from(channel.clone()))
 }
 
+/// Creates a channel which holds at most `capacity` values.
+///
+/// Sending to a full channel drops the oldest value instead of waiting, so a receiver which falls
+/// behind can't hold up the senders, it only misses values.
+pub fn lossy<T>(capacity: NonZero<usize>) -> (Sender<T>, Receiver<T>) {
+  let channel = Arc::new(UnboundedChannel {
+    bound: Some(capacity.get()),
+    ..UnboundedChannel::with_capacity(capacity.get())
+  });
+  (Sender::from(channel.clone()), Receiver::from(channel.clone()))
+}
+
+/// Creates a channel whose senders wait while it holds `capacity` values, see [`BoundedSender`].
+///
+/// A capacity of 0 makes a rendezvous channel: a send only completes once the receiver has taken
+/// its value, so the sender knows the receiver has got that far. This is a buffered send with an
+/// acknowledgement rather than a direct handoff: the value waits in a one-value slot meanwhile,
+/// where [`Receiver::try_recv`] can take it without a `recv` waiting, and the other sends wait for
+/// it to be taken. A send dropped after its value is in the slot leaves the value to be received.
+pub fn channel<T>(capacity: usize) -> (BoundedSender<T>, Receiver<T>) {
+  let channel = Arc::new(UnboundedChannel {
+    capacity: Some(capacity),
+    ..UnboundedChannel::with_capacity(capacity.max(1))
+  });
+  let sender = BoundedSender { sender: Sender::from(channel.clone()) };
+  (sender, Receiver::from(channel))
+}
+
 bitflags::bitflags! {
   #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
   struct ChannelState: u8 {
@@ -39,20 +77,36 @@ impl Default for ChannelState {
 }
 
 pub struct UnboundedChannel<T> {
-  // Will always be written to so RwLock doesn't make sence.
-  list: Mutex<VecDeque<T>>,
-  state: AtomicCell<ChannelState>,
+  // Will always be written to so RwLock doesn't make sence. Senders can be on any thread, so this
+  // has to block instead of failing like the async Mutex's try_lock.
+  list: StdMutex<VecDeque<T>>,
+  state: AtomicState<ChannelState>,
   num_senders: AtomicU16,
   waker: RwLock<Option<Waker>>,
+  // The oldest values are dropped past this many.
+  bound: Option<usize>,
+  // Senders wait past this many, see `channel`.
+  capacity: Option<usize>,
+  // Only for `capacity`. Changed with `list` locked, so a rendezvous send can tell when its value
+  // has been taken.
+  pushed: AtomicUsize,
+  taken: AtomicUsize,
+  // This is not a bottleneck
+  senders_waiting: StdMutex<Vec<Waker>>,
 }
 
 impl<T> Default for UnboundedChannel<T> {
   fn default() -> Self {
     Self {
-      list: Mutex::new(VecDeque::with_capacity(512)),
-      state: AtomicCell::new(ChannelState::INITIALISED),
+      list: StdMutex::new(VecDeque::with_capacity(512)),
+      state: AtomicState::new(ChannelState::INITIALISED),
       num_senders: AtomicU16::new(0),
       waker: RwLock::new(None),
+      bound: None,
+      capacity: None,
+      pushed: AtomicUsize::new(0),
+      taken: AtomicUsize::new(0),
+      senders_waiting: StdMutex::new(Vec::new()),
     }
   }
 }
@@ -60,7 +114,7 @@ impl<T> Default for UnboundedChannel<T> {
 impl<T> UnboundedChannel<T> {
   fn with_capacity(capacity: usize) -> Self {
     Self {
-      list: Mutex::new(VecDeque::with_capacity(capacity)),
+      list: StdMutex::new(VecDeque::with_capacity(capacity)),
       ..Default::default()
     }
   }
@@ -92,6 +146,17 @@ impl<T> Drop for Receiver<T> {
         Some(old)
       })
       .expect("whaat");
+    self.channel.wake_senders();
+  }
+}
+
+impl<T> UnboundedChannel<T> {
+  fn wake_senders(&self) {
+    if self.capacity.is_some() {
+      for waker in self.senders_waiting.lock().unwrap().drain(..) {
+        waker.wake();
+      }
+    }
   }
 }
 
@@ -127,8 +192,16 @@ impl<T> Future for ReceiverFuture<'_, T> {
         RecvError::Empty => {
           let mut lock = self.0.channel.waker.write().unwrap();
           *lock = Some(cx.waker().clone());
-
-          Poll::Pending
+          drop(lock);
+
+          // A send between the first try and the registration wouldn't have seen the waker.
+          match self.0.try_recv() {
+            Ok(value) => Poll::Ready(Ok(value)),
+            Err(RecvError::Disconnected) => {
+              Poll::Ready(Err(RecvError::Disconnected))
+            }
+            Err(RecvError::Empty) => Poll::Pending,
+          }
         }
       },
     }
@@ -146,14 +219,23 @@ impl<T> Receiver<T> {
     ReceiverFuture(self).await
   }
 
+  /// Takes the next value. Values sent before the last sender was dropped are still received,
+  /// [`RecvError::Disconnected`] is only returned once they're all taken.
   pub fn try_recv(&self) -> Result<T, RecvError> {
-    if self.channel.num_senders.load(Ordering::Relaxed) == 0 {
-      return Err(RecvError::Disconnected);
-    }
+    // Loaded before popping, so a sender which sends and then drops in between can't be missed.
+    let disconnected = self.channel.num_senders.load(Ordering::Acquire) == 0;
 
-    let mut lock = self.channel.list.try_lock().unwrap();
+    let mut lock = self.channel.list.lock().unwrap();
     match lock.pop_front() {
-      Some(t) => Ok(t),
+      Some(t) => {
+        if self.channel.capacity.is_some() {
+          self.channel.taken.fetch_add(1, Ordering::SeqCst);
+          drop(lock);
+          self.channel.wake_senders();
+        }
+        Ok(t)
+      }
+      None if disconnected => Err(RecvError::Disconnected),
       None => Err(RecvError::Empty),
     }
   }
@@ -189,17 +271,21 @@ impl<T> From<Arc<UnboundedChannel<T>>> for Sender<T> {
   }
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct ReceiverDroppedError;
 
 impl<T> Sender<T> {
   pub fn send(&self, t: T) -> Result<(), ReceiverDroppedError> {
-    if self.channel.num_senders.load(Ordering::Relaxed) == 0 {
+    if self.channel.state.load().contains(ChannelState::RECEIVER_DROPPED) {
       return Err(ReceiverDroppedError);
     }
 
-    let mut lock = self.channel.list.try_lock().unwrap();
+    let mut lock = self.channel.list.lock().unwrap();
+    if self.channel.bound.is_some_and(|bound| lock.len() >= bound) {
+      lock.pop_front();
+    }
     lock.push_back(t);
+    drop(lock);
 
     let lock = self.channel.waker.read().unwrap();
 
@@ -211,6 +297,137 @@ impl<T> Sender<T> {
   }
 }
 
+/// Sends into a [`channel`], waiting while it's full. It can be cloned for more senders.
+pub struct BoundedSender<T> {
+  sender: Sender<T>,
+}
+
+impl<T> BoundedSender<T> {
+  /// Waits for room in the channel and sends `value`. In a rendezvous channel, it also waits for
+  /// the receiver to take `value`.
+  ///
+  /// A send dropped while it waits for room sends nothing, one dropped while it waits for the
+  /// receiver leaves the value in the channel to be received.
+  pub fn send(&self, value: T) -> SendFuture<'_, T> {
+    SendFuture { sender: self, value: Some(value), ticket: None }
+  }
+}
+
+impl<T> Clone for BoundedSender<T> {
+  fn clone(&self) -> Self {
+    BoundedSender { sender: self.sender.clone() }
+  }
+}
+
+/// Future returned by [`BoundedSender::send`].
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct SendFuture<'a, T> {
+  sender: &'a BoundedSender<T>,
+  // Taken once it's in the channel.
+  value: Option<T>,
+  // For a rendezvous, which value it was.
+  ticket: Option<usize>,
+}
+
+// The value is never pinned.
+impl<T> Unpin for SendFuture<'_, T> {}
+
+impl<T> Future for SendFuture<'_, T> {
+  type Output = Result<(), ReceiverDroppedError>;
+
+  fn poll(
+    mut self: std::pin::Pin<&mut Self>,
+    cx: &mut std::task::Context<'_>,
+  ) -> Poll<Self::Output> {
+    let channel = self.sender.sender.channel.clone();
+    if channel.state.load().contains(ChannelState::RECEIVER_DROPPED) {
+      // The receiver may have taken the value before it was dropped.
+      if let Some(ticket) = self.ticket.take() {
+        if channel.taken.load(Ordering::SeqCst) > ticket {
+          return Poll::Ready(Ok(()));
+        }
+      }
+      return Poll::Ready(Err(ReceiverDroppedError));
+    }
+    let capacity = channel.capacity.expect("made by `channel`");
+
+    // Senders are registered with the list locked, so a receive can't take a value between the
+    // check and the registration without waking them.
+    let mut list = channel.list.lock().unwrap();
+    if let Some(value) = self.value.take() {
+      if list.len() >= capacity.max(1) {
+        self.value = Some(value);
+        channel.senders_waiting.lock().unwrap().push(cx.waker().clone());
+        return Poll::Pending;
+      }
+      list.push_back(value);
+      let ticket = channel.pushed.fetch_add(1, Ordering::SeqCst);
+      drop(list);
+      if let Some(waker) = channel.waker.read().unwrap().as_ref() {
+        waker.wake_by_ref();
+      }
+      if capacity > 0 {
+        return Poll::Ready(Ok(()));
+      }
+      self.ticket = Some(ticket);
+      list = channel.list.lock().unwrap();
+    }
+
+    let ticket = self.ticket.expect("SendFuture polled after completion");
+    if channel.taken.load(Ordering::SeqCst) > ticket {
+      self.ticket = None;
+      return Poll::Ready(Ok(()));
+    }
+    channel.senders_waiting.lock().unwrap().push(cx.waker().clone());
+    drop(list);
+    Poll::Pending
+  }
+}
+
+impl<T> Sender<T> {
+  /// Makes a [`WeakSender`] for the channel, which doesn't keep it open.
+  pub fn downgrade(&self) -> WeakSender<T> {
+    WeakSender { channel: self.channel.clone() }
+  }
+}
+
+/// A sender which doesn't keep the channel open, made by [`Sender::downgrade`].
+///
+/// The channel closes once every [`Sender`] is dropped, whatever weak senders are left, so a
+/// registry can hold on to senders of actors without keeping their receivers waiting forever. It
+/// does keep the channel's allocation.
+pub struct WeakSender<T> {
+  channel: Arc<UnboundedChannel<T>>,
+}
+
+impl<T> WeakSender<T> {
+  /// Returns a [`Sender`], or `None` once every sender has been dropped and the channel is
+  /// closed.
+  pub fn upgrade(&self) -> Option<Sender<T>> {
+    let senders = &self.channel.num_senders;
+    let mut current = senders.load(Ordering::Acquire);
+    // Only from one sender up, a closed channel stays closed.
+    while current > 0 {
+      match senders.compare_exchange(
+        current,
+        current + 1,
+        Ordering::AcqRel,
+        Ordering::Acquire,
+      ) {
+        Ok(_) => return Some(Sender { channel: self.channel.clone() }),
+        Err(actual) => current = actual,
+      }
+    }
+    None
+  }
+}
+
+impl<T> Clone for WeakSender<T> {
+  fn clone(&self) -> Self {
+    WeakSender { channel: self.channel.clone() }
+  }
+}
+
 impl<T> Clone for Sender<T> {
   fn clone(&self) -> Self {
     self.channel.num_senders.fetch_add(1, Ordering::Relaxed);
@@ -220,7 +437,12 @@ impl<T> Clone for Sender<T> {
 
 impl<T> Drop for Sender<T> {
   fn drop(&mut self) {
-    self.channel.num_senders.fetch_sub(1, Ordering::Relaxed);
+    if self.channel.num_senders.fetch_sub(1, Ordering::AcqRel) == 1 {
+      // A pending receiver has to see the disconnect.
+      if let Some(waker) = self.channel.waker.read().unwrap().as_ref() {
+        waker.wake_by_ref();
+      }
+    }
   }
 }
 
@@ -247,3 +469,217 @@ fn sender_testing() {
   assert!(receiver.try_recv().unwrap() == 6);
   assert!(receiver.try_recv() == Err(RecvError::Empty));
 }
+
+#[test]
+fn lossy_drops_oldest() {
+  let (sender, receiver) = lossy(2.try_into().unwrap());
+  for value in 0..5 {
+    sender.send(value).unwrap();
+  }
+
+  assert_eq!(receiver.try_iter().collect::<Vec<_>>(), [3, 4]);
+}
+
+#[test]
+fn drains_before_disconnected() {
+  let (sender, receiver) = unbounded();
+  sender.send(1).unwrap();
+  sender.send(2).unwrap();
+  drop(sender);
+
+  assert_eq!(receiver.try_recv(), Ok(1));
+  assert_eq!(receiver.try_recv(), Ok(2));
+  assert_eq!(receiver.try_recv(), Err(RecvError::Disconnected));
+}
+
+#[test]
+fn send_racing_poll() {
+  use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    task::{Context, Wake},
+    thread,
+  };
+
+  #[derive(Default)]
+  struct Flag(AtomicBool);
+  impl Wake for Flag {
+    fn wake(self: std::sync::Arc<Self>) {
+      self.0.store(true, Ordering::SeqCst);
+    }
+  }
+
+  // A send landing between the empty check and the waker registration used to be lost.
+  for _ in 0..20_000 {
+    let (sender, receiver) = unbounded();
+    let thread = thread::spawn(move || sender.send(1).unwrap());
+
+    loop {
+      let flag = std::sync::Arc::new(Flag::default());
+      let waker = flag.clone().into();
+      let poll =
+        std::pin::pin!(receiver.recv()).poll(&mut Context::from_waker(&waker));
+
+      match poll {
+        Poll::Ready(value) => break assert_eq!(value, Ok(1)),
+        // The sender is done, so it must have woken the waker of this poll.
+        Poll::Pending if thread.is_finished() => {
+          assert!(flag.0.load(Ordering::SeqCst), "lost wakeup")
+        }
+        Poll::Pending => thread::yield_now(),
+      }
+    }
+    thread.join().unwrap();
+  }
+}
+
+#[test]
+fn rendezvous_waits_for_recv() {
+  use crate::test_util::task;
+
+  let (sender, receiver) = channel(0);
+  let mut first = task::spawn(sender.send(1));
+  let mut second = task::spawn(sender.send(2));
+  assert!(first.poll().is_pending());
+  assert!(second.poll().is_pending());
+
+  // The value is in the channel, but the send waits until it's received.
+  assert!(!first.is_woken());
+  assert!(first.poll().is_pending());
+  assert_eq!(receiver.try_recv(), Ok(1));
+  assert!(first.is_woken());
+  assert_eq!(first.poll(), Poll::Ready(Ok(())));
+
+  // Now there's room for the second.
+  assert!(second.is_woken());
+  assert!(second.poll().is_pending());
+  assert_eq!(receiver.try_recv(), Ok(2));
+  assert_eq!(second.poll(), Poll::Ready(Ok(())));
+
+  drop(receiver);
+  let mut late = task::spawn(sender.send(3));
+  assert_eq!(late.poll(), Poll::Ready(Err(ReceiverDroppedError)));
+}
+
+#[test]
+fn rendezvous_received_then_dropped() {
+  use crate::test_util::task;
+
+  let (sender, receiver) = channel(0);
+  let mut send = task::spawn(sender.send(1));
+  assert!(send.poll().is_pending());
+  assert_eq!(receiver.try_recv(), Ok(1));
+  drop(receiver);
+  // The value was delivered, so the send succeeds.
+  assert_eq!(send.poll(), Poll::Ready(Ok(())));
+}
+
+#[crate::internal_test]
+async fn bounded_between_tasks() {
+  let (sender, receiver) = channel(2);
+  let producer = crate::task::spawn(async move {
+    for value in 0..10 {
+      sender.send(value).await.unwrap();
+    }
+  });
+  let mut received = Vec::new();
+  while let Ok(value) = receiver.recv().await {
+    received.push(value);
+  }
+  assert_eq!(received, (0..10).collect::<Vec<_>>());
+  producer.await.unwrap();
+}
+
+#[crate::internal_test]
+async fn last_sender_drop_wakes() {
+  let (sender, receiver) = unbounded::<u8>();
+  let thread = std::thread::spawn(move || {
+    std::thread::sleep(std::time::Duration::from_millis(10));
+    drop(sender);
+  });
+
+  assert_eq!(receiver.recv().await, Err(RecvError::Disconnected));
+  thread.join().unwrap();
+}
+
+#[crate::internal_test]
+async fn weak_sender_upgrade() {
+  let (sender, receiver) = unbounded();
+  let weak = sender.downgrade();
+  weak.upgrade().unwrap().send(1).unwrap();
+
+  drop(sender);
+  // Closed, though a weak sender is left.
+  assert!(weak.upgrade().is_none());
+  assert_eq!(receiver.recv().await, Ok(1));
+  assert_eq!(receiver.recv().await, Err(RecvError::Disconnected));
+}
+
+#[cfg(loom)]
+#[test]
+fn loom_upgrade_racing_drop() {
+  loom::model(|| {
+    let (sender, receiver) = unbounded();
+    let weak = sender.downgrade();
+    let thread = loom::thread::spawn(move || drop(sender));
+
+    // Whichever wins, a value sent by an upgraded sender is received before the disconnect.
+    if let Some(sender) = weak.upgrade() {
+      sender.send(1).unwrap();
+      drop(sender);
+      assert_eq!(loom::future::block_on(receiver.recv()), Ok(1));
+    }
+    thread.join().unwrap();
+    assert!(weak.upgrade().is_none());
+    assert_eq!(
+      loom::future::block_on(receiver.recv()),
+      Err(RecvError::Disconnected)
+    );
+  });
+}
+
+#[cfg(loom)]
+#[test]
+fn loom_two_senders() {
+  loom::model(|| {
+    let (sender, receiver) = unbounded();
+    let other = sender.clone();
+    let thread = loom::thread::spawn(move || other.send(1).unwrap());
+
+    let first = loom::future::block_on(receiver.recv()).unwrap();
+    sender.send(2).unwrap();
+    let second = loom::future::block_on(receiver.recv()).unwrap();
+    assert_eq!(first + second, 3);
+    thread.join().unwrap();
+  });
+}
+
+#[cfg(loom)]
+#[test]
+fn loom_close_after_send() {
+  loom::model(|| {
+    let (sender, receiver) = unbounded();
+    let thread = loom::thread::spawn(move || {
+      sender.send(1).unwrap();
+      drop(sender);
+    });
+
+    assert_eq!(loom::future::block_on(receiver.recv()), Ok(1));
+    assert_eq!(
+      loom::future::block_on(receiver.recv()),
+      Err(RecvError::Disconnected)
+    );
+    thread.join().unwrap();
+  });
+}
+
+#[cfg(loom)]
+#[test]
+fn loom_receiver_dropped() {
+  loom::model(|| {
+    let (sender, receiver) = unbounded();
+    let thread = loom::thread::spawn(move || drop(receiver));
+    // Either outcome is fine, as long as nothing leaks or panics.
+    let _ = sender.send(Arc::new(1));
+    thread.join().unwrap();
+  });
+}
@@ -0,0 +1,259 @@
+use std::{
+  cmp::Ordering,
+  collections::BinaryHeap,
+  future::Future,
+  pin::Pin,
+  task::{Context, Poll, Waker},
+};
+
+use super::{ReceiverDroppedError, RecvError};
+use crate::loom::sync::{Arc, Mutex as StdMutex};
+
+/// Creates a channel which delivers the values with the highest priority first, and the values of
+/// one priority in the order they were sent. Senders wait while it holds `capacity` values.
+///
+/// Meant for mailboxes where a message like "stop" has to get ahead of the work already queued.
+///
+/// # Panics
+///
+/// Panics if `capacity` is 0.
+pub fn priority_channel<T>(
+  capacity: usize,
+) -> (PrioritySender<T>, PriorityReceiver<T>) {
+  assert!(capacity > 0, "priority_channel capacity can't be 0");
+  let channel = Arc::new(Channel {
+    state: StdMutex::new(State {
+      queue: BinaryHeap::with_capacity(capacity),
+      sent: 0,
+      senders: 1,
+      receiver_dropped: false,
+      receiver_waker: None,
+      senders_waiting: Vec::new(),
+    }),
+    capacity,
+  });
+  (PrioritySender { channel: channel.clone() }, PriorityReceiver { channel })
+}
+
+struct Channel<T> {
+  // This is not a bottleneck
+  state: StdMutex<State<T>>,
+  capacity: usize,
+}
+
+struct State<T> {
+  queue: BinaryHeap<Entry<T>>,
+  // How many values have been sent, which orders the values of one priority.
+  sent: u64,
+  senders: usize,
+  receiver_dropped: bool,
+  receiver_waker: Option<Waker>,
+  senders_waiting: Vec<Waker>,
+}
+
+struct Entry<T> {
+  priority: u8,
+  sent: u64,
+  value: T,
+}
+
+// The greatest entry is the one to receive next: the highest priority, sent first.
+impl<T> Ord for Entry<T> {
+  fn cmp(&self, other: &Self) -> Ordering {
+    self.priority.cmp(&other.priority).then(other.sent.cmp(&self.sent))
+  }
+}
+
+impl<T> PartialOrd for Entry<T> {
+  fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+impl<T> PartialEq for Entry<T> {
+  fn eq(&self, other: &Self) -> bool {
+    self.cmp(other) == Ordering::Equal
+  }
+}
+
+impl<T> Eq for Entry<T> {}
+
+/// Sends into a [`priority_channel`]. It can be cloned for more senders.
+pub struct PrioritySender<T> {
+  channel: Arc<Channel<T>>,
+}
+
+impl<T> PrioritySender<T> {
+  /// Waits for room in the channel and sends `value`. Values with a higher `priority` are
+  /// received first.
+  ///
+  /// A send dropped while it waits sends nothing.
+  pub fn send(&self, priority: u8, value: T) -> PrioritySend<'_, T> {
+    PrioritySend { sender: self, value: Some((priority, value)) }
+  }
+}
+
+impl<T> Clone for PrioritySender<T> {
+  fn clone(&self) -> Self {
+    self.channel.state.lock().unwrap().senders += 1;
+    PrioritySender { channel: self.channel.clone() }
+  }
+}
+
+impl<T> Drop for PrioritySender<T> {
+  fn drop(&mut self) {
+    let mut state = self.channel.state.lock().unwrap();
+    state.senders -= 1;
+    // A waiting receiver has to see the disconnect.
+    if state.senders == 0 {
+      if let Some(waker) = state.receiver_waker.take() {
+        waker.wake();
+      }
+    }
+  }
+}
+
+/// Future returned by [`PrioritySender::send`].
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct PrioritySend<'a, T> {
+  sender: &'a PrioritySender<T>,
+  // Taken once it's in the channel.
+  value: Option<(u8, T)>,
+}
+
+// The value is never pinned.
+impl<T> Unpin for PrioritySend<'_, T> {}
+
+impl<T> Future for PrioritySend<'_, T> {
+  type Output = Result<(), ReceiverDroppedError>;
+
+  fn poll(
+    mut self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<Self::Output> {
+    let channel = self.sender.channel.clone();
+    let mut state = channel.state.lock().unwrap();
+    if state.receiver_dropped {
+      return Poll::Ready(Err(ReceiverDroppedError));
+    }
+    if state.queue.len() >= channel.capacity {
+      state.senders_waiting.push(cx.waker().clone());
+      return Poll::Pending;
+    }
+
+    let (priority, value) =
+      self.value.take().expect("PrioritySend polled after completion");
+    let sent = state.sent;
+    state.sent += 1;
+    state.queue.push(Entry { priority, sent, value });
+    if let Some(waker) = state.receiver_waker.take() {
+      waker.wake();
+    }
+    Poll::Ready(Ok(()))
+  }
+}
+
+/// Receives from a [`priority_channel`].
+pub struct PriorityReceiver<T> {
+  channel: Arc<Channel<T>>,
+}
+
+impl<T> PriorityReceiver<T> {
+  /// Takes the value with the highest priority. Values sent before the last sender was dropped
+  /// are still received, [`RecvError::Disconnected`] is only returned once they're all taken.
+  pub fn try_recv(&self) -> Result<T, RecvError> {
+    self.take(None)
+  }
+
+  /// Waits for a value, see [`PriorityReceiver::try_recv`].
+  pub fn recv(&self) -> PriorityRecv<'_, T> {
+    PriorityRecv { receiver: self }
+  }
+
+  // Registers `waker` when there's nothing to take yet.
+  fn take(&self, waker: Option<&Waker>) -> Result<T, RecvError> {
+    let mut state = self.channel.state.lock().unwrap();
+    match state.queue.pop() {
+      Some(entry) => {
+        for waker in state.senders_waiting.drain(..) {
+          waker.wake();
+        }
+        Ok(entry.value)
+      }
+      None if state.senders == 0 => Err(RecvError::Disconnected),
+      None => {
+        if let Some(waker) = waker {
+          state.receiver_waker = Some(waker.clone());
+        }
+        Err(RecvError::Empty)
+      }
+    }
+  }
+}
+
+impl<T> Drop for PriorityReceiver<T> {
+  fn drop(&mut self) {
+    let mut state = self.channel.state.lock().unwrap();
+    state.receiver_dropped = true;
+    for waker in state.senders_waiting.drain(..) {
+      waker.wake();
+    }
+  }
+}
+
+/// Future returned by [`PriorityReceiver::recv`].
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct PriorityRecv<'a, T> {
+  receiver: &'a PriorityReceiver<T>,
+}
+
+impl<T> Future for PriorityRecv<'_, T> {
+  type Output = Result<T, RecvError>;
+
+  fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+    match self.receiver.take(Some(cx.waker())) {
+      Err(RecvError::Empty) => Poll::Pending,
+      result => Poll::Ready(result),
+    }
+  }
+}
+
+#[crate::internal_test]
+async fn high_priority_first() {
+  const CONTROL: u8 = 1;
+  const BULK: u8 = 0;
+
+  let (sender, receiver) = priority_channel(8);
+  for (priority, value) in
+    [(BULK, "work 1"), (BULK, "work 2"), (CONTROL, "stop"), (BULK, "work 3")]
+  {
+    sender.send(priority, value).await.unwrap();
+  }
+  sender.send(CONTROL, "flush").await.unwrap();
+  drop(sender);
+
+  let mut received = vec![];
+  while let Ok(value) = receiver.recv().await {
+    received.push(value);
+  }
+  assert_eq!(received, ["stop", "flush", "work 1", "work 2", "work 3"]);
+}
+
+#[crate::internal_test]
+async fn full_channel_waits() {
+  use crate::test_util::task;
+
+  let (sender, receiver) = priority_channel(1);
+  sender.send(0, 1).await.unwrap();
+  let mut send = task::spawn(sender.send(5, 2));
+  assert!(send.poll().is_pending());
+
+  assert_eq!(receiver.try_recv(), Ok(1));
+  assert!(send.is_woken());
+  assert_eq!(send.poll(), Poll::Ready(Ok(())));
+  assert_eq!(receiver.try_recv(), Ok(2));
+  assert_eq!(receiver.try_recv(), Err(RecvError::Empty));
+
+  drop(receiver);
+  assert_eq!(sender.send(0, 3).await, Err(ReceiverDroppedError));
+}
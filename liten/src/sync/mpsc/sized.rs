@@ -0,0 +1,249 @@
+use std::{
+  collections::VecDeque,
+  future::Future,
+  pin::Pin,
+  task::{Context, Poll, Waker},
+};
+
+use super::{ReceiverDroppedError, RecvError};
+use crate::loom::sync::{Arc, Mutex as StdMutex};
+
+/// Creates a channel bounded by the bytes it holds rather than by how many values: `size` tells
+/// how many bytes a value takes, and senders wait while the values in the channel and theirs
+/// would take more than `max_bytes` together.
+///
+/// A channel bounded by count can still run out of memory on a few huge values, this one keeps
+/// to its budget whatever their sizes. A value larger than the whole budget is sent once the
+/// channel is empty, so it can't wait forever, and is the only one in the channel then.
+///
+/// # Panics
+///
+/// Panics if `max_bytes` is 0.
+pub fn sized_channel<T>(
+  max_bytes: usize,
+  size: impl Fn(&T) -> usize + Send + Sync + 'static,
+) -> (SizedSender<T>, SizedReceiver<T>) {
+  assert!(max_bytes > 0, "sized_channel max_bytes can't be 0");
+  let channel = Arc::new(Channel {
+    state: StdMutex::new(State {
+      queue: VecDeque::new(),
+      bytes: 0,
+      senders: 1,
+      receiver_dropped: false,
+      receiver_waker: None,
+      senders_waiting: Vec::new(),
+    }),
+    max_bytes,
+    size: Box::new(size),
+  });
+  (SizedSender { channel: channel.clone() }, SizedReceiver { channel })
+}
+
+struct Channel<T> {
+  // This is not a bottleneck
+  state: StdMutex<State<T>>,
+  max_bytes: usize,
+  size: Box<dyn Fn(&T) -> usize + Send + Sync>,
+}
+
+struct State<T> {
+  // With the size of each value, so it's only computed once.
+  queue: VecDeque<(usize, T)>,
+  bytes: usize,
+  senders: usize,
+  receiver_dropped: bool,
+  receiver_waker: Option<Waker>,
+  senders_waiting: Vec<Waker>,
+}
+
+/// Sends into a [`sized_channel`]. It can be cloned for more senders.
+pub struct SizedSender<T> {
+  channel: Arc<Channel<T>>,
+}
+
+impl<T> SizedSender<T> {
+  /// Waits for enough room in the channel's budget and sends `value`.
+  ///
+  /// A send dropped while it waits sends nothing.
+  pub fn send(&self, value: T) -> SizedSend<'_, T> {
+    let size = (self.channel.size)(&value);
+    SizedSend { sender: self, value: Some((size, value)) }
+  }
+}
+
+impl<T> Clone for SizedSender<T> {
+  fn clone(&self) -> Self {
+    self.channel.state.lock().unwrap().senders += 1;
+    SizedSender { channel: self.channel.clone() }
+  }
+}
+
+impl<T> Drop for SizedSender<T> {
+  fn drop(&mut self) {
+    let mut state = self.channel.state.lock().unwrap();
+    state.senders -= 1;
+    // A waiting receiver has to see the disconnect.
+    if state.senders == 0 {
+      if let Some(waker) = state.receiver_waker.take() {
+        waker.wake();
+      }
+    }
+  }
+}
+
+/// Future returned by [`SizedSender::send`].
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct SizedSend<'a, T> {
+  sender: &'a SizedSender<T>,
+  // Taken once it's in the channel.
+  value: Option<(usize, T)>,
+}
+
+// The value is never pinned.
+impl<T> Unpin for SizedSend<'_, T> {}
+
+impl<T> Future for SizedSend<'_, T> {
+  type Output = Result<(), ReceiverDroppedError>;
+
+  fn poll(
+    mut self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<Self::Output> {
+    let channel = self.sender.channel.clone();
+    let mut state = channel.state.lock().unwrap();
+    if state.receiver_dropped {
+      return Poll::Ready(Err(ReceiverDroppedError));
+    }
+    let (size, _) =
+      self.value.as_ref().expect("SizedSend polled after completion");
+    if !state.queue.is_empty() && state.bytes + size > channel.max_bytes {
+      state.senders_waiting.push(cx.waker().clone());
+      return Poll::Pending;
+    }
+
+    let (size, value) = self.value.take().unwrap();
+    state.bytes += size;
+    state.queue.push_back((size, value));
+    if let Some(waker) = state.receiver_waker.take() {
+      waker.wake();
+    }
+    Poll::Ready(Ok(()))
+  }
+}
+
+/// Receives from a [`sized_channel`].
+pub struct SizedReceiver<T> {
+  channel: Arc<Channel<T>>,
+}
+
+impl<T> SizedReceiver<T> {
+  /// Takes the next value. Values sent before the last sender was dropped are still received,
+  /// [`RecvError::Disconnected`] is only returned once they're all taken.
+  pub fn try_recv(&self) -> Result<T, RecvError> {
+    self.take(None)
+  }
+
+  /// Waits for a value, see [`SizedReceiver::try_recv`].
+  pub fn recv(&self) -> SizedRecv<'_, T> {
+    SizedRecv { receiver: self }
+  }
+
+  /// How many bytes the values in the channel take, by their `size`.
+  pub fn bytes(&self) -> usize {
+    self.channel.state.lock().unwrap().bytes
+  }
+
+  // Registers `waker` when there's nothing to take yet.
+  fn take(&self, waker: Option<&Waker>) -> Result<T, RecvError> {
+    let mut state = self.channel.state.lock().unwrap();
+    match state.queue.pop_front() {
+      Some((size, value)) => {
+        state.bytes -= size;
+        for waker in state.senders_waiting.drain(..) {
+          waker.wake();
+        }
+        Ok(value)
+      }
+      None if state.senders == 0 => Err(RecvError::Disconnected),
+      None => {
+        if let Some(waker) = waker {
+          state.receiver_waker = Some(waker.clone());
+        }
+        Err(RecvError::Empty)
+      }
+    }
+  }
+}
+
+impl<T> Drop for SizedReceiver<T> {
+  fn drop(&mut self) {
+    let mut state = self.channel.state.lock().unwrap();
+    state.receiver_dropped = true;
+    for waker in state.senders_waiting.drain(..) {
+      waker.wake();
+    }
+  }
+}
+
+/// Future returned by [`SizedReceiver::recv`].
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct SizedRecv<'a, T> {
+  receiver: &'a SizedReceiver<T>,
+}
+
+impl<T> Future for SizedRecv<'_, T> {
+  type Output = Result<T, RecvError>;
+
+  fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+    match self.receiver.take(Some(cx.waker())) {
+      Err(RecvError::Empty) => Poll::Pending,
+      result => Poll::Ready(result),
+    }
+  }
+}
+
+#[crate::internal_test]
+async fn byte_budget_respected() {
+  use crate::task;
+
+  const BUDGET: usize = 1024;
+  let (sender, receiver) = sized_channel(BUDGET, Vec::<u8>::len);
+  let sizes = [100, 700, 300, 1000, 20, 20, 900, 5, 600, 400];
+  let sending = task::spawn(async move {
+    for size in sizes {
+      sender.send(vec![0; size]).await.unwrap();
+    }
+  });
+
+  let mut received = vec![];
+  loop {
+    // Whatever the senders got in before the receiver runs again.
+    assert!(receiver.bytes() <= BUDGET, "{} bytes queued", receiver.bytes());
+    match receiver.recv().await {
+      Ok(value) => received.push(value.len()),
+      Err(_) => break,
+    }
+  }
+  assert_eq!(received, sizes);
+  sending.await.unwrap();
+}
+
+#[crate::internal_test]
+async fn oversized_value_waits_for_empty() {
+  use crate::test_util::task;
+
+  let (sender, receiver) = sized_channel(8, |value: &&str| value.len());
+  sender.send("small").await.unwrap();
+  let mut send = task::spawn(sender.send("far too large"));
+  assert!(send.poll().is_pending());
+
+  assert_eq!(receiver.try_recv(), Ok("small"));
+  assert!(send.is_woken());
+  assert_eq!(send.poll(), Poll::Ready(Ok(())));
+  assert_eq!(receiver.bytes(), 13);
+  // Nothing fits next to it.
+  let mut send = task::spawn(sender.send("a"));
+  assert!(send.poll().is_pending());
+  assert_eq!(receiver.try_recv(), Ok("far too large"));
+  assert_eq!(send.poll(), Poll::Ready(Ok(())));
+}
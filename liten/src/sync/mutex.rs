@@ -1,18 +1,47 @@
 use std::{
   cell::UnsafeCell,
+  future::Future,
   ops::{Deref, DerefMut},
   panic::{RefUnwindSafe, UnwindSafe},
-  sync::atomic::AtomicBool,
+  pin::Pin,
+  sync::{atomic::AtomicBool, Arc, Mutex as StdMutex},
   thread,
+  time::Duration,
 };
 
 use super::semaphore;
+use crate::task::{self, Priority, PriorityState};
 use thiserror::Error;
 
+// How often a release hands the lock to the longest waiter, like parking_lot's mutex does.
+const HANDOFF_INTERVAL: Duration = Duration::from_micros(500);
+
+/// An asynchronous mutual exclusion lock.
+///
+/// A task waiting on the lock with a higher priority than the task holding it boosts the holder
+/// to its priority until the lock is released, so a low priority task can't hold up a high
+/// priority one for longer than it needs the lock.
+///
+/// # Fairness
+///
+/// A released lock wakes the task which has waited the longest, but a task which locks it again
+/// right away can get it first. That keeps the lock busy, and a task holding it in a loop doesn't
+/// have to wait its turn every time. So waiters aren't starved by that, the lock is handed
+/// straight to the longest waiter every half a millisecond or so: nobody waits much longer than
+/// that past the release. [`Mutex::unfair`] makes one without the hand-offs.
 pub struct Mutex<T> {
   inner: UnsafeCell<T>,
   poisoned: AtomicBool,
   guard: semaphore::Semaphore,
+  // This is not a bottleneck
+  holder: StdMutex<Holder>,
+}
+
+// The task holding the lock, and the priority its waiters have boosted it to.
+#[derive(Default)]
+struct Holder {
+  task: Option<Arc<PriorityState>>,
+  boost: Option<Priority>,
 }
 
 // Safety: Mutex logic makes sure this is safe.
@@ -24,10 +53,29 @@ impl<T> RefUnwindSafe for Mutex<T> {}
 
 impl<T> Mutex<T> {
   pub fn new(value: T) -> Self {
+    let guard = semaphore::Semaphore::eventually_fair(
+      1.try_into().unwrap(),
+      HANDOFF_INTERVAL,
+    );
+    Self::with_guard(value, guard)
+  }
+
+  /// A mutex which never hands the lock to a waiter, see [fairness](Mutex#fairness). The lock
+  /// goes to whoever asks for it first after a release, which gives the most throughput when
+  /// waiting a little longer doesn't matter.
+  pub fn unfair(value: T) -> Self {
+    Self::with_guard(
+      value,
+      semaphore::Semaphore::with_size(1.try_into().unwrap()),
+    )
+  }
+
+  fn with_guard(value: T, guard: semaphore::Semaphore) -> Self {
     Self {
       inner: UnsafeCell::new(value),
-      guard: semaphore::Semaphore::with_size(1.try_into().unwrap()),
+      guard,
       poisoned: AtomicBool::new(false),
+      holder: StdMutex::default(),
     }
   }
 
@@ -35,18 +83,86 @@ impl<T> Mutex<T> {
     self.poisoned.store(true, std::sync::atomic::Ordering::Relaxed);
   }
 
+  /// Waits for the lock, or fails if a holder panicked with it.
+  ///
+  /// Taking a lock nobody holds or waits for is a single compare and swap. Otherwise the task
+  /// joins the queue of waiters, which are woken in the order they started waiting.
   pub async fn lock(&self) -> Result<MutexGuard<'_, T>, PoisonError> {
     if self.poisoned.load(std::sync::atomic::Ordering::Relaxed) {
       return Err(PoisonError);
     }
-    let guard = self.guard.acquire().await;
-    Ok(MutexGuard(self, guard))
+    if let Some(permit) = self.guard.try_acquire_uncontended() {
+      return Ok(self.guard_from(permit));
+    }
+
+    let mut acquire = self.guard.acquire();
+    let permit = std::future::poll_fn(|cx| {
+      let poll = Pin::new(&mut acquire).poll(cx);
+      if poll.is_pending() {
+        self.boost_holder();
+      }
+      poll
+    })
+    .await;
+    Ok(self.guard_from(permit))
+  }
+
+  /// Like [`Mutex::lock`], but the guard holds on to the mutex, so it's `'static` and can be moved
+  /// into a spawned task.
+  ///
+  /// The guard can change tasks, so waiters with a higher priority don't boost whoever holds it.
+  pub async fn lock_owned(
+    self: &Arc<Self>,
+  ) -> Result<OwnedMutexGuard<T>, PoisonError> {
+    let guard = self.lock().await?;
+    // The owned guard gives the permit back itself.
+    std::mem::forget(guard);
+    self.clear_holder();
+    Ok(OwnedMutexGuard { mutex: self.clone() })
   }
 
   pub fn try_lock(&self) -> Result<MutexGuard<'_, T>, TryLockError> {
     let guard =
       self.guard.try_acquire().map_err(|_| TryLockError::UnableToAcquireLock);
-    guard.map(|guard| MutexGuard(self, guard))
+    guard.map(|permit| self.guard_from(permit))
+  }
+
+  fn guard_from<'a>(
+    &'a self,
+    permit: semaphore::AcquireLock<'a>,
+  ) -> MutexGuard<'a, T> {
+    let task =
+      task::try_current().ok().map(|task| task.priority_state().clone());
+    *self.holder.lock().unwrap() = Holder { task, boost: None };
+    MutexGuard { mutex: self, _permit: permit }
+  }
+
+  // Takes back the boost of the holder, if it got one.
+  fn clear_holder(&self) {
+    let holder = std::mem::take(&mut *self.holder.lock().unwrap());
+    if let (Some(task), Some(boost)) = (holder.task, holder.boost) {
+      task.unboost(boost);
+    }
+  }
+
+  // Raises the holder to the priority of the waiting task, if that's higher.
+  fn boost_holder(&self) {
+    let Ok(waiter) = task::try_current() else {
+      return;
+    };
+    let priority = waiter.priority();
+
+    let mut holder = self.holder.lock().unwrap();
+    let Holder { task: Some(task), boost } = &mut *holder else {
+      return;
+    };
+    if priority <= task.effective() {
+      return;
+    }
+    task.boost(priority);
+    if let Some(previous) = boost.replace(priority) {
+      task.unboost(previous);
+    }
   }
 }
 
@@ -60,33 +176,72 @@ pub enum TryLockError {
   UnableToAcquireLock,
 }
 
-pub struct MutexGuard<'a, T>(&'a Mutex<T>, semaphore::AcquireLock<'a>);
+pub struct MutexGuard<'a, T> {
+  mutex: &'a Mutex<T>,
+  // Given back when the guard is dropped.
+  _permit: semaphore::AcquireLock<'a>,
+}
 
 impl<T> MutexGuard<'_, T> {
   pub fn release(self) {
-    self.1.release();
+    drop(self);
   }
 }
 
 impl<T> Deref for MutexGuard<'_, T> {
   type Target = T;
   fn deref(&self) -> &Self::Target {
-    unsafe { &*self.0.inner.get() }
+    unsafe { &*self.mutex.inner.get() }
   }
 }
 
 impl<T> DerefMut for MutexGuard<'_, T> {
   fn deref_mut(&mut self) -> &mut Self::Target {
-    unsafe { &mut *self.0.inner.get() }
+    unsafe { &mut *self.mutex.inner.get() }
   }
 }
 
 impl<T> Drop for MutexGuard<'_, T> {
   fn drop(&mut self) {
     if thread::panicking() {
-      self.0.poison();
+      self.mutex.poison();
     }
-    self.1.release();
+    // Before the permit is given back, the next holder could otherwise be boosted by leftovers.
+    self.mutex.clear_holder();
+  }
+}
+
+/// A guard of a [`Mutex`] which keeps it alive, returned by [`Mutex::lock_owned`].
+pub struct OwnedMutexGuard<T> {
+  mutex: Arc<Mutex<T>>,
+}
+
+impl<T> OwnedMutexGuard<T> {
+  pub fn release(self) {
+    drop(self);
+  }
+}
+
+impl<T> Deref for OwnedMutexGuard<T> {
+  type Target = T;
+  fn deref(&self) -> &Self::Target {
+    unsafe { &*self.mutex.inner.get() }
+  }
+}
+
+impl<T> DerefMut for OwnedMutexGuard<T> {
+  fn deref_mut(&mut self) -> &mut Self::Target {
+    unsafe { &mut *self.mutex.inner.get() }
+  }
+}
+
+impl<T> Drop for OwnedMutexGuard<T> {
+  fn drop(&mut self) {
+    if thread::panicking() {
+      self.mutex.poison();
+    }
+    // The permit `lock_owned` forgot.
+    self.mutex.guard.add_permits(1);
   }
 }
 
@@ -119,3 +274,145 @@ fn lock() {
   *value += 1;
   assert!(*value == 2);
 }
+
+#[test]
+fn unlocks_once() {
+  let mutex = Mutex::new(());
+
+  drop(mutex.try_lock().unwrap());
+  let guard = mutex.try_lock().unwrap();
+  // Unlocking used to give back more than one permit.
+  assert!(mutex.try_lock().is_err());
+  guard.release();
+  assert!(mutex.try_lock().is_ok());
+}
+
+#[crate::internal_test]
+async fn holder_inherits_waiter_priority() {
+  use crate::sync::oneshot;
+  use std::time::Duration;
+
+  let mutex = Arc::new(Mutex::new(()));
+  let (locked, is_locked) = oneshot::channel();
+
+  let holder = mutex.clone();
+  let low = task::builder().priority(Priority::Low).build(async move {
+    let guard = holder.lock().await.unwrap();
+    locked.send(()).unwrap();
+
+    // Holds on until the waiter has boosted it, or gives up.
+    for _ in 0..200 {
+      if task::current().priority() == Priority::High {
+        break;
+      }
+      crate::time::sleep(Duration::from_millis(1)).await;
+    }
+    let boosted = task::current().priority();
+    drop(guard);
+    (boosted, task::current().priority())
+  });
+
+  is_locked.await.unwrap();
+  let high = task::spawn_with_priority(Priority::High, async move {
+    drop(mutex.lock().await.unwrap());
+  });
+
+  assert_eq!(low.await.unwrap(), (Priority::High, Priority::Low));
+  high.await.unwrap();
+}
+
+#[crate::internal_test]
+async fn contended() {
+  let mutex = Arc::new(Mutex::new(0));
+  let tasks: Vec<_> = (0..8)
+    .map(|_| {
+      let mutex = mutex.clone();
+      task::spawn(async move {
+        for _ in 0..500 {
+          let mut value = mutex.lock().await.unwrap();
+          let read = *value;
+          // Gives the others a chance to get in while it's held.
+          task::yield_now().await;
+          *value = read + 1;
+        }
+      })
+    })
+    .collect();
+  for task in tasks {
+    task.await.unwrap();
+  }
+  assert_eq!(*mutex.lock().await.unwrap(), 8 * 500);
+  assert_eq!(mutex.guard.available_permits(), 1);
+}
+
+#[crate::internal_test]
+async fn owned_guard_moves_into_task() {
+  let mutex = Arc::new(Mutex::new(Vec::new()));
+  let mut guard = mutex.lock_owned().await.unwrap();
+  guard.push(1);
+
+  let (release, released) = crate::sync::oneshot::channel();
+  let holder = task::spawn(async move {
+    released.await.unwrap();
+    guard.push(2);
+  });
+  // Held by the task.
+  assert!(mutex.try_lock().is_err());
+  release.send(()).unwrap();
+
+  // Woken once the task drops the guard.
+  let value = mutex.lock().await.unwrap().clone();
+  assert_eq!(value, [1, 2]);
+  holder.await.unwrap();
+  assert_eq!(mutex.guard.available_permits(), 1);
+}
+
+#[test]
+fn uncontended_skips_queue() {
+  use crate::{assert_ready, test_util::task::spawn};
+
+  let mutex = Mutex::new(());
+  let mut lock = spawn(mutex.lock());
+  let guard = assert_ready!(lock.poll()).unwrap();
+  // The waker was never stored.
+  assert_eq!(lock.waker_ref_count(), 0);
+
+  // Takes the slow path while it's held, and is woken by the release.
+  let mut waiter = spawn(mutex.lock());
+  assert!(waiter.poll().is_pending());
+  drop(guard);
+  assert!(waiter.is_woken());
+  assert_ready!(waiter.poll()).unwrap();
+}
+
+#[test]
+fn waiter_not_starved() {
+  use crate::{assert_ready, test_util::task::spawn};
+
+  for (mutex, fair) in [(Mutex::new(()), true), (Mutex::unfair(()), false)] {
+    let mut guard = mutex.try_lock().unwrap();
+    let mut waiter = spawn(mutex.lock());
+    assert!(waiter.poll().is_pending());
+
+    // Locks again right after every release, before the woken waiter gets to run.
+    let mut rounds = 0;
+    while rounds < 100 {
+      drop(guard);
+      thread::sleep(Duration::from_micros(100));
+      match mutex.try_lock() {
+        Ok(next) => guard = next,
+        Err(_) => break,
+      }
+      rounds += 1;
+    }
+    if fair {
+      // Handed over within the interval.
+      assert!(rounds * 100 <= HANDOFF_INTERVAL.as_micros(), "{rounds} rounds");
+      assert!(waiter.is_woken());
+      assert_ready!(waiter.poll()).unwrap();
+    } else {
+      assert_eq!(rounds, 100);
+      assert!(waiter.poll().is_pending());
+    }
+  }
+}
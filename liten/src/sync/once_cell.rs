@@ -0,0 +1,215 @@
+use std::{
+  cell::UnsafeCell,
+  future::Future,
+  mem::MaybeUninit,
+  pin::Pin,
+  sync::{
+    atomic::{AtomicU8, Ordering},
+    Mutex as StdMutex,
+  },
+  task::{Context, Poll, Waker},
+};
+
+const EMPTY: u8 = 0;
+const INITIALIZING: u8 = 1;
+const READY: u8 = 2;
+
+/// A value which is set once, either by the first [`OnceCell::get_or_init`] or from outside with
+/// [`OnceCell::set`], and can be waited for by any number of tasks.
+pub struct OnceCell<T> {
+  state: AtomicU8,
+  value: UnsafeCell<MaybeUninit<T>>,
+  // This is not a bottleneck
+  waiters: StdMutex<Vec<Waker>>,
+}
+
+// SAFETY: The value is only written once, before `READY` is stored, and only read after.
+unsafe impl<T: Send + Sync> Sync for OnceCell<T> {}
+unsafe impl<T: Send> Send for OnceCell<T> {}
+
+impl<T> Default for OnceCell<T> {
+  fn default() -> Self {
+    OnceCell {
+      state: AtomicU8::new(EMPTY),
+      value: UnsafeCell::new(MaybeUninit::uninit()),
+      waiters: StdMutex::new(Vec::new()),
+    }
+  }
+}
+
+impl<T> Drop for OnceCell<T> {
+  fn drop(&mut self) {
+    if *self.state.get_mut() == READY {
+      // SAFETY: Written before `READY` was stored.
+      unsafe { self.value.get_mut().assume_init_drop() };
+    }
+  }
+}
+
+impl<T> OnceCell<T> {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// The value, `None` until it's set.
+  pub fn get(&self) -> Option<&T> {
+    if self.state.load(Ordering::Acquire) != READY {
+      return None;
+    }
+    // SAFETY: Written before `READY` was stored, and never again.
+    Some(unsafe { (*self.value.get()).assume_init_ref() })
+  }
+
+  /// Sets the value, and wakes every task waiting for it.
+  ///
+  /// Fails with `value` if the cell already has a value, or is being initialized by
+  /// [`OnceCell::get_or_init`]: only the first to set the cell wins.
+  pub fn set(&self, value: T) -> Result<(), T> {
+    if self
+      .state
+      .compare_exchange(
+        EMPTY,
+        INITIALIZING,
+        Ordering::Acquire,
+        Ordering::Acquire,
+      )
+      .is_err()
+    {
+      return Err(value);
+    }
+    self.write(value);
+    Ok(())
+  }
+
+  /// Returns the value, and sets it to the output of `init` first if it's empty.
+  ///
+  /// Only one `init` runs at a time, the other callers wait for its value. If it's dropped before
+  /// it completes, one of the waiting callers runs its own `init` instead.
+  pub async fn get_or_init<F, Fut>(&self, init: F) -> &T
+  where
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = T>,
+  {
+    let mut init = Some(init);
+    loop {
+      if let Some(value) = self.get() {
+        return value;
+      }
+      if self
+        .state
+        .compare_exchange(
+          EMPTY,
+          INITIALIZING,
+          Ordering::Acquire,
+          Ordering::Acquire,
+        )
+        .is_ok()
+      {
+        let guard = InitGuard(self);
+        let init = init.take().expect("only initializes once");
+        let value = init().await;
+        std::mem::forget(guard);
+        self.write(value);
+        return self.get().expect("just written");
+      }
+      Wait { cell: self, until: |state| state != INITIALIZING }.await;
+    }
+  }
+
+  /// Waits for the value to be set, by [`OnceCell::set`] or [`OnceCell::get_or_init`].
+  pub async fn wait(&self) -> &T {
+    Wait { cell: self, until: |state| state == READY }.await;
+    self.get().expect("waited until ready")
+  }
+
+  // Only with `INITIALIZING` taken by the caller.
+  fn write(&self, value: T) {
+    // SAFETY: Nobody else writes or reads while it's initializing.
+    unsafe { (*self.value.get()).write(value) };
+    self.state.store(READY, Ordering::Release);
+    self.wake_all();
+  }
+
+  fn wake_all(&self) {
+    for waker in self.waiters.lock().unwrap().drain(..) {
+      waker.wake();
+    }
+  }
+}
+
+// Gives the cell back to the other callers when an `init` is dropped before it completes.
+struct InitGuard<'a, T>(&'a OnceCell<T>);
+
+impl<T> Drop for InitGuard<'_, T> {
+  fn drop(&mut self) {
+    self.0.state.store(EMPTY, Ordering::Release);
+    self.0.wake_all();
+  }
+}
+
+// Waits until `until` is true of the state.
+struct Wait<'a, T> {
+  cell: &'a OnceCell<T>,
+  until: fn(u8) -> bool,
+}
+
+impl<T> Future for Wait<'_, T> {
+  type Output = ();
+
+  fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+    let cell = self.cell;
+    if (self.until)(cell.state.load(Ordering::Acquire)) {
+      return Poll::Ready(());
+    }
+    let mut waiters = cell.waiters.lock().unwrap();
+    // The state is changed before the waiters are woken, with the lock taken after.
+    if (self.until)(cell.state.load(Ordering::Acquire)) {
+      return Poll::Ready(());
+    }
+    if !waiters.iter().any(|waker| waker.will_wake(cx.waker())) {
+      waiters.push(cx.waker().clone());
+    }
+    Poll::Pending
+  }
+}
+
+#[crate::internal_test]
+async fn set_from_another_task() {
+  use crate::task;
+  use std::sync::Arc;
+
+  let cell = Arc::new(OnceCell::<String>::new());
+  assert_eq!(cell.get(), None);
+  let waiter = task::spawn({
+    let cell = cell.clone();
+    async move { cell.wait().await.clone() }
+  });
+  task::yield_now().await;
+
+  let config = "late-bound config".to_string();
+  assert_eq!(cell.set(config), Ok(()));
+  assert_eq!(waiter.await.unwrap(), "late-bound config");
+  // Only the first one wins.
+  assert_eq!(cell.set("other".to_string()), Err("other".to_string()));
+  let init = cell.get_or_init(|| async { unreachable!() }).await;
+  assert_eq!(init.as_str(), "late-bound config");
+}
+
+#[crate::internal_test]
+async fn one_init_at_a_time() {
+  use crate::{sync::oneshot, test_util::task};
+
+  let cell = OnceCell::new();
+  let (sender, receiver) = oneshot::channel();
+  let mut first =
+    task::spawn(cell.get_or_init(|| async { receiver.await.unwrap() }));
+  assert!(first.poll().is_pending());
+  let mut second = task::spawn(cell.get_or_init(|| async { 2 }));
+  assert!(second.poll().is_pending());
+
+  // Dropping the running init lets the waiting one run its own.
+  drop(first);
+  assert!(second.is_woken());
+  assert_eq!(second.poll(), Poll::Ready(&2));
+  drop(sender);
+}
@@ -1,15 +1,20 @@
 use std::{
-  cell::UnsafeCell,
   error::Error,
   fmt::Display,
   future::Future,
   mem::MaybeUninit,
   pin::Pin,
-  sync::Arc,
   task::{Context, Poll, Waker},
+  time::Duration,
 };
 
-use crossbeam_utils::atomic::AtomicCell;
+use futures_core::Stream;
+
+use super::state::AtomicState;
+use crate::{
+  loom::{cell::UnsafeCell, sync::Arc},
+  time::{self, Sleep},
+};
 
 bitflags::bitflags! {
   #[repr(transparent)]
@@ -20,6 +25,9 @@ bitflags::bitflags! {
       const SENDER_DROPPED = 1 << 2;
       const SENDER_SENT = 1 << 3;
       const WAKER_REGISTERED = 1 << 4;
+      const RECEIVED = 1 << 5;
+      // The sender waits for the value to be taken, see `Sender::send_and_wait_consumed`.
+      const ACK_REGISTERED = 1 << 6;
   }
 }
 
@@ -34,10 +42,20 @@ pub struct Receiver<V> {
 impl<V> Drop for Receiver<V> {
   fn drop(&mut self) {
     // This doesn't fail
-    let _ = self.channel.state.fetch_update(|mut old| {
-      old.insert(ChannelState::RECEIVER_DROPPED);
-      Some(old)
-    });
+    let old = self
+      .channel
+      .state
+      .fetch_update(|mut old| {
+        old.insert(ChannelState::RECEIVER_DROPPED);
+        Some(old)
+      })
+      .unwrap();
+    if old.contains(ChannelState::ACK_REGISTERED)
+      && !old.contains(ChannelState::RECEIVED)
+    {
+      // SAFETY: The sender doesn't touch its waker after RECEIVER_DROPPED is set.
+      self.channel.wake_ack_unchecked();
+    }
   }
 }
 
@@ -52,16 +70,16 @@ impl<V> Drop for Sender<V> {
         Some(old)
       })
       .unwrap();
-    if value.contains(ChannelState::WAKER_REGISTERED) {
-      let unsafecell_inner =
-        unsafe { self.channel.waker.get().as_ref() }.unwrap();
-      let waker = unsafe { unsafecell_inner.assume_init_ref() };
-      waker.wake_by_ref();
+    // A sent value has woken the receiver already.
+    if value.contains(ChannelState::WAKER_REGISTERED)
+      && !value.contains(ChannelState::SENDER_SENT)
+    {
+      // SAFETY: The receiver doesn't touch the waker after SENDER_DROPPED is set.
+      self.channel.wake_unchecked();
     }
   }
 }
 
-#[derive(Clone)]
 pub struct Sender<V> {
   channel: Arc<Channel<V>>,
 }
@@ -77,40 +95,90 @@ static_assertions::assert_impl_all!(Sender<()>: Send);
 #[cfg(test)]
 static_assertions::assert_impl_all!(Receiver<()>: Send);
 
+// The value is stored inline, so the `Arc` holding this is the only allocation for any `V`. The
+// state and the waker, which both halves touch on every poll, come first to share a cache line
+// with the reference counts.
+#[repr(C)]
 pub struct Channel<V> {
-  state: AtomicCell<ChannelState>,
+  state: AtomicState<ChannelState>,
   waker: UnsafeCell<MaybeUninit<Waker>>,
   value: UnsafeCell<MaybeUninit<V>>,
+  // The sender's, only while it waits in `send_and_wait_consumed`.
+  ack_waker: UnsafeCell<MaybeUninit<Waker>>,
 }
 
 impl<V> Channel<V> {
   fn new() -> Self {
     Self {
-      state: AtomicCell::new(ChannelState::INITIALISED),
+      state: AtomicState::new(ChannelState::INITIALISED),
       waker: UnsafeCell::new(MaybeUninit::uninit()),
       value: UnsafeCell::new(MaybeUninit::uninit()),
+      ack_waker: UnsafeCell::new(MaybeUninit::uninit()),
     }
   }
 
   fn write_waker(&self, waker: Waker) {
-    let waker_uninit = unsafe { self.waker.get().as_mut().unwrap() };
-    waker_uninit.write(waker);
+    self.waker.with_mut(|ptr| unsafe { (*ptr).write(waker) });
   }
 
   fn write_value(&self, value: V) {
-    let waker_uninit = unsafe { self.value.get().as_mut().unwrap() };
-    waker_uninit.write(value);
+    self.value.with_mut(|ptr| unsafe { (*ptr).write(value) });
+  }
+
+  fn drop_waker_unchecked(&self) {
+    // SAFETY: Caller should guarrantee waker is init'ed, and isn't used anymore.
+    self.waker.with_mut(|ptr| unsafe { (*ptr).assume_init_drop() });
+  }
+
+  fn will_wake_unchecked(&self, waker: &Waker) -> bool {
+    // SAFETY: Caller should guarrantee waker is init'ed.
+    self.waker.with(|ptr| unsafe { (*ptr).assume_init_ref() }.will_wake(waker))
   }
 
   fn read_value_unchecked(&self) -> V {
-    unsafe { (*self.value.get()).as_ptr().read() }
+    self.value.with(|ptr| unsafe { (*ptr).as_ptr().read() })
   }
 
   fn wake_unchecked(&self) {
-    // SAFETY: Caller should guarrantee waker is init'ed.
-    let unsafecell_inner = unsafe { self.waker.get().as_ref() }.unwrap();
-    let waker = unsafe { unsafecell_inner.assume_init_ref() };
-    waker.wake_by_ref();
+    self.waker.with(|ptr| unsafe { (*ptr).assume_init_ref() }.wake_by_ref());
+  }
+
+  // The same as the ones above, for the sender's waker.
+  fn write_ack_waker(&self, waker: Waker) {
+    self.ack_waker.with_mut(|ptr| unsafe { (*ptr).write(waker) });
+  }
+
+  fn drop_ack_waker_unchecked(&self) {
+    self.ack_waker.with_mut(|ptr| unsafe { (*ptr).assume_init_drop() });
+  }
+
+  fn ack_will_wake_unchecked(&self, waker: &Waker) -> bool {
+    self
+      .ack_waker
+      .with(|ptr| unsafe { (*ptr).assume_init_ref() }.will_wake(waker))
+  }
+
+  fn wake_ack_unchecked(&self) {
+    self
+      .ack_waker
+      .with(|ptr| unsafe { (*ptr).assume_init_ref() }.wake_by_ref());
+  }
+}
+
+impl<V> Drop for Channel<V> {
+  fn drop(&mut self) {
+    let state = self.state.load();
+    if state.contains(ChannelState::WAKER_REGISTERED) {
+      self.drop_waker_unchecked();
+    }
+    if state.contains(ChannelState::ACK_REGISTERED) {
+      self.drop_ack_waker_unchecked();
+    }
+    if state.contains(ChannelState::SENDER_SENT)
+      && !state.contains(ChannelState::RECEIVED)
+    {
+      self.value.with_mut(|ptr| unsafe { (*ptr).assume_init_drop() });
+    }
   }
 }
 
@@ -120,12 +188,32 @@ impl<V> Channel<V> {
 ///
 /// If a channel is guarranteed to send one piece of data, a number of optimisations can be made.
 /// This makes oneshot channels very optimised for a async runtime.
+///
+/// Creating a channel does exactly one allocation, which holds the value, the waker and the state
+/// together. Sending and receiving don't allocate.
 pub fn channel<V>() -> (Sender<V>, Receiver<V>) {
   let channel = Arc::new(Channel::new());
 
   (Sender { channel: channel.clone() }, Receiver { channel: channel.clone() })
 }
 
+/// Like [`channel`], but the receiver fails with [`RecvTimeoutError::Timeout`] if no value is
+/// sent within `timeout`, for a request with a deadline of its own.
+///
+/// The deadline starts now. Its timer is only registered once the receiver waits, and removed
+/// as soon as the value comes in, so answers in time leave no timer behind.
+///
+/// # Panics
+///
+/// Panics if called outside of a runtime.
+#[track_caller]
+pub fn channel_with_timeout<V>(
+  timeout: Duration,
+) -> (Sender<V>, TimeoutReceiver<V>) {
+  let (sender, receiver) = channel();
+  (sender, TimeoutReceiver { receiver, sleep: Some(time::sleep(timeout)) })
+}
+
 #[derive(Debug)]
 pub struct ReceiverDroppedError;
 
@@ -149,7 +237,7 @@ impl Error for ReceiverDroppedError {
   }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct SenderDroppedError;
 
 impl Display for SenderDroppedError {
@@ -172,79 +260,572 @@ impl Error for SenderDroppedError {
   }
 }
 
+/// Returned by [`Sender::send_and_wait_consumed`] when the receiver is dropped without taking the
+/// value, which is given back.
+#[derive(Debug, PartialEq, Eq)]
+pub struct NotConsumedError<V>(pub V);
+
+impl<V> Display for NotConsumedError<V> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.write_str("NotConsumedError")
+  }
+}
+
+impl<V: std::fmt::Debug> Error for NotConsumedError<V> {
+  fn source(&self) -> Option<&(dyn Error + 'static)> {
+    None
+  }
+
+  fn description(&self) -> &str {
+    "This channels receiver was dropped before taking the value"
+  }
+}
+
 impl<V> Sender<V> {
   pub fn send(self, value: V) -> Result<(), ReceiverDroppedError> {
+    if self.fulfil(value)? {
+      // SAFETY: A waker is initialized because of the state, and the receiver doesn't touch it
+      // after SENDER_SENT is set.
+      self.channel.wake_unchecked();
+    }
+    Ok(())
+  }
+
+  /// Sends `value` like [`Sender::send`], but leaves waking the receiver to `batch`.
+  ///
+  /// A producer which answers many requests at once, like under the lock of a table of pending
+  /// requests, can then send them all and wake the receivers once the lock is released.
+  pub fn send_deferred(
+    self,
+    value: V,
+    batch: &mut WakeBatch,
+  ) -> Result<(), ReceiverDroppedError> {
+    if self.fulfil(value)? {
+      // SAFETY: As in `send`.
+      let waker =
+        self.channel.waker.with(|ptr| unsafe { (*ptr).assume_init_ref() });
+      batch.push(waker);
+    }
+    Ok(())
+  }
+
+  /// Sends `value`, and completes once the receiver has taken it, not just once it's been sent.
+  /// A producer can then be sure the value was delivered before it goes on.
+  ///
+  /// If the receiver is dropped without taking the value, the value is given back in the error.
+  /// Dropping the future before it completes leaves the value to be received, if it was sent.
+  pub fn send_and_wait_consumed(self, value: V) -> SendAndWaitConsumed<V> {
+    SendAndWaitConsumed { sender: self, value: Some(value) }
+  }
+
+  // Stores the value, returns whether the receiver has a waker to be woken.
+  fn fulfil(&self, value: V) -> Result<bool, ReceiverDroppedError> {
     let state = self.channel.state.load();
 
     if state.contains(ChannelState::RECEIVER_DROPPED) {
       return Err(ReceiverDroppedError);
     }
 
-    if state.contains(ChannelState::WAKER_REGISTERED) {
-      // SAFETY: A waker is initialized because of the state.
-      self.channel.wake_unchecked();
-    }
+    // The value has to be written before SENDER_SENT is visible.
+    self.channel.write_value(value);
 
     // This doesn't fail.
-    let _ = self.channel.state.fetch_update(|mut previous| {
-      previous.insert(ChannelState::SENDER_SENT);
-      Some(previous)
-    });
+    let previous = self
+      .channel
+      .state
+      .fetch_update(|mut previous| {
+        previous.insert(ChannelState::SENDER_SENT);
+        Some(previous)
+      })
+      .unwrap();
 
-    self.channel.write_value(value);
+    Ok(previous.contains(ChannelState::WAKER_REGISTERED))
+  }
+}
 
-    Ok(())
+/// Future returned by [`Sender::send_and_wait_consumed`].
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct SendAndWaitConsumed<V> {
+  sender: Sender<V>,
+  // Taken when it's sent.
+  value: Option<V>,
+}
+
+// The value is never pinned.
+impl<V> Unpin for SendAndWaitConsumed<V> {}
+
+impl<V> SendAndWaitConsumed<V> {
+  // Takes back a value the dropped receiver never took.
+  fn take_back(&self) -> Option<V> {
+    let channel = &self.sender.channel;
+    channel
+      .state
+      .fetch_update(|mut old| {
+        (!old.contains(ChannelState::RECEIVED)).then(|| {
+          old.insert(ChannelState::RECEIVED);
+          old
+        })
+      })
+      .ok()
+      // SAFETY: Sent, and RECEIVED makes sure it's only read once.
+      .map(|_| channel.read_value_unchecked())
+  }
+}
+
+impl<V> Future for SendAndWaitConsumed<V> {
+  type Output = Result<(), NotConsumedError<V>>;
+
+  fn poll(
+    mut self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<Self::Output> {
+    let channel = self.sender.channel.clone();
+
+    if let Some(value) = self.value.take() {
+      if channel.state.load().contains(ChannelState::RECEIVER_DROPPED) {
+        return Poll::Ready(Err(NotConsumedError(value)));
+      }
+      // Nothing reads either until the flags say they're there.
+      channel.write_ack_waker(cx.waker().clone());
+      channel.write_value(value);
+      let previous = channel
+        .state
+        .fetch_update(|mut previous| {
+          previous
+            .insert(ChannelState::SENDER_SENT | ChannelState::ACK_REGISTERED);
+          Some(previous)
+        })
+        .unwrap();
+      if previous.contains(ChannelState::RECEIVER_DROPPED) {
+        return Poll::Ready(Err(NotConsumedError(
+          self.take_back().expect("nothing else takes it"),
+        )));
+      }
+      if previous.contains(ChannelState::WAKER_REGISTERED) {
+        // SAFETY: As in `send`.
+        channel.wake_unchecked();
+      }
+      return Poll::Pending;
+    }
+
+    let mut state = channel.state.load();
+    loop {
+      if state.contains(ChannelState::RECEIVED) {
+        return Poll::Ready(Ok(()));
+      }
+      if state.contains(ChannelState::RECEIVER_DROPPED) {
+        return Poll::Ready(match self.take_back() {
+          Some(value) => Err(NotConsumedError(value)),
+          None => Ok(()),
+        });
+      }
+
+      if state.contains(ChannelState::ACK_REGISTERED) {
+        // SAFETY: Registered, and the receiver only wakes it.
+        if channel.ack_will_wake_unchecked(cx.waker()) {
+          return Poll::Pending;
+        }
+        // Take it back before replacing it, like the receiver does with its own.
+        match channel.state.compare_exchange(
+          state,
+          state.difference(ChannelState::ACK_REGISTERED),
+        ) {
+          Ok(_) => {
+            channel.drop_ack_waker_unchecked();
+            state.remove(ChannelState::ACK_REGISTERED);
+          }
+          Err(actual) => {
+            state = actual;
+            continue;
+          }
+        }
+      }
+
+      channel.write_ack_waker(cx.waker().clone());
+      match channel
+        .state
+        .compare_exchange(state, state.union(ChannelState::ACK_REGISTERED))
+      {
+        Ok(_) => return Poll::Pending,
+        Err(actual) => {
+          channel.drop_ack_waker_unchecked();
+          state = actual;
+        }
+      }
+    }
+  }
+}
+
+/// Wakers of receivers, collected by [`Sender::send_deferred`] and woken together by
+/// [`WakeBatch::wake`], or when the batch is dropped.
+///
+/// A waker which is the same as the one collected right before it isn't collected again, so the
+/// receivers of one task joining many of them wake it once.
+#[derive(Default)]
+pub struct WakeBatch {
+  wakers: Vec<Waker>,
+}
+
+impl WakeBatch {
+  pub fn new() -> WakeBatch {
+    WakeBatch::default()
+  }
+
+  /// How many wakers are waiting to be woken.
+  pub fn len(&self) -> usize {
+    self.wakers.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.wakers.is_empty()
+  }
+
+  /// Wakes every collected waker.
+  pub fn wake(mut self) {
+    self.wake_all();
+  }
+
+  fn push(&mut self, waker: &Waker) {
+    if !self.wakers.last().is_some_and(|last| last.will_wake(waker)) {
+      self.wakers.push(waker.clone());
+    }
+  }
+
+  fn wake_all(&mut self) {
+    for waker in self.wakers.drain(..) {
+      waker.wake();
+    }
+  }
+}
+
+impl Drop for WakeBatch {
+  fn drop(&mut self) {
+    self.wake_all();
   }
 }
 
 impl<V> Receiver<V> {
-  pub fn try_get_sender(&self) -> Result<Sender<V>, ()> {
-    if !self.channel.state.load().contains(ChannelState::SENDER_DROPPED) {
-      // There is another receiver alive. This function cannot move forward.
-      return Err(());
-    };
+  /// Returns a future which receives the value and applies `f` to it. An error is passed through
+  /// without calling `f`.
+  pub fn map<F, U>(self, f: F) -> Map<V, F>
+  where
+    F: FnOnce(V) -> U,
+  {
+    Map { receiver: self, f: Some(f) }
+  }
 
-    Ok(Sender { channel: self.channel.clone() })
+  /// Waits for the value without taking the receiver, so a receiver kept in a struct can be awaited
+  /// through `&mut self`. Awaiting the receiver itself does the same.
+  ///
+  /// The value can only be received once: after it's taken, by this or any other way of receiving,
+  /// every later receive fails with [`SenderDroppedError`]. A `Recv` dropped before it completes
+  /// takes nothing, and the value can still be received later.
+  pub fn recv(&mut self) -> Recv<'_, V> {
+    Recv { receiver: self }
   }
+
+  /// Turns the receiver into a stream which yields the value, or nothing if the sender is dropped
+  /// without sending, and then ends. This lets a oneshot be merged with other streams.
+  pub fn into_stream(self) -> IntoStream<V> {
+    IntoStream { receiver: Some(self) }
+  }
+
   pub fn try_recv(&self) -> Result<Option<V>, SenderDroppedError> {
     let state = self.channel.state.load();
+    Self::recv_from_state(&self.channel, state).unwrap_or(Ok(None))
+  }
+
+  // Tries to take the value or the error the state says is there. None when the channel is still
+  // waiting on the sender.
+  fn recv_from_state(
+    channel: &Channel<V>,
+    state: ChannelState,
+  ) -> Option<Result<Option<V>, SenderDroppedError>> {
+    if state.contains(ChannelState::RECEIVED) {
+      // The value has already been taken.
+      return Some(Err(SenderDroppedError));
+    }
 
     if state.contains(ChannelState::SENDER_SENT) {
+      // The only atomic write on the way to a value which was sent before the first poll: the
+      // receiver never registers a waker then. An `or` rather than a compare and swap, so it
+      // can't fail and go around again when the sender drops or waits for the ack meanwhile.
+      let old = channel.state.fetch_insert(ChannelState::RECEIVED);
+      if old.contains(ChannelState::RECEIVED) {
+        return Some(Err(SenderDroppedError));
+      }
       // SAFETY: If ChannelState::SENDER_SENT it's guarranteed for self.channel.value to be
-      // initialised.
-      return Ok(Some(self.channel.read_value_unchecked()));
+      // initialised, and RECEIVED makes sure it's only read once.
+      let value = channel.read_value_unchecked();
+      if old.contains(ChannelState::ACK_REGISTERED) {
+        // SAFETY: The sender doesn't touch its waker after RECEIVED is set.
+        channel.wake_ack_unchecked();
+      }
+      return Some(Ok(Some(value)));
     }
 
     if state.contains(ChannelState::SENDER_DROPPED) {
-      return Err(SenderDroppedError);
+      return Some(Err(SenderDroppedError));
     }
 
-    Ok(None)
+    None
+  }
+}
+
+impl<V> Receiver<V> {
+  /// Returns `true` once the sender has sent or been dropped, without taking the value.
+  pub(crate) fn is_done(&self) -> bool {
+    self.channel.state.load().intersects(
+      ChannelState::SENDER_SENT
+        | ChannelState::SENDER_DROPPED
+        | ChannelState::RECEIVED,
+    )
+  }
+
+  /// Waits for the sender to send or be dropped, without taking the value.
+  pub(crate) fn poll_done(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+    self.poll_state(cx).map(|_| ())
+  }
+
+  // Registers the waker until the sender is done, and returns the state it finished with.
+  fn poll_state(&mut self, cx: &mut Context<'_>) -> Poll<ChannelState> {
+    let channel = &self.channel;
+    let done = ChannelState::SENDER_SENT
+      | ChannelState::SENDER_DROPPED
+      | ChannelState::RECEIVED;
+    let mut state = channel.state.load();
+
+    loop {
+      if state.intersects(done) {
+        return Poll::Ready(state);
+      }
+
+      if !state.contains(ChannelState::WAKER_REGISTERED) {
+        break;
+      }
+      // SAFETY: Registered and the sender isn't done, so it can't be reading the waker.
+      if channel.will_wake_unchecked(cx.waker()) {
+        return Poll::Pending;
+      }
+
+      // Take back the waker before replacing it. Fails if the sender finished meanwhile, in
+      // which case the sender might be reading it.
+      match channel.state.compare_exchange(
+        state,
+        state.difference(ChannelState::WAKER_REGISTERED),
+      ) {
+        Ok(_) => {
+          channel.drop_waker_unchecked();
+          state.remove(ChannelState::WAKER_REGISTERED);
+          break;
+        }
+        Err(actual) => state = actual,
+      }
+    }
+
+    // Nothing reads the waker while WAKER_REGISTERED isn't set.
+    channel.write_waker(cx.waker().clone());
+
+    loop {
+      match channel
+        .state
+        .compare_exchange(state, state.union(ChannelState::WAKER_REGISTERED))
+      {
+        Ok(_) => return Poll::Pending,
+        // The sender finished before it could have seen the waker, so don't wait for a wake.
+        Err(actual) if actual.intersects(done) => {
+          channel.drop_waker_unchecked();
+          return Poll::Ready(actual);
+        }
+        Err(actual) => state = actual,
+      }
+    }
   }
 }
 
 impl<V> Future for Receiver<V> {
   type Output = Result<V, SenderDroppedError>;
 
-  fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-    match self.try_recv() {
-      Ok(value) => match value {
-        Some(value) => Poll::Ready(Ok(value)),
-        None => {
-          self.channel.write_waker(cx.waker().clone());
-          let _ = self.channel.state.fetch_update(|mut previous| {
-            previous.insert(ChannelState::WAKER_REGISTERED);
-            Some(previous)
-          });
-
-          Poll::Pending
+  fn poll(
+    mut self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<Self::Output> {
+    let state = std::task::ready!(self.poll_state(cx));
+    let result =
+      Self::recv_from_state(&self.channel, state).expect("sender is done");
+    Poll::Ready(result.map(|value| value.expect("value is sent")))
+  }
+}
+
+/// Future returned by [`Receiver::recv`].
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Recv<'a, V> {
+  receiver: &'a mut Receiver<V>,
+}
+
+impl<V> Future for Recv<'_, V> {
+  type Output = Result<V, SenderDroppedError>;
+
+  fn poll(
+    mut self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<Self::Output> {
+    Pin::new(&mut *self.receiver).poll(cx)
+  }
+}
+
+/// Future returned by [`Receiver::map`].
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Map<V, F> {
+  receiver: Receiver<V>,
+  // Taken when the value is received.
+  f: Option<F>,
+}
+
+// `f` is never pinned.
+impl<V, F> Unpin for Map<V, F> {}
+
+impl<V, F, U> Future for Map<V, F>
+where
+  F: FnOnce(V) -> U,
+{
+  type Output = Result<U, SenderDroppedError>;
+
+  fn poll(
+    mut self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<Self::Output> {
+    let result = std::task::ready!(Pin::new(&mut self.receiver).poll(cx));
+    let f = self.f.take().expect("Map polled after completion");
+    Poll::Ready(result.map(f))
+  }
+}
+
+/// Waits for the first of `receivers` to get a value, and returns its index along with the value.
+/// The other receivers are dropped. Fits hedged requests, sent to several places at once for the
+/// first answer.
+///
+/// A sender dropped without sending doesn't end the race, unless every one of them is: the error
+/// of the last receiver to fail is returned then.
+///
+/// # Panics
+///
+/// Panics when `receivers` is empty.
+pub fn race<V>(receivers: Vec<Receiver<V>>) -> Race<V> {
+  assert!(!receivers.is_empty(), "oneshot::race needs a receiver");
+  Race { receivers: receivers.into_iter().map(Some).collect() }
+}
+
+/// Future returned by [`race`].
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Race<V> {
+  // Those which have failed are taken out.
+  receivers: Vec<Option<Receiver<V>>>,
+}
+
+impl<V> Future for Race<V> {
+  type Output = (usize, Result<V, SenderDroppedError>);
+
+  fn poll(
+    mut self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<Self::Output> {
+    let mut failed = None;
+    for (index, slot) in self.receivers.iter_mut().enumerate() {
+      let Some(receiver) = slot else { continue };
+      match Pin::new(receiver).poll(cx) {
+        Poll::Ready(Ok(value)) => {
+          self.receivers.clear();
+          return Poll::Ready((index, Ok(value)));
         }
-      },
-      Err(err) => Poll::Ready(Err(err)),
+        Poll::Ready(Err(err)) => {
+          *slot = None;
+          failed = Some((index, err));
+        }
+        Poll::Pending => {}
+      }
+    }
+    match failed {
+      Some((index, err)) if self.receivers.iter().all(Option::is_none) => {
+        Poll::Ready((index, Err(err)))
+      }
+      _ => Poll::Pending,
     }
   }
 }
 
+/// Receiver of [`channel_with_timeout`].
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct TimeoutReceiver<V> {
+  receiver: Receiver<V>,
+  // Dropped once the receiver is done, which removes the timer.
+  sleep: Option<Sleep>,
+}
+
+/// Why a [`TimeoutReceiver`] failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecvTimeoutError {
+  /// No value was sent in time.
+  Timeout,
+  /// The sender was dropped without sending.
+  SenderDropped,
+}
+
+impl Display for RecvTimeoutError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      RecvTimeoutError::Timeout => f.write_str("Timeout"),
+      RecvTimeoutError::SenderDropped => f.write_str("SenderDroppedError"),
+    }
+  }
+}
+
+impl Error for RecvTimeoutError {}
+
+impl<V> Future for TimeoutReceiver<V> {
+  type Output = Result<V, RecvTimeoutError>;
+
+  fn poll(
+    mut self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<Self::Output> {
+    let this = &mut *self;
+    // First, so a value which came in right at the deadline isn't lost.
+    if let Poll::Ready(result) = Pin::new(&mut this.receiver).poll(cx) {
+      this.sleep = None;
+      return Poll::Ready(result.map_err(|_| RecvTimeoutError::SenderDropped));
+    }
+    let sleep =
+      this.sleep.as_mut().expect("TimeoutReceiver polled after completion");
+    std::task::ready!(Pin::new(sleep).poll(cx));
+    this.sleep = None;
+    Poll::Ready(Err(RecvTimeoutError::Timeout))
+  }
+}
+
+/// Stream returned by [`Receiver::into_stream`].
+#[must_use = "streams do nothing unless polled"]
+pub struct IntoStream<V> {
+  // Dropped once the stream has ended.
+  receiver: Option<Receiver<V>>,
+}
+
+impl<V> Stream for IntoStream<V> {
+  type Item = V;
+
+  fn poll_next(
+    mut self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<Option<V>> {
+    let Some(receiver) = self.receiver.as_mut() else {
+      return Poll::Ready(None);
+    };
+    let result = std::task::ready!(Pin::new(receiver).poll(cx));
+    self.receiver = None;
+    Poll::Ready(result.ok())
+  }
+}
+
 #[crate::internal_test]
 async fn simple() {
   let (sender, receiver) = channel();
@@ -253,3 +834,420 @@ async fn simple() {
 
   assert!(receiver.await.unwrap() == 2);
 }
+
+#[crate::internal_test]
+async fn map() {
+  let (sender, receiver) = channel();
+  let mapped = receiver.map(|value: u32| value.to_string());
+  sender.send(42).unwrap();
+  assert_eq!(mapped.await.unwrap(), "42");
+
+  let (sender, receiver) = channel::<u32>();
+  drop(sender);
+  assert!(receiver.map(|_| unreachable!()).await.is_err());
+
+  // The value still goes to one reader only.
+  let (sender, receiver) = channel();
+  sender.send(1).unwrap();
+  assert_eq!(receiver.try_recv().unwrap(), Some(1));
+  assert!(receiver.map(|value: u32| value + 1).await.is_err());
+}
+
+#[crate::internal_test]
+async fn recv_through_mut_self() {
+  struct Request {
+    response: Receiver<u32>,
+  }
+
+  impl Request {
+    async fn response(&mut self) -> Result<u32, SenderDroppedError> {
+      self.response.recv().await
+    }
+  }
+
+  let (sender, response) = channel();
+  let mut request = Request { response };
+  // Dropping an unfinished `Recv` leaves the value to a later one.
+  assert!(crate::test_util::task::spawn(request.response())
+    .poll()
+    .is_pending());
+  sender.send(7).unwrap();
+  assert_eq!(request.response().await, Ok(7));
+  // It's been taken.
+  assert_eq!(request.response().await, Err(SenderDroppedError));
+}
+
+#[crate::internal_test]
+async fn merged_into_stream() {
+  use crate::{stream::StreamExt, sync::mpsc};
+
+  let (sender, receiver) = channel();
+  let (events, event_receiver) = mpsc::unbounded();
+  let mut merged = receiver.into_stream().merge(event_receiver);
+
+  events.send(1).unwrap();
+  assert_eq!(merged.next().await, Some(1));
+  sender.send(2).unwrap();
+  events.send(3).unwrap();
+  drop(events);
+  let mut rest = Vec::new();
+  while let Some(value) = merged.next().await {
+    rest.push(value);
+  }
+  rest.sort();
+  assert_eq!(rest, [2, 3]);
+
+  // A dropped sender ends it without an item.
+  let (sender, receiver) = channel::<u32>();
+  drop(sender);
+  let mut stream = receiver.into_stream();
+  assert_eq!(stream.next().await, None);
+  assert_eq!(stream.next().await, None);
+}
+
+#[test]
+fn waits_until_consumed() {
+  use crate::test_util::task;
+
+  let (sender, receiver) = channel();
+  let mut send = task::spawn(sender.send_and_wait_consumed(1));
+  assert!(send.poll().is_pending());
+  // Sent, but not taken yet.
+  assert!(receiver.is_done());
+  assert!(send.poll().is_pending());
+  assert!(!send.is_woken());
+
+  assert_eq!(receiver.try_recv(), Ok(Some(1)));
+  assert!(send.is_woken());
+  assert_eq!(send.poll(), Poll::Ready(Ok(())));
+
+  // A receiver dropped without taking it gives the value back.
+  let (sender, receiver) = channel();
+  let mut send = task::spawn(sender.send_and_wait_consumed(2));
+  assert!(send.poll().is_pending());
+  drop(receiver);
+  assert!(send.is_woken());
+  assert_eq!(send.poll(), Poll::Ready(Err(NotConsumedError(2))));
+}
+
+#[test]
+fn race_takes_first_value() {
+  use crate::test_util::task;
+
+  let (senders, receivers): (Vec<_>, Vec<_>) =
+    (0..3).map(|_| channel::<&str>()).unzip();
+  let mut senders = senders.into_iter();
+  let (first, second, third) =
+    (senders.next().unwrap(), senders.next().unwrap(), senders.next().unwrap());
+  let mut raced = task::spawn(race(receivers));
+  assert!(raced.poll().is_pending());
+
+  second.send("second").unwrap();
+  assert!(raced.is_woken());
+  assert_eq!(raced.poll(), Poll::Ready((1, Ok("second"))));
+  // The others are dropped.
+  assert!(first.send("first").is_err());
+  drop(third);
+
+  // Only when every sender is dropped does it fail.
+  let (senders, receivers): (Vec<_>, Vec<_>) =
+    (0..2).map(|_| channel::<u8>()).unzip();
+  let mut raced = task::spawn(race(receivers));
+  assert!(raced.poll().is_pending());
+  let mut senders = senders.into_iter();
+  drop(senders.next());
+  assert!(raced.poll().is_pending());
+  drop(senders.next());
+  assert_eq!(raced.poll(), Poll::Ready((1, Err(SenderDroppedError))));
+}
+
+#[crate::internal_test]
+async fn timeout_cancelled_by_value() {
+  let timers = || crate::context::try_handle().unwrap().time().timers();
+
+  let (sender, receiver) = channel_with_timeout(Duration::from_secs(60));
+  let mut receiver = crate::test_util::task::spawn(receiver);
+  assert!(receiver.poll().is_pending());
+  assert_eq!(timers(), 1);
+  sender.send(1).unwrap();
+  assert_eq!(receiver.poll(), Poll::Ready(Ok(1)));
+  // Gone before the receiver is.
+  assert_eq!(timers(), 0);
+
+  let (sender, receiver) = channel_with_timeout::<u8>(Duration::ZERO);
+  drop(sender);
+  assert_eq!(receiver.await, Err(RecvTimeoutError::SenderDropped));
+}
+
+#[crate::internal_test]
+async fn times_out() {
+  time::pause();
+  let start = time::now();
+  let (_sender, receiver) = channel_with_timeout::<u8>(Duration::from_secs(5));
+  assert_eq!(receiver.await, Err(RecvTimeoutError::Timeout));
+  assert_eq!(time::now() - start, Duration::from_secs(5));
+  time::resume();
+}
+
+#[test]
+fn deferred_wakes() {
+  use crate::test_util::task;
+
+  let (senders, receivers): (Vec<_>, Vec<_>) =
+    (0..3).map(|_| channel()).unzip();
+  let mut receivers: Vec<_> = receivers.into_iter().map(task::spawn).collect();
+  for receiver in &mut receivers {
+    assert!(receiver.poll().is_pending());
+  }
+
+  let mut batch = WakeBatch::new();
+  for (value, sender) in senders.into_iter().enumerate() {
+    sender.send_deferred(value, &mut batch).unwrap();
+  }
+  assert_eq!(batch.len(), 3);
+  assert!(receivers.iter().all(|receiver| !receiver.is_woken()));
+
+  batch.wake();
+  for (value, receiver) in receivers.iter_mut().enumerate() {
+    assert!(receiver.is_woken());
+    assert_eq!(receiver.poll(), Poll::Ready(Ok(value)));
+  }
+}
+
+#[crate::internal_test]
+async fn fan_out_under_lock() {
+  use crate::task;
+  use std::sync::Mutex;
+
+  let pending = Arc::new(Mutex::new(Vec::new()));
+  let answered = Arc::new(Mutex::new(0));
+  let requests: Vec<_> = (0..1000)
+    .map(|_| {
+      let (sender, receiver) = channel::<usize>();
+      pending.lock().unwrap().push(sender);
+      let answered = answered.clone();
+      task::spawn(async move {
+        let value = receiver.await.unwrap();
+        *answered.lock().unwrap() += value;
+      })
+    })
+    .collect();
+
+  let mut batch = WakeBatch::new();
+  {
+    let mut pending = pending.lock().unwrap();
+    for sender in pending.drain(..) {
+      sender.send_deferred(1, &mut batch).unwrap();
+    }
+  }
+  batch.wake();
+  for request in requests {
+    request.await.unwrap();
+  }
+  assert_eq!(*answered.lock().unwrap(), 1000);
+}
+
+#[test]
+fn send_racing_poll() {
+  use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    task::Wake,
+    thread,
+  };
+
+  #[derive(Default)]
+  struct Flag(AtomicBool);
+  impl Wake for Flag {
+    fn wake(self: std::sync::Arc<Self>) {
+      self.0.store(true, Ordering::SeqCst);
+    }
+  }
+
+  // A send landing between the receivers state check and the waker registration used to be lost,
+  // so the receiver was never woken up.
+  for _ in 0..20_000 {
+    let (sender, mut receiver) = channel();
+    let thread = thread::spawn(move || sender.send(1).unwrap());
+
+    loop {
+      let flag = std::sync::Arc::new(Flag::default());
+      let waker = flag.clone().into();
+      let poll = Pin::new(&mut receiver).poll(&mut Context::from_waker(&waker));
+
+      match poll {
+        Poll::Ready(value) => break assert_eq!(value.unwrap(), 1),
+        // The sender is done, so it must have woken the waker of this poll.
+        Poll::Pending if thread.is_finished() => {
+          assert!(flag.0.load(Ordering::SeqCst), "lost wakeup")
+        }
+        Poll::Pending => thread::yield_now(),
+      }
+    }
+    thread.join().unwrap();
+  }
+}
+
+#[test]
+fn ping_pong() {
+  use std::{
+    sync::mpsc,
+    task::Wake,
+    thread,
+    time::{Duration, Instant},
+  };
+
+  struct Unpark(thread::Thread);
+  impl Wake for Unpark {
+    fn wake(self: std::sync::Arc<Self>) {
+      self.0.unpark();
+    }
+  }
+
+  let (ping, pings) = mpsc::channel::<(u32, Sender<u32>)>();
+  let ponger = thread::spawn(move || {
+    for (value, pong) in pings {
+      pong.send(value + 1).unwrap();
+    }
+  });
+
+  let waker = std::sync::Arc::new(Unpark(thread::current())).into();
+  let mut cx = Context::from_waker(&waker);
+  for round in 0..20_000 {
+    let (sender, mut receiver) = channel();
+    ping.send((round, sender)).unwrap();
+    // A lost wakeup parks this thread for good, so give up after a while instead.
+    let value = loop {
+      match Pin::new(&mut receiver).poll(&mut cx) {
+        Poll::Ready(value) => break value.unwrap(),
+        Poll::Pending => {
+          let parked = Instant::now();
+          thread::park_timeout(Duration::from_secs(5));
+          assert!(
+            parked.elapsed() < Duration::from_secs(5),
+            "lost wakeup in round {round}"
+          );
+        }
+      }
+    };
+    assert_eq!(value, round + 1);
+  }
+
+  drop(ping);
+  ponger.join().unwrap();
+}
+
+#[test]
+fn send_wakes_once() {
+  use std::{
+    sync::atomic::{AtomicUsize, Ordering},
+    task::Wake,
+  };
+
+  #[derive(Default)]
+  struct Count(AtomicUsize);
+  impl Wake for Count {
+    fn wake(self: std::sync::Arc<Self>) {
+      self.0.fetch_add(1, Ordering::SeqCst);
+    }
+  }
+
+  let (sender, mut receiver) = channel();
+  let count = std::sync::Arc::new(Count::default());
+  let waker = count.clone().into();
+  let poll = Pin::new(&mut receiver).poll(&mut Context::from_waker(&waker));
+  assert!(poll.is_pending());
+
+  // Dropping the sender after the send used to wake the receiver a second time.
+  sender.send(1).unwrap();
+  assert_eq!(count.0.load(Ordering::SeqCst), 1);
+  assert_eq!(receiver.try_recv().unwrap(), Some(1));
+}
+
+#[test]
+fn sent_before_first_poll() {
+  use std::task::Wake;
+
+  struct Unwoken;
+  impl Wake for Unwoken {
+    fn wake(self: std::sync::Arc<Self>) {
+      panic!("nothing to wake for");
+    }
+  }
+
+  let (sender, mut receiver) = channel();
+  sender.send(1).unwrap();
+  let unwoken = std::sync::Arc::new(Unwoken);
+  let waker = unwoken.clone().into();
+  let poll = Pin::new(&mut receiver).poll(&mut Context::from_waker(&waker));
+  assert_eq!(poll, Poll::Ready(Ok(1)));
+  // Never cloned into the channel.
+  let state = receiver.channel.state.load();
+  assert!(!state.contains(ChannelState::WAKER_REGISTERED));
+  assert_eq!(std::sync::Arc::strong_count(&unwoken), 2);
+}
+
+#[cfg(not(loom))]
+#[test]
+fn one_allocation() {
+  use crate::test_util::alloc::allocations;
+
+  let before = allocations();
+  let (sender, receiver) = channel::<u64>();
+  assert_eq!(allocations() - before, 1);
+
+  sender.send(7).unwrap();
+  assert_eq!(receiver.try_recv().unwrap(), Some(7));
+  drop(receiver);
+  assert_eq!(allocations() - before, 1);
+
+  // State and waker are packed ahead of the value, the sender's waker comes after it.
+  assert_eq!(std::mem::size_of::<Channel<u64>>(), 48);
+}
+
+#[cfg(loom)]
+#[test]
+fn loom_send_recv() {
+  loom::model(|| {
+    let (sender, receiver) = channel();
+    let thread = loom::thread::spawn(move || sender.send(1).unwrap());
+    assert_eq!(loom::future::block_on(receiver).unwrap(), 1);
+    thread.join().unwrap();
+  });
+}
+
+#[cfg(loom)]
+#[test]
+fn loom_sender_dropped() {
+  loom::model(|| {
+    let (sender, receiver) = channel::<u8>();
+    let thread = loom::thread::spawn(move || drop(sender));
+    assert!(loom::future::block_on(receiver).is_err());
+    thread.join().unwrap();
+  });
+}
+
+#[cfg(loom)]
+#[test]
+fn loom_receiver_dropped() {
+  loom::model(|| {
+    let (sender, receiver) = channel::<Arc<u8>>();
+    let thread = loom::thread::spawn(move || drop(receiver));
+    // Either way the value is dropped once, loom reports the `Arc` if it leaks.
+    let _ = sender.send(Arc::new(1));
+    thread.join().unwrap();
+  });
+}
+
+// How a task handle waits for the task to complete without taking the output.
+#[cfg(loom)]
+#[test]
+fn loom_done_before_recv() {
+  loom::model(|| {
+    let (sender, mut receiver) = channel();
+    let thread = loom::thread::spawn(move || sender.send(1).unwrap());
+    loom::future::block_on(std::future::poll_fn(|cx| receiver.poll_done(cx)));
+    assert!(receiver.is_done());
+    assert_eq!(receiver.try_recv().unwrap(), Some(1));
+    thread.join().unwrap();
+  });
+}
@@ -9,78 +9,351 @@ use std::{
     Mutex as StdMutex,
   },
   task::{Context, Poll, Waker},
+  time::{Duration, Instant},
 };
 
 pub struct Semaphore {
   count: AtomicUsize,
+  // Permits go to the waiters in order, see `Semaphore::fair`.
+  fair: bool,
+  // See `Semaphore::eventually_fair`.
+  handoff: Option<Duration>,
+  // How many are queued in `waiters`, so a release with nobody waiting doesn't lock it.
+  waiting: AtomicUsize,
   // This is not a bottleneck
-  waiters: StdMutex<VecDeque<Waker>>,
+  waiters: StdMutex<Waiters>,
+}
+
+#[derive(Default)]
+struct Waiters {
+  next_id: usize,
+  // In the order they started waiting.
+  queue: VecDeque<(usize, Waker)>,
+  // In fair mode, or when handed off to, waiters which were given a permit and haven't taken it
+  // yet.
+  granted: Vec<usize>,
+  // When a release next hands off, see `Semaphore::eventually_fair`.
+  next_handoff: Option<Instant>,
 }
 
 impl Semaphore {
   pub fn with_size(size: NonZero<usize>) -> Self {
     Self {
       count: AtomicUsize::new(size.into()),
-      waiters: StdMutex::new(VecDeque::new()),
+      fair: false,
+      handoff: None,
+      waiting: AtomicUsize::new(0),
+      waiters: StdMutex::new(Waiters::default()),
     }
   }
 
+  /// Creates a semaphore which hands out permits in the order they were asked for.
+  ///
+  /// A released permit goes straight to the acquire which has waited the longest, and
+  /// [`Semaphore::try_acquire`] fails while anyone is waiting, so newer acquires can't get ahead
+  /// of older ones. The semaphore of [`Semaphore::with_size`] only wakes the longest waiter, which
+  /// can then find its permit taken by an acquire that came after it.
+  pub fn fair(size: NonZero<usize>) -> Self {
+    Self { fair: true, ..Self::with_size(size) }
+  }
+
+  /// Like [`Semaphore::with_size`], but once every `interval`, a release hands its permit straight
+  /// to the longest waiter instead of letting whoever asks first take it.
+  ///
+  /// Between hand-offs a busy acquirer can take permit after permit, which keeps the permits
+  /// moving, but the hand-offs make sure a waiter doesn't wait for much longer than `interval`.
+  pub(crate) fn eventually_fair(
+    size: NonZero<usize>,
+    interval: Duration,
+  ) -> Self {
+    Self { handoff: Some(interval), ..Self::with_size(size) }
+  }
+
   pub fn try_acquire<'a>(
     &'a self,
   ) -> Result<AcquireLock<'a>, AcquireLockError> {
-    let count = self.count.load(Ordering::Acquire);
+    if self.fair && self.waiting.load(Ordering::SeqCst) != 0 {
+      return Err(AcquireLockError);
+    }
+    self.take_permit()
+  }
 
-    if count > 0 {
-      self.count.store(count - 1, Ordering::Release);
-      Ok(AcquireLock(self))
-    } else {
-      Err(AcquireLockError)
+  fn take_permit(&self) -> Result<AcquireLock<'_>, AcquireLockError> {
+    // SeqCst, against the registration of a waiter, see `AcquireFuture::poll`.
+    let mut count = self.count.load(Ordering::SeqCst);
+    loop {
+      let Some(left) = count.checked_sub(1) else {
+        return Err(AcquireLockError);
+      };
+      match self.count.compare_exchange_weak(
+        count,
+        left,
+        Ordering::AcqRel,
+        Ordering::Acquire,
+      ) {
+        Ok(_) => return Ok(AcquireLock(self)),
+        Err(actual) => count = actual,
+      }
     }
   }
 
+  /// Takes a permit with a single compare and swap, if one is free and nobody is waiting for it.
+  pub(crate) fn try_acquire_uncontended(&self) -> Option<AcquireLock<'_>> {
+    if self.waiting.load(Ordering::SeqCst) != 0 {
+      return None;
+    }
+    let count = self.count.load(Ordering::Relaxed);
+    let left = count.checked_sub(1)?;
+    self
+      .count
+      .compare_exchange(count, left, Ordering::AcqRel, Ordering::Relaxed)
+      .ok()
+      .map(|_| AcquireLock(self))
+  }
+
   pub fn acquire<'a>(&'a self) -> AcquireFuture<'a> {
-    AcquireFuture { semaphore: self }
+    AcquireFuture { semaphore: self, slot: None }
+  }
+
+  /// Adds `permits` permits, which wake as many waiters.
+  pub fn add_permits(&self, permits: usize) {
+    self.release(permits);
+  }
+
+  /// Returns how many permits can be acquired right now.
+  pub fn available_permits(&self) -> usize {
+    self.count.load(Ordering::Acquire)
+  }
+
+  fn release(&self, permits: usize) {
+    if !self.fair {
+      let permits = permits - usize::from(permits > 0 && self.hand_off());
+      self.count.fetch_add(permits, Ordering::SeqCst);
+      self.wake(permits);
+      return;
+    }
+
+    // Everything happens under the lock in fair mode, a permit can't be counted as free while
+    // someone waits for it.
+    let mut waiters = self.waiters.lock().unwrap();
+    let granted = permits.min(waiters.queue.len());
+    let wakers: Vec<_> = waiters.queue.drain(..granted).collect();
+    for (id, _) in &wakers {
+      waiters.granted.push(*id);
+    }
+    self.waiting.fetch_sub(granted, Ordering::SeqCst);
+    self.count.fetch_add(permits - granted, Ordering::SeqCst);
+    drop(waiters);
+    for (_, waker) in wakers {
+      waker.wake();
+    }
+  }
+
+  // Gives a permit to the longest waiter if it's time for a hand-off, returns whether it did.
+  fn hand_off(&self) -> bool {
+    let Some(interval) = self.handoff else {
+      return false;
+    };
+    if self.waiting.load(Ordering::SeqCst) == 0 {
+      return false;
+    }
+    let mut waiters = self.waiters.lock().unwrap();
+    let now = Instant::now();
+    if waiters.next_handoff.is_some_and(|next| now < next) {
+      return false;
+    }
+    let Some((id, waker)) = waiters.queue.pop_front() else {
+      return false;
+    };
+    waiters.granted.push(id);
+    waiters.next_handoff = Some(now + interval);
+    self.waiting.fetch_sub(1, Ordering::SeqCst);
+    drop(waiters);
+    waker.wake();
+    true
+  }
+
+  // Wakes the `count` longest waiters.
+  fn wake(&self, count: usize) {
+    if self.waiting.load(Ordering::SeqCst) == 0 {
+      return;
+    }
+    let wakers: Vec<_> = {
+      let mut waiters = self.waiters.lock().unwrap();
+      let count = count.min(waiters.queue.len());
+      waiters.queue.drain(..count).collect()
+    };
+    self.waiting.fetch_sub(wakers.len(), Ordering::SeqCst);
+    for (_, waker) in wakers {
+      waker.wake();
+    }
   }
 }
 
+#[must_use = "futures do nothing unless you `.await` or poll them"]
 pub struct AcquireFuture<'a> {
   semaphore: &'a Semaphore,
+  // Waiter id, once registered.
+  slot: Option<usize>,
+}
+
+impl<'a> AcquireFuture<'a> {
+  // Returns `true` if the waiter was still queued, `false` if a release has already woken it.
+  fn remove_waiter(&mut self) -> bool {
+    let Some(id) = self.slot.take() else {
+      return false;
+    };
+    let mut waiters = self.semaphore.waiters.lock().unwrap();
+    let position = waiters.queue.iter().position(|(other, _)| *other == id);
+    let removed = position.and_then(|position| waiters.queue.remove(position));
+    if removed.is_some() {
+      self.semaphore.waiting.fetch_sub(1, Ordering::SeqCst);
+    } else if self.semaphore.handoff.is_some() {
+      // Handed a permit it isn't going to take, which goes to the next in line.
+      if let Some(position) = waiters.granted.iter().position(|&g| g == id) {
+        waiters.granted.swap_remove(position);
+        drop(waiters);
+        self.semaphore.release(1);
+      }
+    }
+    removed.is_some()
+  }
+
+  // Takes the permit a hand-off has given this waiter.
+  fn take_handed_off(&mut self) -> Option<AcquireLock<'a>> {
+    let id = self.slot?;
+    let mut waiters = self.semaphore.waiters.lock().unwrap();
+    let position = waiters.granted.iter().position(|&g| g == id)?;
+    waiters.granted.swap_remove(position);
+    self.slot = None;
+    Some(AcquireLock(self.semaphore))
+  }
+
+  // Takes the permit a release has given this waiter, or waits in line for one.
+  fn poll_fair(&mut self, cx: &mut Context<'_>) -> Poll<AcquireLock<'a>> {
+    let semaphore = self.semaphore;
+    let mut waiters = semaphore.waiters.lock().unwrap();
+    match self.slot {
+      Some(id) => {
+        if let Some(position) = waiters.granted.iter().position(|&g| g == id) {
+          waiters.granted.swap_remove(position);
+          self.slot = None;
+          return Poll::Ready(AcquireLock(semaphore));
+        }
+        let registered =
+          waiters.queue.iter_mut().find(|(other, _)| *other == id);
+        if let Some((_, waker)) = registered {
+          if !waker.will_wake(cx.waker()) {
+            *waker = cx.waker().clone();
+          }
+        }
+      }
+      None => {
+        if waiters.queue.is_empty() {
+          if let Ok(lock) = semaphore.take_permit() {
+            return Poll::Ready(lock);
+          }
+        }
+        waiters.next_id += 1;
+        let id = waiters.next_id;
+        waiters.queue.push_back((id, cx.waker().clone()));
+        semaphore.waiting.fetch_add(1, Ordering::SeqCst);
+        self.slot = Some(id);
+      }
+    }
+    Poll::Pending
+  }
 }
 
 impl<'a> Future for AcquireFuture<'a> {
   type Output = AcquireLock<'a>;
 
-  fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+  fn poll(
+    mut self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<Self::Output> {
+    if self.semaphore.fair {
+      return self.poll_fair(cx);
+    }
+    if self.semaphore.handoff.is_some() {
+      if let Some(lock) = self.take_handed_off() {
+        return Poll::Ready(lock);
+      }
+    }
+
+    if let Ok(lock) = self.semaphore.try_acquire() {
+      self.remove_waiter();
+      return Poll::Ready(lock);
+    }
+
+    let mut waiters = self.semaphore.waiters.lock().unwrap();
+    let registered = self
+      .slot
+      .and_then(|id| waiters.queue.iter_mut().find(|(other, _)| *other == id));
+    match registered {
+      Some((_, waker)) => {
+        if !waker.will_wake(cx.waker()) {
+          *waker = cx.waker().clone();
+        }
+      }
+      // Not registered yet, or woken by a release which another acquire got to first.
+      None => {
+        waiters.next_id += 1;
+        let id = waiters.next_id;
+        waiters.queue.push_back((id, cx.waker().clone()));
+        self.semaphore.waiting.fetch_add(1, Ordering::SeqCst);
+        self.slot = Some(id);
+      }
+    }
+    drop(waiters);
+
+    // A release between the first try and the registration would have found no waiter. Both sides
+    // are SeqCst, so either the release sees this waiter, or this sees the permit.
     match self.semaphore.try_acquire() {
-      Ok(lock) => Poll::Ready(lock),
-      Err(_) => {
-        let mut lock = self.semaphore.waiters.lock().unwrap();
-        lock.push_back(cx.waker().clone());
-        Poll::Pending
+      Ok(lock) => {
+        self.remove_waiter();
+        Poll::Ready(lock)
       }
+      Err(_) => Poll::Pending,
     }
   }
 }
 
+impl Drop for AcquireFuture<'_> {
+  fn drop(&mut self) {
+    let Some(id) = self.slot else {
+      return;
+    };
+    if self.remove_waiter() {
+      return;
+    }
+    if self.semaphore.fair {
+      // Given a permit it didn't take, which goes to the next in line.
+      let mut waiters = self.semaphore.waiters.lock().unwrap();
+      if let Some(position) = waiters.granted.iter().position(|&g| g == id) {
+        waiters.granted.swap_remove(position);
+        drop(waiters);
+        self.semaphore.release(1);
+      }
+    } else {
+      // Dropped after a release woke it, hand the wake on so the permit isn't left unclaimed.
+      self.semaphore.wake(1);
+    }
+  }
+}
+
+/// A permit of the [`Semaphore`], given back when dropped.
 pub struct AcquireLock<'a>(&'a Semaphore);
 
 impl AcquireLock<'_> {
-  pub fn release(&self) {
-    let semaphore = self.0;
-    let count = semaphore.count.fetch_add(1, Ordering::Release);
-
-    if count == 0 {
-      let mut lock = self.0.waiters.lock().unwrap();
-      if let Some(waker) = lock.pop_front() {
-        waker.wake();
-      }
-    }
+  pub fn release(self) {
+    drop(self);
   }
 }
 
 impl Drop for AcquireLock<'_> {
   fn drop(&mut self) {
-    self.release();
+    self.0.release(1);
   }
 }
 
@@ -135,3 +408,105 @@ fn size_not_one() {
   let lock6 = semaphore.try_acquire();
   assert!(lock6.is_err());
 }
+
+#[crate::internal_test]
+async fn released_permit_wakes_waiter() {
+  use std::sync::Arc;
+
+  let semaphore = Arc::new(Semaphore::with_size(1.try_into().unwrap()));
+  let permit = semaphore.try_acquire().unwrap();
+
+  let waiter = semaphore.clone();
+  let handle = crate::task::spawn(async move {
+    drop(waiter.acquire().await);
+  });
+
+  crate::time::sleep(std::time::Duration::from_millis(5)).await;
+  permit.release();
+  handle.await.unwrap();
+
+  // Every permit is back, and not more.
+  let permit = semaphore.try_acquire().unwrap();
+  assert!(semaphore.try_acquire().is_err());
+  drop(permit);
+}
+
+#[test]
+fn dropped_waiter_passes_wake_on() {
+  let semaphore = Semaphore::with_size(1.try_into().unwrap());
+  let waker = Waker::noop();
+  let mut cx = Context::from_waker(waker);
+
+  let permit = semaphore.try_acquire().unwrap();
+  let mut first = semaphore.acquire();
+  let mut second = semaphore.acquire();
+  assert!(Pin::new(&mut first).poll(&mut cx).is_pending());
+  assert!(Pin::new(&mut second).poll(&mut cx).is_pending());
+
+  // Wakes `first`, which is dropped without taking the permit.
+  drop(permit);
+  drop(first);
+
+  assert!(semaphore.waiters.lock().unwrap().queue.is_empty());
+  assert!(Pin::new(&mut second).poll(&mut cx).is_ready());
+}
+
+#[test]
+fn fair_in_request_order() {
+  use crate::test_util::task;
+
+  let semaphore = Semaphore::fair(1.try_into().unwrap());
+  let permit = semaphore.try_acquire().unwrap();
+  let mut acquires: Vec<_> =
+    (0..4).map(|_| task::spawn(semaphore.acquire())).collect();
+  for acquire in &mut acquires {
+    assert!(acquire.poll().is_pending());
+  }
+
+  // Both go to the two longest waiters, nothing is left for a newcomer to take.
+  semaphore.add_permits(2);
+  assert!(semaphore.try_acquire().is_err());
+  let woken: Vec<_> =
+    acquires.iter().map(|acquire| acquire.is_woken()).collect();
+  assert_eq!(woken, [true, true, false, false]);
+  // The order they're polled in doesn't matter.
+  let Poll::Ready(second) = acquires[1].poll() else { panic!() };
+  let Poll::Ready(first) = acquires[0].poll() else { panic!() };
+
+  drop(second);
+  assert!(acquires[2].is_woken() && !acquires[3].is_woken());
+  let Poll::Ready(third) = acquires[2].poll() else { panic!() };
+  drop(permit);
+  let Poll::Ready(fourth) = acquires[3].poll() else { panic!() };
+
+  drop((first, third, fourth));
+  assert_eq!(semaphore.available_permits(), 3);
+}
+
+#[test]
+fn hands_off_to_waiter() {
+  use crate::test_util::task;
+
+  let semaphore =
+    Semaphore::eventually_fair(1.try_into().unwrap(), Duration::from_secs(60));
+  let permit = semaphore.try_acquire().unwrap();
+  let mut first = task::spawn(semaphore.acquire());
+  let mut second = task::spawn(semaphore.acquire());
+  assert!(first.poll().is_pending() && second.poll().is_pending());
+
+  // The first release hands off, nobody else can take the permit.
+  drop(permit);
+  assert!(semaphore.try_acquire().is_err());
+  assert!(first.is_woken());
+  let Poll::Ready(permit) = first.poll() else { panic!() };
+
+  // Too soon for another, so the woken waiter has to race for it.
+  drop(permit);
+  assert!(second.is_woken());
+  let barging = semaphore.try_acquire().unwrap();
+  assert!(second.poll().is_pending());
+  drop(barging);
+  let Poll::Ready(permit) = second.poll() else { panic!() };
+  drop(permit);
+  assert_eq!(semaphore.available_permits(), 1);
+}
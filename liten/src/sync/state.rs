@@ -0,0 +1,58 @@
+use std::marker::PhantomData;
+
+use bitflags::Flags;
+
+use crate::loom::sync::atomic::{AtomicU8, Ordering};
+
+/// An atomic set of flags, the channels keep their state in one of these.
+pub(crate) struct AtomicState<S> {
+  bits: AtomicU8,
+  _state: PhantomData<S>,
+}
+
+impl<S: Flags<Bits = u8> + Copy> AtomicState<S> {
+  pub(crate) fn new(state: S) -> AtomicState<S> {
+    AtomicState { bits: AtomicU8::new(state.bits()), _state: PhantomData }
+  }
+
+  pub(crate) fn load(&self) -> S {
+    S::from_bits_retain(self.bits.load(Ordering::SeqCst))
+  }
+
+  /// Sets the state to `new` if it's `current`, and returns the state it had either way.
+  pub(crate) fn compare_exchange(&self, current: S, new: S) -> Result<S, S> {
+    self
+      .bits
+      .compare_exchange(
+        current.bits(),
+        new.bits(),
+        Ordering::SeqCst,
+        Ordering::SeqCst,
+      )
+      .map(S::from_bits_retain)
+      .map_err(S::from_bits_retain)
+  }
+
+  /// Adds `flags` to the state in one step, and returns the state it had.
+  pub(crate) fn fetch_insert(&self, flags: S) -> S {
+    S::from_bits_retain(self.bits.fetch_or(flags.bits(), Ordering::SeqCst))
+  }
+
+  /// Updates the state with `f` until it isn't changed in between, or `f` returns `None`. Returns
+  /// the previous state.
+  pub(crate) fn fetch_update(
+    &self,
+    mut f: impl FnMut(S) -> Option<S>,
+  ) -> Result<S, S> {
+    let mut current = self.load();
+    loop {
+      let Some(new) = f(current) else {
+        return Err(current);
+      };
+      match self.compare_exchange(current, new) {
+        Ok(previous) => return Ok(previous),
+        Err(actual) => current = actual,
+      }
+    }
+  }
+}
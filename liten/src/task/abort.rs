@@ -0,0 +1,256 @@
+use std::{
+  future::Future,
+  pin::Pin,
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex as StdMutex,
+  },
+  task::{Context, Poll, Waker},
+  time::{Duration, Instant},
+};
+
+use pin_project_lite::pin_project;
+
+use super::{builder, TaskHandle};
+use crate::time::{self, Sleep};
+
+/// Spawns `fut`, and returns an [`AbortHandle`] for it along with its handle.
+///
+/// Both exist from the moment the task is spawned, so the abort handle can be stored away before
+/// the task has had any chance to run.
+#[track_caller]
+pub fn spawn_with_abort<F>(fut: F) -> (TaskHandle<F::Output>, AbortHandle)
+where
+  F: Future + Send + 'static,
+  F::Output: Send,
+{
+  let abort = Arc::new(AbortState::default());
+  let handle = builder()
+    .spawn_inner(fut, Some(abort.clone()), "task::spawn_with_abort")
+    .unwrap_or_else(|err| panic!("{err}"));
+  (handle, AbortHandle(abort))
+}
+
+/// Spawns `fut`, which is dropped if it hasn't completed `timeout` after it's spawned. Its handle
+/// then fails with [`TaskHandleError::TimedOut`](super::TaskHandleError::TimedOut).
+///
+/// The timer is the task's own, it's registered when the task first runs and removed along with
+/// the task, so bounding a background job doesn't take racing it against a
+/// [`sleep`](crate::time::sleep) of its own.
+#[track_caller]
+pub fn spawn_timeout<F>(timeout: Duration, fut: F) -> TaskHandle<F::Output>
+where
+  F: Future + Send + 'static,
+  F::Output: Send,
+{
+  let abort = Arc::new(AbortState {
+    deadline: Some(time::now() + timeout),
+    ..AbortState::default()
+  });
+  builder()
+    .spawn_inner(fut, Some(abort), "task::spawn_timeout")
+    .unwrap_or_else(|err| panic!("{err}"))
+}
+
+/// Stops a task spawned with [`spawn_with_abort`].
+#[derive(Clone, Debug)]
+pub struct AbortHandle(Arc<AbortState>);
+
+impl AbortHandle {
+  /// Drops the task's future the next time the task would be polled, or right away if it's
+  /// waiting. Its handle then fails with
+  /// [`TaskHandleError::Aborted`](super::TaskHandleError::Aborted).
+  ///
+  /// A task which has already completed keeps its output.
+  pub fn abort(&self) {
+    self.0.aborted.store(true, Ordering::SeqCst);
+    if let Some(waker) = self.0.waker.lock().unwrap().take() {
+      waker.wake();
+    }
+  }
+
+  /// Returns `true` once [`AbortHandle::abort`] has been called.
+  pub fn is_aborted(&self) -> bool {
+    self.0.is_aborted()
+  }
+}
+
+#[derive(Default, Debug)]
+pub(crate) struct AbortState {
+  aborted: AtomicBool,
+  // This is not a bottleneck
+  waker: StdMutex<Option<Waker>>,
+  // For `spawn_timeout`, the task is dropped once it's reached.
+  deadline: Option<Instant>,
+  timed_out: AtomicBool,
+}
+
+impl AbortState {
+  pub(crate) fn is_aborted(&self) -> bool {
+    self.aborted.load(Ordering::SeqCst)
+  }
+
+  pub(crate) fn is_timed_out(&self) -> bool {
+    self.timed_out.load(Ordering::SeqCst)
+  }
+}
+
+pin_project! {
+  // Completes with `None` once aborted or past its deadline, without polling the future again.
+  pub(crate) struct Abortable<F> {
+    #[pin]
+    future: F,
+    abort: Arc<AbortState>,
+    // Made on the first poll, which runs in the runtime.
+    sleep: Option<Sleep>,
+  }
+}
+
+impl<F> Abortable<F> {
+  pub(crate) fn new(future: F, abort: Arc<AbortState>) -> Abortable<F> {
+    Abortable { future, abort, sleep: None }
+  }
+}
+
+impl<F: Future> Future for Abortable<F> {
+  type Output = Option<F::Output>;
+
+  fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+    let this = self.project();
+    {
+      let mut waker = this.abort.waker.lock().unwrap();
+      match waker.as_ref() {
+        Some(waker) if waker.will_wake(cx.waker()) => {}
+        _ => *waker = Some(cx.waker().clone()),
+      }
+    }
+    // After the waker is stored, so an abort in between still wakes the task.
+    if this.abort.is_aborted() {
+      return Poll::Ready(None);
+    }
+    if let Poll::Ready(output) = this.future.poll(cx) {
+      return Poll::Ready(Some(output));
+    }
+
+    let Some(deadline) = this.abort.deadline else {
+      return Poll::Pending;
+    };
+    let sleep = this.sleep.get_or_insert_with(|| time::sleep_until(deadline));
+    std::task::ready!(Pin::new(sleep).poll(cx));
+    this.abort.timed_out.store(true, Ordering::SeqCst);
+    Poll::Ready(None)
+  }
+}
+
+#[crate::internal_test]
+async fn abort_stored_handle() {
+  use crate::{sync::oneshot, task::TaskHandleError};
+  use std::sync::atomic::AtomicUsize;
+
+  let (handle, abort) = spawn_with_abort(async { 1 });
+  // Kept somewhere else than the handle, like in a table of connections.
+  struct Connection {
+    abort: AbortHandle,
+  }
+  let connection = Connection { abort };
+  assert_eq!(handle.await.unwrap(), 1);
+  // Too late, the output is already there.
+  connection.abort.abort();
+
+  static DROPPED: AtomicUsize = AtomicUsize::new(0);
+  struct Dropped;
+  impl Drop for Dropped {
+    fn drop(&mut self) {
+      DROPPED.fetch_add(1, Ordering::SeqCst);
+    }
+  }
+
+  let (_sender, receiver) = oneshot::channel::<()>();
+  let dropped = Dropped;
+  let (handle, abort) = spawn_with_abort(async move {
+    let _dropped = dropped;
+    receiver.await.unwrap();
+  });
+  abort.abort();
+  assert!(abort.is_aborted());
+  assert!(matches!(handle.await, Err(TaskHandleError::Aborted)));
+  assert_eq!(DROPPED.load(Ordering::SeqCst), 1);
+
+  // Aborting a waiting task wakes it.
+  let (started, has_started) = oneshot::channel();
+  let (_sender, receiver) = oneshot::channel::<()>();
+  let (handle, abort) = spawn_with_abort(async move {
+    started.send(()).unwrap();
+    receiver.await.unwrap();
+  });
+  has_started.await.unwrap();
+  abort.abort();
+  assert!(matches!(handle.await, Err(TaskHandleError::Aborted)));
+}
+
+#[crate::internal_test]
+async fn abort_releases_lock() {
+  use crate::{
+    sync::{oneshot, Mutex},
+    task::{self, TaskHandleError},
+    time,
+  };
+  use std::time::Duration;
+
+  let mutex = Arc::new(Mutex::new(0));
+  let (locked, has_locked) = oneshot::channel();
+  let (_sender, receiver) = oneshot::channel::<()>();
+  let (holder, abort) = spawn_with_abort({
+    let mutex = mutex.clone();
+    async move {
+      let mut guard = mutex.lock().await.unwrap();
+      *guard += 1;
+      locked.send(()).unwrap();
+      receiver.await.unwrap();
+    }
+  });
+  has_locked.await.unwrap();
+
+  let waiter = task::spawn({
+    let mutex = mutex.clone();
+    async move { *mutex.lock().await.unwrap() += 1 }
+  });
+  abort.abort();
+  assert!(matches!(holder.await, Err(TaskHandleError::Aborted)));
+  crate::select! {
+    waited = waiter => waited.unwrap(),
+    () = time::sleep(Duration::from_secs(5)) => panic!("the lock was kept"),
+  }
+  assert_eq!(*mutex.try_lock().unwrap(), 2);
+}
+
+#[crate::internal_test]
+async fn timeout_drops_task() {
+  use crate::task::TaskHandleError;
+  use std::{future::pending, sync::atomic::AtomicUsize};
+
+  static DROPPED: AtomicUsize = AtomicUsize::new(0);
+  struct Dropped;
+  impl Drop for Dropped {
+    fn drop(&mut self) {
+      DROPPED.fetch_add(1, Ordering::SeqCst);
+    }
+  }
+
+  let start = time::now();
+  let dropped = Dropped;
+  let handle = spawn_timeout(Duration::from_millis(20), async move {
+    let _dropped = dropped;
+    pending::<()>().await;
+  });
+  assert!(matches!(handle.await, Err(TaskHandleError::TimedOut)));
+  assert!(time::now() - start >= Duration::from_millis(20));
+  assert_eq!(DROPPED.load(Ordering::SeqCst), 1);
+
+  // In time, the output is there.
+  let handle = spawn_timeout(Duration::from_secs(5), async {
+    time::sleep(Duration::from_millis(1)).await;
+    1
+  });
+  assert_eq!(handle.await.unwrap(), 1);
+}
@@ -0,0 +1,82 @@
+use std::thread::{self, Scope, ScopedJoinHandle};
+
+/// Runs `f` with a [`BlockScope`], whose blocking closures can borrow from the caller's stack,
+/// and returns once every one of them has returned.
+///
+/// Each closure gets a thread of its own, so blocking in one doesn't hold up the others or the
+/// runtime's workers. The caller is blocked for as long as they run though: in a task, this
+/// blocks the worker it runs on, nothing else is run there meanwhile.
+///
+/// A closure which panics and isn't joined makes `block_scope` panic once the others are done.
+///
+/// ```
+/// let mut buf = [0u8; 8];
+/// liten::task::block_scope(|scope| {
+///   for (index, chunk) in buf.chunks_mut(4).enumerate() {
+///     scope.spawn_blocking(move || chunk.fill(index as u8));
+///   }
+/// });
+/// assert_eq!(buf, [0, 0, 0, 0, 1, 1, 1, 1]);
+/// ```
+pub fn block_scope<'env, F, R>(f: F) -> R
+where
+  F: for<'scope> FnOnce(BlockScope<'scope, 'env>) -> R,
+{
+  thread::scope(|scope| f(BlockScope { scope }))
+}
+
+/// Spawns blocking closures which borrow from the caller of [`block_scope`].
+#[derive(Clone, Copy)]
+pub struct BlockScope<'scope, 'env: 'scope> {
+  scope: &'scope Scope<'scope, 'env>,
+}
+
+impl<'scope, 'env> BlockScope<'scope, 'env> {
+  /// Runs `f` on a thread of its own. Its handle can be joined for the return value, the
+  /// closures which aren't are joined when the scope ends.
+  ///
+  /// Inside a runtime, the thread gets the stack size set with
+  /// [`Builder::blocking_thread_stack_size`](crate::runtime::Builder::blocking_thread_stack_size).
+  pub fn spawn_blocking<F, T>(&self, f: F) -> ScopedJoinHandle<'scope, T>
+  where
+    F: FnOnce() -> T + Send + 'scope,
+    T: Send + 'scope,
+  {
+    let mut builder = thread::Builder::new().name("liten-blocking".to_string());
+    if let Some(size) = crate::context::try_handle()
+      .and_then(|handle| handle.blocking_stack_size())
+    {
+      builder = builder.stack_size(size);
+    }
+    builder
+      .spawn_scoped(self.scope, f)
+      .expect("couldn't spawn a blocking thread")
+  }
+}
+
+#[test]
+fn fill_borrowed_buffer() {
+  let mut buf = vec![0usize; 1024];
+  let offset = 10;
+  block_scope(|scope| {
+    for (index, chunk) in buf.chunks_mut(256).enumerate() {
+      // Borrows `offset` too.
+      scope.spawn_blocking(move || chunk.fill(offset + index));
+    }
+  });
+  for (index, chunk) in buf.chunks(256).enumerate() {
+    assert!(chunk.iter().all(|&value| value == offset + index));
+  }
+}
+
+#[crate::internal_test]
+async fn join_from_task() {
+  let numbers: Vec<u64> = (0..100).collect();
+  let (low, high) = numbers.split_at(50);
+  let sum = block_scope(|scope| {
+    let low = scope.spawn_blocking(|| low.iter().sum::<u64>());
+    let high = scope.spawn_blocking(|| high.iter().sum::<u64>());
+    low.join().unwrap() + high.join().unwrap()
+  });
+  assert_eq!(sum, 99 * 100 / 2);
+}
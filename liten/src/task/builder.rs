@@ -1,35 +1,102 @@
 use std::{future::Future, sync::Arc};
 
-use crate::{context, sync::oneshot};
+use crate::{
+  context,
+  runtime::{snapshot, RuntimeEvent},
+  sync::oneshot,
+};
 
-use super::{Task, TaskHandle, TaskId};
+use super::{AbortState, Priority, SpawnError, Task, TaskHandle, TaskId};
 
 pub struct Builder {
-  id: TaskId,
   name: Option<String>,
+  priority: Priority,
+}
+
+impl Default for Builder {
+  fn default() -> Self {
+    Self::new()
+  }
 }
 
 impl Builder {
   pub fn new() -> Self {
-    Builder { id: TaskId::new(), name: None }
+    Builder { name: None, priority: Priority::default() }
   }
   pub fn name(mut self, name: impl Into<String>) -> Self {
     self.name = Some(name.into());
     self
   }
+  pub fn priority(mut self, priority: Priority) -> Self {
+    self.priority = priority;
+    self
+  }
+  /// Spawns the task.
+  ///
+  /// # Panics
+  ///
+  /// Panics outside of a runtime, or if the runtime's task limit rejects it, see
+  /// [`Builder::try_build`].
+  #[track_caller]
   pub fn build<F>(self, fut: F) -> TaskHandle<F::Output>
   where
     F: Future + Send + 'static,
     F::Output: Send,
   {
-    let (write, read) = oneshot::channel();
+    self
+      .spawn_inner(fut, None, "task::Builder::build")
+      .unwrap_or_else(|err| panic!("{err}"))
+  }
 
-    let task = Arc::new(Task::new(self.id, fut, write));
-    context::with_context(|ctx| {
-      ctx.handle().state().push_task(task);
-    });
+  /// Spawns the task, or returns an error outside of a runtime or if the runtime's
+  /// [task limit](crate::runtime::Builder::max_concurrent_tasks) rejects it.
+  #[track_caller]
+  pub fn try_build<F>(self, fut: F) -> Result<TaskHandle<F::Output>, SpawnError>
+  where
+    F: Future + Send + 'static,
+    F::Output: Send,
+  {
+    self.spawn_inner(fut, None, "task::Builder::try_build")
+  }
+
+  // Spawns the task, which can be aborted through `abort` if it's given. `api` is what was called,
+  // for the error outside of a runtime.
+  #[track_caller]
+  pub(super) fn spawn_inner<F>(
+    self,
+    fut: F,
+    abort: Option<Arc<AbortState>>,
+    api: &'static str,
+  ) -> Result<TaskHandle<F::Output>, SpawnError>
+  where
+    F: Future + Send + 'static,
+    F::Output: Send,
+  {
+    let handle = context::handle_for(api)?;
+    let id = TaskId(handle.task_id_inc());
+    let (write, read) = oneshot::channel();
+    #[cfg(feature = "tracing")]
+    let location = std::panic::Location::caller();
 
-    TaskHandle(read)
+    let permit = handle.acquire_task()?;
+    #[allow(unused_mut)]
+    let mut task =
+      Task::new(id, self.priority, fut, write, permit, abort.clone());
+    #[cfg(feature = "tracing")]
+    {
+      task.span = tracing::trace_span!(
+        "task",
+        task.id = id.0,
+        task.name = self.name.as_deref(),
+        priority = ?self.priority,
+        spawned_at = %location,
+      );
+      tracing::trace!(parent: &task.span, "spawned");
+    }
+    snapshot::increment(&handle.counters().tasks_spawned);
+    handle.instrument().emit(|| RuntimeEvent::TaskSpawned { task: id });
+    handle.state().push_task(Arc::new(task));
+    Ok(TaskHandle(read, abort))
   }
 }
 
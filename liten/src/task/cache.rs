@@ -0,0 +1,183 @@
+use std::{
+  alloc::{self, Layout},
+  cell::RefCell,
+  future::Future,
+  pin::Pin,
+  ptr::NonNull,
+};
+
+// Futures up to `MIN_SIZE << (CLASSES - 1)` bytes are allocated in blocks of a power of two,
+// which are kept for the next task once the one using them completes.
+const MIN_SIZE: usize = 64;
+const CLASSES: usize = 7;
+const ALIGN: usize = 16;
+// Per class and per thread, so a burst of tasks completing doesn't keep all of their memory.
+const MAX_CACHED: usize = 64;
+
+std::thread_local! {
+  static CACHE: RefCell<Cache> = RefCell::default();
+}
+
+// Blocks freed by tasks which completed on this thread, by class. A block is only memory of the
+// class's layout, so it doesn't matter which thread allocated it: a task stolen by another worker
+// gives its block to that worker.
+#[derive(Default)]
+struct Cache {
+  free: [Vec<NonNull<u8>>; CLASSES],
+}
+
+impl Drop for Cache {
+  fn drop(&mut self) {
+    for (class, blocks) in self.free.iter_mut().enumerate() {
+      for block in blocks.drain(..) {
+        // SAFETY: Allocated with the layout of its class.
+        unsafe { alloc::dealloc(block.as_ptr(), class_layout(class)) };
+      }
+    }
+  }
+}
+
+fn class(layout: Layout) -> Option<usize> {
+  if layout.align() > ALIGN || layout.size() > MIN_SIZE << (CLASSES - 1) {
+    return None;
+  }
+  let size = layout.size().max(MIN_SIZE).next_power_of_two();
+  Some((size / MIN_SIZE).trailing_zeros() as usize)
+}
+
+fn class_layout(class: usize) -> Layout {
+  Layout::from_size_align(MIN_SIZE << class, ALIGN).unwrap()
+}
+
+fn allocate(layout: Layout) -> NonNull<u8> {
+  let (layout, cached) = match class(layout) {
+    Some(class) => {
+      // Not there while the thread is exiting.
+      let cached = CACHE
+        .try_with(|cache| cache.borrow_mut().free[class].pop())
+        .ok()
+        .flatten();
+      (class_layout(class), cached)
+    }
+    None => (layout, None),
+  };
+  cached.unwrap_or_else(|| {
+    // SAFETY: Not zero-sized, those aren't allocated.
+    let ptr = unsafe { alloc::alloc(layout) };
+    NonNull::new(ptr).unwrap_or_else(|| alloc::handle_alloc_error(layout))
+  })
+}
+
+fn deallocate(ptr: NonNull<u8>, layout: Layout) {
+  let Some(class) = class(layout) else {
+    // SAFETY: Allocated by `allocate` with the same layout.
+    return unsafe { alloc::dealloc(ptr.as_ptr(), layout) };
+  };
+  let kept = CACHE.try_with(|cache| {
+    let blocks = &mut cache.borrow_mut().free[class];
+    let keep = blocks.len() < MAX_CACHED;
+    if keep {
+      blocks.push(ptr);
+    }
+    keep
+  });
+  if kept != Ok(true) {
+    // SAFETY: Allocated with the layout of its class.
+    unsafe { alloc::dealloc(ptr.as_ptr(), class_layout(class)) };
+  }
+}
+
+/// The future of a task, in memory reused from tasks which completed before it on the same thread.
+pub(crate) struct TaskFuture {
+  future: NonNull<dyn Future<Output = ()> + Send>,
+}
+
+// SAFETY: It owns a future which is `Send`.
+unsafe impl Send for TaskFuture {}
+
+impl TaskFuture {
+  pub(crate) fn new<F>(future: F) -> TaskFuture
+  where
+    F: Future<Output = ()> + Send + 'static,
+  {
+    let layout = Layout::new::<F>();
+    let ptr = if layout.size() == 0 {
+      NonNull::<F>::dangling()
+    } else {
+      allocate(layout).cast::<F>()
+    };
+    // SAFETY: Allocated for an `F`, or an `F` doesn't take any place.
+    unsafe { ptr.as_ptr().write(future) };
+    TaskFuture { future: ptr }
+  }
+
+  pub(crate) fn as_mut(
+    &mut self,
+  ) -> Pin<&mut (dyn Future<Output = ()> + Send)> {
+    // SAFETY: The future never moves until it's dropped.
+    unsafe { Pin::new_unchecked(self.future.as_mut()) }
+  }
+}
+
+impl Drop for TaskFuture {
+  fn drop(&mut self) {
+    // SAFETY: Written in `new`, and only dropped here.
+    let layout = Layout::for_value(unsafe { self.future.as_ref() });
+    unsafe { self.future.as_ptr().drop_in_place() };
+    if layout.size() != 0 {
+      deallocate(self.future.cast(), layout);
+    }
+  }
+}
+
+#[test]
+fn reuses_completed_tasks_memory() {
+  use crate::test_util::alloc::allocations;
+
+  let before = allocations();
+  let buf = [1u8; 100];
+  let first = TaskFuture::new(async move {
+    std::hint::black_box(buf);
+  });
+  let address_of =
+    |future: &TaskFuture| future.future.as_ptr().cast::<u8>() as usize;
+  let address = address_of(&first);
+  assert_eq!(allocations() - before, 1);
+  drop(first);
+
+  // Another future of the same class, without the allocator.
+  let before = allocations();
+  let buf = [2u64; 15];
+  let second = TaskFuture::new(async move {
+    std::hint::black_box(buf);
+  });
+  assert_eq!(allocations() - before, 0);
+  assert_eq!(address_of(&second), address);
+
+  // Freed on another thread, like a stolen task, it goes to that thread's cache.
+  std::thread::spawn(move || {
+    drop(second);
+    let before = allocations();
+    let buf = [3u8; 90];
+    let third = TaskFuture::new(async move {
+      std::hint::black_box(buf);
+    });
+    assert_eq!(address_of(&third), address);
+    assert_eq!(allocations() - before, 0);
+  })
+  .join()
+  .unwrap();
+}
+
+#[crate::internal_test]
+async fn spawned_tasks_run() {
+  let tasks: Vec<_> = (0..100u64)
+    .map(|value| {
+      let large = [value; 16];
+      super::spawn(async move { large.iter().sum::<u64>() })
+    })
+    .collect();
+  for (value, task) in (0..100).zip(tasks) {
+    assert_eq!(task.await.unwrap(), value * 16);
+  }
+}
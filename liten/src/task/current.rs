@@ -0,0 +1,122 @@
+use std::{cell::RefCell, sync::Arc, task::Waker};
+
+use thiserror::Error;
+
+use super::{Priority, PriorityState, TaskId};
+
+thread_local! {
+  static CURRENT: RefCell<Option<CurrentTask>> = const { RefCell::new(None) };
+}
+
+/// A handle to the task being polled on this thread, returned by [`current`].
+#[derive(Clone, Debug)]
+pub struct CurrentTask {
+  id: TaskId,
+  priority: Arc<PriorityState>,
+  waker: Waker,
+}
+
+impl CurrentTask {
+  pub fn id(&self) -> TaskId {
+    self.id
+  }
+
+  /// The priority the task is scheduled with right now, which is higher than the one it was
+  /// spawned with while a higher priority task waits on a lock it holds.
+  pub fn priority(&self) -> Priority {
+    self.priority.effective()
+  }
+
+  pub(crate) fn priority_state(&self) -> &Arc<PriorityState> {
+    &self.priority
+  }
+
+  /// The waker of the task's last poll, which schedules it to be polled again.
+  pub fn waker(&self) -> Waker {
+    self.waker.clone()
+  }
+
+  pub fn wake(&self) {
+    self.waker.wake_by_ref();
+  }
+}
+
+/// Returned by [`try_current`] outside of a task.
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+#[error("not called from inside a task")]
+pub struct TryCurrentError;
+
+/// Returns the task being polled on this thread.
+///
+/// # Panics
+///
+/// Panics if called outside of a task, see [`try_current`].
+pub fn current() -> CurrentTask {
+  try_current().unwrap_or_else(|err| panic!("{err}"))
+}
+
+/// Returns the task being polled on this thread, if any.
+pub fn try_current() -> Result<CurrentTask, TryCurrentError> {
+  CURRENT.with(|current| current.borrow().clone()).ok_or(TryCurrentError)
+}
+
+/// Sets the current task for the duration of `f`, and restores the previous one even if `f`
+/// panics.
+pub(super) fn set<R>(
+  id: TaskId,
+  priority: &Arc<PriorityState>,
+  waker: &Waker,
+  f: impl FnOnce() -> R,
+) -> R {
+  struct Guard(Option<CurrentTask>);
+
+  impl Drop for Guard {
+    fn drop(&mut self) {
+      CURRENT.with(|current| *current.borrow_mut() = self.0.take());
+    }
+  }
+
+  let task =
+    CurrentTask { id, priority: priority.clone(), waker: waker.clone() };
+  let _guard = Guard(CURRENT.with(|current| current.replace(Some(task))));
+  f()
+}
+
+#[test]
+fn outside_task() {
+  assert_eq!(try_current().unwrap_err(), TryCurrentError);
+}
+
+#[crate::internal_test]
+async fn woken_from_other_thread() {
+  use std::{
+    sync::{
+      atomic::{AtomicBool, Ordering},
+      Arc,
+    },
+    task::Poll,
+  };
+
+  let handle = crate::task::spawn(async {
+    let done = Arc::new(AtomicBool::new(false));
+    let task = current();
+
+    std::future::poll_fn(|_| {
+      if done.load(Ordering::Acquire) {
+        return Poll::Ready(task.id());
+      }
+
+      // No `Context` is used to wake the task, only the handle.
+      let (done, task) = (done.clone(), current());
+      std::thread::spawn(move || {
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        done.store(true, Ordering::Release);
+        task.wake();
+      });
+      Poll::Pending
+    })
+    .await
+  });
+
+  assert!(handle.await.is_ok());
+}
@@ -0,0 +1,79 @@
+use std::{
+  future::Future,
+  pin::Pin,
+  task::{Context, Poll},
+};
+
+use super::{handle_error, TaskHandle, TaskHandleError};
+
+/// Waits for every task of `handles`, and returns their results in the same order.
+///
+/// Like [`future::join_all`](crate::future::join_all) for the handles of spawned tasks. The tasks
+/// already run on their own, so this only polls the handles which haven't finished on each wake.
+pub fn join_all<T>(handles: Vec<TaskHandle<T>>) -> JoinAll<T> {
+  let results = handles.iter().map(|_| None).collect();
+  JoinAll { handles: handles.into_iter().map(Some).collect(), results }
+}
+
+/// Future returned by [`join_all`].
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct JoinAll<T> {
+  // Taken once the task has finished.
+  handles: Vec<Option<TaskHandle<T>>>,
+  results: Vec<Option<Result<T, TaskHandleError>>>,
+}
+
+// Nothing is ever pinned.
+impl<T> Unpin for JoinAll<T> {}
+
+impl<T> Future for JoinAll<T> {
+  type Output = Vec<Result<T, TaskHandleError>>;
+
+  fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+    let this = self.get_mut();
+    let mut pending = false;
+    for (slot, result) in this.handles.iter_mut().zip(&mut this.results) {
+      let Some(handle) = slot else { continue };
+      match Pin::new(&mut handle.0).poll(cx) {
+        Poll::Ready(output) => {
+          *result = Some(output.map_err(|_| handle_error(&handle.1)));
+          *slot = None;
+        }
+        Poll::Pending => pending = true,
+      }
+    }
+    if pending {
+      return Poll::Pending;
+    }
+    let results = std::mem::take(&mut this.results);
+    Poll::Ready(results.into_iter().map(|result| result.unwrap()).collect())
+  }
+}
+
+#[crate::internal_test]
+async fn results_in_order() {
+  use crate::{sync::oneshot, test_util::task};
+
+  let (sender, receiver) = oneshot::channel::<u32>();
+  let handles = vec![
+    super::spawn(async move { receiver.await.unwrap() }),
+    super::spawn(async { 2 }),
+    super::spawn(async { panic!("task failed") }),
+  ];
+  let mut joined = task::spawn(join_all(handles));
+  while !joined.handles[1..].iter().all(Option::is_none) {
+    assert!(joined.poll().is_pending());
+    super::yield_now().await;
+  }
+
+  // The last to finish is still first.
+  sender.send(1).unwrap();
+  let results = loop {
+    match joined.poll() {
+      Poll::Ready(results) => break results,
+      Poll::Pending => super::yield_now().await,
+    }
+  };
+  let results: Vec<_> = results.into_iter().map(Result::ok).collect();
+  assert_eq!(results, [Some(1), Some(2), None]);
+}
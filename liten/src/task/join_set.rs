@@ -0,0 +1,108 @@
+use std::{
+  future::Future,
+  pin::Pin,
+  task::{Context, Poll},
+};
+
+use super::{handle_error, spawn, TaskHandle, TaskHandleError};
+
+/// A set of spawned tasks, whose results are taken in the order they complete.
+///
+/// Dropping the set doesn't stop the tasks, they keep running detached.
+pub struct JoinSet<T> {
+  tasks: Vec<TaskHandle<T>>,
+}
+
+impl<T> Default for JoinSet<T> {
+  fn default() -> Self {
+    JoinSet { tasks: Vec::new() }
+  }
+}
+
+impl<T> JoinSet<T> {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Spawns `fut` into the set.
+  #[track_caller]
+  pub fn spawn<F>(&mut self, fut: F)
+  where
+    F: Future<Output = T> + Send + 'static,
+    T: Send,
+  {
+    self.tasks.push(spawn(fut));
+  }
+
+  /// How many tasks haven't been joined yet, finished or not.
+  pub fn len(&self) -> usize {
+    self.tasks.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.tasks.is_empty()
+  }
+
+  /// Takes the result of a task which has completed, panicked or was aborted, `None` once the set
+  /// is empty.
+  pub fn poll_join_next(
+    &mut self,
+    cx: &mut Context<'_>,
+  ) -> Poll<Option<Result<T, TaskHandleError>>> {
+    if self.tasks.is_empty() {
+      return Poll::Ready(None);
+    }
+    for index in 0..self.tasks.len() {
+      if let Poll::Ready(result) = Pin::new(&mut self.tasks[index].0).poll(cx) {
+        let task = self.tasks.swap_remove(index);
+        return Poll::Ready(Some(result.map_err(|_| handle_error(&task.1))));
+      }
+    }
+    Poll::Pending
+  }
+
+  /// Waits for the next task to finish, see [`JoinSet::poll_join_next`].
+  pub fn join_next(&mut self) -> JoinNext<'_, T> {
+    JoinNext { set: self }
+  }
+}
+
+/// Future returned by [`JoinSet::join_next`].
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct JoinNext<'a, T> {
+  set: &'a mut JoinSet<T>,
+}
+
+impl<T> Future for JoinNext<'_, T> {
+  type Output = Option<Result<T, TaskHandleError>>;
+
+  fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+    self.get_mut().set.poll_join_next(cx)
+  }
+}
+
+#[crate::internal_test]
+async fn joined_in_completion_order() {
+  use crate::sync::oneshot;
+
+  let (sender, receiver) = oneshot::channel::<()>();
+  let mut set = JoinSet::new();
+  set.spawn(async move {
+    receiver.await.unwrap();
+    1
+  });
+  set.spawn(async { 2 });
+  set.spawn(async { panic!("handler failed") });
+
+  let mut results = vec![];
+  for _ in 0..2 {
+    results.push(set.join_next().await.unwrap().ok());
+  }
+  results.sort();
+  assert_eq!(results, [None, Some(2)]);
+  assert_eq!(set.len(), 1);
+
+  sender.send(()).unwrap();
+  assert_eq!(set.join_next().await.unwrap().unwrap(), 1);
+  assert!(set.join_next().await.is_none());
+}
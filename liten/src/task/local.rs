@@ -0,0 +1,452 @@
+use std::{
+  cell::RefCell,
+  future::Future,
+  pin::Pin,
+  task::{Context, Poll},
+};
+
+use thiserror::Error;
+
+use super::TaskHandle;
+
+std::thread_local! {
+  // The keys whose values `spawn_with_context` copies into the tasks it spawns, in the order
+  // their scopes were entered.
+  static INHERITED: RefCell<Vec<&'static dyn Inherit>> =
+    const { RefCell::new(Vec::new()) };
+}
+
+/// Declares a new task-local key of type [`LocalKey`].
+///
+/// A task-local value is only reachable from inside a future that has been given a value through
+/// [`LocalKey::scope`], which makes it follow a task across worker threads.
+///
+/// ```
+/// liten::task_local! {
+///   static REQUEST_ID: u64;
+/// }
+///
+/// REQUEST_ID.sync_scope(7, || {
+///   assert_eq!(REQUEST_ID.get(), 7);
+/// });
+/// assert!(REQUEST_ID.try_with(|id| *id).is_err());
+/// ```
+#[macro_export]
+macro_rules! task_local {
+  () => {};
+  ($(#[$attr:meta])* $vis:vis static $name:ident: $t:ty; $($rest:tt)*) => {
+    $(#[$attr])*
+    $vis static $name: $crate::task::LocalKey<$t> = {
+      ::std::thread_local! {
+        static __KEY: ::std::cell::RefCell<::std::option::Option<$t>> =
+          const { ::std::cell::RefCell::new(::std::option::Option::None) };
+      }
+      $crate::task::LocalKey { inner: __KEY }
+    };
+    $crate::task_local!($($rest)*);
+  };
+}
+
+/// A key for task-local data, created with [`task_local!`](crate::task_local).
+pub struct LocalKey<T: 'static> {
+  #[doc(hidden)]
+  pub inner: std::thread::LocalKey<RefCell<Option<T>>>,
+}
+
+/// Returned by [`LocalKey::try_with`] outside of a scope.
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+#[error("task-local value not set in this scope")]
+pub struct AccessError;
+
+// Entering a scope needs to replace the value, which can't be done while `with` hands out a
+// reference to it.
+#[derive(Error, Debug)]
+#[error(
+  "cannot enter a task-local scope while the task-local value is borrowed"
+)]
+struct BorrowedError;
+
+impl<T: 'static> LocalKey<T> {
+  /// Runs the future with `value` set for every poll.
+  ///
+  /// # Panics
+  ///
+  /// Polling the returned future panics if the value is borrowed, i.e. from inside
+  /// [`LocalKey::with`].
+  pub fn scope<F>(&'static self, value: T, future: F) -> TaskLocalFuture<T, F>
+  where
+    F: Future,
+  {
+    TaskLocalFuture {
+      key: self,
+      slot: Some(value),
+      future: Some(future),
+      inherit: None,
+    }
+  }
+
+  /// Like [`LocalKey::scope`], but tasks spawned from inside it with [`spawn_with_context`] get a
+  /// clone of the value too, and so on for the tasks they spawn the same way.
+  ///
+  /// # Panics
+  ///
+  /// Polling the returned future panics if the value is borrowed, i.e. from inside
+  /// [`LocalKey::with`].
+  pub fn scope_inherited<F>(
+    &'static self,
+    value: T,
+    future: F,
+  ) -> TaskLocalFuture<T, F>
+  where
+    T: Clone + Send,
+    F: Future,
+  {
+    TaskLocalFuture {
+      key: self,
+      slot: Some(value),
+      future: Some(future),
+      inherit: Some(self),
+    }
+  }
+
+  /// Runs the closure with `value` set, synchronously.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the value is borrowed, i.e. when called from inside [`LocalKey::with`].
+  pub fn sync_scope<F, R>(&'static self, value: T, f: F) -> R
+  where
+    F: FnOnce() -> R,
+  {
+    let mut slot = Some(value);
+    self.swapped(&mut slot, f).unwrap_or_else(|err| panic!("{err}"))
+  }
+
+  /// Calls `f` with a reference to the value, panics if called outside of a scope.
+  pub fn with<F, R>(&'static self, f: F) -> R
+  where
+    F: FnOnce(&T) -> R,
+  {
+    self.try_with(f).expect("task-local value not set in this scope")
+  }
+
+  /// Calls `f` with a reference to the value, if it is set.
+  pub fn try_with<F, R>(&'static self, f: F) -> Result<R, AccessError>
+  where
+    F: FnOnce(&T) -> R,
+  {
+    self.inner.with(|cell| match cell.borrow().as_ref() {
+      Some(value) => Ok(f(value)),
+      None => Err(AccessError),
+    })
+  }
+
+  /// Returns a clone of the value, panics if called outside of a scope.
+  pub fn get(&'static self) -> T
+  where
+    T: Clone,
+  {
+    self.with(T::clone)
+  }
+
+  // Puts the slot in the thread local for the duration of `f`, and takes it back out even if `f`
+  // panics.
+  fn swapped<F, R>(
+    &'static self,
+    slot: &mut Option<T>,
+    f: F,
+  ) -> Result<R, BorrowedError>
+  where
+    F: FnOnce() -> R,
+  {
+    struct Guard<'a, T: 'static> {
+      key: &'static LocalKey<T>,
+      slot: &'a mut Option<T>,
+    }
+
+    impl<T: 'static> Drop for Guard<'_, T> {
+      fn drop(&mut self) {
+        self
+          .key
+          .inner
+          .with(|cell| std::mem::swap(self.slot, &mut *cell.borrow_mut()));
+      }
+    }
+
+    self.inner.with(|cell| {
+      let mut value = cell.try_borrow_mut().map_err(|_| BorrowedError)?;
+      std::mem::swap(slot, &mut *value);
+      Ok(())
+    })?;
+    let _guard = Guard { key: self, slot };
+
+    Ok(f())
+  }
+}
+
+/// Future returned by [`LocalKey::scope`].
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct TaskLocalFuture<T: 'static, F> {
+  key: &'static LocalKey<T>,
+  slot: Option<T>,
+  // Only taken in `Drop`, so the inner future is dropped with the value set.
+  future: Option<F>,
+  // The key again, for `scope_inherited`.
+  inherit: Option<&'static dyn Inherit>,
+}
+
+impl<T: 'static, F: Future> Future for TaskLocalFuture<T, F> {
+  type Output = F::Output;
+
+  fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+    // SAFETY: `future` is never moved out of self, the rest of the fields are not pinned.
+    let this = unsafe { self.get_unchecked_mut() };
+    let future = this
+      .future
+      .as_mut()
+      .map(|future| unsafe { Pin::new_unchecked(future) })
+      .expect("polled TaskLocalFuture after drop");
+
+    let inherit = this.inherit;
+    this
+      .key
+      .swapped(&mut this.slot, || inheriting(inherit, || future.poll(cx)))
+      .unwrap_or_else(|err| panic!("{err}"))
+  }
+}
+
+impl<T: 'static, F> Drop for TaskLocalFuture<T, F> {
+  fn drop(&mut self) {
+    // Assigning drops the future in place, so it's never moved out of the pin.
+    let future = &mut self.future;
+    let drop_future = || *future = None;
+
+    // Don't panic in drop, if the value is borrowed the future is dropped without it.
+    if let Err(BorrowedError) = self.key.swapped(&mut self.slot, drop_future) {
+      self.future = None;
+    }
+  }
+}
+
+/// Spawns `fut` with the task-local values of the scopes it's spawned from which were entered with
+/// [`LocalKey::scope_inherited`], like to have the trace id of a request follow the work it
+/// spawns.
+///
+/// The task gets a clone of each value as they are when it's spawned. Values set with
+/// [`LocalKey::scope`] aren't copied.
+#[track_caller]
+pub fn spawn_with_context<F>(fut: F) -> TaskHandle<F::Output>
+where
+  F: Future + Send + 'static,
+  F::Output: Send,
+{
+  let mut keys: Vec<&'static dyn Inherit> = Vec::new();
+  INHERITED.with(|inherited| {
+    for &key in inherited.borrow().iter() {
+      // The same key can be inherited by nested scopes, the innermost value is the one set.
+      if !keys.iter().any(|other| std::ptr::addr_eq(*other, key)) {
+        keys.push(key);
+      }
+    }
+  });
+  let slots = keys.iter().filter_map(|key| key.snapshot()).collect();
+  super::spawn(WithContext { slots, future: Some(fut) })
+}
+
+// A task-local key whose value can be copied into another task.
+trait Inherit: Sync {
+  fn snapshot(&'static self) -> Option<Box<dyn Slot>>;
+}
+
+impl<T: Clone + Send + 'static> Inherit for LocalKey<T> {
+  fn snapshot(&'static self) -> Option<Box<dyn Slot>> {
+    let value = self.try_with(T::clone).ok()?;
+    Some(Box::new(KeySlot { key: self, slot: Some(value) }))
+  }
+}
+
+// The value of one key in a `WithContext`.
+trait Slot: Send {
+  // Runs `f` with the value set, and makes the key inherited meanwhile.
+  fn scoped(
+    &mut self,
+    f: &mut dyn FnMut() -> Result<(), BorrowedError>,
+  ) -> Result<(), BorrowedError>;
+}
+
+struct KeySlot<T: 'static> {
+  key: &'static LocalKey<T>,
+  slot: Option<T>,
+}
+
+impl<T: Clone + Send + 'static> Slot for KeySlot<T> {
+  fn scoped(
+    &mut self,
+    f: &mut dyn FnMut() -> Result<(), BorrowedError>,
+  ) -> Result<(), BorrowedError> {
+    let key = self.key;
+    key.swapped(&mut self.slot, || inheriting(Some(key), f))?
+  }
+}
+
+// Runs `f` with `key` added to the inherited keys.
+fn inheriting<R>(
+  key: Option<&'static dyn Inherit>,
+  f: impl FnOnce() -> R,
+) -> R {
+  let Some(key) = key else {
+    return f();
+  };
+
+  struct Guard;
+
+  impl Drop for Guard {
+    fn drop(&mut self) {
+      INHERITED.with(|inherited| inherited.borrow_mut().pop());
+    }
+  }
+
+  INHERITED.with(|inherited| inherited.borrow_mut().push(key));
+  let _guard = Guard;
+  f()
+}
+
+// Runs `f` with the value of every slot set.
+fn scoped_all(
+  slots: &mut [Box<dyn Slot>],
+  f: &mut dyn FnMut() -> Result<(), BorrowedError>,
+) -> Result<(), BorrowedError> {
+  match slots.split_first_mut() {
+    Some((first, rest)) => first.scoped(&mut || scoped_all(rest, f)),
+    None => f(),
+  }
+}
+
+// Polls the future of `spawn_with_context` with the values it was spawned with.
+struct WithContext<F> {
+  slots: Vec<Box<dyn Slot>>,
+  // Only taken in `Drop`, so the inner future is dropped with the values set.
+  future: Option<F>,
+}
+
+impl<F: Future> Future for WithContext<F> {
+  type Output = F::Output;
+
+  fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+    // SAFETY: `future` is never moved out of self, the rest of the fields are not pinned.
+    let this = unsafe { self.get_unchecked_mut() };
+    let mut future = this
+      .future
+      .as_mut()
+      .map(|future| unsafe { Pin::new_unchecked(future) })
+      .expect("polled WithContext after drop");
+
+    let mut poll = None;
+    scoped_all(&mut this.slots, &mut || {
+      poll = Some(future.as_mut().poll(cx));
+      Ok(())
+    })
+    .unwrap_or_else(|err| panic!("{err}"));
+    poll.expect("polled with the values set")
+  }
+}
+
+impl<F> Drop for WithContext<F> {
+  fn drop(&mut self) {
+    // Assigning drops the future in place, so it's never moved out of the pin.
+    let future = &mut self.future;
+    let result = scoped_all(&mut self.slots, &mut || {
+      *future = None;
+      Ok(())
+    });
+    // Don't panic in drop, if a value is borrowed the future is dropped without them.
+    if let Err(BorrowedError) = result {
+      self.future = None;
+    }
+  }
+}
+
+#[cfg(test)]
+crate::task_local! {
+  static NUMBER: u32;
+}
+
+#[crate::internal_test]
+async fn scoped() {
+  assert_eq!(NUMBER.try_with(|n| *n), Err(AccessError));
+
+  let value = NUMBER
+    .scope(1, async {
+      let inner = NUMBER.scope(2, async { NUMBER.get() }).await;
+      crate::task::yield_now().await;
+      (NUMBER.get(), inner)
+    })
+    .await;
+
+  assert_eq!(value, (1, 2));
+  assert_eq!(NUMBER.try_with(|n| *n), Err(AccessError));
+}
+
+#[test]
+fn value_set_while_dropping() {
+  struct Check;
+
+  impl Drop for Check {
+    fn drop(&mut self) {
+      assert_eq!(NUMBER.try_with(|n| *n), Ok(3));
+    }
+  }
+
+  let check = Check;
+  drop(NUMBER.scope(3, async move {
+    let _check = check;
+  }));
+  assert_eq!(NUMBER.try_with(|n| *n), Err(AccessError));
+}
+
+#[test]
+#[should_panic(
+  expected = "cannot enter a task-local scope while the task-local value is borrowed"
+)]
+fn scope_inside_with() {
+  NUMBER.sync_scope(1, || NUMBER.with(|_| NUMBER.sync_scope(2, || ())));
+}
+
+#[cfg(test)]
+crate::task_local! {
+  static TRACE_ID: u64;
+}
+
+#[crate::internal_test]
+async fn spawn_inherits_context() {
+  let child = TRACE_ID
+    .scope_inherited(
+      7,
+      NUMBER.scope(1, async {
+        spawn_with_context(async {
+          let grandchild = spawn_with_context(async { TRACE_ID.get() });
+          (TRACE_ID.get(), NUMBER.try_with(|n| *n), grandchild)
+        })
+      }),
+    )
+    .await;
+
+  let (trace_id, number, grandchild) = child.await.unwrap();
+  assert_eq!(trace_id, 7);
+  // Not inherited.
+  assert_eq!(number, Err(AccessError));
+  assert_eq!(grandchild.await.unwrap(), 7);
+  // Nothing is left behind.
+  assert!(INHERITED.with(|inherited| inherited.borrow().is_empty()));
+  assert!(spawn_with_context(async { TRACE_ID.try_with(|_| ()) })
+    .await
+    .unwrap()
+    .is_err());
+}
+
+#[test]
+fn scopes_are_send() {
+  fn send<T: Send>(_: T) {}
+  send(NUMBER.scope(1, async {}));
+  send(TRACE_ID.scope_inherited(1, async {}));
+}
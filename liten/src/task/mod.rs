@@ -1,3 +1,4 @@
+#[allow(clippy::module_inception)]
 mod task;
 pub use task::*;
 mod yield_now;
@@ -6,5 +7,31 @@ mod builder;
 pub use builder::*;
 mod spawn;
 pub use spawn::*;
+mod abort;
+pub use abort::{spawn_timeout, spawn_with_abort, AbortHandle};
+pub(crate) use abort::{AbortState, Abortable};
+mod join_all;
+pub use join_all::{join_all, JoinAll};
+mod join_set;
+pub use join_set::{JoinNext, JoinSet};
+mod local;
+pub use local::*;
+mod blocking;
+mod cache;
+pub use blocking::{block_scope, BlockScope};
+pub(crate) use cache::TaskFuture;
+mod stream;
+pub use stream::spawn_stream;
+mod current;
+mod priority;
+pub use priority::Priority;
+pub(crate) use priority::PriorityState;
+#[cfg(feature = "rayon")]
+mod rayon;
+#[cfg(feature = "rayon")]
+pub use rayon::{scope_rayon, spawn_rayon};
+mod spawner;
+pub use current::{current, try_current, CurrentTask, TryCurrentError};
+pub use spawner::{Spawn, SpawnExt, Spawner};
 
 pub type ArcTask = std::sync::Arc<Task>;
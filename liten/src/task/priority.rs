@@ -0,0 +1,62 @@
+use crate::loom::sync::atomic::{AtomicUsize, Ordering};
+
+/// How urgently a task is scheduled.
+///
+/// When a task above [`Priority::Normal`] is woken, it runs before the other tasks queued on its
+/// worker.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+  Low,
+  #[default]
+  Normal,
+  High,
+}
+
+impl Priority {
+  const ALL: [Priority; 3] = [Priority::Low, Priority::Normal, Priority::High];
+}
+
+// The priority a task was spawned with, and the boosts other tasks waiting on it have given it.
+#[derive(Debug)]
+pub(crate) struct PriorityState {
+  base: Priority,
+  // Number of boosts held at each priority.
+  boosts: [AtomicUsize; 3],
+}
+
+impl PriorityState {
+  pub fn new(base: Priority) -> Self {
+    PriorityState { base, boosts: Default::default() }
+  }
+
+  /// The highest of the base priority and every boost.
+  pub fn effective(&self) -> Priority {
+    let boosted = Priority::ALL.into_iter().rev().find(|&priority| {
+      self.boosts[priority as usize].load(Ordering::Acquire) > 0
+    });
+    boosted.map_or(self.base, |boosted| boosted.max(self.base))
+  }
+
+  pub fn boost(&self, to: Priority) {
+    self.boosts[to as usize].fetch_add(1, Ordering::AcqRel);
+  }
+
+  pub fn unboost(&self, from: Priority) {
+    self.boosts[from as usize].fetch_sub(1, Ordering::AcqRel);
+  }
+}
+
+#[test]
+fn highest_boost_wins() {
+  let state = PriorityState::new(Priority::Normal);
+  state.boost(Priority::Low);
+  assert_eq!(state.effective(), Priority::Normal);
+
+  state.boost(Priority::High);
+  state.boost(Priority::High);
+  state.unboost(Priority::High);
+  assert_eq!(state.effective(), Priority::High);
+
+  state.unboost(Priority::High);
+  assert_eq!(state.effective(), Priority::Normal);
+}
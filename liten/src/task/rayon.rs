@@ -0,0 +1,112 @@
+use std::{
+  future::Future,
+  panic::{self, AssertUnwindSafe},
+};
+
+use crate::sync::oneshot;
+
+/// Runs `f` on the global rayon pool, and completes once it has returned. Awaiting it doesn't
+/// block a worker, so other tasks keep running while `f` does.
+///
+/// The job starts right away, even if the future is never awaited. If `f` panics, the panic is
+/// resumed in the awaiting task, which then surfaces through its
+/// [`TaskHandle`](super::TaskHandle) like any other panicking task.
+pub fn spawn_rayon<F, R>(f: F) -> impl Future<Output = R>
+where
+  F: FnOnce() -> R + Send + 'static,
+  R: Send + 'static,
+{
+  let (sender, receiver) = oneshot::channel();
+  rayon::spawn(move || {
+    let result = panic::catch_unwind(AssertUnwindSafe(f));
+    // Ignore, the future has been dropped in this case.
+    let _ = sender.send(result);
+  });
+
+  async move {
+    match receiver.await.expect("rayon job was dropped without running") {
+      Ok(value) => value,
+      Err(payload) => panic::resume_unwind(payload),
+    }
+  }
+}
+
+/// Lends `value` to `f` on the global rayon pool, and gives it back with the result.
+///
+/// A rayon job can't borrow from the awaiting task, since the task could be dropped while the job
+/// still runs. Parallel iterators over data the task owns go through here instead: the value is
+/// moved to the job for as long as it runs.
+///
+/// ```no_run
+/// use rayon::prelude::*;
+///
+/// # async fn example() {
+/// let (numbers, sum) =
+///   liten::task::scope_rayon(vec![1u64, 2, 3], |numbers| {
+///     numbers.par_iter().sum::<u64>()
+///   })
+///   .await;
+/// assert_eq!(sum, 6);
+/// # drop(numbers);
+/// # }
+/// ```
+pub fn scope_rayon<T, F, R>(value: T, f: F) -> impl Future<Output = (T, R)>
+where
+  T: Send + 'static,
+  F: FnOnce(&mut T) -> R + Send + 'static,
+  R: Send + 'static,
+{
+  let mut value = value;
+  spawn_rayon(move || {
+    let result = f(&mut value);
+    (value, result)
+  })
+}
+
+#[crate::internal_test]
+async fn parallel_sum() {
+  use rayon::prelude::*;
+  use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+  };
+
+  let ticks = Arc::new(AtomicUsize::new(0));
+  let (ticked, wait_for_ticks) = std::sync::mpsc::channel();
+
+  let counter = ticks.clone();
+  let ticker = super::spawn(async move {
+    for _ in 0..5 {
+      counter.fetch_add(1, Ordering::Relaxed);
+      crate::time::sleep(std::time::Duration::from_millis(1)).await;
+    }
+    ticked.send(()).unwrap();
+  });
+
+  let numbers: Vec<u64> = (0..1_000_000).collect();
+  let sum = super::spawn(async move {
+    let (numbers, sum) = scope_rayon(numbers, move |numbers| {
+      let sum = numbers.par_iter().sum::<u64>();
+      // Only returns once the ticker has made progress meanwhile.
+      wait_for_ticks.recv().unwrap();
+      sum
+    })
+    .await;
+    assert_eq!(numbers.len(), 1_000_000);
+    sum
+  });
+
+  assert_eq!(sum.await.unwrap(), 999_999 * 1_000_000 / 2);
+  assert_eq!(ticks.load(Ordering::Relaxed), 5);
+  ticker.await.unwrap();
+}
+
+#[crate::internal_test]
+async fn panic_propagates() {
+  let handle = super::spawn(async {
+    spawn_rayon(|| -> u32 { panic!("rayon job panicked") }).await
+  });
+  assert!(matches!(handle.await, Err(super::TaskHandleError::BodyPanicked)));
+
+  assert_eq!(spawn_rayon(|| 7).await, 7);
+}
@@ -1,27 +1,98 @@
 use std::{
   future::{Future, IntoFuture},
   pin::Pin,
+  sync::Arc,
+  task::{Context, Poll},
 };
 use thiserror::Error;
 
-use crate::sync::oneshot;
+use crate::{runtime::NoRuntimeError, sync::oneshot};
 
-use super::builder;
+use super::{builder, AbortState, Priority};
 
+#[track_caller]
 pub fn spawn<F>(fut: F) -> TaskHandle<F::Output>
 where
   F: Future + Send + 'static,
   F::Output: Send,
 {
-  builder().build(fut)
+  builder()
+    .spawn_inner(fut, None, "task::spawn")
+    .unwrap_or_else(|err| panic!("{err}"))
 }
 
-pub struct TaskHandle<Out>(pub(super) oneshot::Receiver<Out>);
+/// Spawns `fut` with the given [`Priority`].
+#[track_caller]
+pub fn spawn_with_priority<F>(
+  priority: Priority,
+  fut: F,
+) -> TaskHandle<F::Output>
+where
+  F: Future + Send + 'static,
+  F::Output: Send,
+{
+  builder()
+    .priority(priority)
+    .spawn_inner(fut, None, "task::spawn_with_priority")
+    .unwrap_or_else(|err| panic!("{err}"))
+}
+
+/// Spawns `fut`, or returns an error outside of a runtime or if the runtime's
+/// [task limit](crate::runtime::Builder::max_concurrent_tasks) rejects it.
+#[track_caller]
+pub fn try_spawn<F>(fut: F) -> Result<TaskHandle<F::Output>, SpawnError>
+where
+  F: Future + Send + 'static,
+  F::Output: Send,
+{
+  builder().spawn_inner(fut, None, "task::try_spawn")
+}
+
+/// Returned when a task can't be spawned.
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpawnError {
+  #[error("the runtime's task limit is reached")]
+  LimitReached,
+  #[error(transparent)]
+  NoRuntime(#[from] NoRuntimeError),
+}
+
+pub struct TaskHandle<Out>(
+  pub(super) oneshot::Receiver<Out>,
+  // Set for tasks spawned with `spawn_with_abort` or `spawn_timeout`.
+  pub(super) Option<Arc<AbortState>>,
+);
+
+impl<Out> TaskHandle<Out> {
+  /// Returns `true` if the task has completed, panicked or was aborted. The output is kept until the handle is
+  /// awaited.
+  pub fn is_finished(&self) -> bool {
+    self.0.is_done()
+  }
+
+  /// Waits for the task to complete or panic, without taking its output.
+  pub fn poll_finished(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+    self.0.poll_done(cx)
+  }
+}
+
+// Why a task has no output, once its sender is gone.
+pub(super) fn handle_error(abort: &Option<Arc<AbortState>>) -> TaskHandleError {
+  match abort {
+    Some(abort) if abort.is_aborted() => TaskHandleError::Aborted,
+    Some(abort) if abort.is_timed_out() => TaskHandleError::TimedOut,
+    _ => TaskHandleError::BodyPanicked,
+  }
+}
 
 #[derive(Error, Debug)]
 pub enum TaskHandleError {
   #[error("task panicked")]
   BodyPanicked,
+  #[error("task was aborted")]
+  Aborted,
+  #[error("task timed out")]
+  TimedOut,
 }
 
 impl<Out> IntoFuture for TaskHandle<Out>
@@ -31,8 +102,24 @@ where
   type Output = Result<Out, TaskHandleError>;
   type IntoFuture = Pin<Box<dyn Future<Output = Self::Output>>>;
   fn into_future(self) -> Self::IntoFuture {
-    Box::pin(
-      async move { self.0.await.map_err(|_| TaskHandleError::BodyPanicked) },
-    )
+    Box::pin(async move { self.0.await.map_err(|_| handle_error(&self.1)) })
   }
 }
+
+#[crate::internal_test]
+async fn finished_keeps_output() {
+  let (sender, receiver) = oneshot::channel();
+  let mut handle = spawn(async move { receiver.await.unwrap() * 2 });
+  assert!(!handle.is_finished());
+
+  std::thread::spawn(move || {
+    std::thread::sleep(std::time::Duration::from_millis(5));
+    sender.send(21).unwrap();
+  });
+  std::future::poll_fn(|cx| handle.poll_finished(cx)).await;
+  assert!(handle.is_finished());
+  // Polling again doesn't take the output either.
+  std::future::poll_fn(|cx| handle.poll_finished(cx)).await;
+
+  assert_eq!(handle.await.unwrap(), 42);
+}
@@ -0,0 +1,86 @@
+use std::{future::Future, pin::Pin, sync::Arc};
+
+use crate::{context, runtime::scheduler};
+
+/// Something which can run futures in the background, for code which shouldn't depend on a
+/// specific runtime.
+///
+/// The trait is object-safe, so a library can take a `&dyn Spawn` or an `Arc<dyn Spawn>` and
+/// leave the choice of runtime to the application. [`SpawnExt::spawn`] boxes the future for it.
+pub trait Spawn: Send + Sync {
+  /// Runs `future` in the background. There is no handle to it, so it's detached.
+  fn spawn_boxed(&self, future: Pin<Box<dyn Future<Output = ()> + Send>>);
+}
+
+/// Convenience methods for every [`Spawn`].
+pub trait SpawnExt: Spawn {
+  fn spawn<F>(&self, future: F)
+  where
+    F: Future<Output = ()> + Send + 'static,
+  {
+    self.spawn_boxed(Box::pin(future))
+  }
+}
+
+impl<S: Spawn + ?Sized> SpawnExt for S {}
+
+/// Spawns tasks on the runtime it was created from, obtained from
+/// [`Handle::spawner`](crate::runtime::Handle::spawner).
+///
+/// It can be cloned cheaply and sent to other threads, spawning from outside of the runtime works.
+#[derive(Clone)]
+pub struct Spawner {
+  handle: Arc<scheduler::Handle>,
+}
+
+impl Spawner {
+  pub(crate) fn new(handle: Arc<scheduler::Handle>) -> Spawner {
+    Spawner { handle }
+  }
+}
+
+impl Spawn for Spawner {
+  fn spawn_boxed(&self, future: Pin<Box<dyn Future<Output = ()> + Send>>) {
+    let _guard = context::enter(self.handle.clone());
+    drop(super::spawn(future));
+  }
+}
+
+#[cfg(feature = "futures-compat")]
+impl futures_task::Spawn for Spawner {
+  fn spawn_obj(
+    &self,
+    future: futures_task::FutureObj<'static, ()>,
+  ) -> Result<(), futures_task::SpawnError> {
+    self.spawn_boxed(Box::pin(future));
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+static_assertions::assert_impl_all!(Spawner: Clone, Send, Sync);
+
+#[crate::internal_test]
+async fn runtime_agnostic_library() {
+  use crate::{runtime::Handle, sync::mpsc};
+
+  // Only knows about the trait.
+  fn start_counting(spawner: &dyn Spawn, sender: mpsc::Sender<u32>) {
+    spawner.spawn(async move {
+      for n in 0..3 {
+        crate::task::yield_now().await;
+        sender.send(n).unwrap();
+      }
+    });
+  }
+
+  let (sender, receiver) = mpsc::unbounded();
+  let spawner = Handle::current().spawner();
+
+  // From a thread outside of the runtime.
+  std::thread::spawn(move || start_counting(&spawner, sender)).join().unwrap();
+
+  for n in 0..3 {
+    assert_eq!(receiver.recv().await.unwrap(), n);
+  }
+}
@@ -0,0 +1,84 @@
+use std::{future::Future, num::NonZero};
+
+use crate::sync::bridge;
+
+use super::builder;
+
+// Items the producer can be ahead of the consumer by.
+const CAPACITY: NonZero<usize> = NonZero::new(16).unwrap();
+
+/// Spawns the future `f` returns, which produces items with the sender it's given, and returns
+/// the stream they're received from.
+///
+/// The channel between them holds 16 items, after which [`Sender::send`](bridge::Sender::send)
+/// waits for the stream to catch up, so a producer can't run away from a slow consumer. The
+/// stream ends once the producer is done, and sends fail once the stream is dropped, which is the
+/// producer's cue to stop.
+///
+/// ```
+/// use liten::stream::StreamExt;
+///
+/// # liten::runtime::Runtime::new().block_on(async {
+/// let mut pages = liten::task::spawn_stream(|sender| async move {
+///   for page in 1.. {
+///     if sender.send(page).await.is_err() {
+///       break;
+///     }
+///   }
+/// });
+/// assert_eq!(pages.next().await, Some(1));
+/// # });
+/// ```
+#[track_caller]
+pub fn spawn_stream<F, Fut, T>(f: F) -> bridge::Receiver<T>
+where
+  F: FnOnce(bridge::Sender<T>) -> Fut,
+  Fut: Future<Output = ()> + Send + 'static,
+  T: Send + 'static,
+{
+  let (sender, receiver) = bridge::channel(CAPACITY);
+  builder()
+    .spawn_inner(f(sender), None, "task::spawn_stream")
+    .unwrap_or_else(|err| panic!("{err}"));
+  receiver
+}
+
+#[crate::internal_test]
+async fn consume_incrementally() {
+  use crate::stream::StreamExt;
+  use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+  };
+
+  let produced = Arc::new(AtomicUsize::new(0));
+  let counter = produced.clone();
+  let mut items = spawn_stream(|sender| async move {
+    for item in 0..100 {
+      if sender.send(item).await.is_err() {
+        return;
+      }
+      counter.fetch_add(1, Ordering::SeqCst);
+    }
+  });
+
+  for expected in 0..3 {
+    assert_eq!(items.next().await, Some(expected));
+  }
+  // Held back by the full channel, which has room for as many as the capacity.
+  crate::time::sleep(std::time::Duration::from_millis(20)).await;
+  assert_eq!(produced.load(Ordering::SeqCst), 3 + CAPACITY.get());
+  assert_eq!(items.next().await, Some(3));
+  drop(items);
+}
+
+#[crate::internal_test]
+async fn ends_with_producer() {
+  use crate::stream::StreamExt;
+
+  let mut items = spawn_stream(|sender| async move {
+    sender.send("only").await.unwrap();
+  });
+  assert_eq!(items.next().await, Some("only"));
+  assert_eq!(items.next().await, None);
+}
@@ -2,18 +2,27 @@ use std::{
   cell::UnsafeCell,
   future::Future,
   panic::RefUnwindSafe,
-  pin::{self as stdpin, Pin},
+  sync::Arc,
   task::{Context, Poll},
 };
 
 use crate::{
   context::{self},
+  runtime::scheduler::TaskPermit,
   sync::oneshot::Sender,
 };
 
+use super::{AbortState, Abortable, Priority, PriorityState, TaskFuture};
+
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub struct TaskId(pub usize);
 
+impl Default for TaskId {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
 impl TaskId {
   pub fn new() -> Self {
     Self(context::with_context(|ctx| ctx.handle().task_id_inc()))
@@ -22,7 +31,10 @@ impl TaskId {
 
 pub struct Task {
   id: TaskId,
-  pub future: UnsafeCell<Pin<Box<dyn Future<Output = ()> + Send>>>,
+  priority: Arc<PriorityState>,
+  #[cfg(feature = "tracing")]
+  pub(super) span: tracing::Span,
+  pub(crate) future: UnsafeCell<TaskFuture>,
 }
 
 impl RefUnwindSafe for Task {}
@@ -42,27 +54,157 @@ impl std::fmt::Debug for Task {
 }
 
 impl Task {
-  pub(super) fn new<F>(id: TaskId, future: F, sender: Sender<F::Output>) -> Task
+  pub(super) fn new<F>(
+    id: TaskId,
+    priority: Priority,
+    future: F,
+    sender: Sender<F::Output>,
+    permit: Option<TaskPermit>,
+    abort: Option<Arc<AbortState>>,
+  ) -> Task
   where
     F: Future + Send + 'static,
     F::Output: Send,
   {
-    let future = Box::pin(async move {
-      let fut = future.await;
-      if sender.send(fut).is_err() {
-        // Ignore, task handler has been dropped in this case.
+    let future = TaskFuture::new(async move {
+      let fut = match abort {
+        // An aborted future is dropped along with the `Abortable`, before the sender, so what it
+        // held, like a `MutexGuard`, is released by the time its handle fails.
+        Some(abort) => Abortable::new(future, abort).await,
+        None => Some(future.await),
+      };
+      #[cfg(feature = "tracing")]
+      match fut {
+        Some(_) => tracing::trace!("completed"),
+        None => tracing::trace!("aborted"),
+      }
+      // The slot is free before the handle sees the output. A panic drops it while unwinding.
+      drop(permit);
+      // An aborted task drops the sender, the handle then checks why.
+      if let Some(fut) = fut {
+        if sender.send(fut).is_err() {
+          // Ignore, task handler has been dropped in this case.
+        }
       }
     });
-    Self { id, future: UnsafeCell::new(future) }
+    Self {
+      id,
+      priority: Arc::new(PriorityState::new(priority)),
+      #[cfg(feature = "tracing")]
+      span: tracing::Span::none(),
+      future: UnsafeCell::new(future),
+    }
   }
 
   pub fn id(&self) -> TaskId {
     self.id
   }
 
+  /// The priority it's scheduled with, including boosts.
+  pub fn priority(&self) -> Priority {
+    self.priority.effective()
+  }
+
+  /// Entered around every poll of the task.
+  #[cfg(feature = "tracing")]
+  pub(crate) fn span(&self) -> &tracing::Span {
+    &self.span
+  }
+
   pub fn poll(&self, cx: &mut Context) -> Poll<()> {
+    #[cfg(feature = "tracing")]
+    let _span = self.span.enter();
     let future = unsafe { &mut *self.future.get() };
 
-    stdpin::pin!(future).poll(cx)
+    super::current::set(self.id, &self.priority, cx.waker(), || {
+      future.as_mut().poll(cx)
+    })
   }
 }
+
+#[cfg(feature = "tracing")]
+#[test]
+fn task_span() {
+  use std::{fmt::Write, sync::Mutex};
+  use tracing::{field, span, Event, Metadata, Subscriber};
+
+  // Keeps the fields of every span, and every event with the span it belongs to.
+  #[derive(Default)]
+  struct Capture {
+    spans: Mutex<Vec<String>>,
+    events: Mutex<Vec<(Option<u64>, String)>>,
+    entered: Mutex<Vec<u64>>,
+  }
+
+  #[derive(Default)]
+  struct Fields(String);
+  impl field::Visit for Fields {
+    fn record_debug(
+      &mut self,
+      field: &field::Field,
+      value: &dyn std::fmt::Debug,
+    ) {
+      write!(self.0, "{}={:?} ", field.name(), value).unwrap();
+    }
+  }
+
+  impl Subscriber for Capture {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+      true
+    }
+    fn new_span(&self, span: &span::Attributes<'_>) -> span::Id {
+      let mut fields = Fields::default();
+      span.record(&mut fields);
+      let mut spans = self.spans.lock().unwrap();
+      spans.push(format!("{} {}", span.metadata().name(), fields.0));
+      span::Id::from_u64(spans.len() as u64)
+    }
+    fn record(&self, _: &span::Id, _: &span::Record<'_>) {}
+    fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+    fn event(&self, event: &Event<'_>) {
+      let mut fields = Fields::default();
+      event.record(&mut fields);
+      let span = match event.parent() {
+        Some(parent) => Some(parent.into_u64()),
+        None => self.entered.lock().unwrap().last().copied(),
+      };
+      self.events.lock().unwrap().push((span, fields.0));
+    }
+    fn enter(&self, span: &span::Id) {
+      self.entered.lock().unwrap().push(span.into_u64());
+    }
+    fn exit(&self, _: &span::Id) {
+      self.entered.lock().unwrap().pop();
+    }
+  }
+
+  let capture = Arc::new(Capture::default());
+  tracing::subscriber::with_default(capture.clone(), || {
+    let mut runtime = crate::test_util::StepRuntime::new();
+    runtime.spawn(async {
+      super::builder().name("child").build(super::yield_now());
+    });
+    runtime.run_until_stalled();
+  });
+
+  let spans = capture.spans.lock().unwrap();
+  assert_eq!(spans.len(), 2);
+  assert!(spans[0].starts_with("task task.id=0 priority=Normal spawned_at="));
+  assert!(spans[1].starts_with("task task.id=1 task.name=\"child\""));
+  assert!(spans[1].contains(file!()), "{}", spans[1]);
+
+  let events = capture.events.lock().unwrap();
+  let count = |span, message: &str| {
+    let message = format!("message={message} ");
+    events
+      .iter()
+      .filter(|event| **event == (Some(span), message.clone()))
+      .count()
+  };
+  assert_eq!((count(1, "spawned"), count(1, "completed")), (1, 1));
+  assert_eq!((count(2, "spawned"), count(2, "completed")), (1, 1));
+  // Yielding wakes the task from inside its own span.
+  assert!(events.iter().any(|(span, fields)| {
+    *span == Some(2) && fields == "message=woken task.id=1 "
+  }));
+}
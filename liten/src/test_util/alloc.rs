@@ -0,0 +1,28 @@
+//! Counts the allocations made by the current thread, so other tests running at the same time
+//! don't show up.
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+struct CountingAllocator;
+
+std::thread_local! {
+  static ALLOCATIONS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
+unsafe impl std::alloc::GlobalAlloc for CountingAllocator {
+  unsafe fn alloc(&self, layout: std::alloc::Layout) -> *mut u8 {
+    // `try_with` because the thread local may already be destroyed when a thread exits.
+    let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+    unsafe { std::alloc::System.alloc(layout) }
+  }
+
+  unsafe fn dealloc(&self, ptr: *mut u8, layout: std::alloc::Layout) {
+    unsafe { std::alloc::System.dealloc(ptr, layout) }
+  }
+}
+
+/// How many allocations the current thread has made so far.
+pub(crate) fn allocations() -> usize {
+  ALLOCATIONS.with(|count| count.get())
+}
@@ -0,0 +1,314 @@
+//! A scripted stream for testing code which does io, without sockets.
+
+use std::{
+  collections::VecDeque,
+  fmt,
+  future::Future,
+  io,
+  pin::Pin,
+  task::{Context, Poll, Waker},
+  thread,
+  time::Duration,
+};
+
+use crate::{
+  io::{AsyncRead, AsyncWrite},
+  time::{self, Sleep},
+};
+
+/// Scripts what a [`Mock`] does, step by step.
+///
+/// ```
+/// use liten::test_util::io::Builder;
+///
+/// let mock = Builder::new()
+///   .write(b"PING\r\n")
+///   .read(b"+PONG\r\n")
+///   .build();
+/// # std::mem::forget(mock);
+/// ```
+#[derive(Default)]
+pub struct Builder {
+  actions: VecDeque<Action>,
+}
+
+enum Action {
+  Read(Vec<u8>),
+  Write(Vec<u8>),
+  Wait(Duration),
+  // Taken when it's returned.
+  ReadError(Option<io::Error>),
+  WriteError(Option<io::Error>),
+}
+
+impl fmt::Debug for Action {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Action::Read(data) => write!(f, "read \"{}\"", data.escape_ascii()),
+      Action::Write(data) => write!(f, "write \"{}\"", data.escape_ascii()),
+      Action::Wait(duration) => write!(f, "wait {duration:?}"),
+      Action::ReadError(err) => write!(f, "read error {err:?}"),
+      Action::WriteError(err) => write!(f, "write error {err:?}"),
+    }
+  }
+}
+
+impl Builder {
+  pub fn new() -> Builder {
+    Builder::default()
+  }
+
+  /// The next read returns these bytes, possibly over several reads.
+  pub fn read(&mut self, data: &[u8]) -> &mut Builder {
+    self.actions.push_back(Action::Read(data.to_vec()));
+    self
+  }
+
+  /// The next writes have to be exactly these bytes, a write of anything else panics.
+  pub fn write(&mut self, data: &[u8]) -> &mut Builder {
+    self.actions.push_back(Action::Write(data.to_vec()));
+    self
+  }
+
+  /// Reads and writes wait for `duration` before going on with the script. The wait uses the
+  /// runtime's clock, so it's skipped over under [paused](crate::time::pause) time.
+  pub fn wait(&mut self, duration: Duration) -> &mut Builder {
+    self.actions.push_back(Action::Wait(duration));
+    self
+  }
+
+  /// The next read fails with `err`.
+  pub fn read_error(&mut self, err: io::Error) -> &mut Builder {
+    self.actions.push_back(Action::ReadError(Some(err)));
+    self
+  }
+
+  /// The next write fails with `err`.
+  pub fn write_error(&mut self, err: io::Error) -> &mut Builder {
+    self.actions.push_back(Action::WriteError(Some(err)));
+    self
+  }
+
+  /// Returns a stream following the script so far.
+  pub fn build(&mut self) -> Mock {
+    Mock {
+      actions: std::mem::take(&mut self.actions),
+      sleep: None,
+      waker: None,
+    }
+  }
+}
+
+/// A fake stream made by [`Builder`], which follows its script.
+///
+/// Reads at the end of the script return `Ok(0)`, while a read the script expects a write for
+/// waits for that write. It panics when something is written which the script doesn't expect,
+/// and when it's dropped before the script is done.
+pub struct Mock {
+  actions: VecDeque<Action>,
+  // The wait at the front of the script, once started.
+  sleep: Option<Pin<Box<Sleep>>>,
+  // A read waiting for a write.
+  waker: Option<Waker>,
+}
+
+impl Mock {
+  // Goes through a wait at the front of the script.
+  fn poll_wait(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+    while let Some(Action::Wait(duration)) = self.actions.front() {
+      let sleep =
+        self.sleep.get_or_insert_with(|| Box::pin(time::sleep(*duration)));
+      std::task::ready!(sleep.as_mut().poll(cx));
+      self.sleep = None;
+      self.actions.pop_front();
+    }
+    Poll::Ready(())
+  }
+}
+
+impl AsyncRead for Mock {
+  fn poll_read(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+    buf: &mut [u8],
+  ) -> Poll<io::Result<usize>> {
+    let this = self.get_mut();
+    std::task::ready!(this.poll_wait(cx));
+
+    match this.actions.front_mut() {
+      None => Poll::Ready(Ok(0)),
+      Some(Action::Read(data)) => {
+        let len = data.len().min(buf.len());
+        buf[..len].copy_from_slice(&data[..len]);
+        data.drain(..len);
+        if data.is_empty() {
+          this.actions.pop_front();
+        }
+        Poll::Ready(Ok(len))
+      }
+      Some(Action::ReadError(err)) => {
+        let err = err.take().unwrap();
+        this.actions.pop_front();
+        Poll::Ready(Err(err))
+      }
+      Some(Action::Write(_) | Action::WriteError(_)) => {
+        this.waker = Some(cx.waker().clone());
+        Poll::Pending
+      }
+      Some(Action::Wait(_)) => unreachable!("waits are done"),
+    }
+  }
+}
+
+impl AsyncWrite for Mock {
+  fn poll_write(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+    buf: &[u8],
+  ) -> Poll<io::Result<usize>> {
+    let this = self.get_mut();
+    std::task::ready!(this.poll_wait(cx));
+
+    let result = match this.actions.front_mut() {
+      Some(Action::Write(expected)) => {
+        let len = expected.len().min(buf.len());
+        if let Some(at) = (0..len).find(|&at| buf[at] != expected[at]) {
+          panic!(
+            "wrote \"{}\", but the script expects \"{}\", they differ at byte {at}",
+            buf.escape_ascii(),
+            expected.escape_ascii(),
+          );
+        }
+        expected.drain(..len);
+        if expected.is_empty() {
+          this.actions.pop_front();
+        }
+        Ok(len)
+      }
+      Some(Action::WriteError(err)) => {
+        let err = err.take().unwrap();
+        this.actions.pop_front();
+        Err(err)
+      }
+      Some(action) => {
+        panic!(
+          "wrote \"{}\", but the script expects a {action:?}",
+          buf.escape_ascii()
+        )
+      }
+      None => {
+        panic!("wrote \"{}\" after the end of the script", buf.escape_ascii())
+      }
+    };
+
+    if let Some(waker) = this.waker.take() {
+      waker.wake();
+    }
+    Poll::Ready(result)
+  }
+
+  fn poll_flush(
+    self: Pin<&mut Self>,
+    _: &mut Context<'_>,
+  ) -> Poll<io::Result<()>> {
+    Poll::Ready(Ok(()))
+  }
+
+  fn poll_shutdown(
+    self: Pin<&mut Self>,
+    _: &mut Context<'_>,
+  ) -> Poll<io::Result<()>> {
+    Poll::Ready(Ok(()))
+  }
+}
+
+impl Drop for Mock {
+  fn drop(&mut self) {
+    // A failing test shouldn't turn into an abort.
+    if !self.actions.is_empty() && !thread::panicking() {
+      panic!(
+        "the mock was dropped before its script was done: {:?}",
+        self.actions
+      );
+    }
+  }
+}
+
+#[cfg(test)]
+async fn read(mock: &mut Mock, buf: &mut [u8]) -> io::Result<usize> {
+  std::future::poll_fn(|cx| Pin::new(&mut *mock).poll_read(cx, buf)).await
+}
+
+#[cfg(test)]
+async fn write(mock: &mut Mock, buf: &[u8]) -> io::Result<usize> {
+  std::future::poll_fn(|cx| Pin::new(&mut *mock).poll_write(cx, buf)).await
+}
+
+#[crate::internal_test]
+async fn follows_script() {
+  time::pause();
+  let start = time::now();
+  let mut mock = Builder::new()
+    .read(b"hello")
+    .write(b"world")
+    .wait(Duration::from_secs(5))
+    .read_error(io::ErrorKind::ConnectionReset.into())
+    .write_error(io::ErrorKind::BrokenPipe.into())
+    .build();
+
+  let mut buf = [0; 3];
+  assert_eq!(read(&mut mock, &mut buf).await.unwrap(), 3);
+  assert_eq!(&buf, b"hel");
+  assert_eq!(read(&mut mock, &mut buf).await.unwrap(), 2);
+  assert_eq!(&buf[..2], b"lo");
+
+  assert_eq!(write(&mut mock, b"wor").await.unwrap(), 3);
+  assert_eq!(write(&mut mock, b"ld and more").await.unwrap(), 2);
+
+  let err = read(&mut mock, &mut buf).await.unwrap_err();
+  assert_eq!(err.kind(), io::ErrorKind::ConnectionReset);
+  assert_eq!(time::now() - start, Duration::from_secs(5));
+  let err = write(&mut mock, b"late").await.unwrap_err();
+  assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+
+  // The script is done.
+  assert_eq!(read(&mut mock, &mut buf).await.unwrap(), 0);
+  time::resume();
+}
+
+#[test]
+fn read_waits_for_write() {
+  let mut mock = crate::test_util::task::spawn(
+    Builder::new().write(b"ping").read(b"pong").build(),
+  );
+  let mut buf = [0; 4];
+
+  let read = mock.enter(|cx, mock| mock.poll_read(cx, &mut buf));
+  assert!(read.is_pending());
+  let written = mock.enter(|cx, mock| mock.poll_write(cx, b"ping"));
+  assert_eq!(written.map(Result::unwrap), Poll::Ready(4));
+  assert!(mock.is_woken());
+
+  let read = mock.enter(|cx, mock| mock.poll_read(cx, &mut buf));
+  assert_eq!(read.map(Result::unwrap), Poll::Ready(4));
+  assert_eq!(&buf, b"pong");
+}
+
+#[test]
+#[should_panic(
+  expected = "wrote \"pinG\", but the script expects \"ping\", they differ at byte 3"
+)]
+fn wrong_write() {
+  let mut mock = Builder::new().write(b"ping").build();
+  let waker = Waker::noop();
+  let _ =
+    Pin::new(&mut mock).poll_write(&mut Context::from_waker(waker), b"pinG");
+}
+
+#[test]
+#[should_panic(
+  expected = "dropped before its script was done: [read \"rest\"]"
+)]
+fn unfinished_script() {
+  drop(Builder::new().read(b"rest").build());
+}
@@ -0,0 +1,8 @@
+//! Utilities for testing code which runs on liten.
+#[cfg(test)]
+pub(crate) mod alloc;
+pub mod io;
+mod step;
+pub mod task;
+
+pub use step::StepRuntime;
@@ -0,0 +1,204 @@
+use std::{
+  future::Future,
+  sync::Arc,
+  time::{Duration, Instant},
+};
+
+use crate::{
+  context, events,
+  runtime::scheduler::{
+    self,
+    worker::{shared::Shared, worker::Worker},
+  },
+  task::{self, TaskHandle},
+};
+
+/// A runtime on the current thread which only runs when told to.
+///
+/// Nothing happens on its own: [`tick`](StepRuntime::tick) polls the tasks which are ready, and
+/// the clock is [paused](crate::time::pause) from the start, so it only moves through
+/// [`advance`](StepRuntime::advance). The same tasks therefore run in the same order on every run,
+/// which makes it possible to test exact interleavings. [`step`](StepRuntime::step) goes further
+/// and polls a single task, to force one interleaving in particular.
+///
+/// Sockets aren't polled, so tasks waiting on io never wake up.
+pub struct StepRuntime {
+  handle: Arc<scheduler::Handle>,
+  worker: Worker,
+  _io: events::Driver,
+}
+
+impl Default for StepRuntime {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl StepRuntime {
+  pub fn new() -> StepRuntime {
+    let (io, io_handle) = events::Driver::new().unwrap();
+    let handle = Arc::new(scheduler::Handle::without_shared(io_handle));
+    let worker = Worker::new(0, handle.clone());
+    handle.set_handle(Shared::from_workers(std::slice::from_ref(&worker)));
+    handle.time().pause();
+
+    StepRuntime { handle, worker, _io: io }
+  }
+
+  /// Spawns `fut`, which first runs on the next tick.
+  #[track_caller]
+  pub fn spawn<F>(&self, fut: F) -> TaskHandle<F::Output>
+  where
+    F: Future + Send + 'static,
+    F::Output: Send,
+  {
+    let _guard = context::enter(self.handle.clone());
+    task::spawn(fut)
+  }
+
+  /// Polls every task which is ready once, and returns how many were polled.
+  ///
+  /// Tasks woken or spawned during the tick, even ones woken by themselves, are polled on the next
+  /// one.
+  pub fn tick(&mut self) -> usize {
+    let _guard = context::enter(self.handle.clone());
+    self.worker.tick()
+  }
+
+  /// Polls the task which is next in line, and returns `false` if none was ready.
+  ///
+  /// Woken tasks go first, in the order they were woken, and then the ones spawned since the last
+  /// step, in the order they were spawned.
+  pub fn step(&mut self) -> bool {
+    let _guard = context::enter(self.handle.clone());
+    self.worker.step()
+  }
+
+  /// Ticks until no task is ready, and returns how many polls that took altogether.
+  pub fn run_until_stalled(&mut self) -> usize {
+    let mut polled = 0;
+    loop {
+      match self.tick() {
+        0 => return polled,
+        n => polled += n,
+      }
+    }
+  }
+
+  /// Moves the clock forward, waking the tasks whose timers expire on the way. They run on the next
+  /// tick.
+  pub fn advance(&self, duration: Duration) {
+    self.handle.time().advance(duration);
+  }
+
+  /// Returns the current time of the runtime's clock.
+  pub fn now(&self) -> Instant {
+    self.handle.time().now()
+  }
+}
+
+#[test]
+fn exact_interleaving() {
+  use crate::{sync::oneshot, time};
+  use std::sync::Mutex;
+
+  for _ in 0..100 {
+    let mut runtime = StepRuntime::new();
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let push = |log: &Arc<Mutex<Vec<&'static str>>>, event| {
+      log.lock().unwrap().push(event)
+    };
+
+    let (sender, receiver) = oneshot::channel();
+    let consumer_log = log.clone();
+    runtime.spawn(async move {
+      receiver.await.unwrap();
+      push(&consumer_log, "consumed");
+    });
+    let producer_log = log.clone();
+    runtime.spawn(async move {
+      push(&producer_log, "produced");
+      sender.send(()).unwrap();
+    });
+    let sleeper_log = log.clone();
+    let sleeper = runtime.spawn(async move {
+      time::sleep(Duration::from_millis(10)).await;
+      push(&sleeper_log, "slept");
+      let child_log = sleeper_log.clone();
+      task::spawn(async move { push(&child_log, "child") });
+    });
+
+    assert_eq!(runtime.tick(), 3);
+    assert_eq!(*log.lock().unwrap(), ["produced"]);
+    assert_eq!(runtime.tick(), 1);
+    assert_eq!(*log.lock().unwrap(), ["produced", "consumed"]);
+    assert_eq!(runtime.tick(), 0);
+
+    runtime.advance(Duration::from_millis(5));
+    assert_eq!(runtime.tick(), 0);
+    runtime.advance(Duration::from_millis(5));
+    assert_eq!(runtime.tick(), 1);
+    assert!(sleeper.is_finished());
+    assert_eq!(runtime.tick(), 1);
+    assert_eq!(runtime.tick(), 0);
+    assert_eq!(
+      *log.lock().unwrap(),
+      ["produced", "consumed", "slept", "child"]
+    );
+  }
+}
+
+#[test]
+fn forced_lost_update() {
+  use crate::sync::oneshot;
+  use std::sync::atomic::{AtomicUsize, Ordering};
+
+  let mut runtime = StepRuntime::new();
+  let counter = Arc::new(AtomicUsize::new(0));
+  let mut writes = Vec::new();
+  for _ in 0..2 {
+    let counter = counter.clone();
+    let (write, may_write) = oneshot::channel::<()>();
+    writes.push(write);
+    runtime.spawn(async move {
+      // A read-modify-write which isn't atomic across the wait.
+      let read = counter.load(Ordering::SeqCst);
+      may_write.await.unwrap();
+      counter.store(read + 1, Ordering::SeqCst);
+    });
+  }
+
+  // Both read before either writes.
+  assert!(runtime.step());
+  assert!(runtime.step());
+  assert!(!runtime.step());
+  for write in writes {
+    write.send(()).unwrap();
+    assert!(runtime.step());
+  }
+  assert!(!runtime.step());
+  assert_eq!(counter.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn run_until_stalled() {
+  let mut runtime = StepRuntime::new();
+  let start = runtime.now();
+  let handle = runtime.spawn(async {
+    for _ in 0..3 {
+      let mut yielded = false;
+      std::future::poll_fn(|cx| {
+        if std::mem::replace(&mut yielded, true) {
+          return std::task::Poll::Ready(());
+        }
+        cx.waker().wake_by_ref();
+        std::task::Poll::Pending
+      })
+      .await;
+    }
+  });
+
+  assert_eq!(runtime.run_until_stalled(), 4);
+  assert!(handle.is_finished());
+  assert_eq!(runtime.now(), start);
+}
@@ -0,0 +1,203 @@
+//! Polling futures and streams by hand, with a waker which keeps track of its wakes.
+
+use std::{
+  future::Future,
+  ops::{Deref, DerefMut},
+  pin::Pin,
+  sync::{
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+    Arc,
+  },
+  task::{Context, Poll, Wake, Waker},
+};
+
+use crate::stream::Stream;
+
+/// Wraps `value` so it can be polled without a runtime.
+///
+/// ```
+/// use liten::{assert_pending, assert_ready_eq, sync::oneshot, test_util::task};
+///
+/// let (sender, receiver) = oneshot::channel();
+/// let mut receiver = task::spawn(receiver);
+/// assert_pending!(receiver.poll());
+///
+/// sender.send(1).unwrap();
+/// assert!(receiver.is_woken());
+/// assert_ready_eq!(receiver.poll(), Ok(1));
+/// ```
+pub fn spawn<T>(value: T) -> Spawn<T> {
+  Spawn { value: Box::pin(value), waker: Arc::new(MockWaker::default()) }
+}
+
+/// A future or stream polled with a mock waker, returned by [`spawn`].
+pub struct Spawn<T> {
+  value: Pin<Box<T>>,
+  waker: Arc<MockWaker>,
+}
+
+#[derive(Default)]
+struct MockWaker {
+  woken: AtomicBool,
+  wakes: AtomicUsize,
+}
+
+impl Wake for MockWaker {
+  fn wake(self: Arc<Self>) {
+    self.wake_by_ref();
+  }
+
+  fn wake_by_ref(self: &Arc<Self>) {
+    self.woken.store(true, Ordering::SeqCst);
+    self.wakes.fetch_add(1, Ordering::SeqCst);
+  }
+}
+
+impl<T> Spawn<T> {
+  /// Calls `f` with the value and a context of the mock waker.
+  ///
+  /// This counts as a poll: [`is_woken`](Spawn::is_woken) is `false` again afterwards, unless `f`
+  /// or something else wakes the waker in the meantime.
+  pub fn enter<F, R>(&mut self, f: F) -> R
+  where
+    F: FnOnce(&mut Context<'_>, Pin<&mut T>) -> R,
+  {
+    self.waker.woken.store(false, Ordering::SeqCst);
+    let waker = Waker::from(self.waker.clone());
+    f(&mut Context::from_waker(&waker), self.value.as_mut())
+  }
+
+  /// Returns `true` if the waker has been woken since the last poll.
+  pub fn is_woken(&self) -> bool {
+    self.waker.woken.load(Ordering::SeqCst)
+  }
+
+  /// Returns how many times the waker has been woken altogether.
+  pub fn wake_count(&self) -> usize {
+    self.waker.wakes.load(Ordering::SeqCst)
+  }
+
+  /// Returns how many clones of the waker are kept by the value, which shows whether it lets go
+  /// of wakers it no longer needs.
+  pub fn waker_ref_count(&self) -> usize {
+    Arc::strong_count(&self.waker) - 1
+  }
+}
+
+impl<T: Future> Spawn<T> {
+  pub fn poll(&mut self) -> Poll<T::Output> {
+    self.enter(|cx, value| value.poll(cx))
+  }
+}
+
+impl<T: Stream> Spawn<T> {
+  pub fn poll_next(&mut self) -> Poll<Option<T::Item>> {
+    self.enter(|cx, value| value.poll_next(cx))
+  }
+}
+
+impl<T: Unpin> Deref for Spawn<T> {
+  type Target = T;
+
+  fn deref(&self) -> &T {
+    &self.value
+  }
+}
+
+impl<T: Unpin> DerefMut for Spawn<T> {
+  fn deref_mut(&mut self) -> &mut T {
+    &mut self.value
+  }
+}
+
+/// Asserts that a [`Poll`] is ready, and evaluates to its value.
+#[macro_export]
+macro_rules! assert_ready {
+  ($poll:expr) => {
+    match $poll {
+      ::std::task::Poll::Ready(value) => value,
+      ::std::task::Poll::Pending => panic!("pending"),
+    }
+  };
+  ($poll:expr, $($msg:tt)+) => {
+    match $poll {
+      ::std::task::Poll::Ready(value) => value,
+      ::std::task::Poll::Pending => panic!("pending; {}", format_args!($($msg)+)),
+    }
+  };
+}
+
+/// Asserts that a [`Poll`] is pending.
+#[macro_export]
+macro_rules! assert_pending {
+  ($poll:expr) => {
+    match $poll {
+      ::std::task::Poll::Pending => {}
+      ::std::task::Poll::Ready(value) => panic!("ready; value = {:?}", value),
+    }
+  };
+  ($poll:expr, $($msg:tt)+) => {
+    match $poll {
+      ::std::task::Poll::Pending => {}
+      ::std::task::Poll::Ready(value) => {
+        panic!("ready; value = {:?}; {}", value, format_args!($($msg)+))
+      }
+    }
+  };
+}
+
+/// Asserts that a [`Poll`] is ready with the given value.
+#[macro_export]
+macro_rules! assert_ready_eq {
+  ($poll:expr, $expected:expr $(,)?) => {
+    assert_eq!($crate::assert_ready!($poll), $expected)
+  };
+  ($poll:expr, $expected:expr, $($msg:tt)+) => {
+    assert_eq!($crate::assert_ready!($poll, $($msg)+), $expected, $($msg)+)
+  };
+}
+
+#[test]
+fn oneshot() {
+  use crate::sync::oneshot;
+
+  let (sender, receiver) = oneshot::channel();
+  let mut receiver = spawn(receiver);
+  assert_pending!(receiver.poll());
+  assert_pending!(receiver.poll());
+  assert!(!receiver.is_woken());
+  assert_eq!(receiver.waker_ref_count(), 1);
+
+  sender.send("value").unwrap();
+  assert!(receiver.is_woken());
+  assert_eq!(receiver.wake_count(), 1);
+  assert_ready_eq!(receiver.poll(), Ok("value"));
+  assert!(!receiver.is_woken());
+}
+
+#[crate::internal_test]
+async fn paused_sleep() {
+  use crate::time;
+  use std::time::Duration;
+
+  time::pause();
+  let mut sleep = spawn(time::sleep(Duration::from_millis(10)));
+  assert_pending!(sleep.poll());
+
+  time::advance(Duration::from_millis(5));
+  assert!(!sleep.is_woken());
+  assert_pending!(sleep.poll());
+
+  time::advance(Duration::from_millis(5));
+  assert!(sleep.is_woken());
+  assert_ready!(sleep.poll());
+  time::resume();
+}
+
+#[test]
+fn stream() {
+  let mut stream = spawn(crate::stream::iter([1, 2]));
+  assert_ready_eq!(stream.poll_next(), Some(1));
+  assert_ready_eq!(stream.poll_next(), Some(2));
+  assert_ready_eq!(stream.poll_next(), None);
+}
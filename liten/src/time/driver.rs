@@ -0,0 +1,401 @@
+use std::{
+  collections::HashMap,
+  sync::Mutex,
+  task::Waker,
+  time::{Duration, Instant},
+};
+
+use super::park::Park;
+
+/// Timer driver, run on its own thread by the scheduler.
+///
+/// Timers live in a min-heap of deadlines, and every timer knows where its entry is, so removing or
+/// resetting one moves its entry in place instead of leaving a stale one behind. The heap only
+/// ever holds live timers, and gives memory back once a burst of them is gone.
+///
+/// On Linux the thread sleeps on a `timerfd` armed for the next deadline, elsewhere on a condvar
+/// with a timeout.
+pub(crate) struct Handle {
+  // Using a stdMutex because the timer thread isn't in a async context.
+  state: Mutex<State>,
+  park: Park,
+}
+
+// Below this many timers, the allocations are kept.
+const MIN_CAPACITY: usize = 1024;
+
+#[derive(Default)]
+struct State {
+  // Deadline and id of every timer, the earliest first.
+  heap: Vec<(Instant, usize)>,
+  timers: HashMap<usize, Timer>,
+  next_id: usize,
+
+  // Set while time is paused, the clock only moves when it's advanced.
+  paused: Option<Instant>,
+  // How far the clock has been advanced past the real time, kept after resuming so it never
+  // goes backwards.
+  offset: Duration,
+  shutdown: bool,
+}
+
+struct Timer {
+  waker: Waker,
+  // Position of the timer in the heap.
+  index: usize,
+}
+
+impl State {
+  fn now(&self) -> Instant {
+    self.paused.unwrap_or_else(|| Instant::now() + self.offset)
+  }
+
+  fn next_deadline(&self) -> Option<Instant> {
+    self.heap.first().map(|&(deadline, _)| deadline)
+  }
+
+  fn expired(&mut self) -> Vec<Waker> {
+    let now = self.now();
+    let mut wakers = Vec::new();
+
+    while let Some(&(deadline, id)) = self.heap.first() {
+      if deadline > now {
+        break;
+      }
+      wakers.push(self.remove(id).unwrap().waker);
+    }
+
+    wakers
+  }
+
+  fn deadline(&self, id: usize) -> Option<Instant> {
+    self.timers.get(&id).map(|timer| self.heap[timer.index].0)
+  }
+
+  fn insert(&mut self, id: usize, deadline: Instant, waker: Waker) {
+    let index = self.heap.len();
+    self.heap.push((deadline, id));
+    self.timers.insert(id, Timer { waker, index });
+    self.sift_up(index);
+  }
+
+  fn remove(&mut self, id: usize) -> Option<Timer> {
+    let timer = self.timers.remove(&id)?;
+    self.heap.swap_remove(timer.index);
+    // The last entry took its place.
+    if let Some(&(_, moved)) = self.heap.get(timer.index) {
+      self.timers.get_mut(&moved).unwrap().index = timer.index;
+      self.sift(timer.index);
+    }
+    self.shrink();
+    Some(timer)
+  }
+
+  // Halves the allocations once they're mostly empty, which keeps the cost amortized.
+  fn shrink(&mut self) {
+    let capacity = self.heap.capacity();
+    if capacity > MIN_CAPACITY && self.heap.len() < capacity / 4 {
+      self.heap.shrink_to(capacity / 2);
+      self.timers.shrink_to(capacity / 2);
+    }
+  }
+
+  fn reschedule(&mut self, id: usize, deadline: Instant) {
+    let index = self.timers[&id].index;
+    self.heap[index].0 = deadline;
+    self.sift(index);
+  }
+
+  fn sift(&mut self, index: usize) {
+    let index = self.sift_up(index);
+    self.sift_down(index);
+  }
+
+  fn sift_up(&mut self, mut index: usize) -> usize {
+    while index > 0 {
+      let parent = (index - 1) / 2;
+      if self.heap[parent].0 <= self.heap[index].0 {
+        break;
+      }
+      self.swap(parent, index);
+      index = parent;
+    }
+    index
+  }
+
+  fn sift_down(&mut self, mut index: usize) {
+    loop {
+      let mut earliest = index;
+      for child in [2 * index + 1, 2 * index + 2] {
+        if self.heap.get(child).is_some_and(|c| c.0 < self.heap[earliest].0) {
+          earliest = child;
+        }
+      }
+      if earliest == index {
+        break;
+      }
+      self.swap(earliest, index);
+      index = earliest;
+    }
+  }
+
+  fn swap(&mut self, a: usize, b: usize) {
+    self.heap.swap(a, b);
+    for index in [a, b] {
+      let id = self.heap[index].1;
+      self.timers.get_mut(&id).unwrap().index = index;
+    }
+  }
+}
+
+impl Handle {
+  pub fn new() -> Handle {
+    Handle { state: Mutex::new(State::default()), park: Park::new() }
+  }
+
+  pub fn now(&self) -> Instant {
+    self.state.lock().unwrap().now()
+  }
+
+  pub fn pause(&self) {
+    let mut state = self.state.lock().unwrap();
+    assert!(state.paused.is_none(), "time is already paused");
+    state.paused = Some(state.now());
+  }
+
+  pub fn resume(&self) {
+    let mut state = self.state.lock().unwrap();
+    let paused = state.paused.take().expect("time isn't paused");
+    state.offset =
+      state.offset.max(paused.saturating_duration_since(Instant::now()));
+    drop(state);
+    self.park.notify();
+  }
+
+  pub fn advance(&self, duration: Duration) {
+    let mut state = self.state.lock().unwrap();
+    let now = state.paused.as_mut().expect("time isn't paused");
+    *now += duration;
+
+    let wakers = state.expired();
+    drop(state);
+    wakers.into_iter().for_each(Waker::wake);
+  }
+
+  /// Moves a paused clock to the next deadline and fires it.
+  ///
+  /// Returns `false` if time isn't paused or there are no timers.
+  pub fn advance_to_next(&self) -> bool {
+    let mut state = self.state.lock().unwrap();
+    if state.paused.is_none() {
+      return false;
+    }
+    let Some(deadline) = state.next_deadline() else {
+      return false;
+    };
+    state.paused = state.paused.max(Some(deadline));
+
+    let wakers = state.expired();
+    drop(state);
+    wakers.into_iter().for_each(Waker::wake);
+    true
+  }
+
+  /// Registers or updates the timer in `slot`.
+  ///
+  /// Returns `true` if the deadline has already been reached, in which case nothing is registered.
+  pub fn register(
+    &self,
+    slot: &mut Option<usize>,
+    deadline: Instant,
+    waker: &Waker,
+  ) -> bool {
+    let mut state = self.state.lock().unwrap();
+    if state.now() >= deadline {
+      if let Some(id) = slot.take() {
+        state.remove(id);
+      }
+      return true;
+    }
+
+    let earliest = state.next_deadline();
+    let id = *slot.get_or_insert_with(|| {
+      state.next_id += 1;
+      state.next_id
+    });
+
+    match state.deadline(id) {
+      Some(current) => {
+        let timer = state.timers.get_mut(&id).unwrap();
+        if !timer.waker.will_wake(waker) {
+          timer.waker = waker.clone();
+        }
+        if current == deadline {
+          return false;
+        }
+        state.reschedule(id, deadline);
+      }
+      None => state.insert(id, deadline, waker.clone()),
+    }
+    drop(state);
+
+    if earliest.is_none_or(|earliest| deadline < earliest) {
+      self.park.notify();
+    }
+    false
+  }
+
+  /// Moves the deadline of a registered timer, keeping its waker. Does nothing if the timer has
+  /// fired or was removed.
+  pub fn reset(&self, id: usize, deadline: Instant) {
+    let mut state = self.state.lock().unwrap();
+    if state.deadline(id).is_none() {
+      return;
+    }
+    let earliest = state.next_deadline();
+    state.reschedule(id, deadline);
+    drop(state);
+
+    if earliest.is_none_or(|earliest| deadline < earliest) {
+      self.park.notify();
+    }
+  }
+
+  pub fn deregister(&self, id: usize) {
+    self.state.lock().unwrap().remove(id);
+  }
+
+  /// How many timers are registered.
+  #[cfg(test)]
+  pub(crate) fn timers(&self) -> usize {
+    self.state.lock().unwrap().timers.len()
+  }
+
+  /// Fires timers as they expire, until [`Handle::shutdown`] is called.
+  pub fn run(&self) {
+    let mut state = self.state.lock().unwrap();
+
+    loop {
+      if state.shutdown {
+        break;
+      }
+
+      let wakers = state.expired();
+      if !wakers.is_empty() {
+        drop(state);
+        wakers.into_iter().for_each(Waker::wake);
+        state = self.state.lock().unwrap();
+        continue;
+      }
+
+      let deadline = match (state.paused, state.next_deadline()) {
+        // The deadline is on the runtime's clock, the park waits on the real one.
+        (None, Some(deadline)) => Some(deadline - state.offset),
+        // A paused clock is only moved by `advance`, which fires timers itself.
+        _ => None,
+      };
+      state = self.park.wait(&self.state, state, deadline);
+    }
+  }
+
+  pub fn shutdown(&self) {
+    self.state.lock().unwrap().shutdown = true;
+    self.park.notify();
+  }
+}
+
+#[test]
+fn reset_in_place() {
+  let handle = Handle::new();
+  handle.pause();
+  let waker = Waker::noop();
+  let deadline = handle.now() + Duration::from_secs(1);
+  let later = deadline + Duration::from_secs(1);
+
+  let mut reset = None;
+  assert!(!handle.register(&mut reset, deadline, waker));
+  assert!(!handle.register(&mut reset, later, waker));
+  handle.reset(reset.unwrap(), later + Duration::from_secs(1));
+
+  let mut removed = None;
+  assert!(!handle.register(&mut removed, deadline, waker));
+  handle.deregister(removed.unwrap());
+
+  let state = handle.state.lock().unwrap();
+  assert_eq!(state.heap.len(), 1);
+  assert_eq!(state.next_deadline(), Some(later + Duration::from_secs(1)));
+}
+
+#[test]
+fn fires_in_order() {
+  let handle = Handle::new();
+  handle.pause();
+  let start = handle.now();
+
+  // Registered out of order, and some moved around afterwards.
+  let mut slots: Vec<_> = [5, 3, 8, 1, 9, 2, 7]
+    .into_iter()
+    .map(|secs| {
+      let mut slot = None;
+      handle.register(
+        &mut slot,
+        start + Duration::from_secs(secs),
+        Waker::noop(),
+      );
+      slot.unwrap()
+    })
+    .collect();
+  handle.reset(slots[2], start + Duration::from_secs(4));
+  handle.deregister(slots.remove(4));
+
+  let mut fired = Vec::new();
+  for secs in 1..=10 {
+    handle.advance(Duration::from_secs(1));
+    let state = handle.state.lock().unwrap();
+    let left = slots.iter().filter(|id| state.timers.contains_key(id)).count();
+    fired.push((secs, slots.len() - left));
+  }
+  let counts: Vec<_> = fired.into_iter().map(|(_, count)| count).collect();
+  assert_eq!(counts, [1, 2, 3, 4, 5, 5, 6, 6, 6, 6]);
+}
+
+#[test]
+fn cancelled_timers_leave_nothing() {
+  let handle = Handle::new();
+  handle.pause();
+  let waker = Waker::noop();
+  let deadline = handle.now() + Duration::from_secs(30);
+
+  // Timeouts which are mostly cancelled before they fire, with a few live ones meanwhile.
+  let mut live = Vec::new();
+  for round in 0..100_000 {
+    let mut slot = None;
+    handle.register(&mut slot, deadline, waker);
+    if round % 1000 == 0 {
+      live.push(slot.unwrap());
+    } else {
+      handle.deregister(slot.unwrap());
+    }
+  }
+  {
+    let state = handle.state.lock().unwrap();
+    assert_eq!(state.heap.len(), live.len());
+    assert_eq!(state.timers.len(), live.len());
+    assert!(state.heap.capacity() <= MIN_CAPACITY);
+  }
+
+  // A burst which completes gives its memory back.
+  let burst: Vec<_> = (0..50_000)
+    .map(|_| {
+      let mut slot = None;
+      handle.register(&mut slot, deadline, waker);
+      slot.unwrap()
+    })
+    .collect();
+  for id in burst {
+    handle.deregister(id);
+  }
+  let state = handle.state.lock().unwrap();
+  assert_eq!(state.heap.len(), live.len());
+  assert!(state.heap.capacity() <= 4 * MIN_CAPACITY);
+}
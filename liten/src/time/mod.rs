@@ -1,2 +1,72 @@
+pub(crate) mod driver;
+mod park;
 mod sleep;
 pub use sleep::*;
+
+use std::time::{Duration, Instant};
+
+use crate::context;
+
+/// Returns the current time of the runtime's clock.
+///
+/// This is the real time unless time is [paused](pause), outside of a runtime it's always the
+/// real time.
+pub fn now() -> Instant {
+  match context::try_handle() {
+    Some(handle) => handle.time().now(),
+    None => Instant::now(),
+  }
+}
+
+/// Freezes the runtime's clock, which is useful for testing code depending on time.
+///
+/// While paused, the clock only moves by calling [`advance`], or when the future given to
+/// `block_on` has nothing to do and is waiting for a timer: the clock then jumps to that timer's
+/// deadline. Tasks on the worker threads are not waited on, so paused time is meant to be driven
+/// from the `block_on` future.
+///
+/// # Panics
+///
+/// Panics if called outside of a runtime or if time is already paused.
+#[track_caller]
+pub fn pause() {
+  context::expect_handle("time::pause").time().pause()
+}
+
+/// Lets the runtime's clock move again, starting from where it was paused.
+///
+/// # Panics
+///
+/// Panics if called outside of a runtime or if time isn't paused.
+#[track_caller]
+pub fn resume() {
+  context::expect_handle("time::resume").time().resume()
+}
+
+/// Moves a paused clock forward, firing every timer which expires on the way.
+///
+/// # Panics
+///
+/// Panics if called outside of a runtime or if time isn't paused.
+#[track_caller]
+pub fn advance(duration: Duration) {
+  context::expect_handle("time::advance").time().advance(duration)
+}
+
+#[crate::internal_test]
+async fn advance_fires_timers() {
+  use std::future::Future;
+
+  pause();
+  let start = now();
+
+  let mut sleep = std::pin::pin!(sleep(Duration::from_millis(10)));
+  let waker = std::task::Waker::noop();
+  let mut cx = std::task::Context::from_waker(waker);
+  assert!(sleep.as_mut().poll(&mut cx).is_pending());
+
+  advance(Duration::from_millis(10));
+  assert_eq!(now() - start, Duration::from_millis(10));
+  assert!(sleep.as_mut().poll(&mut cx).is_ready());
+  resume();
+}
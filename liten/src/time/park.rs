@@ -0,0 +1,201 @@
+use std::{
+  sync::{Mutex, MutexGuard},
+  time::Instant,
+};
+
+#[cfg(not(target_os = "linux"))]
+pub(super) use condvar::Park;
+#[cfg(target_os = "linux")]
+pub(super) use timerfd::Park;
+
+// Blocks the timer thread until the next deadline, or until it's notified.
+//
+// `wait` is only called from the timer thread, with the driver state locked. A `notify` after the
+// state has been unlocked is never lost.
+#[cfg(not(target_os = "linux"))]
+mod condvar {
+  use super::*;
+  use std::sync::Condvar;
+
+  pub(crate) struct Park {
+    condvar: Condvar,
+  }
+
+  impl Park {
+    pub fn new() -> Park {
+      Park { condvar: Condvar::new() }
+    }
+
+    pub fn wait<'a, T>(
+      &self,
+      _: &'a Mutex<T>,
+      guard: MutexGuard<'a, T>,
+      deadline: Option<Instant>,
+    ) -> MutexGuard<'a, T> {
+      match deadline {
+        Some(deadline) => {
+          let timeout = deadline.saturating_duration_since(Instant::now());
+          self.condvar.wait_timeout(guard, timeout).unwrap().0
+        }
+        None => self.condvar.wait(guard).unwrap(),
+      }
+    }
+
+    pub fn notify(&self) {
+      self.condvar.notify_one();
+    }
+  }
+}
+
+// Waits on a `timerfd` in its own poll, so the kernel fires the deadline instead of the wait
+// being computed from a timeout. Notifications go through a `mio::Waker`, which stays set until
+// the poll sees it.
+#[cfg(target_os = "linux")]
+mod timerfd {
+  use super::*;
+  use std::{
+    io,
+    mem::MaybeUninit,
+    os::fd::{AsRawFd, FromRawFd, OwnedFd},
+  };
+
+  use mio::{unix::SourceFd, Events, Interest, Poll, Token, Waker};
+
+  const TIMER: Token = Token(0);
+  const NOTIFY: Token = Token(1);
+
+  pub(crate) struct Park {
+    // Only locked by the timer thread.
+    poll: Mutex<(Poll, Events)>,
+    notify: Waker,
+    timer: OwnedFd,
+  }
+
+  impl Park {
+    pub fn new() -> Park {
+      Park::try_new().expect("internal 'liten' error: couldn't create timerfd")
+    }
+
+    fn try_new() -> io::Result<Park> {
+      let poll = Poll::new()?;
+      let flags = libc::TFD_NONBLOCK | libc::TFD_CLOEXEC;
+      let fd = unsafe { libc::timerfd_create(libc::CLOCK_MONOTONIC, flags) };
+      if fd < 0 {
+        return Err(io::Error::last_os_error());
+      }
+      // SAFETY: The fd was just created and isn't owned by anything else.
+      let timer = unsafe { OwnedFd::from_raw_fd(fd) };
+
+      poll.registry().register(
+        &mut SourceFd(&timer.as_raw_fd()),
+        TIMER,
+        Interest::READABLE,
+      )?;
+      let notify = Waker::new(poll.registry(), NOTIFY)?;
+
+      Ok(Park {
+        poll: Mutex::new((poll, Events::with_capacity(2))),
+        notify,
+        timer,
+      })
+    }
+
+    pub fn wait<'a, T>(
+      &self,
+      mutex: &'a Mutex<T>,
+      guard: MutexGuard<'a, T>,
+      deadline: Option<Instant>,
+    ) -> MutexGuard<'a, T> {
+      self.arm(deadline);
+      drop(guard);
+
+      let mut poll = self.poll.lock().unwrap();
+      let (poll, events) = &mut *poll;
+      if let Err(err) = poll.poll(events, None) {
+        assert_eq!(err.kind(), io::ErrorKind::Interrupted, "{err}");
+      }
+      self.disarm();
+
+      mutex.lock().unwrap()
+    }
+
+    pub fn notify(&self) {
+      self.notify.wake().expect("internal 'liten' error: timer notify failed");
+    }
+
+    fn arm(&self, deadline: Option<Instant>) {
+      let value = match deadline {
+        // A zero value disarms the timer, so a deadline which has passed fires in a nanosecond.
+        Some(deadline) => {
+          let timeout = deadline.saturating_duration_since(Instant::now());
+          libc::timespec {
+            tv_sec: timeout.as_secs() as libc::time_t,
+            tv_nsec: timeout.subsec_nanos().max(1) as libc::c_long,
+          }
+        }
+        None => libc::timespec { tv_sec: 0, tv_nsec: 0 },
+      };
+      self.set(value);
+    }
+
+    fn disarm(&self) {
+      self.set(libc::timespec { tv_sec: 0, tv_nsec: 0 });
+      // Clears the readiness of an expiration, if there was one.
+      let mut expirations = MaybeUninit::<u64>::uninit();
+      unsafe {
+        libc::read(self.timer.as_raw_fd(), expirations.as_mut_ptr().cast(), 8)
+      };
+    }
+
+    fn set(&self, value: libc::timespec) {
+      let spec = libc::itimerspec {
+        it_interval: libc::timespec { tv_sec: 0, tv_nsec: 0 },
+        it_value: value,
+      };
+      let result = unsafe {
+        libc::timerfd_settime(
+          self.timer.as_raw_fd(),
+          0,
+          &spec,
+          std::ptr::null_mut(),
+        )
+      };
+      assert_eq!(result, 0, "{}", io::Error::last_os_error());
+    }
+  }
+}
+
+#[cfg(target_os = "linux")]
+#[crate::internal_test]
+async fn precise_while_busy() {
+  use std::{
+    sync::{
+      atomic::{AtomicBool, Ordering},
+      Arc,
+    },
+    time::Duration,
+  };
+
+  let stop = Arc::new(AtomicBool::new(false));
+  let busy: Vec<_> = (0..8)
+    .map(|_| {
+      let stop = stop.clone();
+      crate::task::spawn(async move {
+        while !stop.load(Ordering::Relaxed) {
+          crate::task::yield_now().await;
+        }
+      })
+    })
+    .collect();
+
+  let start = Instant::now();
+  crate::time::sleep(Duration::from_millis(50)).await;
+  let elapsed = start.elapsed();
+  stop.store(true, Ordering::Relaxed);
+
+  assert!(elapsed >= Duration::from_millis(50), "{elapsed:?}");
+  assert!(elapsed < Duration::from_millis(60), "{elapsed:?}");
+  for task in busy {
+    task.await.unwrap();
+  }
+}
@@ -1,14 +1,91 @@
-use crate::sync::oneshot;
-use std::time::Duration;
+use std::{
+  future::Future,
+  pin::Pin,
+  sync::Arc,
+  task::{Context, Poll},
+  time::{Duration, Instant},
+};
 
-pub async fn sleep(duration: Duration) {
-  let (sender, receiver) = oneshot::channel();
-  std::thread::spawn(move || {
-    std::thread::sleep(duration);
-    sender.send(()).unwrap(); // in this scenario: oneshot errors if panics
-  });
+use crate::{context, runtime::scheduler};
 
-  receiver.await.unwrap(); // in this scenario: oneshot errors if panics
+/// Waits until `duration` has elapsed.
+///
+/// # Panics
+///
+/// Panics if called outside of a runtime.
+#[track_caller]
+pub fn sleep(duration: Duration) -> Sleep {
+  let handle = context::expect_handle("time::sleep");
+  let deadline = handle.time().now() + duration;
+  Sleep { handle, deadline, slot: None }
+}
+
+/// Waits until `deadline` is reached.
+///
+/// # Panics
+///
+/// Panics if called outside of a runtime.
+#[track_caller]
+pub fn sleep_until(deadline: Instant) -> Sleep {
+  let handle = context::expect_handle("time::sleep_until");
+  Sleep { handle, deadline, slot: None }
+}
+
+/// Future returned by [`sleep`] and [`sleep_until`].
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Sleep {
+  handle: Arc<scheduler::Handle>,
+  deadline: Instant,
+  // Id of the timer, once registered.
+  slot: Option<usize>,
+}
+
+impl Sleep {
+  /// The instant this sleep completes at.
+  pub fn deadline(&self) -> Instant {
+    self.deadline
+  }
+
+  /// Returns `true` if the deadline has been reached.
+  pub fn is_elapsed(&self) -> bool {
+    self.handle.time().now() >= self.deadline
+  }
+
+  /// Moves the deadline, which can also be done after the sleep has completed.
+  ///
+  /// A registered timer is moved in place, so a single `Sleep` can be reset on every bit of
+  /// activity, like an idle timeout, without allocating. Dropping the sleep before it completes
+  /// is fine too, it only deregisters the timer.
+  pub fn reset(&mut self, deadline: Instant) {
+    self.deadline = deadline;
+    if let Some(id) = self.slot {
+      self.handle.time().reset(id, deadline);
+    }
+  }
+}
+
+impl Future for Sleep {
+  type Output = ();
+
+  fn poll(
+    mut self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<Self::Output> {
+    let this = &mut *self;
+    if this.handle.time().register(&mut this.slot, this.deadline, cx.waker()) {
+      Poll::Ready(())
+    } else {
+      Poll::Pending
+    }
+  }
+}
+
+impl Drop for Sleep {
+  fn drop(&mut self) {
+    if let Some(id) = self.slot {
+      self.handle.time().deregister(id);
+    }
+  }
 }
 
 #[crate::internal_test]
@@ -16,3 +93,44 @@ async fn no_testing() {
   sleep(Duration::from_millis(10)).await;
   sleep(Duration::from_millis(0)).await;
 }
+
+#[crate::internal_test]
+async fn paused() {
+  super::pause();
+  let start = super::now();
+
+  sleep(Duration::from_secs(60)).await;
+  assert_eq!(super::now() - start, Duration::from_secs(60));
+
+  let mut sleep = sleep(Duration::from_secs(1));
+  (&mut sleep).await;
+  sleep.reset(sleep.deadline() + Duration::from_secs(1));
+  assert!(!sleep.is_elapsed());
+  sleep.await;
+  assert_eq!(super::now() - start, Duration::from_secs(62));
+}
+
+#[crate::internal_test]
+async fn idle_timeout() {
+  super::pause();
+  let start = super::now();
+  let mut timeout = sleep(Duration::from_millis(100));
+  async fn poll(sleep: &mut Sleep) -> bool {
+    std::future::poll_fn(|cx| Poll::Ready(Pin::new(&mut *sleep).poll(cx)))
+      .await
+      .is_ready()
+  }
+
+  // Activity every 50ms keeps pushing the deadline.
+  for _ in 0..10 {
+    assert!(!poll(&mut timeout).await);
+    super::advance(Duration::from_millis(50));
+    timeout.reset(super::now() + Duration::from_millis(100));
+  }
+
+  super::advance(Duration::from_millis(99));
+  assert!(!poll(&mut timeout).await);
+  super::advance(Duration::from_millis(1));
+  assert!(poll(&mut timeout).await);
+  assert_eq!(super::now() - start, Duration::from_millis(600));
+}
@@ -0,0 +1,80 @@
+use std::{future::Future, num::NonZero};
+
+use crate::sync::Semaphore;
+
+/// Caps how many futures of one subsystem run at the same time.
+///
+/// Giving a slow dependency its own bulkhead keeps it from taking up every task of the runtime:
+/// past the cap, callers wait for a slot instead of piling more work onto it.
+pub struct Bulkhead {
+  name: String,
+  capacity: NonZero<usize>,
+  semaphore: Semaphore,
+}
+
+impl Bulkhead {
+  pub fn new(name: impl Into<String>, capacity: NonZero<usize>) -> Bulkhead {
+    Bulkhead {
+      name: name.into(),
+      capacity,
+      semaphore: Semaphore::with_size(capacity),
+    }
+  }
+
+  pub fn name(&self) -> &str {
+    &self.name
+  }
+
+  pub fn capacity(&self) -> NonZero<usize> {
+    self.capacity
+  }
+
+  /// Waits for a slot, and runs `fut` while holding it.
+  pub async fn run<F: Future>(&self, fut: F) -> F::Output {
+    let _permit = self.semaphore.acquire().await;
+    fut.await
+  }
+
+  /// Returns how many futures are running in the bulkhead.
+  pub fn in_flight(&self) -> usize {
+    self.capacity.get() - self.available()
+  }
+
+  /// Returns how many more futures can start without waiting.
+  pub fn available(&self) -> usize {
+    self.semaphore.available_permits()
+  }
+}
+
+#[test]
+fn caps_concurrency() {
+  use crate::{test_util::StepRuntime, time};
+  use std::{sync::Arc, time::Duration};
+
+  let mut runtime = StepRuntime::new();
+  let bulkhead = Arc::new(Bulkhead::new("db", 2.try_into().unwrap()));
+  let handles: Vec<_> = (0..3)
+    .map(|_| {
+      let bulkhead = bulkhead.clone();
+      runtime.spawn(async move {
+        let work = async { time::sleep(Duration::from_millis(10)).await };
+        bulkhead.run(work).await
+      })
+    })
+    .collect();
+
+  runtime.tick();
+  assert_eq!(bulkhead.name(), "db");
+  assert_eq!((bulkhead.in_flight(), bulkhead.available()), (2, 0));
+
+  runtime.advance(Duration::from_millis(10));
+  runtime.run_until_stalled();
+  // The third one only started once a slot was free.
+  assert_eq!((bulkhead.in_flight(), bulkhead.available()), (1, 1));
+  assert!(!handles[2].is_finished());
+
+  runtime.advance(Duration::from_millis(10));
+  runtime.run_until_stalled();
+  assert_eq!((bulkhead.in_flight(), bulkhead.available()), (0, 2));
+  assert!(handles.iter().all(|handle| handle.is_finished()));
+}
@@ -0,0 +1,4 @@
+mod bulkhead;
+pub use bulkhead::*;
+mod shutdown;
+pub use shutdown::{Shutdown, Subscription};
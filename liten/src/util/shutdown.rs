@@ -0,0 +1,111 @@
+use crate::sync::{
+  mpsc::{self, Receiver, Sender},
+  CancellationToken, Cancelled,
+};
+
+/// Tells the components of a program to shut down, and waits for them to be done with it.
+///
+/// Every component [subscribes](Shutdown::subscribe), and waits on its [`Subscription`] for
+/// [`Shutdown::trigger`] next to its own work. Once it's done draining, like finishing the
+/// requests it has started, it drops the subscription, and [`Shutdown::wait`] completes once
+/// every subscription is dropped.
+pub struct Shutdown {
+  token: CancellationToken,
+  // Every subscription holds a clone, the channel disconnects once they're all dropped. Nothing is
+  // ever sent.
+  drain: Sender<()>,
+  drained: Receiver<()>,
+}
+
+impl Default for Shutdown {
+  fn default() -> Self {
+    let (drain, drained) = mpsc::unbounded_with_capacity(0);
+    Shutdown { token: CancellationToken::new(), drain, drained }
+  }
+}
+
+impl Shutdown {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn subscribe(&self) -> Subscription {
+    Subscription { token: self.token.clone(), _drain: self.drain.clone() }
+  }
+
+  /// Wakes every subscription waiting in [`Subscription::recv`].
+  pub fn trigger(&self) {
+    self.token.cancel();
+  }
+
+  pub fn is_triggered(&self) -> bool {
+    self.token.is_cancelled()
+  }
+
+  /// Waits for every subscription to be dropped. It doesn't trigger the shutdown, a subscription
+  /// dropped before it is done already.
+  pub async fn wait(self) {
+    let Shutdown { token: _, drain, drained } = self;
+    drop(drain);
+    while drained.recv().await.is_ok() {}
+  }
+}
+
+/// The end of a [`Shutdown`] held by a component. Dropping it tells the shutdown the component is
+/// done.
+pub struct Subscription {
+  token: CancellationToken,
+  _drain: Sender<()>,
+}
+
+impl Subscription {
+  /// Completes once the shutdown is triggered, right away if it already is.
+  pub fn recv(&self) -> Cancelled<'_> {
+    self.token.cancelled()
+  }
+
+  pub fn is_triggered(&self) -> bool {
+    self.token.is_cancelled()
+  }
+}
+
+#[crate::internal_test]
+async fn waits_for_every_subscriber() {
+  use crate::{sync::oneshot, task, test_util};
+  use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+  };
+
+  let shutdown = Shutdown::new();
+  let drained = Arc::new(AtomicUsize::new(0));
+  let (release, released) = oneshot::channel::<()>();
+  let mut released = Some(released);
+  for component in 0..3 {
+    let subscription = shutdown.subscribe();
+    let drained = drained.clone();
+    // The last one takes longer to drain than the others.
+    let released = (component == 2).then(|| released.take().unwrap());
+    task::spawn(async move {
+      subscription.recv().await;
+      if let Some(released) = released {
+        released.await.unwrap();
+      }
+      drained.fetch_add(1, Ordering::SeqCst);
+    });
+  }
+
+  shutdown.trigger();
+  assert!(shutdown.is_triggered());
+  let mut wait = test_util::task::spawn(shutdown.wait());
+  while drained.load(Ordering::SeqCst) < 2 {
+    task::yield_now().await;
+  }
+  assert!(wait.poll().is_pending());
+
+  release.send(()).unwrap();
+  while wait.poll().is_pending() {
+    task::yield_now().await;
+  }
+  assert_eq!(drained.load(Ordering::SeqCst), 3);
+}
@@ -0,0 +1,66 @@
+use std::{
+  collections::HashSet,
+  sync::{Arc, Mutex},
+  thread::{self, ThreadId},
+  time::{Duration, Instant},
+};
+
+// Runs blocking tasks until they're spread over the workers, and returns the threads they ran on.
+async fn worker_thread_ids() -> HashSet<ThreadId> {
+  let threads = Arc::new(Mutex::new(HashSet::new()));
+  let handles: Vec<_> = (0..16)
+    .map(|_| {
+      let threads = threads.clone();
+      liten::task::spawn(async move {
+        threads.lock().unwrap().insert(thread::current().id());
+        thread::sleep(Duration::from_millis(5));
+      })
+    })
+    .collect();
+  for handle in handles {
+    handle.await.unwrap();
+  }
+  Arc::try_unwrap(threads).unwrap().into_inner().unwrap()
+}
+
+#[liten::test(flavor = "multi_thread", worker_threads = 4)]
+async fn multi_thread() {
+  assert!(worker_thread_ids().await.len() > 1);
+}
+
+#[liten::test(flavor = "current_thread")]
+async fn current_thread() {
+  assert_eq!(worker_thread_ids().await.len(), 1);
+}
+
+#[liten::test]
+#[should_panic(expected = "inside the runtime")]
+async fn should_panic() {
+  liten::task::yield_now().await;
+  panic!("inside the runtime");
+}
+
+#[liten::test]
+async fn returns_ok() -> Result<(), std::num::ParseIntError> {
+  assert_eq!("7".parse::<u8>()?, 7);
+  Ok(())
+}
+
+#[liten::test]
+#[ignore = "fails on purpose, run by result_err_fails"]
+async fn returns_err() -> Result<(), std::num::ParseIntError> {
+  "seven".parse::<u8>()?;
+  Ok(())
+}
+
+#[test]
+fn result_err_fails() {
+  assert!(returns_err().is_err());
+}
+
+#[liten::test(start_paused = true)]
+async fn start_paused() {
+  let start = Instant::now();
+  liten::time::sleep(Duration::from_secs(60 * 60)).await;
+  assert!(start.elapsed() < Duration::from_secs(5));
+}